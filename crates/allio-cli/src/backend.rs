@@ -0,0 +1,142 @@
+/*!
+Where commands are answered: an in-process `Allio`, or an `allio-ws` server.
+
+Both speak the WebSocket RPC methods, so commands behave the same either way.
+*/
+
+use allio::Allio;
+use serde_json::{json, Value};
+use std::collections::VecDeque;
+use std::net::TcpStream;
+use std::time::{Duration, Instant};
+use tungstenite::stream::MaybeTlsStream;
+use tungstenite::{Message, WebSocket};
+
+/// How long a fresh in-process `Allio` gets to find the open windows.
+const FIRST_POLL_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Answers RPC calls and streams events.
+pub(crate) trait Backend {
+  /// Make an RPC call, returning its result.
+  fn call(&mut self, method: &str, args: Value) -> Result<Value, String>;
+  /// Wait for the next event, as sent over WebSocket.
+  fn next_event(&mut self) -> Result<Value, String>;
+}
+
+/// Result of an RPC response (`{ result }` or `{ error, message }`).
+fn unpack(mut response: Value) -> Result<Value, String> {
+  if let Some(error) = response.get("error") {
+    let message = response.get("message").unwrap_or(error);
+    return Err(
+      message
+        .as_str()
+        .map_or_else(|| message.to_string(), Into::into),
+    );
+  }
+  Ok(
+    response
+      .get_mut("result")
+      .map(Value::take)
+      .unwrap_or_default(),
+  )
+}
+
+/// An `Allio` instance in this process.
+pub(crate) struct Local {
+  allio: Allio,
+  /// Subscribed up front, so events following a `snapshot` call are never missed.
+  events: async_broadcast::Receiver<allio::SequencedEvent>,
+}
+
+impl Local {
+  /// Start Allio and wait briefly for its first window poll.
+  pub(crate) fn new() -> Result<Self, String> {
+    let allio = Allio::new().map_err(|e| e.to_string())?;
+    let deadline = Instant::now() + FIRST_POLL_TIMEOUT;
+    while allio.all_windows().is_empty() && Instant::now() < deadline {
+      std::thread::sleep(Duration::from_millis(10));
+    }
+    let events = allio.subscribe();
+    Ok(Self { allio, events })
+  }
+}
+
+impl Backend for Local {
+  fn call(&mut self, method: &str, args: Value) -> Result<Value, String> {
+    unpack(allio_ws::dispatch_json(&self.allio, method, &args))
+  }
+
+  fn next_event(&mut self) -> Result<Value, String> {
+    loop {
+      match self.events.recv_blocking() {
+        Ok(event) => return serde_json::to_value(event).map_err(|e| e.to_string()),
+        // Fell behind; carry on from the oldest event still queued.
+        Err(async_broadcast::RecvError::Overflowed(_)) => {}
+        Err(async_broadcast::RecvError::Closed) => return Err("Event stream closed".into()),
+      }
+    }
+  }
+}
+
+/// A connection to a running `allio-ws` server.
+pub(crate) struct Remote {
+  socket: WebSocket<MaybeTlsStream<TcpStream>>,
+  next_id: u64,
+  /// Events that arrived while waiting for a response.
+  events: VecDeque<Value>,
+}
+
+impl Remote {
+  pub(crate) fn connect(url: &str) -> Result<Self, String> {
+    let (socket, _) =
+      tungstenite::connect(url).map_err(|e| format!("Failed to connect to {url}: {e}"))?;
+    Ok(Self {
+      socket,
+      next_id: 1,
+      events: VecDeque::new(),
+    })
+  }
+
+  /// Read the next JSON message. Responses carry an `id`; everything else is an event.
+  fn read(&mut self) -> Result<Value, String> {
+    loop {
+      match self.socket.read().map_err(|e| e.to_string())? {
+        Message::Text(text) => return serde_json::from_str(&text).map_err(|e| e.to_string()),
+        Message::Close(_) => return Err("Server closed the connection".into()),
+        Message::Binary(_) | Message::Ping(_) | Message::Pong(_) | Message::Frame(_) => {}
+      }
+    }
+  }
+}
+
+impl Backend for Remote {
+  fn call(&mut self, method: &str, args: Value) -> Result<Value, String> {
+    let id = self.next_id;
+    self.next_id += 1;
+    let request = json!({ "id": id, "method": method, "args": args });
+    self
+      .socket
+      .send(Message::Text(request.to_string()))
+      .map_err(|e| e.to_string())?;
+    loop {
+      let message = self.read()?;
+      match message.get("id") {
+        Some(response_id) if *response_id == json!(id) => return unpack(message),
+        Some(_) => {}
+        None => self.events.push_back(message),
+      }
+    }
+  }
+
+  fn next_event(&mut self) -> Result<Value, String> {
+    if let Some(event) = self.events.pop_front() {
+      return Ok(event);
+    }
+    loop {
+      let message = self.read()?;
+      if message.get("id").is_none() {
+        return Ok(message);
+      }
+    }
+  }
+}
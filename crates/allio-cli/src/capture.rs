@@ -0,0 +1,83 @@
+/*!
+`capture`: record platform calls from real apps, for replay in tests.
+
+Unlike a journal, which holds events, a capture holds every platform call with
+its result. `MockDesktop::replay` answers from it, so registry and observation
+logic can be tested against the recorded apps on any OS.
+*/
+
+use crate::backend::{Backend, Local};
+use serde_json::Value;
+use std::fs::File;
+use std::io::{self, BufWriter};
+use std::path::Path;
+
+/// Capture to `path`: fetch every window's tree, then record until Enter is pressed.
+pub(crate) fn capture(path: &Path) -> Result<(), String> {
+  capture_until(path, || {
+    eprintln!("Capturing; use the apps, then press Enter to stop");
+    io::stdin().read_line(&mut String::new()).map(drop)
+  })
+}
+
+fn capture_until(path: &Path, wait: impl FnOnce() -> io::Result<()>) -> Result<(), String> {
+  let file = File::create(path).map_err(|e| format!("{}: {e}", path.display()))?;
+  // Started before `Allio`, so every watch it makes is recorded.
+  allio::capture::start(BufWriter::new(file));
+  let captured = Local::new().and_then(|mut local| {
+    fetch_trees(&mut local)?;
+    wait().map_err(|e| e.to_string())
+  });
+  let stopped = allio::capture::stop().map_err(|e| format!("{}: {e}", path.display()));
+  captured.and(stopped)
+}
+
+/// Fetch every open window's tree, so the capture holds more than the window list.
+fn fetch_trees(backend: &mut dyn Backend) -> Result<(), String> {
+  let snapshot = backend.call("snapshot", Value::Null)?;
+  let windows = snapshot
+    .get("windows")
+    .and_then(Value::as_array)
+    .into_iter()
+    .flatten()
+    .filter_map(|w| w.get("id").and_then(Value::as_u64));
+  for window in windows.collect::<Vec<_>>() {
+    let window = u32::try_from(window).map_err(|e| e.to_string())?;
+    crate::commands::tree(backend, window, None, None)?;
+  }
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use allio::a11y::Role;
+  use allio::mock::{MockDesktop, MockElement};
+  use allio::Recency;
+
+  #[test]
+  fn captured_trees_replay_without_the_apps() {
+    let path = std::env::temp_dir().join(format!("allio-capture-{}.jsonl", std::process::id()));
+    let desktop = MockDesktop::acquire();
+    let window = desktop.open_window("Notes", "Untitled");
+    let group = desktop
+      .add_element(window.node, MockElement::new(Role::Group))
+      .unwrap();
+    desktop.add_element(group, MockElement::new(Role::Button).label("Save"));
+    capture_until(&path, || Ok(())).unwrap();
+    drop(desktop);
+
+    // A fresh, empty desktop: the tree below comes from the capture.
+    let desktop = MockDesktop::acquire();
+    let file = std::io::BufReader::new(File::open(&path).unwrap());
+    desktop.replay(allio::capture::Capture::read(file).unwrap());
+    std::fs::remove_file(&path).unwrap();
+
+    let allio = allio::Allio::new().unwrap();
+    desktop.wait_for_window(&allio, window.id);
+    let root = allio.window_root(window.id).unwrap().unwrap();
+    let groups = allio.children(root.id, Recency::Current).unwrap();
+    let buttons = allio.children(groups[0].id, Recency::Current).unwrap();
+    assert_eq!(buttons[0].label.as_deref(), Some("Save"));
+  }
+}
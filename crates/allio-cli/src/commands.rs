@@ -0,0 +1,307 @@
+/*!
+Subcommands, run against a `Backend` and written to any output.
+*/
+
+use crate::backend::Backend;
+use crate::{Command, Format};
+use serde_json::{json, Value};
+use std::fmt::Write as _;
+use std::io::Write;
+
+pub(crate) fn run(
+  backend: &mut dyn Backend,
+  command: &Command,
+  out: &mut impl Write,
+) -> Result<(), String> {
+  let result = match command {
+    Command::Windows => {
+      let mut snapshot = backend.call("snapshot", Value::Null)?;
+      let mut windows = snapshot
+        .get_mut("windows")
+        .map(Value::take)
+        .and_then(|w| serde_json::from_value::<Vec<Value>>(w).ok())
+        .unwrap_or_default();
+      windows.sort_by_key(|w| w.get("z_index").and_then(Value::as_u64));
+      Value::Array(windows)
+    }
+    Command::Tree {
+      window,
+      depth,
+      max_children,
+      format,
+    } => {
+      let tree = tree(backend, *window, *depth, Some(*max_children))?;
+      if *format == Format::Text {
+        return write!(out, "{}", render_text(&tree)).map_err(|e| e.to_string());
+      }
+      tree
+    }
+    Command::Get { id } => {
+      backend.call("get", json!({ "element_id": id, "recency": "current" }))?
+    }
+    Command::At { x, y } => backend.call("element_at", json!({ "x": x, "y": y }))?,
+    Command::Set { id, value } => backend.call(
+      "set",
+      json!({ "element_id": id, "value": parse_value(value) }),
+    )?,
+    Command::Perform { id, action } => {
+      backend.call("perform", json!({ "element_id": id, "action": action }))?
+    }
+    Command::Watch => return watch(backend, out),
+    Command::Record { file } => return crate::journal::record(backend, file),
+    // Handled by `main`, without a backend.
+    Command::Replay { .. } => return Err("replay does not run against a backend".into()),
+    Command::Capture { .. } => return Err("capture does not run against a backend".into()),
+  };
+
+  // Writes succeed silently.
+  if result.is_null() && matches!(command, Command::Set { .. } | Command::Perform { .. }) {
+    return Ok(());
+  }
+  let json = serde_json::to_string_pretty(&result).map_err(|e| e.to_string())?;
+  writeln!(out, "{json}").map_err(|e| e.to_string())
+}
+
+/// Window root with `children` replaced by nested elements, down to `depth` levels.
+/// Elements at the depth limit keep their child IDs. Past `max_children`,
+/// children are cut and the element gets a `children_total`.
+pub(crate) fn tree(
+  backend: &mut dyn Backend,
+  window: u32,
+  depth: Option<usize>,
+  max_children: Option<usize>,
+) -> Result<Value, String> {
+  let root = backend.call("window_root", json!({ "window_id": window }))?;
+  expand(backend, root, depth, max_children)
+}
+
+fn expand(
+  backend: &mut dyn Backend,
+  mut element: Value,
+  depth: Option<usize>,
+  max_children: Option<usize>,
+) -> Result<Value, String> {
+  if depth == Some(0) {
+    return Ok(element);
+  }
+  let id = element.get("id").cloned().unwrap_or_default();
+  // With `max_children`, fetch the first page to learn whether the list is cut.
+  let (children, total) = match max_children {
+    None => (backend.call("children", json!({ "element_id": id }))?, None),
+    Some(limit) => {
+      let args = json!({ "element_id": id, "limit": limit });
+      let mut page = backend.call("children_page", args)?;
+      let total = page
+        .get("next_offset")
+        .filter(|next| !next.is_null())
+        .map(|_| page.get("total").cloned().unwrap_or_default());
+      (
+        page
+          .get_mut("children")
+          .map(Value::take)
+          .unwrap_or_default(),
+        total,
+      )
+    }
+  };
+  let nested = children
+    .as_array()
+    .into_iter()
+    .flatten()
+    .map(|child| expand(backend, child.clone(), depth.map(|d| d - 1), max_children))
+    .collect::<Result<Vec<_>, _>>()?;
+  if let Some(fields) = element.as_object_mut() {
+    fields.insert("children".into(), Value::Array(nested));
+    if let Some(total) = total {
+      fields.insert("children_total".into(), total);
+    }
+  }
+  Ok(element)
+}
+
+/// One line per element: `role "label" = value #id`, indented by depth. Cut
+/// child lists end with a `… N more` line.
+fn render_text(tree: &Value) -> String {
+  let mut text = String::new();
+  render_node(&mut text, tree, 0);
+  text
+}
+
+fn render_node(text: &mut String, element: &Value, indent: usize) {
+  let field = |name: &str| element.get(name).filter(|v| !v.is_null());
+  text.push_str(&"  ".repeat(indent));
+  if let Some(role) = field("role").and_then(Value::as_str) {
+    text.push_str(role);
+  }
+  if let Some(label) = field("label") {
+    write!(text, " {label}").ok();
+  }
+  if let Some(value) = field("value") {
+    write!(text, " = {value}").ok();
+  }
+  if let Some(id) = field("id") {
+    write!(text, " #{id}").ok();
+  }
+  text.push('\n');
+
+  let children = field("children").and_then(Value::as_array);
+  for child in children.into_iter().flatten() {
+    if child.is_object() {
+      render_node(text, child, indent + 1);
+    }
+  }
+  if let Some(total) = field("children_total").and_then(Value::as_u64) {
+    let shown = children.map_or(0, Vec::len) as u64;
+    text.push_str(&"  ".repeat(indent + 1));
+    writeln!(text, "… {} more", total.saturating_sub(shown)).ok();
+  }
+}
+
+/// JSON literals (`true`, `42`, `"quoted"`) as themselves, anything else as text.
+fn parse_value(value: &str) -> Value {
+  serde_json::from_str::<Value>(value)
+    .ok()
+    .filter(|v| v.is_boolean() || v.is_number() || v.is_string())
+    .unwrap_or_else(|| Value::String(value.into()))
+}
+
+fn watch(backend: &mut dyn Backend, out: &mut impl Write) -> Result<(), String> {
+  loop {
+    let event = backend.next_event()?;
+    // The reader went away (e.g. `allio watch | head`).
+    if writeln!(out, "{event}").and_then(|()| out.flush()).is_err() {
+      return Ok(());
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::backend::Local;
+  use allio::a11y::{Role, Value as AXValue};
+  use allio::mock::{MockDesktop, MockElement, MockNodeId};
+
+  /// A window with a group holding a text field: the desktop, backend, window
+  /// ID, group and field.
+  fn setup() -> (MockDesktop, Local, u32, MockNodeId, MockNodeId) {
+    let desktop = MockDesktop::acquire();
+    let window = desktop.open_window("Notes", "Untitled");
+    let group = desktop
+      .add_element(window.node, MockElement::new(Role::Group))
+      .unwrap();
+    let field = desktop
+      .add_element(
+        group,
+        MockElement::new(Role::TextField)
+          .label("Name")
+          .value(AXValue::String("Ada".into())),
+      )
+      .unwrap();
+    let local = Local::new().unwrap();
+    (desktop, local, window.id.0, group, field)
+  }
+
+  fn output(backend: &mut dyn Backend, command: &Command) -> String {
+    let mut out = Vec::new();
+    run(backend, command, &mut out).unwrap();
+    String::from_utf8(out).unwrap()
+  }
+
+  #[test]
+  fn tree_renders_text_and_json() {
+    let (_desktop, mut local, window, _, _) = setup();
+    let windows: Value = serde_json::from_str(&output(&mut local, &Command::Windows)).unwrap();
+    assert_eq!(windows[0]["title"], "Untitled");
+
+    let text = output(
+      &mut local,
+      &Command::Tree {
+        window,
+        depth: None,
+        max_children: 1000,
+        format: Format::Text,
+      },
+    );
+    let lines: Vec<&str> = text.lines().collect();
+    assert_eq!(lines.len(), 3);
+    assert!(lines[0].starts_with("window \"Untitled\""));
+    assert!(lines[2].starts_with("    textfield \"Name\" = \"Ada\" #"));
+
+    // Depth 1 stops below the group, which keeps its child IDs.
+    let json = output(
+      &mut local,
+      &Command::Tree {
+        window,
+        depth: Some(1),
+        max_children: 1000,
+        format: Format::Json,
+      },
+    );
+    let tree: Value = serde_json::from_str(&json).unwrap();
+    let group = &tree["children"][0];
+    assert_eq!(group["role"], "group");
+    assert!(group["children"][0].is_u64());
+  }
+
+  #[test]
+  fn tree_marks_cut_child_lists() {
+    let (desktop, mut local, window, group, _) = setup();
+    for label in ["Email", "Phone"] {
+      desktop.add_element(group, MockElement::new(Role::TextField).label(label));
+    }
+    let tree = |local: &mut Local, format| {
+      let command = Command::Tree {
+        window,
+        depth: None,
+        max_children: 1,
+        format,
+      };
+      output(local, &command)
+    };
+
+    let text = tree(&mut local, Format::Text);
+    let lines: Vec<&str> = text.lines().collect();
+    assert_eq!(lines.len(), 4);
+    assert_eq!(lines[3], "    … 2 more");
+    let json: Value = serde_json::from_str(&tree(&mut local, Format::Json)).unwrap();
+    let group = &json["children"][0];
+    assert_eq!(group["children"].as_array().unwrap().len(), 1);
+    assert_eq!(group["children_total"], 3);
+    assert!(json.get("children_total").is_none());
+  }
+
+  #[test]
+  fn set_parses_json_literals() {
+    let (desktop, mut local, window, _, field) = setup();
+    let root = local
+      .call("window_root", json!({ "window_id": window }))
+      .unwrap();
+    let group = local
+      .call("children", json!({ "element_id": root["id"] }))
+      .unwrap();
+    let fields = local
+      .call("children", json!({ "element_id": group[0]["id"] }))
+      .unwrap();
+    let id = fields[0]["id"].as_u64().unwrap();
+
+    let set = Command::Set {
+      id,
+      value: "hello world".into(),
+    };
+    assert_eq!(output(&mut local, &set), "");
+    assert_eq!(
+      desktop.element(field).unwrap().value,
+      Some(AXValue::String("hello world".into()))
+    );
+
+    assert_eq!(parse_value("true"), json!(true));
+    assert_eq!(parse_value("42"), json!(42));
+    assert_eq!(parse_value("[1]"), json!("[1]"));
+
+    let get = output(&mut local, &Command::Get { id });
+    assert!(get.contains("\"hello world\""));
+    let missing = run(&mut local, &Command::Get { id: 999_999 }, &mut Vec::new());
+    assert!(missing.is_err());
+  }
+}
@@ -0,0 +1,85 @@
+/*!
+`record` and `replay`: capture a session to a journal, and serve it back.
+*/
+
+use crate::backend::Backend;
+use allio::journal::{JournalReader, JournalWriter, Replayer, Speed};
+use allio::{SequencedEvent, Snapshot};
+use serde_json::Value;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Write};
+use std::path::Path;
+
+/// Parse `--speed`: `max` for no gaps, otherwise a factor (`1` keeps the recorded pace).
+pub(crate) fn parse_speed(speed: &str) -> Result<Speed, String> {
+  if speed == "max" {
+    return Ok(Speed::Unpaced);
+  }
+  match speed.parse::<f64>() {
+    Ok(factor) if (factor - 1.0).abs() < f64::EPSILON => Ok(Speed::Original),
+    Ok(factor) if factor > 0.0 && factor.is_finite() => Ok(Speed::Factor(factor)),
+    _ => Err(format!(
+      "expected a positive factor or `max`, got `{speed}`"
+    )),
+  }
+}
+
+/// Write the current state and every following event to `path`, until interrupted.
+pub(crate) fn record(backend: &mut dyn Backend, path: &Path) -> Result<(), String> {
+  let file = File::create(path).map_err(|e| format!("{}: {e}", path.display()))?;
+  let snapshot: Snapshot =
+    serde_json::from_value(backend.call("snapshot", Value::Null)?).map_err(|e| e.to_string())?;
+  let mut journal =
+    JournalWriter::new(BufWriter::new(file), snapshot).map_err(|e| e.to_string())?;
+  record_events(backend, &mut journal)
+}
+
+fn record_events<W: Write>(
+  backend: &mut dyn Backend,
+  journal: &mut JournalWriter<W>,
+) -> Result<(), String> {
+  loop {
+    let event: SequencedEvent =
+      serde_json::from_value(backend.next_event()?).map_err(|e| e.to_string())?;
+    // Flushed per event, so an interrupted recording is still a valid journal.
+    journal
+      .write(&event)
+      .and_then(|()| journal.flush())
+      .map_err(|e| e.to_string())?;
+  }
+}
+
+/// Serve `path` over WebSocket on `port`, replaying it at `speed` once Enter is pressed.
+pub(crate) fn replay(path: &Path, speed: Speed, port: u16) -> Result<(), String> {
+  let file = File::open(path).map_err(|e| format!("{}: {e}", path.display()))?;
+  let journal = JournalReader::new(BufReader::new(file)).map_err(|e| e.to_string())?;
+  let replayer = Replayer::new(journal.snapshot().clone());
+
+  let runtime = tokio::runtime::Runtime::new().map_err(|e| e.to_string())?;
+  runtime.spawn(allio_ws::start_journal_server(replayer.clone(), port));
+
+  // Clients connected before playback see the whole recording.
+  eprintln!("Press Enter to start the replay");
+  std::io::stdin()
+    .read_line(&mut String::new())
+    .map_err(|e| e.to_string())?;
+  replayer.play(journal, speed).map_err(|e| e.to_string())?;
+
+  eprintln!("Replay finished; serving the final state until interrupted");
+  runtime.block_on(std::future::pending::<()>());
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn speeds() {
+    assert_eq!(parse_speed("max"), Ok(Speed::Unpaced));
+    assert_eq!(parse_speed("1"), Ok(Speed::Original));
+    assert_eq!(parse_speed("2.5"), Ok(Speed::Factor(2.5)));
+    assert!(parse_speed("0").is_err());
+    assert!(parse_speed("fast").is_err());
+  }
+}
@@ -0,0 +1,203 @@
+/*!
+`allio` command-line tool: dump, query and drive accessibility trees.
+
+Runs against an in-process `Allio` by default, or a running `allio-ws` server
+with `--connect`. Element IDs belong to the `Allio` instance that assigned
+them, so `get`, `set` and `perform` need `--connect`: without it, each run
+starts a new instance that has never seen the ID.
+
+Output is JSON, except `tree --format text` and `watch` (NDJSON events).
+`record` captures a session to a journal file; `replay` serves one over
+WebSocket, as a stand-in `allio-ws` server. `capture` records platform calls
+instead, as a fixture for `MockDesktop::replay`.
+*/
+
+mod backend;
+mod capture;
+mod commands;
+mod journal;
+
+use allio::journal::Speed;
+use backend::{Backend, Local, Remote};
+use clap::{Parser, Subcommand, ValueEnum};
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+#[derive(Debug, Parser)]
+#[command(
+  name = "allio",
+  version,
+  about = "Dump, query and drive accessibility trees"
+)]
+struct Cli {
+  /// Connect to a running allio-ws server instead of starting Allio in-process.
+  #[arg(long, global = true, value_name = "URL")]
+  connect: Option<String>,
+
+  #[command(subcommand)]
+  command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+pub(crate) enum Command {
+  /// List open windows, front to back.
+  Windows,
+  /// Dump a window's element tree, fetched from the OS.
+  Tree {
+    /// Window ID.
+    window: u32,
+    /// Levels below the root to fetch (unlimited by default).
+    #[arg(long)]
+    depth: Option<usize>,
+    /// Children to fetch per element. Longer lists are cut, with a marker.
+    #[arg(long, default_value_t = 1000)]
+    max_children: usize,
+    #[arg(long, value_enum, default_value_t = Format::Text)]
+    format: Format,
+  },
+  /// Get an element by ID, fresh from the OS.
+  Get { id: u64 },
+  /// Get the deepest element at screen coordinates.
+  At { x: f64, y: f64 },
+  /// Set an element's value. JSON literals (`true`, `42`) are parsed, anything else is text.
+  Set { id: u64, value: String },
+  /// Perform an action (`press`, `increment`, ...) on an element.
+  Perform { id: u64, action: String },
+  /// Stream events as NDJSON until interrupted.
+  Watch,
+  /// Record the current state and all following events to a journal, until interrupted.
+  Record { file: PathBuf },
+  /// Serve a recorded journal over WebSocket, replaying it once Enter is pressed.
+  Replay {
+    file: PathBuf,
+    /// Playback speed: a factor of the recorded pace, or `max` for no gaps.
+    #[arg(long, default_value = "1", value_parser = journal::parse_speed)]
+    speed: Speed,
+    #[arg(long, default_value_t = allio_ws::DEFAULT_WS_PORT)]
+    port: u16,
+  },
+  /// Record platform calls to a capture for `MockDesktop::replay`, until Enter is pressed.
+  /// Fetches every window's tree first.
+  Capture { file: PathBuf },
+}
+
+/// Output format for `tree`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub(crate) enum Format {
+  Json,
+  Text,
+}
+
+fn main() -> ExitCode {
+  env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("warn")).init();
+  let cli = Cli::parse();
+
+  if let Command::Replay { file, speed, port } = &cli.command {
+    return exit_code(journal::replay(file, *speed, *port));
+  }
+  if let Command::Capture { file } = &cli.command {
+    if cli.connect.is_some() {
+      return exit_code(Err(
+        "capture records this process's platform calls; run it without --connect".into(),
+      ));
+    }
+    return exit_code(capture::capture(file));
+  }
+  if let Err(e) = check_ids(&cli) {
+    return exit_code(Err(e));
+  }
+
+  let backend: Result<Box<dyn Backend>, String> = match &cli.connect {
+    Some(url) => Remote::connect(url).map(|b| Box::new(b) as Box<dyn Backend>),
+    None => Local::new().map(|b| Box::new(b) as Box<dyn Backend>),
+  };
+  let result = backend.and_then(|mut backend| {
+    commands::run(
+      backend.as_mut(),
+      &cli.command,
+      &mut std::io::stdout().lock(),
+    )
+  });
+  exit_code(result)
+}
+
+/// Refuse element IDs in-process: a new `Allio` never assigned them.
+fn check_ids(cli: &Cli) -> Result<(), String> {
+  let (Command::Get { id } | Command::Set { id, .. } | Command::Perform { id, .. }) = cli.command
+  else {
+    return Ok(());
+  };
+  if cli.connect.is_some() {
+    return Ok(());
+  }
+  Err(format!(
+    "element {id} can't be found without --connect: IDs belong to the Allio instance that \
+     assigned them, and each run without --connect starts a new one. Run allio-ws, then pass \
+     --connect ws://127.0.0.1:{}/ws to this and the command the ID came from",
+    allio_ws::DEFAULT_WS_PORT
+  ))
+}
+
+fn exit_code(result: Result<(), String>) -> ExitCode {
+  match result {
+    Ok(()) => ExitCode::SUCCESS,
+    Err(e) => {
+      eprintln!("allio: {e}");
+      ExitCode::FAILURE
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn parses_subcommands() {
+    let cli = Cli::try_parse_from([
+      "allio",
+      "--connect",
+      "ws://127.0.0.1:3030/ws",
+      "tree",
+      "3",
+      "--depth",
+      "2",
+      "--format",
+      "json",
+    ])
+    .unwrap();
+    assert_eq!(cli.connect.as_deref(), Some("ws://127.0.0.1:3030/ws"));
+    assert!(matches!(
+      cli.command,
+      Command::Tree {
+        window: 3,
+        depth: Some(2),
+        max_children: 1000,
+        format: Format::Json
+      }
+    ));
+
+    let cli = Cli::try_parse_from(["allio", "at", "10", "20.5"]).unwrap();
+    assert_eq!(format!("{:?}", cli.command), "At { x: 10.0, y: 20.5 }");
+    assert!(Cli::try_parse_from(["allio", "tree", "3", "--format", "xml"]).is_err());
+
+    let cli = Cli::try_parse_from(["allio", "replay", "bug.jsonl", "--speed", "max"]).unwrap();
+    assert!(matches!(
+      cli.command,
+      Command::Replay {
+        speed: Speed::Unpaced,
+        ..
+      }
+    ));
+  }
+
+  #[test]
+  fn ids_need_a_server() {
+    let local = Cli::try_parse_from(["allio", "perform", "42", "press"]).unwrap();
+    assert!(check_ids(&local).unwrap_err().contains("--connect"));
+    let remote = Cli::try_parse_from(["allio", "--connect", "ws://x", "get", "42"]).unwrap();
+    assert!(check_ids(&remote).is_ok());
+    let tree = Cli::try_parse_from(["allio", "tree", "3"]).unwrap();
+    assert!(check_ids(&tree).is_ok());
+  }
+}
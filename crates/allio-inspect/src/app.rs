@@ -0,0 +1,575 @@
+/*!
+Inspector state: the window list, the expandable element tree and key handling.
+
+The tree shows elements fetched with `children` as rows expand. The open
+window's root is observed and the selected element watched, so events from
+`Allio::subscribe` keep both up to date.
+*/
+
+use allio::a11y::{Action, Value, ValueType};
+use allio::{Allio, Element, ElementId, Event, ObservationHandle, ObserveConfig, Recency, Window};
+use ratatui::crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use std::collections::{HashMap, HashSet};
+
+/// Pane that receives movement keys.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Pane {
+  Windows,
+  Tree,
+}
+
+/// What keys do right now.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Mode {
+  Browse,
+  /// Editing the selected element's value.
+  Edit(String),
+  /// Picking one of the selected element's actions.
+  Actions(usize),
+}
+
+/// A visible tree row.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Row {
+  pub(crate) id: ElementId,
+  pub(crate) depth: usize,
+}
+
+pub(crate) struct App {
+  allio: Allio,
+  pub(crate) windows: Vec<Window>,
+  pub(crate) window_index: usize,
+  pub(crate) pane: Pane,
+  pub(crate) mode: Mode,
+  root: Option<ElementId>,
+  elements: HashMap<ElementId, Element>,
+  expanded: HashSet<ElementId>,
+  pub(crate) rows: Vec<Row>,
+  pub(crate) row_index: usize,
+  watched: Option<ElementId>,
+  /// Keeps the open window's tree live. Replaced when another window opens.
+  observation: Option<ObservationHandle>,
+  pub(crate) status: String,
+  pub(crate) quit: bool,
+}
+
+impl App {
+  pub(crate) fn new(allio: Allio) -> Self {
+    let mut app = Self {
+      windows: Vec::new(),
+      allio,
+      window_index: 0,
+      pane: Pane::Tree,
+      mode: Mode::Browse,
+      root: None,
+      elements: HashMap::new(),
+      expanded: HashSet::new(),
+      rows: Vec::new(),
+      row_index: 0,
+      watched: None,
+      observation: None,
+      status: String::new(),
+      quit: false,
+    };
+    app.set_windows(app.allio.all_windows());
+    app.open_window();
+    app
+  }
+
+  pub(crate) fn element(&self, id: ElementId) -> Option<&Element> {
+    self.elements.get(&id)
+  }
+
+  pub(crate) fn selected(&self) -> Option<&Element> {
+    self
+      .rows
+      .get(self.row_index)
+      .and_then(|row| self.element(row.id))
+  }
+
+  /// Whether the open window's tree is observed for changes.
+  pub(crate) const fn is_live(&self) -> bool {
+    self.observation.is_some()
+  }
+
+  pub(crate) fn is_expanded(&self, id: ElementId) -> bool {
+    self.expanded.contains(&id)
+  }
+
+  fn set_windows(&mut self, mut windows: Vec<Window>) {
+    windows.sort_by_key(|w| w.z_index);
+    self.windows = windows;
+    self.window_index = self.window_index.min(self.windows.len().saturating_sub(1));
+  }
+
+  /// Load the selected window's tree, replacing the current one.
+  fn open_window(&mut self) {
+    self.unwatch();
+    self.root = None;
+    self.observation = None;
+    self.elements.clear();
+    self.expanded.clear();
+    self.rows.clear();
+    self.row_index = 0;
+
+    let Some(window) = self.windows.get(self.window_index) else {
+      return;
+    };
+    match self.allio.window_root(window.id) {
+      Ok(Some(root)) => {
+        let root_id = root.id;
+        self.root = Some(root_id);
+        self.elements.insert(root_id, root);
+        self.observation = self.allio.observe(root_id, ObserveConfig::default()).ok();
+        self.expand(root_id);
+        self.select(0);
+      }
+      Ok(None) => self.status = "Window has no accessibility tree".into(),
+      Err(e) => self.status = e.to_string(),
+    }
+  }
+
+  /// Fetch an element's children and show them.
+  fn expand(&mut self, id: ElementId) {
+    match self.allio.children(id, Recency::Current) {
+      Ok(children) => {
+        let ids = children.iter().map(|c| c.id).collect();
+        if let Some(element) = self.elements.get_mut(&id) {
+          element.children = Some(ids);
+        }
+        self
+          .elements
+          .extend(children.into_iter().map(|c| (c.id, c)));
+        self.expanded.insert(id);
+      }
+      Err(e) => self.status = e.to_string(),
+    }
+    self.rebuild_rows();
+  }
+
+  fn collapse(&mut self, id: ElementId) {
+    self.expanded.remove(&id);
+    self.rebuild_rows();
+  }
+
+  fn rebuild_rows(&mut self) {
+    let selected = self.rows.get(self.row_index).map(|row| row.id);
+    self.rows.clear();
+    let mut stack: Vec<Row> = self
+      .root
+      .map(|id| Row { id, depth: 0 })
+      .into_iter()
+      .collect();
+    while let Some(row) = stack.pop() {
+      let Some(element) = self.elements.get(&row.id) else {
+        continue;
+      };
+      self.rows.push(row);
+      if self.expanded.contains(&row.id) {
+        let children = element.children.iter().flatten().rev();
+        stack.extend(
+          children
+            .filter(|id| self.elements.contains_key(id))
+            .map(|&id| Row {
+              id,
+              depth: row.depth + 1,
+            }),
+        );
+      }
+    }
+    // Keep the selection on the same element when rows shift.
+    if let Some(index) = selected.and_then(|id| self.rows.iter().position(|r| r.id == id)) {
+      self.row_index = index;
+    }
+    self.row_index = self.row_index.min(self.rows.len().saturating_sub(1));
+  }
+
+  /// Select a row, watching its element so the detail pane stays live.
+  fn select(&mut self, index: usize) {
+    self.row_index = index.min(self.rows.len().saturating_sub(1));
+    let id = self.rows.get(self.row_index).map(|row| row.id);
+    if id == self.watched {
+      return;
+    }
+    self.unwatch();
+    if let Some(id) = id {
+      if self.allio.watch(id).is_ok() {
+        self.watched = Some(id);
+      }
+    }
+  }
+
+  fn unwatch(&mut self) {
+    if let Some(id) = self.watched.take() {
+      // Already unwatched if the element was removed.
+      self.allio.unwatch(id).ok();
+    }
+  }
+
+  /// Apply an event from `Allio::subscribe`.
+  pub(crate) fn handle_event(&mut self, event: Event) {
+    match event {
+      Event::WindowAdded { .. } | Event::WindowChanged { .. } | Event::WindowRemoved { .. } => {
+        let open = self.windows.get(self.window_index).map(|w| w.id);
+        self.set_windows(self.allio.all_windows());
+        if let Some(index) = open.and_then(|id| self.windows.iter().position(|w| w.id == id)) {
+          self.window_index = index;
+        } else if open.is_some() {
+          self.open_window();
+        }
+      }
+      Event::ElementAdded { element } => {
+        if self.windows.get(self.window_index).map(|w| w.id) == Some(element.window_id) {
+          self.elements.insert(element.id, element);
+          self.rebuild_rows();
+        }
+      }
+      Event::ElementChanged {
+        element_id,
+        changes,
+      } => {
+        if let Some(element) = self.elements.get_mut(&element_id) {
+          element.apply(&changes);
+          self.rebuild_rows();
+        }
+      }
+      Event::ElementRemoved { element_id, .. } => {
+        if self.elements.remove(&element_id).is_some() {
+          self.expanded.remove(&element_id);
+          if self.watched == Some(element_id) {
+            self.watched = None;
+          }
+          self.rebuild_rows();
+        }
+      }
+      Event::SyncInit(_)
+      | Event::ElementReplaced { .. }
+      | Event::FocusWindow { .. }
+      | Event::FocusElement { .. }
+      | Event::SelectionChanged { .. }
+      | Event::SubtreeChanged { .. }
+      | Event::MousePosition(_) => {}
+    }
+  }
+
+  pub(crate) fn handle_key(&mut self, key: KeyEvent) {
+    if key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL) {
+      self.quit = true;
+      return;
+    }
+    match std::mem::replace(&mut self.mode, Mode::Browse) {
+      Mode::Browse => self.browse_key(key.code),
+      Mode::Edit(text) => self.edit_key(key.code, text),
+      Mode::Actions(index) => self.actions_key(key.code, index),
+    }
+  }
+
+  #[allow(clippy::wildcard_enum_match_arm)] // Unbound keys do nothing
+  fn browse_key(&mut self, code: KeyCode) {
+    match (self.pane, code) {
+      (_, KeyCode::Char('q')) => self.quit = true,
+      (_, KeyCode::Tab) => {
+        self.pane = match self.pane {
+          Pane::Windows => Pane::Tree,
+          Pane::Tree => Pane::Windows,
+        }
+      }
+      (Pane::Windows, KeyCode::Up | KeyCode::Char('k')) => {
+        self.window_index = self.window_index.saturating_sub(1);
+      }
+      (Pane::Windows, KeyCode::Down | KeyCode::Char('j')) => {
+        self.window_index = (self.window_index + 1).min(self.windows.len().saturating_sub(1));
+      }
+      (Pane::Windows, KeyCode::Enter) => {
+        self.open_window();
+        self.pane = Pane::Tree;
+      }
+      (Pane::Tree, KeyCode::Up | KeyCode::Char('k')) => {
+        self.select(self.row_index.saturating_sub(1));
+      }
+      (Pane::Tree, KeyCode::Down | KeyCode::Char('j')) => self.select(self.row_index + 1),
+      (Pane::Tree, KeyCode::Enter | KeyCode::Right | KeyCode::Char('l')) => self.expand_selected(),
+      (Pane::Tree, KeyCode::Left | KeyCode::Char('h')) => self.collapse_selected(),
+      (Pane::Tree, KeyCode::Char('r')) => self.refresh_selected(),
+      (Pane::Tree, KeyCode::Char('e')) => {
+        if let Some(element) = self.selected() {
+          let text = element
+            .value
+            .clone()
+            .map(Value::into_string)
+            .unwrap_or_default();
+          self.mode = Mode::Edit(text);
+        }
+      }
+      (Pane::Tree, KeyCode::Char('a')) => {
+        if self.selected().is_some_and(|e| !e.actions.is_empty()) {
+          self.mode = Mode::Actions(0);
+        } else {
+          self.status = "No actions".into();
+        }
+      }
+      _ => {}
+    }
+  }
+
+  /// Expand the selected element, or step into it if already expanded.
+  fn expand_selected(&mut self) {
+    let Some(row) = self.rows.get(self.row_index).copied() else {
+      return;
+    };
+    if self.expanded.contains(&row.id) {
+      let has_children = self
+        .rows
+        .get(self.row_index + 1)
+        .is_some_and(|r| r.depth > row.depth);
+      if has_children {
+        self.select(self.row_index + 1);
+      }
+    } else {
+      self.expand(row.id);
+    }
+  }
+
+  /// Collapse the selected element, or step out to its parent.
+  fn collapse_selected(&mut self) {
+    let Some(row) = self.rows.get(self.row_index).copied() else {
+      return;
+    };
+    if self.expanded.contains(&row.id) {
+      self.collapse(row.id);
+    } else if let Some(parent) = self
+      .rows
+      .get(..self.row_index)
+      .and_then(|rows| rows.iter().rposition(|r| r.depth < row.depth))
+    {
+      self.select(parent);
+    }
+  }
+
+  fn refresh_selected(&mut self) {
+    let Some(id) = self.selected().map(|e| e.id) else {
+      return;
+    };
+    match self.allio.get(id, Recency::Current) {
+      Ok(element) => {
+        let children = self.elements.get(&id).and_then(|e| e.children.clone());
+        self.elements.insert(
+          id,
+          Element {
+            children,
+            ..element
+          },
+        );
+        self.status = "Refreshed".into();
+      }
+      Err(e) => self.status = e.to_string(),
+    }
+    self.rebuild_rows();
+  }
+
+  #[allow(clippy::wildcard_enum_match_arm)] // Unbound keys do nothing
+  fn edit_key(&mut self, code: KeyCode, mut text: String) {
+    match code {
+      KeyCode::Esc => return,
+      KeyCode::Enter => {
+        self.submit_value(&text);
+        return;
+      }
+      KeyCode::Backspace => {
+        text.pop();
+      }
+      KeyCode::Char(c) => text.push(c),
+      _ => {}
+    }
+    self.mode = Mode::Edit(text);
+  }
+
+  fn submit_value(&mut self, text: &str) {
+    let Some(element) = self.selected() else {
+      return;
+    };
+    let id = element.id;
+    let value = match element.role.value_type() {
+      ValueType::String => Some(Value::String(text.into())),
+      ValueType::Number => text.trim().parse::<f64>().ok().map(Value::Number),
+      ValueType::Boolean => text.trim().parse::<bool>().ok().map(Value::Boolean),
+      ValueType::Color | ValueType::None => None,
+    };
+    let Some(value) = value else {
+      self.status = format!("Can't set {:?} to {text:?}", element.role);
+      return;
+    };
+    self.status = match self.allio.set_value(id, &value) {
+      Ok(()) => "Value set".into(),
+      Err(e) => e.to_string(),
+    };
+  }
+
+  #[allow(clippy::wildcard_enum_match_arm)] // Unbound keys do nothing
+  fn actions_key(&mut self, code: KeyCode, index: usize) {
+    let actions = self.actions();
+    match code {
+      KeyCode::Esc => {}
+      KeyCode::Up | KeyCode::Char('k') => self.mode = Mode::Actions(index.saturating_sub(1)),
+      KeyCode::Down | KeyCode::Char('j') => {
+        self.mode = Mode::Actions((index + 1).min(actions.len().saturating_sub(1)));
+      }
+      KeyCode::Enter => {
+        if let (Some(id), Some(&action)) = (self.selected().map(|e| e.id), actions.get(index)) {
+          self.status = match self.allio.perform_action(id, action) {
+            Ok(()) => format!("Performed {action:?}"),
+            Err(e) => e.to_string(),
+          };
+        }
+      }
+      _ => self.mode = Mode::Actions(index),
+    }
+  }
+
+  /// Actions of the selected element.
+  pub(crate) fn actions(&self) -> Vec<Action> {
+    self
+      .selected()
+      .map(|e| e.actions.clone())
+      .unwrap_or_default()
+  }
+}
+
+impl Drop for App {
+  fn drop(&mut self) {
+    self.unwatch();
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use allio::a11y::Role;
+  use allio::mock::{MockDesktop, MockElement};
+
+  fn key(code: KeyCode) -> KeyEvent {
+    KeyEvent::new(code, KeyModifiers::NONE)
+  }
+
+  fn setup() -> (MockDesktop, App, allio::mock::MockNodeId) {
+    let desktop = MockDesktop::acquire();
+    let window = desktop.open_window("Notes", "Untitled");
+    let group = desktop
+      .add_element(window.node, MockElement::new(Role::Group).label("Form"))
+      .unwrap();
+    let field = desktop
+      .add_element(group, MockElement::new(Role::TextField).label("Name"))
+      .unwrap();
+    desktop.add_element(
+      group,
+      MockElement::new(Role::Button)
+        .label("Save")
+        .actions(&[Action::Press]),
+    );
+
+    let allio = desktop.start_allio().unwrap();
+    (desktop, App::new(allio), field)
+  }
+
+  fn labels(app: &App) -> Vec<(usize, String)> {
+    app
+      .rows
+      .iter()
+      .map(|row| {
+        (
+          row.depth,
+          app
+            .element(row.id)
+            .unwrap()
+            .label
+            .clone()
+            .unwrap_or_default(),
+        )
+      })
+      .collect()
+  }
+
+  #[test]
+  fn expands_and_collapses_rows() {
+    let (_desktop, mut app, _) = setup();
+    assert_eq!(labels(&app), [(0, "Untitled".into()), (1, "Form".into())]);
+
+    app.handle_key(key(KeyCode::Down));
+    app.handle_key(key(KeyCode::Enter));
+    assert_eq!(
+      labels(&app),
+      [
+        (0, "Untitled".into()),
+        (1, "Form".into()),
+        (2, "Name".into()),
+        (2, "Save".into())
+      ]
+    );
+
+    // Enter again steps into the group; Left steps back out, then collapses.
+    app.handle_key(key(KeyCode::Enter));
+    assert_eq!(app.selected().unwrap().label.as_deref(), Some("Name"));
+    app.handle_key(key(KeyCode::Left));
+    app.handle_key(key(KeyCode::Left));
+    assert_eq!(app.rows.len(), 2);
+    assert_eq!(app.selected().unwrap().label.as_deref(), Some("Form"));
+  }
+
+  #[test]
+  fn edits_values_and_applies_changes() {
+    let (desktop, mut app, field) = setup();
+    for code in [
+      KeyCode::Down,
+      KeyCode::Enter,
+      KeyCode::Enter,
+      KeyCode::Char('e'),
+    ] {
+      app.handle_key(key(code));
+    }
+    for c in "hi".chars() {
+      app.handle_key(key(KeyCode::Char(c)));
+    }
+    assert_eq!(app.mode, Mode::Edit("hi".into()));
+    app.handle_key(key(KeyCode::Enter));
+    assert_eq!(app.mode, Mode::Browse);
+    assert_eq!(
+      desktop.element(field).unwrap().value,
+      Some(Value::String("hi".into()))
+    );
+
+    let id = app.selected().unwrap().id;
+    app.handle_event(Event::ElementChanged {
+      element_id: id,
+      changes: vec![allio::ElementChange::Value {
+        old: None,
+        new: Some(Value::String("hi".into())),
+      }],
+    });
+    assert_eq!(
+      app.selected().unwrap().value,
+      Some(Value::String("hi".into()))
+    );
+
+    app.handle_event(Event::ElementRemoved {
+      element_id: id,
+      reason: allio::RemovalReason::Destroyed,
+    });
+    assert_eq!(app.rows.len(), 3);
+  }
+
+  #[test]
+  fn performs_actions() {
+    let (_desktop, mut app, _) = setup();
+    for code in [KeyCode::Down, KeyCode::Enter, KeyCode::Down, KeyCode::Down] {
+      app.handle_key(key(code));
+    }
+    assert_eq!(app.selected().unwrap().label.as_deref(), Some("Save"));
+    app.handle_key(key(KeyCode::Char('a')));
+    assert_eq!(app.mode, Mode::Actions(0));
+    app.handle_key(key(KeyCode::Enter));
+    assert_eq!(app.status, "Performed Press");
+
+    app.handle_key(key(KeyCode::Char('q')));
+    assert!(app.quit);
+  }
+}
@@ -0,0 +1,68 @@
+/*!
+Terminal accessibility inspector.
+
+Shows the open windows, an expandable element tree that updates live, and
+every field of the selected element. Elements can be edited and their actions
+performed from the keyboard, so it works over SSH where the web overlay
+isn't available.
+*/
+
+mod app;
+mod ui;
+
+use allio::Allio;
+use app::App;
+use ratatui::crossterm::event::{self, Event as TermEvent, KeyEventKind};
+use ratatui::DefaultTerminal;
+use std::io;
+use std::process::ExitCode;
+use std::time::Duration;
+
+/// How often to check for Allio events while waiting for keys.
+const TICK: Duration = Duration::from_millis(100);
+
+fn main() -> ExitCode {
+  env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("off")).init();
+
+  let allio = match Allio::new() {
+    Ok(allio) => allio,
+    Err(e) => {
+      eprintln!("allio-inspect: {e}");
+      return ExitCode::FAILURE;
+    }
+  };
+
+  let mut terminal = ratatui::init();
+  let result = run(&mut terminal, &allio);
+  ratatui::restore();
+  match result {
+    Ok(()) => ExitCode::SUCCESS,
+    Err(e) => {
+      eprintln!("allio-inspect: {e}");
+      ExitCode::FAILURE
+    }
+  }
+}
+
+fn run(terminal: &mut DefaultTerminal, allio: &Allio) -> io::Result<()> {
+  let mut events = allio.subscribe();
+  // Give the first poll a moment to find windows.
+  std::thread::sleep(TICK);
+  let mut app = App::new(allio.clone());
+
+  while !app.quit {
+    while let Ok(stamped) = events.try_recv() {
+      app.handle_event(stamped.event);
+    }
+    terminal.draw(|frame| ui::render(frame, &app))?;
+
+    if event::poll(TICK)? {
+      if let TermEvent::Key(key) = event::read()? {
+        if key.kind == KeyEventKind::Press {
+          app.handle_key(key);
+        }
+      }
+    }
+  }
+  Ok(())
+}
@@ -0,0 +1,225 @@
+/*!
+Rendering: window list, element tree and detail pane, with a status line.
+*/
+
+use crate::app::{App, Mode, Pane};
+use allio::a11y::Value;
+use allio::Element;
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::{Modifier, Style};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, Borders, List, ListItem, ListState, Paragraph, Wrap};
+use ratatui::Frame;
+use std::fmt::{Debug, Write as _};
+
+const HIGHLIGHT: Style = Style::new().add_modifier(Modifier::REVERSED);
+
+pub(crate) fn render(frame: &mut Frame<'_>, app: &App) {
+  let [main, status] =
+    Layout::vertical([Constraint::Min(0), Constraint::Length(1)]).areas(frame.area());
+  let [windows, tree, details] = Layout::horizontal([
+    Constraint::Percentage(25),
+    Constraint::Percentage(40),
+    Constraint::Percentage(35),
+  ])
+  .areas(main);
+
+  render_windows(frame, app, windows);
+  render_tree(frame, app, tree);
+  render_details(frame, app, details);
+  frame.render_widget(Paragraph::new(status_line(app)), status);
+}
+
+fn pane_block(title: &str, focused: bool) -> Block<'_> {
+  let block = Block::default().borders(Borders::ALL).title(title);
+  if focused {
+    block.border_style(Style::new().add_modifier(Modifier::BOLD))
+  } else {
+    block
+  }
+}
+
+fn render_windows(frame: &mut Frame<'_>, app: &App, area: Rect) {
+  let items: Vec<ListItem<'_>> = app
+    .windows
+    .iter()
+    .map(|w| ListItem::new(format!("{}: {}", w.app_name, w.title)))
+    .collect();
+  let list = List::new(items)
+    .block(pane_block("Windows", app.pane == Pane::Windows))
+    .highlight_style(HIGHLIGHT);
+  let mut state = ListState::default().with_selected(Some(app.window_index));
+  frame.render_stateful_widget(list, area, &mut state);
+}
+
+fn render_tree(frame: &mut Frame<'_>, app: &App, area: Rect) {
+  let items: Vec<ListItem<'_>> = app
+    .rows
+    .iter()
+    .filter_map(|row| {
+      let element = app.element(row.id)?;
+      let marker = if app.is_expanded(row.id) {
+        "▾"
+      } else if element.children.as_ref().is_some_and(Vec::is_empty) {
+        " "
+      } else {
+        "▸"
+      };
+      let indent = "  ".repeat(row.depth);
+      Some(ListItem::new(format!(
+        "{indent}{marker} {}",
+        summary(element)
+      )))
+    })
+    .collect();
+  let list = List::new(items)
+    .block(pane_block(
+      if app.is_live() { "Tree (live)" } else { "Tree" },
+      app.pane == Pane::Tree,
+    ))
+    .highlight_style(HIGHLIGHT);
+  let mut state = ListState::default().with_selected(Some(app.row_index));
+  frame.render_stateful_widget(list, area, &mut state);
+}
+
+/// `role "label" = value`
+fn summary(element: &Element) -> String {
+  let mut text = format!("{:?}", element.role);
+  if let Some(label) = &element.label {
+    write!(text, " {label:?}").ok();
+  }
+  if let Some(value) = &element.value {
+    write!(text, " = {}", value.clone().into_string()).ok();
+  }
+  text
+}
+
+fn render_details(frame: &mut Frame<'_>, app: &App, area: Rect) {
+  let block = pane_block("Element", false);
+  let Some(element) = app.selected() else {
+    frame.render_widget(Paragraph::new("").block(block), area);
+    return;
+  };
+
+  if let Mode::Actions(index) = app.mode {
+    let items: Vec<ListItem<'_>> = app
+      .actions()
+      .iter()
+      .map(|action| ListItem::new(format!("{action:?}")))
+      .collect();
+    let list = List::new(items)
+      .block(block.title("Perform action"))
+      .highlight_style(HIGHLIGHT);
+    let mut state = ListState::default().with_selected(Some(index));
+    frame.render_stateful_widget(list, area, &mut state);
+    return;
+  }
+
+  let lines: Vec<Line<'_>> = fields(element)
+    .into_iter()
+    .map(|(name, value)| Line::from(format!("{name}: {value}")))
+    .collect();
+  frame.render_widget(
+    Paragraph::new(lines)
+      .block(block)
+      .wrap(Wrap { trim: false }),
+    area,
+  );
+}
+
+fn opt<T: Debug>(value: Option<&T>) -> String {
+  value.map_or_else(|| "-".into(), |v| format!("{v:?}"))
+}
+
+/// Every `Element` field, by name.
+fn fields(e: &Element) -> Vec<(&'static str, String)> {
+  vec![
+    ("id", e.id.to_string()),
+    ("window_id", e.window_id.to_string()),
+    ("pid", e.pid.to_string()),
+    ("is_root", e.is_root.to_string()),
+    ("parent_id", opt(e.parent_id.as_ref())),
+    ("children", opt(e.children.as_ref().map(Vec::len).as_ref())),
+    ("role", format!("{:?}", e.role)),
+    ("platform_role", e.platform_role.clone()),
+    ("label", opt(e.label.as_ref())),
+    ("description", opt(e.description.as_ref())),
+    ("placeholder", opt(e.placeholder.as_ref())),
+    ("url", opt(e.url.as_ref())),
+    (
+      "value",
+      e.value
+        .clone()
+        .map_or_else(|| "-".into(), Value::into_string),
+    ),
+    ("bounds", opt(e.bounds.as_ref())),
+    ("focused", opt(e.focused.as_ref())),
+    ("disabled", e.disabled.to_string()),
+    ("selected", opt(e.selected.as_ref())),
+    ("expanded", opt(e.expanded.as_ref())),
+    ("row_index", opt(e.row_index.as_ref())),
+    ("column_index", opt(e.column_index.as_ref())),
+    ("row_count", opt(e.row_count.as_ref())),
+    ("column_count", opt(e.column_count.as_ref())),
+    ("actions", format!("{:?}", e.actions)),
+    ("identifier", opt(e.identifier.as_ref())),
+    ("is_fallback", e.is_fallback.to_string()),
+  ]
+}
+
+fn status_line(app: &App) -> String {
+  match &app.mode {
+    Mode::Edit(text) => format!("New value: {text}▏ (enter: set, esc: cancel)"),
+    Mode::Actions(_) => "enter: perform, esc: cancel".into(),
+    Mode::Browse => {
+      let keys =
+        "tab: pane  ↑↓: move  ⏎/→: expand  ←: collapse  e: edit  a: actions  r: refresh  q: quit";
+      if app.status.is_empty() {
+        keys.into()
+      } else {
+        format!("{}  |  {keys}", app.status)
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use allio::a11y::{Action, Role};
+  use allio::mock::{MockDesktop, MockElement};
+  use ratatui::backend::TestBackend;
+  use ratatui::crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+  use ratatui::Terminal;
+
+  #[test]
+  fn details_show_every_field() {
+    let desktop = MockDesktop::acquire();
+    let window = desktop.open_window("Notes", "Untitled");
+    desktop.add_element(
+      window.node,
+      MockElement::new(Role::Button)
+        .label("Save")
+        .actions(&[Action::Press]),
+    );
+    let allio = desktop.start_allio().unwrap();
+
+    let mut app = App::new(allio);
+    app.handle_key(KeyEvent::new(KeyCode::Down, KeyModifiers::NONE));
+    let mut terminal = Terminal::new(TestBackend::new(160, 40)).unwrap();
+    terminal.draw(|frame| render(frame, &app)).unwrap();
+
+    let screen: String = terminal
+      .backend()
+      .buffer()
+      .content()
+      .iter()
+      .map(ratatui::buffer::Cell::symbol)
+      .collect();
+    assert!(screen.contains("Notes: Untitled"));
+    assert!(screen.contains("Button \"Save\""));
+    for field in ["platform_role:", "actions: [Press]", "is_fallback: false"] {
+      assert!(screen.contains(field), "missing {field}");
+    }
+  }
+}
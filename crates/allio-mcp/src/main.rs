@@ -0,0 +1,37 @@
+/*!
+Allio over the Model Context Protocol.
+
+Speaks MCP (JSON-RPC 2.0, one message per line) on stdin/stdout so local agents
+can read and drive desktop apps. Tools and resources are answered with
+`allio_ws::dispatch`, so they behave exactly like the WebSocket API.
+
+Logs go to stderr; set `RUST_LOG` to see more.
+*/
+
+mod server;
+mod tools;
+
+use allio::Allio;
+use server::Server;
+use std::process::ExitCode;
+
+fn main() -> ExitCode {
+  env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("warn")).init();
+
+  let allio = match Allio::new() {
+    Ok(allio) => allio,
+    Err(e) => {
+      log::error!("[mcp] Failed to start Allio: {e}");
+      return ExitCode::FAILURE;
+    }
+  };
+
+  let server = Server::new(allio);
+  match server.serve(std::io::stdin().lock(), std::io::stdout().lock()) {
+    Ok(()) => ExitCode::SUCCESS,
+    Err(e) => {
+      log::error!("[mcp] stdio failed: {e}");
+      ExitCode::FAILURE
+    }
+  }
+}
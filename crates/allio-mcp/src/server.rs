@@ -0,0 +1,421 @@
+/*!
+MCP session: newline-delimited JSON-RPC 2.0 over any reader/writer pair.
+
+Requests are answered in order. Notifications (`notifications/initialized`,
+cancellations) need no reply and are ignored.
+*/
+
+use crate::tools;
+use allio::{Allio, AllioResult};
+use allio_ws::{RpcRequest, RpcResponse};
+use serde_json::{json, Value};
+use std::io::{self, BufRead, Write};
+
+/// Protocol versions we speak. The latest is offered when the client asks for another.
+const PROTOCOL_VERSIONS: &[&str] = &[LATEST_PROTOCOL_VERSION, "2025-03-26", "2024-11-05"];
+const LATEST_PROTOCOL_VERSION: &str = "2025-06-18";
+
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+const RESOURCE_NOT_FOUND: i64 = -32002;
+
+/// Answers Allio RPC requests.
+pub(crate) trait Dispatch {
+  fn dispatch(&self, request: RpcRequest) -> AllioResult<RpcResponse>;
+}
+
+impl Dispatch for Allio {
+  fn dispatch(&self, request: RpcRequest) -> AllioResult<RpcResponse> {
+    allio_ws::dispatch(self, request)
+  }
+}
+
+/// A JSON-RPC error reply.
+#[derive(Debug)]
+pub(crate) struct RpcError {
+  code: i64,
+  message: String,
+}
+
+impl RpcError {
+  pub(crate) fn invalid_params(message: impl Into<String>) -> Self {
+    Self {
+      code: INVALID_PARAMS,
+      message: message.into(),
+    }
+  }
+
+  pub(crate) fn resource_not_found(uri: &str) -> Self {
+    Self {
+      code: RESOURCE_NOT_FOUND,
+      message: format!("Resource not found: {uri}"),
+    }
+  }
+}
+
+/// MCP server answering with `dispatch`.
+pub(crate) struct Server<D> {
+  dispatch: D,
+}
+
+impl<D: Dispatch> Server<D> {
+  pub(crate) const fn new(dispatch: D) -> Self {
+    Self { dispatch }
+  }
+
+  /// Serve requests from `input` until it closes.
+  pub(crate) fn serve(&self, input: impl BufRead, mut output: impl Write) -> io::Result<()> {
+    for line in input.lines() {
+      let line = line?;
+      if line.trim().is_empty() {
+        continue;
+      }
+      let reply = match serde_json::from_str::<Value>(&line) {
+        Ok(message) => self.handle(&message),
+        Err(e) => Some(error_reply(
+          &Value::Null,
+          &RpcError {
+            code: PARSE_ERROR,
+            message: format!("Parse error: {e}"),
+          },
+        )),
+      };
+      if let Some(reply) = reply {
+        writeln!(output, "{reply}")?;
+        output.flush()?;
+      }
+    }
+    Ok(())
+  }
+
+  /// Reply to one message, or None if it needs no reply.
+  fn handle(&self, message: &Value) -> Option<Value> {
+    let id = message.get("id")?;
+    let Some(method) = message.get("method").and_then(Value::as_str) else {
+      // A response to a request we never sent, or garbage.
+      return Some(error_reply(
+        id,
+        &RpcError {
+          code: INVALID_REQUEST,
+          message: "Missing method".into(),
+        },
+      ));
+    };
+    let params = message.get("params").unwrap_or(&Value::Null);
+
+    let result = match method {
+      "initialize" => Ok(initialize(params)),
+      "ping" => Ok(json!({})),
+      "tools/list" => Ok(json!({ "tools": tools::definitions() })),
+      "tools/call" => self.call_tool(params),
+      "resources/list" => Ok(json!({ "resources": tools::resources(&self.dispatch) })),
+      "resources/read" => self.read_resource(params),
+      _ => Err(RpcError {
+        code: METHOD_NOT_FOUND,
+        message: format!("Method not found: {method}"),
+      }),
+    };
+
+    Some(match result {
+      Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+      Err(error) => {
+        log::warn!("[mcp] {method} failed: {}", error.message);
+        error_reply(id, &error)
+      }
+    })
+  }
+
+  fn call_tool(&self, params: &Value) -> Result<Value, RpcError> {
+    let name = params
+      .get("name")
+      .and_then(Value::as_str)
+      .ok_or_else(|| RpcError::invalid_params("Missing tool name"))?;
+    let args = params
+      .get("arguments")
+      .cloned()
+      .unwrap_or_else(|| json!({}));
+
+    // Failed Allio calls are tool results the agent can read, not protocol errors.
+    let (text, is_error) = match tools::call(&self.dispatch, name, &args)? {
+      Ok(result) => (result, false),
+      Err(error) => (error, true),
+    };
+    Ok(json!({
+      "content": [{ "type": "text", "text": text }],
+      "isError": is_error,
+    }))
+  }
+
+  fn read_resource(&self, params: &Value) -> Result<Value, RpcError> {
+    let uri = params
+      .get("uri")
+      .and_then(Value::as_str)
+      .ok_or_else(|| RpcError::invalid_params("Missing resource uri"))?;
+    let text = tools::read_resource(&self.dispatch, uri)?;
+    Ok(json!({
+      "contents": [{ "uri": uri, "mimeType": "application/json", "text": text }],
+    }))
+  }
+}
+
+fn initialize(params: &Value) -> Value {
+  let requested = params.get("protocolVersion").and_then(Value::as_str);
+  let version = requested
+    .filter(|v| PROTOCOL_VERSIONS.contains(v))
+    .unwrap_or(LATEST_PROTOCOL_VERSION);
+  json!({
+    "protocolVersion": version,
+    "capabilities": { "tools": {}, "resources": {} },
+    "serverInfo": { "name": "allio", "version": env!("CARGO_PKG_VERSION") },
+  })
+}
+
+fn error_reply(id: &Value, error: &RpcError) -> Value {
+  json!({
+    "jsonrpc": "2.0",
+    "id": id,
+    "error": { "code": error.code, "message": error.message },
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use allio::{AllioError, Bounds, Element, ElementId, ProcessId, Snapshot, Window, WindowId};
+  use std::cell::RefCell;
+
+  /// Dispatch stub: one window (id 7) whose root is element 1. Records requests.
+  #[derive(Default)]
+  struct Stub {
+    requests: RefCell<Vec<String>>,
+  }
+
+  fn window() -> Window {
+    Window {
+      id: WindowId(7),
+      title: "Untitled".into(),
+      app_name: "Notes".into(),
+      bounds: Bounds {
+        x: 0.0,
+        y: 0.0,
+        w: 800.0,
+        h: 600.0,
+      },
+      focused: true,
+      process_id: ProcessId(100),
+      z_index: 0,
+    }
+  }
+
+  fn element(id: u32) -> Element {
+    serde_json::from_value(json!({
+      "id": id, "window_id": 7, "pid": 100, "is_root": id == 1,
+      "parent_id": null, "children": null, "role": "textfield",
+      "platform_role": "AXTextField", "label": "Name", "description": null,
+      "placeholder": null, "url": null, "value": null, "bounds": null,
+      "focused": null, "disabled": false, "selected": null, "expanded": null,
+      "row_index": null, "column_index": null, "row_count": null,
+      "column_count": null, "actions": [], "identifier": null, "is_fallback": false,
+    }))
+    .unwrap()
+  }
+
+  impl Dispatch for Stub {
+    fn dispatch(&self, request: RpcRequest) -> AllioResult<RpcResponse> {
+      self.requests.borrow_mut().push(format!("{request:?}"));
+      match request {
+        RpcRequest::Snapshot => Ok(RpcResponse::Snapshot(Box::new(Snapshot {
+          windows: vec![window()],
+          elements: vec![],
+          focused_window: None,
+          focused_element: None,
+          selection: None,
+          z_order: vec![WindowId(7)],
+          mouse_position: None,
+          seq: 0,
+          session: 1,
+        }))),
+        RpcRequest::WindowRoot { .. } => Ok(RpcResponse::Element(Box::new(element(1)))),
+        RpcRequest::View { .. } => Ok(RpcResponse::View(None)),
+        RpcRequest::Get {
+          element_id: ElementId(2),
+          ..
+        } => Ok(RpcResponse::Element(Box::new(element(2)))),
+        RpcRequest::Set { .. } | RpcRequest::Perform { .. } => Ok(RpcResponse::Null),
+        RpcRequest::ElementAt { .. }
+        | RpcRequest::Get { .. }
+        | RpcRequest::GetMany { .. }
+        | RpcRequest::Subtree { .. }
+        | RpcRequest::Query { .. }
+        | RpcRequest::Children { .. }
+        | RpcRequest::ChildrenPage { .. }
+        | RpcRequest::Parent { .. }
+        | RpcRequest::PathOf { .. }
+        | RpcRequest::Resolve { .. }
+        | RpcRequest::SetVerified { .. }
+        | RpcRequest::PerformVerified { .. }
+        | RpcRequest::Watch { .. }
+        | RpcRequest::Unwatch { .. }
+        | RpcRequest::Observe { .. }
+        | RpcRequest::Unobserve { .. }
+        | RpcRequest::Subscribe { .. }
+        | RpcRequest::Unsubscribe { .. } => Err(AllioError::ElementNotFound(ElementId(3))),
+      }
+    }
+  }
+
+  /// Run a script of messages through a session, returning its replies.
+  fn run(stub: Stub, script: &[Value]) -> (Stub, Vec<Value>) {
+    let input: String = script.iter().map(|m| m.to_string() + "\n").collect();
+    let mut output = Vec::new();
+    let server = Server::new(stub);
+    server.serve(input.as_bytes(), &mut output).unwrap();
+    let replies = String::from_utf8(output)
+      .unwrap()
+      .lines()
+      .map(|line| serde_json::from_str(line).unwrap())
+      .collect();
+    (server.dispatch, replies)
+  }
+
+  fn request(id: u32, method: &str, params: &Value) -> Value {
+    json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params })
+  }
+
+  fn tool_call(id: u32, name: &str, arguments: &Value) -> Value {
+    request(
+      id,
+      "tools/call",
+      &json!({ "name": name, "arguments": arguments }),
+    )
+  }
+
+  #[test]
+  fn handshake_and_tool_listing() {
+    let (_, replies) = run(
+      Stub::default(),
+      &[
+        request(1, "initialize", &json!({ "protocolVersion": "2024-11-05" })),
+        json!({ "jsonrpc": "2.0", "method": "notifications/initialized" }),
+        request(2, "tools/list", &json!({})),
+        request(3, "no/such/method", &json!({})),
+      ],
+    );
+
+    // The notification gets no reply.
+    assert_eq!(replies.len(), 3);
+    assert_eq!(replies[0]["result"]["protocolVersion"], "2024-11-05");
+    assert!(replies[0]["result"]["capabilities"]["tools"].is_object());
+
+    let names: Vec<&str> = replies[1]["result"]["tools"]
+      .as_array()
+      .unwrap()
+      .iter()
+      .map(|t| t["name"].as_str().unwrap())
+      .collect();
+    assert_eq!(
+      names,
+      [
+        "windows",
+        "element_at",
+        "get",
+        "children",
+        "set_value",
+        "perform_action"
+      ]
+    );
+    assert_eq!(replies[2]["error"]["code"], METHOD_NOT_FOUND);
+  }
+
+  #[test]
+  fn tools_are_answered_by_dispatch() {
+    let (stub, replies) = run(
+      Stub::default(),
+      &[
+        tool_call(1, "windows", &json!({})),
+        tool_call(2, "get", &json!({ "element_id": 2 })),
+        tool_call(
+          3,
+          "set_value",
+          &json!({ "element_id": 2, "value": "hello" }),
+        ),
+        tool_call(
+          4,
+          "perform_action",
+          &json!({ "element_id": 2, "action": "press" }),
+        ),
+        tool_call(5, "get", &json!({ "element_id": 3 })),
+        tool_call(6, "get", &json!({})),
+        tool_call(7, "no_such_tool", &json!({})),
+      ],
+    );
+
+    let text = |i: usize| replies[i]["result"]["content"][0]["text"].as_str().unwrap();
+    let windows: Value = serde_json::from_str(text(0)).unwrap();
+    assert_eq!(windows[0]["title"], "Untitled");
+    let element: Value = serde_json::from_str(text(1)).unwrap();
+    assert_eq!(element["label"], "Name");
+    assert_eq!(replies[2]["result"]["isError"], false);
+
+    // Allio errors are tool results; malformed calls are protocol errors.
+    assert_eq!(replies[4]["result"]["isError"], true);
+    let error: Value = serde_json::from_str(text(4)).unwrap();
+    assert_eq!(
+      error,
+      json!({
+        "error": { "kind": "ElementNotFound", "details": 3 },
+        "message": "Element not found: 3",
+      })
+    );
+    assert_eq!(replies[5]["error"]["code"], INVALID_PARAMS);
+    assert_eq!(replies[6]["error"]["code"], INVALID_PARAMS);
+
+    let requests = stub.requests.borrow();
+    assert!(requests[2].starts_with("Set"));
+    assert!(requests[2].contains("hello"));
+    assert!(requests[3].contains("Press"));
+  }
+
+  #[test]
+  fn windows_are_resources() {
+    let (_, replies) = run(
+      Stub::default(),
+      &[
+        request(1, "resources/list", &json!({})),
+        request(2, "resources/read", &json!({ "uri": "allio://windows/7" })),
+        request(3, "resources/read", &json!({ "uri": "allio://windows/8" })),
+      ],
+    );
+
+    let resources = replies[0]["result"]["resources"].as_array().unwrap();
+    assert_eq!(resources.len(), 1);
+    assert_eq!(resources[0]["uri"], "allio://windows/7");
+    assert_eq!(resources[0]["name"], "Notes: Untitled");
+
+    let text = replies[1]["result"]["contents"][0]["text"]
+      .as_str()
+      .unwrap();
+    let snapshot: Value = serde_json::from_str(text).unwrap();
+    assert_eq!(snapshot["window"]["id"], 7);
+    assert_eq!(snapshot["root"]["id"], 1);
+    assert_eq!(replies[2]["error"]["code"], RESOURCE_NOT_FOUND);
+  }
+
+  #[test]
+  fn malformed_lines_get_parse_errors() {
+    let input = "not json\n\n{\"jsonrpc\":\"2.0\",\"id\":1,\"method\":\"ping\"}\n";
+    let mut output = Vec::new();
+    Server::new(Stub::default())
+      .serve(input.as_bytes(), &mut output)
+      .unwrap();
+    let replies: Vec<Value> = String::from_utf8(output)
+      .unwrap()
+      .lines()
+      .map(|line| serde_json::from_str(line).unwrap())
+      .collect();
+    assert_eq!(replies[0]["error"]["code"], PARSE_ERROR);
+    assert_eq!(replies[1]["result"], json!({}));
+  }
+}
@@ -0,0 +1,187 @@
+/*!
+MCP tools and resources, answered with Allio RPC requests.
+
+Tool arguments are the RPC arguments, so each tool takes exactly what the
+matching WebSocket method does. Results are returned as JSON text.
+*/
+
+use crate::server::{Dispatch, RpcError};
+use allio::a11y::Action;
+use allio::{AllioError, AllioResult, Window};
+use allio_ws::{RpcRequest, RpcResponse};
+use serde_json::{json, Value};
+
+/// URI prefix of window snapshot resources, followed by the window ID.
+const WINDOW_URI: &str = "allio://windows/";
+
+/// Tool name and the RPC method it forwards to (`windows` is built from a snapshot).
+const TOOLS: &[(&str, &str)] = &[
+  ("element_at", "element_at"),
+  ("get", "get"),
+  ("children", "children_page"),
+  ("set_value", "set"),
+  ("perform_action", "perform"),
+];
+
+/// Tool definitions for `tools/list`.
+pub(crate) fn definitions() -> Value {
+  let element_id = json!({ "type": "integer", "description": "Element ID" });
+  let actions: Vec<Value> = Action::ALL
+    .iter()
+    .filter_map(|action| serde_json::to_value(action).ok())
+    .collect();
+  json!([
+    tool(
+      "windows",
+      "List open windows, front to back.",
+      &json!({}),
+      &[],
+    ),
+    tool(
+      "element_at",
+      "Get the deepest element at screen coordinates.",
+      &json!({ "x": { "type": "number" }, "y": { "type": "number" } }),
+      &["x", "y"],
+    ),
+    tool(
+      "get",
+      "Get an element by ID. Recency \"current\" refetches it from the OS.",
+      &json!({
+        "element_id": element_id,
+        "recency": { "type": "string", "enum": ["any", "current"] },
+      }),
+      &["element_id"],
+    ),
+    tool(
+      "children",
+      "Fetch a page of an element's children from the OS, with the total count and the offset of the next page.",
+      &json!({
+        "element_id": element_id,
+        "offset": { "type": "integer", "description": "First child to return (default 0)" },
+        "limit": { "type": "integer", "description": "Children to return (default 1000)" },
+      }),
+      &["element_id"],
+    ),
+    tool(
+      "set_value",
+      "Set an element's value: text for text fields, a number for sliders, a boolean for checkboxes.",
+      &json!({
+        "element_id": element_id,
+        "value": { "type": ["string", "number", "boolean"] },
+      }),
+      &["element_id", "value"],
+    ),
+    tool(
+      "perform_action",
+      "Perform an action on an element.",
+      &json!({
+        "element_id": element_id,
+        "action": { "type": "string", "enum": actions },
+      }),
+      &["element_id", "action"],
+    ),
+  ])
+}
+
+fn tool(name: &str, description: &str, properties: &Value, required: &[&str]) -> Value {
+  json!({
+    "name": name,
+    "description": description,
+    "inputSchema": { "type": "object", "properties": properties, "required": required },
+  })
+}
+
+/// Run a tool. The outer error is for calls that can't be made (unknown tool,
+/// bad arguments); the inner one is a failed Allio request, as the same
+/// `{ error, message }` JSON the WebSocket API answers with.
+pub(crate) fn call(
+  dispatch: &impl Dispatch,
+  name: &str,
+  args: &Value,
+) -> Result<Result<String, String>, RpcError> {
+  let result = if name == "windows" {
+    windows(dispatch).map(|windows| json!(windows))
+  } else {
+    let method = TOOLS
+      .iter()
+      .find_map(|&(tool, method)| (tool == name).then_some(method))
+      .ok_or_else(|| RpcError::invalid_params(format!("Unknown tool: {name}")))?;
+    let response = dispatch.dispatch(request(method, args)?);
+    response.and_then(|response| {
+      serde_json::to_value(response).map_err(|e| AllioError::Internal(e.to_string()))
+    })
+  };
+  Ok(
+    result
+      .map(|value| text(&value))
+      .map_err(|error| text(&allio_ws::error_json(&error))),
+  )
+}
+
+/// One resource per open window, for `resources/list`.
+pub(crate) fn resources(dispatch: &impl Dispatch) -> Vec<Value> {
+  // A failed snapshot just lists nothing.
+  windows(dispatch)
+    .unwrap_or_default()
+    .iter()
+    .map(|window| {
+      json!({
+        "uri": format!("{WINDOW_URI}{}", window.id),
+        "name": format!("{}: {}", window.app_name, window.title),
+        "description": "Window info, root element and simplified accessibility tree",
+        "mimeType": "application/json",
+      })
+    })
+    .collect()
+}
+
+/// Read a window snapshot: the window, its root element and a simplified tree.
+pub(crate) fn read_resource(dispatch: &impl Dispatch, uri: &str) -> Result<String, RpcError> {
+  let not_found = || RpcError::resource_not_found(uri);
+  let window_id: u32 = uri
+    .strip_prefix(WINDOW_URI)
+    .and_then(|id| id.parse().ok())
+    .ok_or_else(not_found)?;
+  let window = windows(dispatch)
+    .unwrap_or_default()
+    .into_iter()
+    .find(|w| w.id.0 == window_id)
+    .ok_or_else(not_found)?;
+
+  let root = dispatch
+    .dispatch(request("window_root", &json!({ "window_id": window_id }))?)
+    .map_err(|e| RpcError::invalid_params(e.to_string()))?;
+  let RpcResponse::Element(root) = root else {
+    return Err(not_found());
+  };
+  let args = json!({ "element_id": root.id, "recency": "current" });
+  let tree = dispatch
+    .dispatch(request("view", &args)?)
+    .map_err(|e| RpcError::invalid_params(e.to_string()))?;
+  Ok(text(
+    &json!({ "window": window, "root": root, "tree": tree }),
+  ))
+}
+
+/// Windows from a snapshot, front to back.
+fn windows(dispatch: &impl Dispatch) -> AllioResult<Vec<Window>> {
+  let RpcResponse::Snapshot(snapshot) = dispatch.dispatch(RpcRequest::Snapshot)? else {
+    return Err(AllioError::Internal("Unexpected snapshot response".into()));
+  };
+  let mut windows = snapshot.windows;
+  windows.sort_by_key(|w| w.z_index);
+  Ok(windows)
+}
+
+/// Build an RPC request the way the WebSocket server parses one.
+fn request(method: &str, args: &Value) -> Result<RpcRequest, RpcError> {
+  serde_json::from_value(json!({ "method": method, "args": args }))
+    .map_err(|e| RpcError::invalid_params(format!("Invalid arguments: {e}")))
+}
+
+fn text(value: &Value) -> String {
+  if value.is_null() {
+    return "OK".into();
+  }
+  serde_json::to_string_pretty(value).unwrap_or_else(|_| value.to_string())
+}
@@ -0,0 +1,347 @@
+/*!
+Serve a replayed journal over the same WebSocket protocol as a live server.
+
+Clients get a `SyncInit` of the replayed state on connect, then the recorded
+events as they are replayed. Reads are answered from the replayed state; writes,
+watches and subscriptions have no desktop behind them and fail.
+*/
+
+use crate::rpc::{error_json, parse_request, response_json, RpcRequest, RpcResponse};
+use allio::journal::Replayer;
+use allio::{
+  AllioError, AllioResult, ChildrenPage, Element, ElementId, ElementNode, SequencedEvent, Snapshot,
+};
+use axum::{
+  extract::ws::{Message, WebSocket, WebSocketUpgrade},
+  extract::State,
+  response::Response,
+  routing::get,
+  Router,
+};
+use log::error;
+use serde_json::Value;
+use tower_http::cors::{Any, CorsLayer};
+
+/// Start a WebSocket server for `replayer`. Play the journal separately, e.g.
+/// with [`Replayer::play`] on another thread.
+pub async fn start_journal_server(replayer: Replayer, port: u16) {
+  let cors = CorsLayer::new()
+    .allow_origin(Any)
+    .allow_methods(Any)
+    .allow_headers(Any);
+
+  let app = Router::new()
+    .route("/ws", get(websocket_handler))
+    .layer(cors)
+    .with_state(replayer);
+
+  let addr = format!("127.0.0.1:{port}");
+  let listener = match tokio::net::TcpListener::bind(&addr).await {
+    Ok(l) => l,
+    Err(e) => {
+      error!("Failed to bind WebSocket server to {addr}: {e}");
+      std::process::exit(1);
+    }
+  };
+
+  println!("WebSocket server (replaying journal): ws://{addr}/ws");
+
+  if let Err(e) = axum::serve(listener, app).await {
+    error!("WebSocket server failed: {e}");
+    std::process::exit(1);
+  }
+}
+
+async fn websocket_handler(ws: WebSocketUpgrade, State(replayer): State<Replayer>) -> Response {
+  ws.on_upgrade(move |socket| handle_websocket(socket, replayer))
+}
+
+async fn handle_websocket(mut socket: WebSocket, replayer: Replayer) {
+  // Subscribe before the snapshot, so nothing falls between the two.
+  let mut rx = replayer.subscribe();
+  let init = SequencedEvent::sync_init(replayer.snapshot());
+  let mut last = init.seq;
+  if send(&mut socket, &init).await.is_none() {
+    return;
+  }
+
+  loop {
+    tokio::select! {
+        msg = socket.recv() => {
+            match msg {
+                Some(Ok(Message::Text(text))) => {
+                    let response = handle_request(&text, &replayer);
+                    if socket.send(Message::Text(response)).await.is_err() {
+                        break;
+                    }
+                }
+                Some(Ok(Message::Close(_)) | Err(_)) | None => break,
+                Some(Ok(Message::Binary(_) | Message::Ping(_) | Message::Pong(_))) => {}
+            }
+        }
+
+        event = rx.recv() => {
+            match event {
+                Ok(event) if event.seq <= last => {}
+                Ok(event) => {
+                    last = event.seq;
+                    if send(&mut socket, &event).await.is_none() {
+                        break;
+                    }
+                }
+                Err(async_broadcast::RecvError::Overflowed(n)) => {
+                    log::warn!("[ws] Client lagged, dropped {n} replayed events");
+                }
+                Err(async_broadcast::RecvError::Closed) => break,
+            }
+        }
+    }
+  }
+}
+
+async fn send(socket: &mut WebSocket, event: &SequencedEvent) -> Option<()> {
+  let msg = serde_json::to_string(event).ok()?;
+  socket.send(Message::Text(msg)).await.ok()
+}
+
+fn handle_request(request: &str, replayer: &Replayer) -> String {
+  let req: Value = match serde_json::from_str(request) {
+    Ok(v) => v,
+    Err(e) => {
+      return error_json(&AllioError::InvalidRequest(format!("Invalid JSON: {e}"))).to_string()
+    }
+  };
+
+  let id = req.get("id").cloned().unwrap_or(Value::Null);
+  let method = req.get("method").and_then(Value::as_str).unwrap_or("");
+  let args = req.get("args").cloned().unwrap_or(Value::Null);
+
+  let mut response = match parse_request(method, &args) {
+    Ok(request) => response_json(method, dispatch(&replayer.snapshot(), &request)),
+    Err(error) => error,
+  };
+  if let Some(obj) = response.as_object_mut() {
+    obj.insert("id".to_string(), id);
+  }
+  response.to_string()
+}
+
+/// Answer a request from replayed state.
+fn dispatch(snapshot: &Snapshot, request: &RpcRequest) -> AllioResult<RpcResponse> {
+  let find = |id: ElementId| snapshot.elements.iter().find(|e| e.id == id);
+  let get = |id: ElementId| find(id).cloned().ok_or(AllioError::ElementNotFound(id));
+  let page = |element: Element, offset: usize, limit: usize| {
+    let ids = element.children.unwrap_or_default();
+    let end = offset.saturating_add(limit).min(ids.len());
+    let children = ids
+      .get(offset.min(end)..end)
+      .unwrap_or_default()
+      .iter()
+      .filter_map(|&id| find(id).cloned())
+      .collect();
+    ChildrenPage {
+      children,
+      total: Some(ids.len()),
+      next_offset: (end < ids.len()).then_some(end),
+    }
+  };
+
+  match request {
+    RpcRequest::Snapshot => Ok(RpcResponse::Snapshot(Box::new(snapshot.clone()))),
+    RpcRequest::Get { element_id, .. } => {
+      get(*element_id).map(|e| RpcResponse::Element(Box::new(e)))
+    }
+    RpcRequest::GetMany { element_ids, .. } => element_ids
+      .iter()
+      .map(|&id| get(id))
+      .collect::<Result<_, _>>()
+      .map(RpcResponse::Elements),
+    RpcRequest::Subtree {
+      element_id,
+      depth,
+      flat,
+      ..
+    } => {
+      let tree = subtree(snapshot, get(*element_id)?, *depth);
+      Ok(if *flat {
+        RpcResponse::Elements(tree.flatten())
+      } else {
+        RpcResponse::Subtree(Box::new(tree))
+      })
+    }
+    RpcRequest::WindowRoot { window_id } => snapshot
+      .elements
+      .iter()
+      .find(|e| e.is_root && e.window_id == *window_id)
+      .map(|e| RpcResponse::Element(Box::new(e.clone())))
+      .ok_or(AllioError::WindowNotFound(*window_id)),
+    RpcRequest::Children {
+      element_id,
+      max_children,
+    } => {
+      let limit = max_children.unwrap_or(usize::MAX);
+      Ok(RpcResponse::Elements(
+        page(get(*element_id)?, 0, limit).children,
+      ))
+    }
+    RpcRequest::ChildrenPage {
+      element_id,
+      offset,
+      limit,
+      ..
+    } => Ok(RpcResponse::ChildrenPage(page(
+      get(*element_id)?,
+      *offset,
+      *limit,
+    ))),
+    RpcRequest::Parent { element_id } => {
+      let element = get(*element_id)?;
+      let parent = element.parent_id.and_then(find).cloned();
+      Ok(RpcResponse::OptionalElement(parent.map(Box::new)))
+    }
+    RpcRequest::ElementAt { .. }
+    | RpcRequest::PathOf { .. }
+    | RpcRequest::Resolve { .. }
+    | RpcRequest::Query { .. }
+    | RpcRequest::View { .. }
+    | RpcRequest::Set { .. }
+    | RpcRequest::Perform { .. }
+    | RpcRequest::SetVerified { .. }
+    | RpcRequest::PerformVerified { .. }
+    | RpcRequest::Watch { .. }
+    | RpcRequest::Unwatch { .. }
+    | RpcRequest::Observe { .. }
+    | RpcRequest::Unobserve { .. }
+    | RpcRequest::Subscribe { .. }
+    | RpcRequest::Unsubscribe { .. } => Err(AllioError::NotSupported(
+      "Not available while replaying a journal: there is no desktop behind it".into(),
+    )),
+  }
+}
+
+/// The replayed subtree at `element`, `depth` levels deep.
+fn subtree(snapshot: &Snapshot, element: Element, depth: Option<usize>) -> ElementNode {
+  let children = if depth == Some(0) {
+    Vec::new()
+  } else {
+    let below = depth.map(|d| d.saturating_sub(1));
+    element
+      .children
+      .iter()
+      .flatten()
+      .filter_map(|&id| snapshot.elements.iter().find(|e| e.id == id))
+      .map(|child| subtree(snapshot, child.clone(), below))
+      .collect()
+  };
+  ElementNode { element, children }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use allio::journal::{JournalReader, JournalWriter, Speed};
+  use allio::{Event, WindowId};
+  use serde_json::json;
+
+  fn element(id: u32, parent: Option<u32>, children: &[u32]) -> Element {
+    serde_json::from_value(json!({
+      "id": id, "window_id": 1, "pid": 100, "is_root": parent.is_none(),
+      "parent_id": parent, "children": children, "role": "group",
+      "platform_role": "AXGroup", "label": null, "description": null,
+      "placeholder": null, "url": null, "value": null, "bounds": null,
+      "focused": null, "disabled": false, "selected": null, "expanded": null,
+      "row_index": null, "column_index": null, "row_count": null,
+      "column_count": null, "actions": [], "identifier": null, "is_fallback": false,
+    }))
+    .unwrap()
+  }
+
+  fn journal() -> Vec<u8> {
+    let snapshot = Snapshot {
+      windows: vec![],
+      elements: vec![element(1, None, &[2])],
+      focused_window: None,
+      focused_element: None,
+      selection: None,
+      z_order: vec![],
+      mouse_position: None,
+      seq: 4,
+      session: 1,
+    };
+    let mut writer = JournalWriter::new(Vec::new(), snapshot).unwrap();
+    let added = Event::ElementAdded {
+      element: element(2, Some(1), &[]),
+    };
+    writer.write(&SequencedEvent::new(5, added)).unwrap();
+    writer.into_inner().unwrap()
+  }
+
+  #[test]
+  fn reads_are_answered_from_replayed_state() {
+    let bytes = journal();
+    let journal = JournalReader::new(bytes.as_slice()).unwrap();
+    let replayer = Replayer::new(journal.snapshot().clone());
+    replayer.play(journal, Speed::Unpaced).unwrap();
+    let snapshot = replayer.snapshot();
+    assert_eq!(snapshot.seq, 5);
+
+    let root = dispatch(
+      &snapshot,
+      &RpcRequest::WindowRoot {
+        window_id: WindowId(1),
+      },
+    )
+    .unwrap();
+    assert!(matches!(root, RpcResponse::Element(e) if e.id == ElementId(1)));
+    let children = dispatch(
+      &snapshot,
+      &RpcRequest::Children {
+        element_id: ElementId(1),
+        max_children: None,
+      },
+    )
+    .unwrap();
+    assert!(matches!(children, RpcResponse::Elements(c) if c.len() == 1));
+    let cut = dispatch(
+      &snapshot,
+      &RpcRequest::Children {
+        element_id: ElementId(1),
+        max_children: Some(0),
+      },
+    )
+    .unwrap();
+    assert!(matches!(cut, RpcResponse::Elements(c) if c.is_empty()));
+    let page = dispatch(
+      &snapshot,
+      &RpcRequest::ChildrenPage {
+        element_id: ElementId(1),
+        offset: 0,
+        limit: 0,
+        recency: None,
+      },
+    )
+    .unwrap();
+    assert!(
+      matches!(page, RpcResponse::ChildrenPage(p) if p.total == Some(1) && p.next_offset == Some(0))
+    );
+    let tree = dispatch(
+      &snapshot,
+      &RpcRequest::Subtree {
+        element_id: ElementId(1),
+        depth: None,
+        recency: None,
+        flat: false,
+      },
+    )
+    .unwrap();
+    assert!(matches!(tree, RpcResponse::Subtree(t) if t.children[0].element.id == ElementId(2)));
+
+    let response: Value = serde_json::from_str(&handle_request(
+      r#"{"id":3,"method":"set","args":{"element_id":2,"value":"x"}}"#,
+      &replayer,
+    ))
+    .unwrap();
+    assert_eq!(response["id"], 3);
+    assert_eq!(response["error"]["kind"], "NotSupported");
+  }
+}
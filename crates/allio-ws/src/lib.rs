@@ -1,8 +1,13 @@
 /*! Allio JSON-RPC over WebSocket. */
 
+mod journal;
+mod replay;
+mod resources;
 mod rpc;
 mod server;
+mod subscription;
 
-pub use rpc::{dispatch, dispatch_json, RpcRequest, RpcResponse};
+pub use journal::start_journal_server;
+pub use rpc::{dispatch, dispatch_json, error_json, RpcRequest, RpcResponse};
 pub use server::{start_server, CustomRpcHandler, WebSocketState, DEFAULT_WS_PORT};
-
+pub use subscription::{EventFilter, EventKind};
@@ -0,0 +1,207 @@
+/*!
+Replay buffer for resuming event streams.
+
+The server keeps the most recent events so a reconnecting client can ask to
+resume after the last sequence number it saw (`/ws?resume=N`) and receive only
+what it missed. When the missed events are no longer buffered, the client gets
+a fresh `SyncInit` instead.
+
+Mouse positions are ephemeral: a resumed client only needs the next one. They
+are kept as sequence ranges that take no slot, so a moving mouse doesn't evict
+the events that matter, and are not replayed.
+*/
+
+use allio::{Event, SequencedEvent};
+use std::collections::VecDeque;
+use std::sync::Arc;
+
+/// An event with its JSON, serialized once for all connections.
+pub(crate) struct Broadcast {
+  pub(crate) event: SequencedEvent,
+  pub(crate) json: String,
+}
+
+impl Broadcast {
+  pub(crate) fn new(event: SequencedEvent) -> Option<Self> {
+    let json = serde_json::to_string(&event).ok()?;
+    Some(Self { event, json })
+  }
+
+  pub(crate) const fn seq(&self) -> u64 {
+    self.event.seq
+  }
+
+  const fn is_ephemeral(&self) -> bool {
+    matches!(self.event.event, Event::MousePosition(_))
+  }
+}
+
+/// A buffered event, or a run of ephemeral ones by sequence number only.
+enum Slot {
+  Event(Arc<Broadcast>),
+  Skipped { last: u64 },
+}
+
+impl Slot {
+  fn last(&self) -> u64 {
+    match self {
+      Self::Event(broadcast) => broadcast.seq(),
+      Self::Skipped { last } => *last,
+    }
+  }
+}
+
+/// The most recent broadcasts, in sequence order and without gaps.
+pub(crate) struct ReplayBuffer {
+  /// Events kept, not counting skipped ones.
+  capacity: usize,
+  events: usize,
+  slots: VecDeque<Slot>,
+  /// First sequence number the slots account for.
+  first_seq: u64,
+  /// Sequence number of the last pushed event, including evicted ones.
+  last_seq: u64,
+}
+
+impl ReplayBuffer {
+  pub(crate) fn new(capacity: usize) -> Self {
+    Self {
+      capacity,
+      events: 0,
+      slots: VecDeque::with_capacity(capacity),
+      first_seq: 1,
+      last_seq: 0,
+    }
+  }
+
+  pub(crate) fn push(&mut self, broadcast: Arc<Broadcast>) {
+    let seq = broadcast.seq();
+    // Events before a gap can't be replayed without it.
+    if seq != self.last_seq + 1 {
+      self.slots.clear();
+      self.events = 0;
+      self.first_seq = seq;
+    }
+    self.last_seq = seq;
+
+    if broadcast.is_ephemeral() {
+      if let Some(Slot::Skipped { last }) = self.slots.back_mut() {
+        *last = seq;
+      } else {
+        self.slots.push_back(Slot::Skipped { last: seq });
+      }
+      return;
+    }
+    if self.events == self.capacity {
+      self.evict();
+    }
+    self.slots.push_back(Slot::Event(broadcast));
+    self.events += 1;
+  }
+
+  /// Drop the oldest event, and the skipped run before it.
+  fn evict(&mut self) {
+    while let Some(slot) = self.slots.pop_front() {
+      self.first_seq = slot.last() + 1;
+      if matches!(slot, Slot::Event(_)) {
+        self.events -= 1;
+        return;
+      }
+    }
+  }
+
+  /// Every event after `seq` but ephemeral ones, or None if some of them are
+  /// no longer buffered (or `seq` is ahead of this stream, e.g. from before a
+  /// server restart).
+  pub(crate) fn since(&self, seq: u64) -> Option<Vec<Arc<Broadcast>>> {
+    if seq > self.last_seq || seq + 1 < self.first_seq {
+      return None;
+    }
+    let start = self.slots.partition_point(|slot| slot.last() <= seq);
+    let missed = self
+      .slots
+      .range(start..)
+      .filter_map(|slot| match slot {
+        Slot::Event(broadcast) => Some(Arc::clone(broadcast)),
+        Slot::Skipped { .. } => None,
+      })
+      .collect();
+    Some(missed)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use allio::Point;
+
+  fn broadcast(seq: u64) -> Arc<Broadcast> {
+    let event = Event::FocusWindow { window_id: None };
+    Arc::new(Broadcast::new(SequencedEvent::new(seq, event)).unwrap())
+  }
+
+  fn mouse(seq: u64) -> Arc<Broadcast> {
+    let event = Event::MousePosition(Point::new(0.0, 0.0));
+    Arc::new(Broadcast::new(SequencedEvent::new(seq, event)).unwrap())
+  }
+
+  fn seqs(missed: Option<Vec<Arc<Broadcast>>>) -> Option<Vec<u64>> {
+    missed.map(|m| m.iter().map(|b| b.seq()).collect())
+  }
+
+  #[test]
+  fn replays_buffered_events_after_seq() {
+    let mut buffer = ReplayBuffer::new(3);
+    assert_eq!(seqs(buffer.since(0)), Some(vec![]));
+    for seq in 1..=5 {
+      buffer.push(broadcast(seq));
+    }
+    assert_eq!(seqs(buffer.since(5)), Some(vec![]));
+    assert_eq!(seqs(buffer.since(3)), Some(vec![4, 5]));
+    assert_eq!(seqs(buffer.since(2)), Some(vec![3, 4, 5]));
+    // Event 2 was evicted.
+    assert_eq!(seqs(buffer.since(1)), None);
+    // Ahead of the stream.
+    assert_eq!(seqs(buffer.since(6)), None);
+  }
+
+  #[test]
+  fn gaps_force_a_resync() {
+    let mut buffer = ReplayBuffer::new(10);
+    for seq in [1, 2, 4, 5] {
+      buffer.push(broadcast(seq));
+    }
+    assert_eq!(seqs(buffer.since(4)), Some(vec![5]));
+    assert_eq!(seqs(buffer.since(3)), Some(vec![4, 5]));
+    assert_eq!(seqs(buffer.since(1)), None);
+    assert!(broadcast(7).json.contains("\"seq\":7"));
+  }
+
+  #[test]
+  fn mouse_positions_take_no_slot() {
+    let mut buffer = ReplayBuffer::new(2);
+    buffer.push(broadcast(1));
+    for seq in 2..=1000 {
+      buffer.push(mouse(seq));
+    }
+    buffer.push(broadcast(1001));
+    for seq in 1002..=2000 {
+      buffer.push(mouse(seq));
+    }
+
+    assert_eq!(buffer.slots.len(), 4);
+    assert_eq!(seqs(buffer.since(0)), Some(vec![1, 1001]));
+    // Resuming from a mouse position skips the rest of them.
+    assert_eq!(seqs(buffer.since(500)), Some(vec![1001]));
+    assert_eq!(seqs(buffer.since(1500)), Some(vec![]));
+
+    // Event 1 is evicted; the mouse positions after it needn't be replayed.
+    buffer.push(broadcast(2001));
+    assert_eq!(seqs(buffer.since(0)), None);
+    assert_eq!(seqs(buffer.since(1)), Some(vec![1001, 2001]));
+    buffer.push(broadcast(2002));
+    assert_eq!(seqs(buffer.since(1)), None);
+    assert_eq!(seqs(buffer.since(1000)), None);
+    assert_eq!(seqs(buffer.since(1001)), Some(vec![2001, 2002]));
+  }
+}
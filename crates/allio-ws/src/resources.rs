@@ -0,0 +1,122 @@
+/*!
+Observations and watches owned by one connection.
+
+Both are reference-counted in `Allio`, so one client releasing its observation
+or watch leaves other clients' untouched. Whatever a connection still holds is
+released when it closes.
+*/
+
+use allio::{Allio, AllioResult, ElementId, ObservationHandle, ObserveConfig};
+use std::collections::{HashMap, HashSet};
+
+/// Observations and watches held by a connection. Released on drop.
+pub(crate) struct Resources {
+  allio: Allio,
+  observations: HashMap<ElementId, ObservationHandle>,
+  watches: HashSet<ElementId>,
+}
+
+impl Resources {
+  pub(crate) fn new(allio: Allio) -> Self {
+    Self {
+      allio,
+      observations: HashMap::new(),
+      watches: HashSet::new(),
+    }
+  }
+
+  /// Observe a subtree. Observing it again on the same connection does nothing.
+  pub(crate) fn observe(&mut self, root_id: ElementId, config: ObserveConfig) -> AllioResult<()> {
+    if !self.observations.contains_key(&root_id) {
+      let handle = self.allio.observe(root_id, config)?;
+      self.observations.insert(root_id, handle);
+    }
+    Ok(())
+  }
+
+  /// Release this connection's observation of a subtree.
+  pub(crate) fn unobserve(&mut self, root_id: ElementId) {
+    self.observations.remove(&root_id);
+  }
+
+  /// Watch an element. Watching it again on the same connection does nothing.
+  pub(crate) fn watch(&mut self, element_id: ElementId) -> AllioResult<()> {
+    if !self.watches.contains(&element_id) {
+      self.allio.watch(element_id)?;
+      self.watches.insert(element_id);
+    }
+    Ok(())
+  }
+
+  /// Release this connection's watch of an element.
+  pub(crate) fn unwatch(&mut self, element_id: ElementId) -> AllioResult<()> {
+    if self.watches.remove(&element_id) {
+      self.allio.unwatch(element_id)?;
+    }
+    Ok(())
+  }
+}
+
+impl Drop for Resources {
+  fn drop(&mut self) {
+    for element_id in self.watches.drain() {
+      // Elements removed since are already unwatched.
+      self.allio.unwatch(element_id).ok();
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use allio::a11y::Role;
+  use allio::mock::{MockDesktop, MockElement};
+  use allio::Recency;
+
+  fn setup() -> (MockDesktop, Allio, ElementId) {
+    let desktop = MockDesktop::acquire();
+    let window = desktop.open_window("Notes", "Untitled");
+    desktop.add_element(window.node, MockElement::new(Role::TextField));
+
+    let allio = desktop.start_allio().unwrap();
+    let root = allio.window_root(window.id).unwrap().unwrap();
+    allio.children(root.id, Recency::Current).unwrap();
+    (desktop, allio, root.id)
+  }
+
+  #[test]
+  fn observations_are_shared_and_released_per_connection() {
+    let (_desktop, allio, root) = setup();
+    let mut first = Resources::new(allio.clone());
+    let mut second = Resources::new(allio.clone());
+    first.observe(root, ObserveConfig::default()).unwrap();
+    first.observe(root, ObserveConfig::default()).unwrap();
+    second.observe(root, ObserveConfig::default()).unwrap();
+
+    // One connection letting go leaves the other's observation running.
+    first.unobserve(root);
+    assert!(allio.is_observed(root));
+    drop(second);
+    assert!(!allio.is_observed(root));
+    assert!(allio
+      .observe(ElementId(999_999), ObserveConfig::default())
+      .is_err());
+  }
+
+  #[test]
+  fn watches_are_released_on_drop() {
+    let (_desktop, allio, root) = setup();
+    let field = allio.children(root, Recency::Any).unwrap()[0].id;
+    let mut first = Resources::new(allio.clone());
+    let mut second = Resources::new(allio.clone());
+    first.watch(field).unwrap();
+    second.watch(field).unwrap();
+    second.watch(field).unwrap();
+    assert!(allio.is_watched(field));
+
+    first.unwatch(field).unwrap();
+    assert!(allio.is_watched(field));
+    drop(second);
+    assert!(!allio.is_watched(field));
+  }
+}
@@ -4,13 +4,18 @@ RPC request/response types and dispatch.
 
 #![allow(missing_docs)]
 
+use crate::resources::Resources;
+use crate::subscription::EventFilter;
 use allio::a11y::{Action, Value as AXValue};
-use allio::{Allio, Element, ElementId, Snapshot, WindowId};
+use allio::{
+  Allio, AllioError, AllioResult, ChildrenPage, Element, ElementId, ElementNode, ElementPath,
+  Expect, Snapshot, Verification, ViewNode, WindowId,
+};
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value as JsonValue};
 use ts_rs::TS;
 
-/// Recency for RPC requests (serializable subset of allio::Recency).
+/// Recency for RPC requests (serializable subset of `allio::Recency`).
 #[derive(Debug, Clone, Copy, Deserialize, TS)]
 #[serde(rename_all = "snake_case")]
 #[ts(export)]
@@ -48,16 +53,66 @@ pub enum RpcRequest {
     #[serde(default)]
     recency: Option<Recency>,
   },
+  /// Get many elements in one call, in order. Fails if any of them can't be read.
+  GetMany {
+    element_ids: Vec<ElementId>,
+    #[serde(default)]
+    recency: Option<Recency>,
+  },
+  /// Load the subtree under `element_id` (inclusive) in one call, `depth` levels
+  /// below it (default: all). Nested, or listed in document order if `flat`.
+  Subtree {
+    element_id: ElementId,
+    #[serde(default)]
+    depth: Option<usize>,
+    #[serde(default)]
+    recency: Option<Recency>,
+    #[serde(default)]
+    flat: bool,
+  },
+  /// Find elements under `element_id` (inclusive) matching a CSS-like selector.
+  Query {
+    element_id: ElementId,
+    selector: String,
+    #[serde(default)]
+    recency: Option<Recency>,
+  },
+  /// Simplified projection of the subtree under `element_id`.
+  View {
+    element_id: ElementId,
+    #[serde(default)]
+    recency: Option<Recency>,
+  },
   /// Get root element for a window.
   WindowRoot { window_id: WindowId },
-  /// Discover children of element.
+  /// Discover children of element, only the first `max_children` if given.
+  /// `children_page` pages through the rest and gives the total.
   Children {
     element_id: ElementId,
+    #[serde(default)]
+    max_children: Option<usize>,
+  },
+  /// Discover a page of element's children: `limit` of them from `offset`, with
+  /// the total count. Attributes are only fetched for children in the page.
+  ChildrenPage {
+    element_id: ElementId,
+    #[serde(default)]
+    offset: usize,
     #[serde(default = "default_max_children")]
-    max_children: usize,
+    limit: usize,
+    #[serde(default)]
+    recency: Option<Recency>,
   },
   /// Discover parent of element.
   Parent { element_id: ElementId },
+  /// Portable path to an element, for finding it again in a later run.
+  PathOf { element_id: ElementId },
+  /// Find the element at a portable path.
+  Resolve {
+    path: ElementPath,
+    #[serde(default)]
+    recency: Option<Recency>,
+  },
   /// Set value on element.
   Set {
     element_id: ElementId,
@@ -68,6 +123,19 @@ pub enum RpcRequest {
     element_id: ElementId,
     action: Action,
   },
+  /// Set value on element, then read it back until it shows, setting it
+  /// again if the app ignored the write.
+  SetVerified {
+    element_id: ElementId,
+    value: AXValue,
+  },
+  /// Perform an action, then read the element back until `expect` holds,
+  /// performing it again if the app ignored it and the action is idempotent.
+  PerformVerified {
+    element_id: ElementId,
+    action: Action,
+    expect: Expect,
+  },
   /// Watch element for changes.
   Watch { element_id: ElementId },
   /// Stop watching element.
@@ -83,6 +151,10 @@ pub enum RpcRequest {
   },
   /// Stop observing a subtree.
   Unobserve { element_id: ElementId },
+  /// Receive only matching events on this connection. Returns a subscription ID.
+  Subscribe { filter: EventFilter },
+  /// Remove a subscription made with `subscribe`.
+  Unsubscribe { subscription_id: u32 },
 }
 
 const fn default_max_children() -> usize {
@@ -102,29 +174,57 @@ pub enum RpcResponse {
   OptionalElement(Option<Box<Element>>),
   /// List of elements.
   Elements(Vec<Element>),
+  /// A page of children.
+  ChildrenPage(ChildrenPage),
+  /// Nested element subtree.
+  Subtree(Box<ElementNode>),
+  /// Simplified tree projection.
+  View(Option<Box<ViewNode>>),
+  /// Portable element path.
+  Path(ElementPath),
+  /// A verified write that took.
+  Verification(Box<Verification>),
+  /// Subscription ID.
+  SubscriptionId(u32),
   /// No data.
   Null,
 }
 
 pub fn dispatch_json(allio: &Allio, method: &str, args: &JsonValue) -> JsonValue {
+  match parse_request(method, args) {
+    Ok(request) => response_json(method, dispatch(allio, request)),
+    Err(error) => error,
+  }
+}
+
+/// Parse a request, or the JSON error response for an invalid one.
+pub(crate) fn parse_request(method: &str, args: &JsonValue) -> Result<RpcRequest, JsonValue> {
   let request_value = json!({ "method": method, "args": args });
+  serde_json::from_value::<RpcRequest>(request_value).map_err(|e| {
+    log::warn!("[rpc] Invalid request for {method}: {e}");
+    error_json(&AllioError::InvalidRequest(e.to_string()))
+  })
+}
 
-  match serde_json::from_value::<RpcRequest>(request_value) {
-    Ok(request) => match dispatch(allio, request) {
-      Ok(response) => json!({ "result": response }),
-      Err(e) => {
-        log::warn!("[rpc] {method} failed: {e}");
-        json!({ "error": e })
-      }
-    },
+/// JSON response for a dispatch result.
+pub(crate) fn response_json(method: &str, result: AllioResult<RpcResponse>) -> JsonValue {
+  match result {
+    Ok(response) => json!({ "result": response }),
     Err(e) => {
-      log::warn!("[rpc] Invalid request for {method}: {e}");
-      json!({ "error": format!("Invalid request: {}", e) })
+      log::warn!("[rpc] {method} failed: {e}");
+      error_json(&e)
     }
   }
 }
 
-pub fn dispatch(allio: &Allio, request: RpcRequest) -> Result<RpcResponse, String> {
+/// JSON error response: the error itself (`{ kind, details }`) for clients to
+/// match on, and its message for people.
+pub fn error_json(error: &AllioError) -> JsonValue {
+  json!({ "error": error, "message": error.to_string() })
+}
+
+#[allow(clippy::too_many_lines)]
+pub fn dispatch(allio: &Allio, request: RpcRequest) -> AllioResult<RpcResponse> {
   match request {
     RpcRequest::Snapshot => {
       let snapshot = allio.snapshot();
@@ -132,7 +232,7 @@ pub fn dispatch(allio: &Allio, request: RpcRequest) -> Result<RpcResponse, Strin
     }
 
     RpcRequest::ElementAt { x, y } => {
-      let element = allio.element_at(x, y).map_err(|e| e.to_string())?;
+      let element = allio.element_at(x, y)?;
       Ok(RpcResponse::OptionalElement(element.map(Box::new)))
     }
 
@@ -140,60 +240,158 @@ pub fn dispatch(allio: &Allio, request: RpcRequest) -> Result<RpcResponse, Strin
       element_id,
       recency,
     } => {
-      let recency = recency.map(Into::into).unwrap_or(allio::Recency::Any);
-      let element = allio.get(element_id, recency).map_err(|e| e.to_string())?;
+      let recency = recency.map_or(allio::Recency::Any, Into::into);
+      let element = allio.get(element_id, recency)?;
       Ok(RpcResponse::Element(Box::new(element)))
     }
 
+    RpcRequest::GetMany {
+      element_ids,
+      recency,
+    } => {
+      let recency = recency.map_or(allio::Recency::Any, Into::into);
+      let elements = allio.get_many(&element_ids, recency)?;
+      Ok(RpcResponse::Elements(elements))
+    }
+
+    RpcRequest::Subtree {
+      element_id,
+      depth,
+      recency,
+      flat,
+    } => {
+      let recency = recency.map_or(allio::Recency::Current, Into::into);
+      let tree = allio.fetch_subtree(element_id, depth, recency)?;
+      Ok(if flat {
+        RpcResponse::Elements(tree.flatten())
+      } else {
+        RpcResponse::Subtree(Box::new(tree))
+      })
+    }
+
+    RpcRequest::Query {
+      element_id,
+      selector,
+      recency,
+    } => {
+      let recency = recency.map_or(allio::Recency::Any, Into::into);
+      let elements = allio.query(element_id, &selector, recency)?;
+      Ok(RpcResponse::Elements(elements))
+    }
+
+    RpcRequest::View {
+      element_id,
+      recency,
+    } => {
+      let recency = recency.map_or(allio::Recency::Any, Into::into);
+      let view = allio.view(element_id, recency)?;
+      Ok(RpcResponse::View(view.tree().cloned().map(Box::new)))
+    }
+
     RpcRequest::WindowRoot { window_id } => {
       let element = allio
-        .window_root(window_id)
-        .map_err(|e| e.to_string())?
-        .ok_or_else(|| format!("Window not found or has no accessibility: {window_id}"))?;
+        .window_root(window_id)?
+        .ok_or(AllioError::WindowNotFound(window_id))?;
       Ok(RpcResponse::Element(Box::new(element)))
     }
 
     RpcRequest::Children {
       element_id,
-      max_children: _max_children,
+      max_children: None,
     } => {
-      let children = allio
-        .children(element_id, allio::Recency::Current)
-        .map_err(|e| e.to_string())?;
+      let children = allio.children(element_id, allio::Recency::Current)?;
       Ok(RpcResponse::Elements(children))
     }
 
+    RpcRequest::Children {
+      element_id,
+      max_children: Some(max_children),
+    } => {
+      let page = allio.children_page(element_id, 0, max_children, allio::Recency::Current)?;
+      Ok(RpcResponse::Elements(page.children))
+    }
+
+    RpcRequest::ChildrenPage {
+      element_id,
+      offset,
+      limit,
+      recency,
+    } => {
+      let recency = recency.map_or(allio::Recency::Current, Into::into);
+      let page = allio.children_page(element_id, offset, limit, recency)?;
+      Ok(RpcResponse::ChildrenPage(page))
+    }
+
     RpcRequest::Parent { element_id } => {
-      let parent = allio
-        .parent(element_id, allio::Recency::Current)
-        .map_err(|e| e.to_string())?;
+      let parent = allio.parent(element_id, allio::Recency::Current)?;
       Ok(RpcResponse::OptionalElement(parent.map(Box::new)))
     }
 
+    RpcRequest::PathOf { element_id } => {
+      let path = allio.path_of(element_id)?;
+      Ok(RpcResponse::Path(path))
+    }
+
+    RpcRequest::Resolve { path, recency } => {
+      let recency = recency.map_or(allio::Recency::Current, Into::into);
+      let element = allio.resolve(&path, recency)?;
+      Ok(RpcResponse::OptionalElement(element.map(Box::new)))
+    }
+
     RpcRequest::Set { element_id, value } => {
-      allio
-        .set_value(element_id, &value)
-        .map_err(|e| e.to_string())?;
+      allio.set_value(element_id, &value)?;
       Ok(RpcResponse::Null)
     }
 
     RpcRequest::Perform { element_id, action } => {
-      allio
-        .perform_action(element_id, action)
-        .map_err(|e| e.to_string())?;
+      allio.perform_action(element_id, action)?;
       Ok(RpcResponse::Null)
     }
 
+    RpcRequest::SetVerified { element_id, value } => {
+      let verification = allio.set_value_verified(element_id, &value)?;
+      Ok(RpcResponse::Verification(Box::new(verification)))
+    }
+
+    RpcRequest::PerformVerified {
+      element_id,
+      action,
+      expect,
+    } => {
+      let verification = allio.perform_verified(element_id, action, &expect)?;
+      Ok(RpcResponse::Verification(Box::new(verification)))
+    }
+
     RpcRequest::Watch { element_id } => {
-      allio.watch(element_id).map_err(|e| e.to_string())?;
+      allio.watch(element_id)?;
       Ok(RpcResponse::Null)
     }
 
     RpcRequest::Unwatch { element_id } => {
-      allio.unwatch(element_id).map_err(|e| e.to_string())?;
+      allio.unwatch(element_id)?;
       Ok(RpcResponse::Null)
     }
 
+    // Both are scoped to a connection, which a bare dispatch doesn't have.
+    RpcRequest::Observe { .. } | RpcRequest::Unobserve { .. } => Err(AllioError::NotSupported(
+      "Observations are only available on WebSocket connections".into(),
+    )),
+    RpcRequest::Subscribe { .. } | RpcRequest::Unsubscribe { .. } => Err(AllioError::NotSupported(
+      "Subscriptions are only available on WebSocket connections".into(),
+    )),
+  }
+}
+
+/// Dispatch a request on a connection. Observations and watches are owned by
+/// the connection's `resources`, so they are released when it closes.
+pub(crate) fn dispatch_owned(
+  allio: &Allio,
+  resources: &mut Resources,
+  request: RpcRequest,
+) -> AllioResult<RpcResponse> {
+  match request {
+    RpcRequest::Watch { element_id } => resources.watch(element_id),
+    RpcRequest::Unwatch { element_id } => resources.unwatch(element_id),
     RpcRequest::Observe {
       element_id,
       depth,
@@ -203,19 +401,302 @@ pub fn dispatch(allio: &Allio, request: RpcRequest) -> Result<RpcResponse, Strin
         depth,
         wait_between: wait_between_ms.map(std::time::Duration::from_millis),
       };
-      // Note: We don't return the handle - the observation stays active until Unobserve is called.
-      // This is a simplification for the RPC interface. The handle's Drop won't clean up
-      // because we std::mem::forget it.
-      let handle = allio
-        .observe(element_id, config)
-        .map_err(|e| e.to_string())?;
-      std::mem::forget(handle);
-      Ok(RpcResponse::Null)
+      resources.observe(element_id, config)
     }
-
     RpcRequest::Unobserve { element_id } => {
-      allio.unobserve(element_id);
-      Ok(RpcResponse::Null)
+      resources.unobserve(element_id);
+      Ok(())
     }
+    request @ (RpcRequest::Snapshot
+    | RpcRequest::ElementAt { .. }
+    | RpcRequest::Get { .. }
+    | RpcRequest::GetMany { .. }
+    | RpcRequest::Subtree { .. }
+    | RpcRequest::Query { .. }
+    | RpcRequest::View { .. }
+    | RpcRequest::WindowRoot { .. }
+    | RpcRequest::Children { .. }
+    | RpcRequest::ChildrenPage { .. }
+    | RpcRequest::Parent { .. }
+    | RpcRequest::PathOf { .. }
+    | RpcRequest::Resolve { .. }
+    | RpcRequest::Set { .. }
+    | RpcRequest::Perform { .. }
+    | RpcRequest::SetVerified { .. }
+    | RpcRequest::PerformVerified { .. }
+    | RpcRequest::Subscribe { .. }
+    | RpcRequest::Unsubscribe { .. }) => return dispatch(allio, request),
+  }
+  .map(|()| RpcResponse::Null)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use allio::a11y::Role;
+  use allio::mock::{MockDesktop, MockElement, MockNodeId};
+
+  /// Desktop with one window holding a text field, and an Allio that has polled it.
+  fn setup() -> (MockDesktop, Allio, WindowId, MockNodeId) {
+    let desktop = MockDesktop::acquire();
+    let window = desktop.open_window("Notes", "Untitled");
+    let field = desktop
+      .add_element(window.node, MockElement::new(Role::TextField).label("Name"))
+      .unwrap();
+
+    let allio = desktop.start_allio().unwrap();
+    (desktop, allio, window.id, field)
+  }
+
+  fn call(allio: &Allio, method: &str, args: &JsonValue) -> JsonValue {
+    let response = dispatch_json(allio, method, args);
+    assert!(
+      response.get("error").is_none(),
+      "{method} failed: {response}"
+    );
+    response["result"].clone()
+  }
+
+  #[test]
+  fn window_root_children_get() {
+    let (_desktop, allio, window_id, _) = setup();
+
+    let root = call(&allio, "window_root", &json!({ "window_id": window_id }));
+    assert_eq!(root["role"], "window");
+
+    let children = call(&allio, "children", &json!({ "element_id": root["id"] }));
+    assert_eq!(children.as_array().unwrap().len(), 1);
+    assert_eq!(children[0]["label"], "Name");
+
+    let element = call(
+      &allio,
+      "get",
+      &json!({ "element_id": children[0]["id"], "recency": "current" }),
+    );
+    assert_eq!(element["role"], "textfield");
+  }
+
+  #[test]
+  fn children_honour_limits_and_pages() {
+    let (_desktop, allio, window_id, _) = setup();
+    let root = call(&allio, "window_root", &json!({ "window_id": window_id }));
+
+    let all = call(&allio, "children", &json!({ "element_id": root["id"] }));
+    assert_eq!(all.as_array().unwrap().len(), 1);
+    let cut = call(
+      &allio,
+      "children",
+      &json!({ "element_id": root["id"], "max_children": 0 }),
+    );
+    assert_eq!(cut, json!([]));
+
+    let page = call(
+      &allio,
+      "children_page",
+      &json!({ "element_id": root["id"], "limit": 1 }),
+    );
+    assert_eq!(page["total"], 1);
+    assert_eq!(page["next_offset"], JsonValue::Null);
+    assert_eq!(page["children"][0]["label"], "Name");
+  }
+
+  #[test]
+  fn subscribe_requires_a_connection() {
+    let (_desktop, allio, _, _) = setup();
+    let response = dispatch_json(&allio, "subscribe", &json!({ "filter": {} }));
+    assert_eq!(response["error"]["kind"], "NotSupported");
+    assert!(response["message"]
+      .as_str()
+      .unwrap()
+      .contains("WebSocket connections"));
+  }
+
+  #[test]
+  fn set_writes_through_to_platform() {
+    let (desktop, allio, window_id, field) = setup();
+    let root = call(&allio, "window_root", &json!({ "window_id": window_id }));
+    let children = call(&allio, "children", &json!({ "element_id": root["id"] }));
+
+    call(
+      &allio,
+      "set",
+      &json!({ "element_id": children[0]["id"], "value": "hello" }),
+    );
+    assert_eq!(
+      desktop.element(field).unwrap().value,
+      Some(AXValue::String("hello".into()))
+    );
+  }
+
+  #[test]
+  fn verified_writes_report_the_element() {
+    let (_desktop, allio, window_id, _) = setup();
+    let root = call(&allio, "window_root", &json!({ "window_id": window_id }));
+    let children = call(&allio, "children", &json!({ "element_id": root["id"] }));
+
+    let verified = call(
+      &allio,
+      "set_verified",
+      &json!({ "element_id": children[0]["id"], "value": "hello" }),
+    );
+    assert_eq!(verified["attempts"], 1);
+    assert_eq!(verified["element"]["value"], "hello");
+
+    let unsupported = dispatch_json(
+      &allio,
+      "perform_verified",
+      &json!({
+        "element_id": children[0]["id"],
+        "action": "expand",
+        "expect": { "expect": "expanded", "expanded": true },
+      }),
+    );
+    assert_eq!(unsupported["error"]["kind"], "ActionFailed");
+    assert!(unsupported["message"]
+      .as_str()
+      .unwrap()
+      .starts_with("Action 'Expand' failed"));
+  }
+
+  #[test]
+  fn query_matches_selector() {
+    let (_desktop, allio, window_id, _) = setup();
+    let root = call(&allio, "window_root", &json!({ "window_id": window_id }));
+
+    let matched = call(
+      &allio,
+      "query",
+      &json!({
+        "element_id": root["id"],
+        "selector": "window > textfield[label=\"Name\"]",
+        "recency": "current",
+      }),
+    );
+    assert_eq!(matched.as_array().unwrap().len(), 1);
+    assert_eq!(matched[0]["label"], "Name");
+
+    let invalid = dispatch_json(
+      &allio,
+      "query",
+      &json!({ "element_id": root["id"], "selector": "window >" }),
+    );
+    assert_eq!(invalid["error"]["kind"], "InvalidSelector");
+    assert_eq!(invalid["error"]["details"]["selector"], "window >");
+  }
+
+  #[test]
+  fn view_projects_subtree() {
+    let (_desktop, allio, window_id, _) = setup();
+    let root = call(&allio, "window_root", &json!({ "window_id": window_id }));
+
+    let view = call(
+      &allio,
+      "view",
+      &json!({ "element_id": root["id"], "recency": "current" }),
+    );
+    assert_eq!(view["role"], "window");
+    assert_eq!(view["children"][0]["label"], "Name");
+  }
+
+  #[test]
+  fn subtree_and_get_many_batch_reads() {
+    let (_desktop, allio, window_id, _) = setup();
+    let root = call(&allio, "window_root", &json!({ "window_id": window_id }));
+
+    let tree = call(&allio, "subtree", &json!({ "element_id": root["id"] }));
+    assert_eq!(tree["element"]["role"], "window");
+    assert_eq!(tree["children"][0]["element"]["label"], "Name");
+
+    let flat = call(
+      &allio,
+      "subtree",
+      &json!({ "element_id": root["id"], "recency": "any", "flat": true }),
+    );
+    let ids: Vec<_> = flat.as_array().unwrap().iter().map(|e| &e["id"]).collect();
+    assert_eq!(ids, [&root["id"], &tree["children"][0]["element"]["id"]]);
+
+    let many = call(
+      &allio,
+      "get_many",
+      &json!({ "element_ids": [ids[1], ids[0]], "recency": "current" }),
+    );
+    assert_eq!(many[0]["label"], "Name");
+    assert_eq!(many[1]["role"], "window");
+  }
+
+  #[test]
+  fn paths_resolve_back_to_elements() {
+    let (_desktop, allio, window_id, _) = setup();
+    let root = call(&allio, "window_root", &json!({ "window_id": window_id }));
+    let children = call(&allio, "children", &json!({ "element_id": root["id"] }));
+
+    let path = call(
+      &allio,
+      "path_of",
+      &json!({ "element_id": children[0]["id"] }),
+    );
+    assert_eq!(path["app"], "Notes");
+    assert_eq!(path["steps"][0]["role"], "textfield");
+
+    let resolved = call(&allio, "resolve", &json!({ "path": path }));
+    assert_eq!(resolved["id"], children[0]["id"]);
+  }
+
+  #[test]
+  fn errors_are_reported() {
+    let (_desktop, allio, _, _) = setup();
+
+    let foreign = dispatch_json(&allio, "get", &json!({ "element_id": 999_999 }));
+    assert_eq!(
+      foreign["error"],
+      json!({ "kind": "ForeignElementId", "details": 999_999 })
+    );
+    assert_eq!(
+      foreign["message"],
+      "Element ID 999999 is from another session"
+    );
+
+    let invalid = dispatch_json(&allio, "no_such_method", &json!({}));
+    assert_eq!(invalid["error"]["kind"], "InvalidRequest");
+    assert!(invalid["message"]
+      .as_str()
+      .unwrap()
+      .starts_with("Invalid request"));
+  }
+
+  #[test]
+  fn removed_elements_report_why() {
+    let (desktop, allio, window_id, field) = setup();
+    let root = call(&allio, "window_root", &json!({ "window_id": window_id }));
+    let children = call(&allio, "children", &json!({ "element_id": root["id"] }));
+
+    desktop.remove_element(field);
+    call(&allio, "children", &json!({ "element_id": root["id"] }));
+    let removed = dispatch_json(&allio, "get", &json!({ "element_id": children[0]["id"] }));
+    assert_eq!(removed["error"]["kind"], "ElementRemoved");
+    assert_eq!(removed["error"]["details"]["id"], children[0]["id"]);
+    assert_eq!(removed["error"]["details"]["reason"], "destroyed");
+  }
+
+  #[test]
+  fn ignored_writes_report_attempts() {
+    let desktop = MockDesktop::acquire();
+    let window = desktop.open_window("Notes", "Untitled");
+    desktop.add_element(
+      window.node,
+      MockElement::new(Role::TextField).ignore_writes(u32::MAX),
+    );
+    let allio = desktop.start_allio().unwrap();
+    let root = call(&allio, "window_root", &json!({ "window_id": window.id }));
+    let children = call(&allio, "children", &json!({ "element_id": root["id"] }));
+
+    let ignored = dispatch_json(
+      &allio,
+      "set_verified",
+      &json!({ "element_id": children[0]["id"], "value": "hello" }),
+    );
+    assert_eq!(ignored["error"]["kind"], "WriteNotApplied");
+    assert_eq!(ignored["error"]["details"]["element_id"], children[0]["id"]);
+    assert!(ignored["error"]["details"]["attempts"].as_u64().unwrap() > 1);
   }
 }
@@ -1,26 +1,44 @@
 /*!
 WebSocket server implementation.
+
+Every message a connection receives is a `SequencedEvent`. Clients can resume
+a dropped connection with `/ws?resume=<last seq seen>&session=<epoch>`: the
+missed events are replayed if still buffered, otherwise a fresh `SyncInit` is
+sent. A connection that falls behind the live stream is caught up the same way.
+
+`session` is the epoch from the client's last `SyncInit`. When it isn't this
+server's (the server restarted), sequence numbers and element IDs from the old
+session mean nothing here, so the client gets a fresh `SyncInit` too.
 */
 
-use allio::{Allio, Event};
+use crate::replay::{Broadcast, ReplayBuffer};
+use crate::resources::Resources;
+use crate::rpc::{
+  dispatch_owned, error_json, parse_request, response_json, RpcRequest, RpcResponse,
+};
+use crate::subscription::{Subscription, Subscriptions};
+use allio::{Allio, AllioError, SequencedEvent};
 use axum::{
   extract::{
     ws::{Message, WebSocket, WebSocketUpgrade},
-    State,
+    Query, State,
   },
   response::Response,
   routing::get,
   Router,
 };
 use log::error;
-use serde_json::{json, Value};
-use std::sync::Arc;
+use serde::Deserialize;
+use serde_json::Value;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
 use tokio::sync::broadcast;
 use tower_http::cors::{Any, CorsLayer};
 
 /// Default WebSocket server port.
 pub const DEFAULT_WS_PORT: u16 = 3030;
 const DEFAULT_CHANNEL_CAPACITY: usize = 1000;
+/// Events kept for resuming connections, not counting mouse positions.
+const DEFAULT_REPLAY_CAPACITY: usize = 4096;
 
 /// Handler for app-specific RPC methods.
 pub type CustomRpcHandler = Arc<dyn Fn(&str, &Value) -> Option<Value> + Send + Sync>;
@@ -29,7 +47,8 @@ pub type CustomRpcHandler = Arc<dyn Fn(&str, &Value) -> Option<Value> + Send + S
 #[derive(Clone)]
 pub struct WebSocketState {
   allio: Allio,
-  json_sender: Arc<broadcast::Sender<String>>,
+  event_sender: Arc<broadcast::Sender<Arc<Broadcast>>>,
+  replay: Arc<Mutex<ReplayBuffer>>,
   custom_handler: Option<CustomRpcHandler>,
   port: u16,
 }
@@ -50,10 +69,11 @@ impl WebSocketState {
 
   /// Create with custom port.
   pub fn with_port(allio: Allio, port: u16) -> Self {
-    let (json_tx, _) = broadcast::channel::<String>(DEFAULT_CHANNEL_CAPACITY);
+    let (event_tx, _) = broadcast::channel::<Arc<Broadcast>>(DEFAULT_CHANNEL_CAPACITY);
     Self {
       allio,
-      json_sender: Arc::new(json_tx),
+      event_sender: Arc::new(event_tx),
+      replay: Arc::new(Mutex::new(ReplayBuffer::new(DEFAULT_REPLAY_CAPACITY))),
       custom_handler: None,
       port,
     }
@@ -65,17 +85,33 @@ impl WebSocketState {
     self.custom_handler = Some(handler);
     self
   }
+
+  fn replay(&self) -> MutexGuard<'_, ReplayBuffer> {
+    self.replay.lock().unwrap_or_else(PoisonError::into_inner)
+  }
 }
 
 /// Start the WebSocket server.
 pub async fn start_server(ws_state: WebSocketState) {
   let port = ws_state.port;
-  let sender = ws_state.json_sender.clone();
+  let forwarder_state = ws_state.clone();
   let mut rx = ws_state.allio.subscribe();
   tokio::spawn(async move {
-    while let Ok(event) = rx.recv().await {
-      if let Ok(json) = serde_json::to_string(&event) {
-        drop(sender.send(json));
+    loop {
+      match rx.recv().await {
+        Ok(event) => {
+          let Some(broadcast) = Broadcast::new(event).map(Arc::new) else {
+            continue;
+          };
+          // Buffer before sending, so a connection that subscribes in between finds it in the buffer.
+          forwarder_state.replay().push(Arc::clone(&broadcast));
+          drop(forwarder_state.event_sender.send(broadcast));
+        }
+        // Connections resync when they reach the gap in sequence numbers (see `forward`).
+        Err(async_broadcast::RecvError::Overflowed(n)) => {
+          log::warn!("[ws] Event forwarder lagged, dropped {n} events");
+        }
+        Err(async_broadcast::RecvError::Closed) => break,
       }
     }
   });
@@ -107,39 +143,53 @@ pub async fn start_server(ws_state: WebSocketState) {
   }
 }
 
+/// Query parameters of the WebSocket URL.
+#[derive(Deserialize)]
+struct ConnectParams {
+  /// Last sequence number the client saw on a previous connection.
+  resume: Option<u64>,
+  /// Session epoch of that connection's `SyncInit`.
+  session: Option<u32>,
+}
+
 async fn websocket_handler(
   ws: WebSocketUpgrade,
+  Query(params): Query<ConnectParams>,
   State(ws_state): State<WebSocketState>,
 ) -> Response {
-  ws.on_upgrade(|socket| handle_websocket(socket, ws_state))
+  // Only resume within the session the client last synced with.
+  let resume = params
+    .resume
+    .filter(|_| params.session == Some(ws_state.allio.session()));
+  ws.on_upgrade(move |socket| handle_websocket(socket, ws_state, resume))
 }
 
-async fn handle_websocket(mut socket: WebSocket, ws_state: WebSocketState) {
-  let mut rx = ws_state.json_sender.subscribe();
-  let allio_for_init = ws_state.allio.clone();
-  let init_result = tokio::task::spawn_blocking(move || allio_for_init.snapshot()).await;
+async fn handle_websocket(mut socket: WebSocket, ws_state: WebSocketState, resume: Option<u64>) {
+  // Subscribe before catching up, so nothing falls between the two.
+  let mut rx = ws_state.event_sender.subscribe();
+  let mut subscriptions = Subscriptions::default();
+  let resources = Arc::new(Mutex::new(Resources::new(ws_state.allio.clone())));
 
-  let Ok(init) = init_result else {
-    return;
+  // Last sequence number this connection is up to date with.
+  let mut seq = match resume {
+    Some(seq) => catch_up(&mut socket, &ws_state, &mut subscriptions, seq).await,
+    None => send_sync_init(&mut socket, &ws_state.allio).await,
   };
 
-  let event = Event::SyncInit(init);
-  if let Ok(msg) = serde_json::to_string(&event) {
-    if socket.send(Message::Text(msg)).await.is_err() {
-      return;
-    }
-  }
-
-  loop {
-    tokio::select! {
+  while let Some(last) = seq {
+    seq = tokio::select! {
         msg = socket.recv() => {
             match msg {
                 Some(Ok(Message::Text(text))) => {
-                    let response = handle_request_async(&text, &ws_state).await;
-                    while let Ok(event_json) = rx.try_recv() {
-                        drop(socket.send(Message::Text(event_json)).await);
+                    let response = handle_request_async(&text, &ws_state, &mut subscriptions, &resources).await;
+                    let mut last = Some(last);
+                    while let (Some(seen), Ok(broadcast)) = (last, rx.try_recv()) {
+                        last = forward(&mut socket, &ws_state.allio, &mut subscriptions, &broadcast, seen).await;
                     }
-                    drop(socket.send(Message::Text(response)).await);
+                    if socket.send(Message::Text(response)).await.is_err() {
+                        break;
+                    }
+                    last
                 }
                 Some(Ok(Message::Close(_))) => {
                     println!("[client] closed connection");
@@ -153,33 +203,105 @@ async fn handle_websocket(mut socket: WebSocket, ws_state: WebSocketState) {
                     println!("[client] disconnected");
                     break;
                 }
-                _ => {}
+                _ => Some(last),
             }
         }
 
         broadcast = rx.recv() => {
             match broadcast {
-                Ok(event_json) => {
-                    if socket.send(Message::Text(event_json)).await.is_err() {
-                        break;
-                    }
-                }
+                Ok(broadcast) => forward(&mut socket, &ws_state.allio, &mut subscriptions, &broadcast, last).await,
                 Err(broadcast::error::RecvError::Lagged(n)) => {
-                    log::warn!("[ws] Client lagged, dropped {n} events - consider increasing event_channel_capacity or client needs resync");
+                    log::warn!("[ws] Client lagged, dropped {n} events - catching up from seq {last}");
+                    catch_up(&mut socket, &ws_state, &mut subscriptions, last).await
                 }
                 Err(broadcast::error::RecvError::Closed) => break,
             }
         }
-    }
+    };
   }
+
+  // Release the connection's observations and watches (unwatching calls the OS).
+  tokio::task::spawn_blocking(move || drop(resources))
+    .await
+    .ok();
 }
 
-async fn handle_request_async(request: &str, ws_state: &WebSocketState) -> String {
+/// Send a broadcast if it is new to this connection and subscribed to.
+/// Returns the connection's new last sequence number, or None if the socket failed.
+///
+/// Resyncs instead when events between `last` and the broadcast are missing
+/// (the forwarder dropped them, so they were never broadcast or buffered).
+async fn forward(
+  socket: &mut WebSocket,
+  allio: &Allio,
+  subscriptions: &mut Subscriptions,
+  broadcast: &Broadcast,
+  last: u64,
+) -> Option<u64> {
+  if broadcast.seq() <= last {
+    return Some(last);
+  }
+  if broadcast.seq() != last + 1 {
+    log::warn!(
+      "[ws] Events {}..{} were dropped - resyncing",
+      last + 1,
+      broadcast.seq()
+    );
+    return send_sync_init(socket, allio).await;
+  }
+  if subscriptions.wants(&broadcast.event.event) {
+    socket
+      .send(Message::Text(broadcast.json.clone()))
+      .await
+      .ok()?;
+  }
+  Some(broadcast.seq())
+}
+
+/// Bring a connection that has seen events up to `seq` up to date: replay what
+/// it missed if still buffered, otherwise resync with a fresh `SyncInit`.
+async fn catch_up(
+  socket: &mut WebSocket,
+  ws_state: &WebSocketState,
+  subscriptions: &mut Subscriptions,
+  seq: u64,
+) -> Option<u64> {
+  let missed = ws_state.replay().since(seq);
+  let Some(missed) = missed else {
+    return send_sync_init(socket, &ws_state.allio).await;
+  };
+  let mut last = seq;
+  for broadcast in missed {
+    last = forward(socket, &ws_state.allio, subscriptions, &broadcast, last).await?;
+  }
+  Some(last)
+}
+
+/// Send a `SyncInit` with a fresh snapshot. Returns the snapshot's sequence number.
+async fn send_sync_init(socket: &mut WebSocket, allio: &Allio) -> Option<u64> {
+  let allio = allio.clone();
+  let init = tokio::task::spawn_blocking(move || allio.snapshot())
+    .await
+    .ok()?;
+  let event = SequencedEvent::sync_init(init);
+  let msg = serde_json::to_string(&event).ok()?;
+  socket.send(Message::Text(msg)).await.ok()?;
+  Some(event.seq)
+}
+
+async fn handle_request_async(
+  request: &str,
+  ws_state: &WebSocketState,
+  subscriptions: &mut Subscriptions,
+  resources: &Arc<Mutex<Resources>>,
+) -> String {
   let parsed: Result<Value, _> = serde_json::from_str(request);
 
   let req = match parsed {
     Ok(v) => v,
-    Err(e) => return json!({ "error": format!("Invalid JSON: {}", e) }).to_string(),
+    Err(e) => {
+      return error_json(&AllioError::InvalidRequest(format!("Invalid JSON: {e}"))).to_string()
+    }
   };
 
   let id = req.get("id").cloned().unwrap_or(Value::Null);
@@ -199,13 +321,42 @@ async fn handle_request_async(request: &str, ws_state: &WebSocketState) -> Strin
     }
   }
 
-  let allio = ws_state.allio.clone();
-  let dispatch_result =
-    tokio::task::spawn_blocking(move || crate::rpc::dispatch_json(&allio, &method, &args)).await;
-
-  let mut response = match dispatch_result {
-    Ok(r) => r,
-    Err(_) => json!({ "error": "RPC task panicked" }),
+  // Subscriptions belong to this connection, so they are handled here rather than in `dispatch`.
+  let mut response = match parse_request(&method, &args) {
+    Err(error) => error,
+    Ok(RpcRequest::Subscribe { filter }) => {
+      let allio = ws_state.allio.clone();
+      match tokio::task::spawn_blocking(move || Subscription::new(&allio, filter)).await {
+        Ok(subscription) => {
+          let subscription_id = subscriptions.subscribe(subscription);
+          response_json(&method, Ok(RpcResponse::SubscriptionId(subscription_id)))
+        }
+        Err(_) => error_json(&AllioError::Internal("RPC task panicked".into())),
+      }
+    }
+    Ok(RpcRequest::Unsubscribe { subscription_id }) => {
+      let result = if subscriptions.unsubscribe(subscription_id) {
+        Ok(RpcResponse::Null)
+      } else {
+        Err(AllioError::InvalidRequest(format!(
+          "Unknown subscription {subscription_id}"
+        )))
+      };
+      response_json(&method, result)
+    }
+    Ok(request) => {
+      let allio = ws_state.allio.clone();
+      let resources = Arc::clone(resources);
+      let dispatch_result = tokio::task::spawn_blocking(move || {
+        let mut resources = resources.lock().unwrap_or_else(PoisonError::into_inner);
+        response_json(&method, dispatch_owned(&allio, &mut resources, request))
+      })
+      .await;
+      match dispatch_result {
+        Ok(r) => r,
+        Err(_) => error_json(&AllioError::Internal("RPC task panicked".into())),
+      }
+    }
   };
   if let Some(obj) = response.as_object_mut() {
     obj.insert("id".to_string(), id);
@@ -0,0 +1,448 @@
+/*!
+Per-connection event subscriptions.
+
+A connection receives every event until it calls `subscribe`. From then on it
+receives only events matched by at least one of its subscriptions; after
+unsubscribing from all of them it receives nothing (`SyncInit` is always sent,
+on connect and on resync).
+
+Scoped subscriptions track the elements and windows they have matched: those
+cached when the subscription was made, plus those arriving in `element:added`,
+`window:added` and similar payloads. Events that carry only an ID (changes,
+removals, window focus) match through that set, so delivering an event never
+reads the cache.
+*/
+
+use allio::{Allio, Element, ElementId, Event, ProcessId, Window, WindowId};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use ts_rs::TS;
+
+/// Event kinds, named as in the serialized `Event` tag.
+#[allow(missing_docs)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub enum EventKind {
+  #[serde(rename = "sync:init")]
+  SyncInit,
+  #[serde(rename = "window:added")]
+  WindowAdded,
+  #[serde(rename = "window:changed")]
+  WindowChanged,
+  #[serde(rename = "window:removed")]
+  WindowRemoved,
+  #[serde(rename = "element:added")]
+  ElementAdded,
+  #[serde(rename = "element:changed")]
+  ElementChanged,
+  #[serde(rename = "element:removed")]
+  ElementRemoved,
+  #[serde(rename = "element:replaced")]
+  ElementReplaced,
+  #[serde(rename = "focus:window")]
+  FocusWindow,
+  #[serde(rename = "focus:element")]
+  FocusElement,
+  #[serde(rename = "selection:changed")]
+  SelectionChanged,
+  #[serde(rename = "mouse:position")]
+  MousePosition,
+  #[serde(rename = "subtree:changed")]
+  SubtreeChanged,
+}
+
+impl EventKind {
+  /// Kind of an event.
+  pub const fn of(event: &Event) -> Self {
+    match event {
+      Event::SyncInit(_) => Self::SyncInit,
+      Event::WindowAdded { .. } => Self::WindowAdded,
+      Event::WindowChanged { .. } => Self::WindowChanged,
+      Event::WindowRemoved { .. } => Self::WindowRemoved,
+      Event::ElementAdded { .. } => Self::ElementAdded,
+      Event::ElementChanged { .. } => Self::ElementChanged,
+      Event::ElementRemoved { .. } => Self::ElementRemoved,
+      Event::ElementReplaced { .. } => Self::ElementReplaced,
+      Event::FocusWindow { .. } => Self::FocusWindow,
+      Event::FocusElement { .. } => Self::FocusElement,
+      Event::SelectionChanged { .. } => Self::SelectionChanged,
+      Event::MousePosition(_) => Self::MousePosition,
+      Event::SubtreeChanged { .. } => Self::SubtreeChanged,
+    }
+  }
+}
+
+/// Which events a subscription receives.
+///
+/// Every given field must match; omitted fields match everything. Window,
+/// process and subtree filters only match events about windows and elements
+/// (so never `mouse:position`).
+#[derive(Debug, Clone, Default, Deserialize, TS)]
+#[ts(export)]
+pub struct EventFilter {
+  /// Event kinds to receive.
+  #[serde(default)]
+  #[ts(optional)]
+  pub events: Option<Vec<EventKind>>,
+  /// Only events about these windows (or their elements).
+  #[serde(default)]
+  #[ts(optional)]
+  pub window_ids: Option<Vec<WindowId>>,
+  /// Only events about windows or elements of these processes.
+  #[serde(default)]
+  #[ts(optional)]
+  pub process_ids: Option<Vec<ProcessId>>,
+  /// Only events about elements in this element's subtree (inclusive).
+  #[serde(default)]
+  #[ts(optional)]
+  pub subtree: Option<ElementId>,
+}
+
+impl EventFilter {
+  const fn is_scoped(&self) -> bool {
+    self.window_ids.is_some() || self.process_ids.is_some() || self.subtree.is_some()
+  }
+
+  fn matches_window(&self, window: &Window) -> bool {
+    self.subtree.is_none()
+      && self
+        .window_ids
+        .as_ref()
+        .is_none_or(|ids| ids.contains(&window.id))
+      && self
+        .process_ids
+        .as_ref()
+        .is_none_or(|pids| pids.contains(&window.process_id))
+  }
+
+  /// Does a cached element match? Walks the cache for the subtree check.
+  fn matches_cached(&self, allio: &Allio, element: &Element) -> bool {
+    self.matches_owner(element)
+      && self
+        .subtree
+        .is_none_or(|root| allio.in_subtree(root, element.id))
+  }
+
+  /// Does an event's element match? `known` says whether an element has matched
+  /// before, which places the element in the subtree if it or its parent has.
+  fn matches_element(&self, element: &Element, known: impl Fn(ElementId) -> bool) -> bool {
+    self.matches_owner(element)
+      && self.subtree.is_none_or(|root| {
+        element.id == root || known(element.id) || element.parent_id.is_some_and(&known)
+      })
+  }
+
+  fn matches_owner(&self, element: &Element) -> bool {
+    self
+      .window_ids
+      .as_ref()
+      .is_none_or(|ids| ids.contains(&element.window_id))
+      && self
+        .process_ids
+        .as_ref()
+        .is_none_or(|pids| pids.contains(&element.pid))
+  }
+}
+
+/// One `subscribe` call.
+pub(crate) struct Subscription {
+  filter: EventFilter,
+  /// Matched elements and windows. Events carrying only an ID match through these.
+  known_elements: HashSet<ElementId>,
+  known_windows: HashSet<WindowId>,
+}
+
+impl Subscription {
+  /// Start from the cached elements and windows in scope. Scans the whole cache,
+  /// so call it off the async runtime.
+  pub(crate) fn new(allio: &Allio, filter: EventFilter) -> Self {
+    let (known_elements, known_windows) = if filter.is_scoped() {
+      (
+        allio
+          .all_elements()
+          .iter()
+          .filter(|e| filter.matches_cached(allio, e))
+          .map(|e| e.id)
+          .collect(),
+        allio
+          .all_windows()
+          .iter()
+          .filter(|w| filter.matches_window(w))
+          .map(|w| w.id)
+          .collect(),
+      )
+    } else {
+      (HashSet::new(), HashSet::new())
+    };
+    Self {
+      filter,
+      known_elements,
+      known_windows,
+    }
+  }
+
+  fn matches(&mut self, event: &Event) -> bool {
+    // Scope first, so the known sets follow events of every kind.
+    self.in_scope(event)
+      && self
+        .filter
+        .events
+        .as_ref()
+        .is_none_or(|kinds| kinds.contains(&EventKind::of(event)))
+  }
+
+  /// Is the event about a window or element in scope? Resolved from the payload
+  /// and the known sets, never from the cache.
+  fn in_scope(&mut self, event: &Event) -> bool {
+    if !self.filter.is_scoped() {
+      return true;
+    }
+    match event {
+      Event::WindowAdded { window } | Event::WindowChanged { window } => {
+        let matched = self.filter.matches_window(window);
+        if matched {
+          self.known_windows.insert(window.id);
+        }
+        matched
+      }
+      Event::WindowRemoved { window_id } => self.known_windows.remove(window_id),
+      Event::FocusWindow { window_id } => {
+        window_id.is_some_and(|id| self.known_windows.contains(&id))
+      }
+      Event::ElementAdded { element } | Event::FocusElement { element, .. } => {
+        let known = &self.known_elements;
+        let matched = self
+          .filter
+          .matches_element(element, |id| known.contains(&id));
+        if matched {
+          self.known_elements.insert(element.id);
+        }
+        matched
+      }
+      Event::ElementRemoved { element_id, .. } => self.known_elements.remove(element_id),
+      Event::ElementChanged { element_id, .. }
+      | Event::SelectionChanged { element_id, .. }
+      | Event::ElementReplaced {
+        new: element_id, ..
+      }
+      | Event::SubtreeChanged {
+        root_id: element_id,
+        ..
+      } => self.known_elements.contains(element_id),
+      Event::SyncInit(_) | Event::MousePosition(_) => false,
+    }
+  }
+}
+
+/// Event subscriptions of one connection.
+#[derive(Default)]
+pub(crate) struct Subscriptions {
+  next_id: u32,
+  /// None until the first `subscribe`: the connection receives everything.
+  active: Option<HashMap<u32, Subscription>>,
+}
+
+impl Subscriptions {
+  /// Add a subscription. Returns its ID.
+  pub(crate) fn subscribe(&mut self, subscription: Subscription) -> u32 {
+    self.next_id += 1;
+    self
+      .active
+      .get_or_insert_with(HashMap::new)
+      .insert(self.next_id, subscription);
+    self.next_id
+  }
+
+  /// Remove a subscription. Returns false if there was none with this ID.
+  pub(crate) fn unsubscribe(&mut self, id: u32) -> bool {
+    self
+      .active
+      .as_mut()
+      .is_some_and(|active| active.remove(&id).is_some())
+  }
+
+  /// Should this connection receive `event`?
+  pub(crate) fn wants(&mut self, event: &Event) -> bool {
+    let Some(active) = &mut self.active else {
+      return true;
+    };
+    // Every subscription sees the event, so each keeps its known set current.
+    active
+      .values_mut()
+      .fold(false, |wanted, sub| sub.matches(event) | wanted)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use allio::a11y::Role;
+  use allio::mock::{MockDesktop, MockElement, MockWindow};
+  use allio::{Bounds, Point, Recency, RemovalReason};
+
+  const fn bounds(x: f64) -> Bounds {
+    Bounds {
+      x,
+      y: 0.0,
+      w: 400.0,
+      h: 300.0,
+    }
+  }
+
+  /// Two windows from different processes, each with a group holding a button.
+  fn setup() -> (MockDesktop, Allio, [MockWindow; 2], [Element; 2]) {
+    let desktop = MockDesktop::acquire();
+    let windows = [
+      desktop.add_window(100, "Notes", "One", bounds(0.0)),
+      desktop.add_window(200, "Mail", "Two", bounds(500.0)),
+    ];
+    for window in &windows {
+      let group = desktop
+        .add_element(window.node, MockElement::new(Role::Group))
+        .unwrap();
+      desktop.add_element(group, MockElement::new(Role::Button));
+    }
+
+    let allio = desktop.start_allio().unwrap();
+    let groups = windows.each_ref().map(|w| {
+      let root = allio.window_root(w.id).unwrap().unwrap();
+      let group = allio.children(root.id, Recency::Current).unwrap().remove(0);
+      allio.children(group.id, Recency::Current).unwrap();
+      group
+    });
+    (desktop, allio, windows, groups)
+  }
+
+  fn changed(id: ElementId) -> Event {
+    Event::ElementChanged {
+      element_id: id,
+      changes: vec![],
+    }
+  }
+
+  fn removed(id: ElementId) -> Event {
+    Event::ElementRemoved {
+      element_id: id,
+      reason: RemovalReason::Destroyed,
+    }
+  }
+
+  #[test]
+  fn receives_everything_until_first_subscribe() {
+    let (_desktop, allio, _, _) = setup();
+    let mut subs = Subscriptions::default();
+    let mouse = Event::MousePosition(Point::new(1.0, 2.0));
+    assert!(subs.wants(&mouse));
+
+    let id = subs.subscribe(Subscription::new(
+      &allio,
+      EventFilter {
+        events: Some(vec![EventKind::WindowAdded]),
+        ..EventFilter::default()
+      },
+    ));
+    assert!(!subs.wants(&mouse));
+    assert!(subs.unsubscribe(id));
+    assert!(!subs.unsubscribe(id));
+    assert!(!subs.wants(&mouse));
+  }
+
+  #[test]
+  fn filters_by_window_and_process() {
+    let (_desktop, allio, windows, groups) = setup();
+    let mut subs = Subscriptions::default();
+    subs.subscribe(Subscription::new(
+      &allio,
+      EventFilter {
+        window_ids: Some(vec![windows[0].id]),
+        ..EventFilter::default()
+      },
+    ));
+    assert!(subs.wants(&changed(groups[0].id)));
+    assert!(!subs.wants(&changed(groups[1].id)));
+    assert!(!subs.wants(&Event::MousePosition(Point::new(0.0, 0.0))));
+    let second = allio.window(windows[1].id).unwrap();
+    assert!(!subs.wants(&Event::WindowChanged {
+      window: second.clone()
+    }));
+
+    let mut by_pid = Subscriptions::default();
+    by_pid.subscribe(Subscription::new(
+      &allio,
+      EventFilter {
+        process_ids: Some(vec![second.process_id]),
+        ..EventFilter::default()
+      },
+    ));
+    assert!(by_pid.wants(&Event::WindowChanged { window: second }));
+    assert!(by_pid.wants(&changed(groups[1].id)));
+    assert!(!by_pid.wants(&changed(groups[0].id)));
+    assert!(by_pid.wants(&Event::WindowRemoved {
+      window_id: windows[1].id
+    }));
+    assert!(!by_pid.wants(&Event::WindowRemoved {
+      window_id: windows[0].id
+    }));
+  }
+
+  #[test]
+  fn filters_by_subtree_and_kind() {
+    let (_desktop, allio, windows, groups) = setup();
+    let root = allio.window_root(windows[0].id).unwrap().unwrap();
+    let button = allio.children(groups[0].id, Recency::Any).unwrap()[0].id;
+    let mut subs = Subscriptions::default();
+    subs.subscribe(Subscription::new(
+      &allio,
+      EventFilter {
+        events: Some(vec![EventKind::ElementChanged, EventKind::ElementRemoved]),
+        subtree: Some(groups[0].id),
+        ..EventFilter::default()
+      },
+    ));
+
+    assert!(subs.wants(&changed(button)));
+    assert!(subs.wants(&changed(groups[0].id)));
+    assert!(!subs.wants(&changed(root.id)));
+    assert!(!subs.wants(&Event::ElementAdded {
+      element: allio.get(button, Recency::Any).unwrap()
+    }));
+    assert!(subs.wants(&removed(button)));
+    assert!(!subs.wants(&removed(root.id)));
+  }
+
+  #[test]
+  fn tracks_scope_from_event_payloads() {
+    let (desktop, allio, windows, groups) = setup();
+    let mut subs = Subscriptions::default();
+    subs.subscribe(Subscription::new(
+      &allio,
+      EventFilter {
+        events: Some(vec![EventKind::ElementChanged]),
+        subtree: Some(groups[0].id),
+        ..EventFilter::default()
+      },
+    ));
+
+    // New elements join the scope from their `element:added` payload, even when
+    // that kind isn't delivered.
+    let group_nodes = windows.each_ref().map(|w| desktop.children(w.node)[0]);
+    for node in group_nodes {
+      desktop.add_element(node, MockElement::new(Role::Checkbox));
+    }
+    let added = groups.each_ref().map(|g| {
+      let children = allio.children(g.id, Recency::Current).unwrap();
+      children.last().unwrap().clone()
+    });
+    for element in &added {
+      assert!(!subs.wants(&Event::ElementAdded {
+        element: element.clone()
+      }));
+    }
+    assert!(subs.wants(&changed(added[0].id)));
+    assert!(!subs.wants(&changed(added[1].id)));
+
+    // Once removed, an element is out of scope.
+    assert!(!subs.wants(&removed(added[0].id)));
+    assert!(!subs.wants(&changed(added[0].id)));
+  }
+}
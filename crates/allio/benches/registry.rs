@@ -0,0 +1,86 @@
+//! Registry read and write costs on a 50k-element tree (mock desktop).
+//!
+//! Run with `cargo bench -p allio --features mock`.
+
+#![allow(missing_docs, clippy::unwrap_used)]
+
+use allio::a11y::Role;
+use allio::mock::{MockDesktop, MockElement};
+use allio::{Allio, Bounds, ElementId, Recency};
+use criterion::{criterion_group, criterion_main, Criterion};
+use std::hint::black_box;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+const GROUPS: usize = 50;
+const BUTTONS_PER_GROUP: usize = 1000;
+
+/// A window with 50 groups of 1000 buttons, all loaded into the cache.
+fn setup() -> (MockDesktop, Allio, ElementId) {
+  let desktop = MockDesktop::acquire();
+  let bounds = Bounds {
+    x: 0.0,
+    y: 0.0,
+    w: 1200.0,
+    h: 800.0,
+  };
+  let window = desktop.add_window(100, "Bench", "Large", bounds);
+  for g in 0..GROUPS {
+    let group = desktop
+      .add_element(window.node, MockElement::new(Role::Group))
+      .unwrap();
+    for b in 0..BUTTONS_PER_GROUP {
+      desktop.add_element(
+        group,
+        MockElement::new(Role::Button).label(format!("{g}.{b}")),
+      );
+    }
+  }
+
+  let allio = desktop.start_allio().unwrap();
+  let root = allio.window_root(window.id).unwrap().unwrap().id;
+  let buttons = allio.query(root, "button", Recency::Current).unwrap();
+  assert_eq!(buttons.len(), GROUPS * BUTTONS_PER_GROUP);
+  (desktop, allio, buttons.first().unwrap().id)
+}
+
+fn reads(c: &mut Criterion) {
+  let (_desktop, allio, _) = setup();
+  let mut group = c.benchmark_group("50k");
+  group.sample_size(20);
+  group.bench_function("snapshot", |b| b.iter(|| black_box(allio.snapshot())));
+  group.bench_function("all_elements", |b| {
+    b.iter(|| black_box(allio.all_elements()));
+  });
+  group.finish();
+}
+
+/// A write (refreshing one element from the OS), alone and while another
+/// thread takes snapshots back to back.
+fn writes(c: &mut Criterion) {
+  let (_desktop, allio, button) = setup();
+  let mut group = c.benchmark_group("50k_refresh");
+  group.bench_function("idle", |b| {
+    b.iter(|| black_box(allio.get(button, Recency::Current).unwrap()));
+  });
+
+  let stop = Arc::new(AtomicBool::new(false));
+  let reader = {
+    let allio = allio.clone();
+    let stop = Arc::clone(&stop);
+    std::thread::spawn(move || {
+      while !stop.load(Ordering::Relaxed) {
+        black_box(allio.snapshot());
+      }
+    })
+  };
+  group.bench_function("while_snapshotting", |b| {
+    b.iter(|| black_box(allio.get(button, Recency::Current).unwrap()));
+  });
+  stop.store(true, Ordering::Relaxed);
+  reader.join().unwrap();
+  group.finish();
+}
+
+criterion_group!(benches, reads, writes);
+criterion_main!(benches);
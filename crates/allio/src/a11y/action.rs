@@ -66,4 +66,18 @@ impl Action {
     Self::Collapse,
     Self::ScrollToVisible,
   ];
+
+  /// Whether performing the action twice has the same effect as once, so a
+  /// verified write may safely repeat it. `Press` and `Increment` don't.
+  pub const fn is_idempotent(self) -> bool {
+    match self {
+      Self::Raise | Self::Pick | Self::Expand | Self::Collapse | Self::ScrollToVisible => true,
+      Self::Press
+      | Self::ShowMenu
+      | Self::Increment
+      | Self::Decrement
+      | Self::Confirm
+      | Self::Cancel => false,
+    }
+  }
 }
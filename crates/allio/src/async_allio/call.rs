@@ -0,0 +1,121 @@
+/*! `Call`: the future an `AsyncAllio` method returns, completed by a pool worker. */
+
+use parking_lot::Mutex;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll, Waker};
+
+use crate::types::{AllioError, AllioResult};
+
+/// State shared between a `Call` and the worker that completes it.
+struct Slot<T> {
+  result: Option<AllioResult<T>>,
+  waker: Option<Waker>,
+  /// The worker dropped its end without completing (the pool shut down).
+  closed: bool,
+  /// The `Call` was dropped. The worker skips the call if it hasn't started.
+  cancelled: bool,
+}
+
+/// A platform call running on `AsyncAllio`'s worker pool.
+///
+/// Runtime-agnostic: completion wakes whatever task last polled it. Dropping
+/// a `Call` before a worker picks it up cancels it; once started, the OS call
+/// runs to completion and its result is discarded.
+#[must_use = "calls are cancelled when dropped"]
+pub struct Call<T> {
+  slot: Arc<Mutex<Slot<T>>>,
+}
+
+impl<T> std::fmt::Debug for Call<T> {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    let slot = self.slot.lock();
+    f.debug_struct("Call")
+      .field("done", &(slot.result.is_some() || slot.closed))
+      .finish()
+  }
+}
+
+/// The worker's end of a `Call`.
+pub(super) struct Completer<T> {
+  slot: Arc<Mutex<Slot<T>>>,
+}
+
+/// A linked `Call` and `Completer`.
+pub(super) fn call<T>() -> (Call<T>, Completer<T>) {
+  let slot = Arc::new(Mutex::new(Slot {
+    result: None,
+    waker: None,
+    closed: false,
+    cancelled: false,
+  }));
+  (
+    Call {
+      slot: Arc::clone(&slot),
+    },
+    Completer { slot },
+  )
+}
+
+/// A `Call` that has already failed with `error`.
+pub(super) fn failed<T>(error: AllioError) -> Call<T> {
+  let (call, completer) = call();
+  completer.complete(Err(error));
+  call
+}
+
+impl<T> Completer<T> {
+  /// Whether the `Call` was dropped, so there is no point running it.
+  pub(super) fn is_cancelled(&self) -> bool {
+    self.slot.lock().cancelled
+  }
+
+  pub(super) fn complete(self, result: AllioResult<T>) {
+    let waker = {
+      let mut slot = self.slot.lock();
+      slot.result = Some(result);
+      slot.waker.take()
+    };
+    if let Some(waker) = waker {
+      waker.wake();
+    }
+  }
+}
+
+impl<T> Drop for Completer<T> {
+  fn drop(&mut self) {
+    let waker = {
+      let mut slot = self.slot.lock();
+      slot.closed = true;
+      slot.waker.take()
+    };
+    if let Some(waker) = waker {
+      waker.wake();
+    }
+  }
+}
+
+impl<T> Future for Call<T> {
+  type Output = AllioResult<T>;
+
+  fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+    let mut slot = self.slot.lock();
+    if let Some(result) = slot.result.take() {
+      return Poll::Ready(result);
+    }
+    if slot.closed {
+      return Poll::Ready(Err(AllioError::Internal(
+        "worker pool shut down before the call ran".into(),
+      )));
+    }
+    slot.waker = Some(cx.waker().clone());
+    Poll::Pending
+  }
+}
+
+impl<T> Drop for Call<T> {
+  fn drop(&mut self) {
+    self.slot.lock().cancelled = true;
+  }
+}
@@ -0,0 +1,249 @@
+/*!
+Async API (the `async` feature).
+
+`Allio`'s OS-facing methods block on IPC with the target app, often for
+milliseconds and sometimes far longer when an app is busy. `AsyncAllio` runs
+them on a dedicated pool of worker threads instead and returns [`Call`]
+futures, which any executor can await (they only rely on `Waker`).
+
+```ignore
+let allio = AsyncAllio::new(Allio::new()?)?;
+let root = allio.window_root(window_id).await?;
+let children = allio.children(root.id, Recency::Current).await?;
+allio.perform_action(children[0].id, Action::Press).await?;
+```
+
+Calls into the same process run one at a time, in the order they were made
+(see `pool.rs`); calls into different processes run in parallel, up to the
+number of workers. Dropping a `Call` cancels it unless a worker already
+started it. A call that panics fails with [`AllioError::Internal`] instead of
+taking its worker down. Cache-only reads (`window`, `snapshot`,
+`subscribe`...) don't block, so they stay on the wrapped `Allio`: see
+[`AsyncAllio::allio`].
+*/
+
+mod call;
+mod pool;
+
+pub use call::Call;
+
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::Arc;
+
+use crate::a11y::{Action, Value};
+use crate::core::Allio;
+use crate::types::{AllioError, AllioResult, Element, ElementId, ProcessId, Recency, WindowId};
+use pool::WorkerPool;
+
+/// Workers used by [`AsyncAllio::new`].
+const DEFAULT_WORKERS: usize = 4;
+
+/// Async wrapper around an [`Allio`], running platform calls on a worker pool.
+///
+/// Clone is cheap (Arc bumps) and clones share the pool. The workers stop
+/// when the last clone is dropped.
+#[derive(Clone)]
+pub struct AsyncAllio {
+  allio: Allio,
+  pool: Arc<WorkerPool>,
+}
+
+impl std::fmt::Debug for AsyncAllio {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.debug_struct("AsyncAllio").finish_non_exhaustive()
+  }
+}
+
+impl AsyncAllio {
+  /// Wrap `allio` with a pool of 4 workers.
+  pub fn new(allio: Allio) -> AllioResult<Self> {
+    Self::with_workers(allio, DEFAULT_WORKERS)
+  }
+
+  /// Wrap `allio` with a pool of `workers` threads. Fails if `workers` is 0
+  /// or the threads can't be spawned.
+  pub fn with_workers(allio: Allio, workers: usize) -> AllioResult<Self> {
+    Ok(Self {
+      allio,
+      pool: Arc::new(WorkerPool::new(workers)?),
+    })
+  }
+
+  /// The wrapped instance, for cache reads and subscriptions.
+  pub const fn allio(&self) -> &Allio {
+    &self.allio
+  }
+
+  /// Run `f` on the pool, in `pid`'s lane if given. The call fails right
+  /// away if the pool's queue is full.
+  fn spawn<T: Send + 'static>(
+    &self,
+    pid: Option<ProcessId>,
+    f: impl FnOnce(&Allio) -> AllioResult<T> + Send + 'static,
+  ) -> Call<T> {
+    let (call, completer) = call::call();
+    let allio = self.allio.clone();
+    let submitted = self.pool.submit(
+      pid,
+      Box::new(move || {
+        if completer.is_cancelled() {
+          return;
+        }
+        let result = panic::catch_unwind(AssertUnwindSafe(|| f(&allio)))
+          .unwrap_or_else(|_| Err(AllioError::Internal("call panicked".into())));
+        completer.complete(result);
+      }),
+    );
+    match submitted {
+      Ok(()) => call,
+      Err(e) => call::failed(e),
+    }
+  }
+
+  /// Process an element belongs to, if cached.
+  fn pid_of(&self, element_id: ElementId) -> Option<ProcessId> {
+    self.allio.read(|r| r.element(element_id).map(|e| e.pid))
+  }
+
+  /// [`Allio::get`] on the pool.
+  pub fn get(&self, element_id: ElementId, recency: Recency) -> Call<Element> {
+    self.spawn(self.pid_of(element_id), move |a| a.get(element_id, recency))
+  }
+
+  /// [`Allio::children`] on the pool.
+  pub fn children(&self, element_id: ElementId, recency: Recency) -> Call<Vec<Element>> {
+    self.spawn(self.pid_of(element_id), move |a| {
+      a.children(element_id, recency)
+    })
+  }
+
+  /// [`Allio::parent`] on the pool.
+  pub fn parent(&self, element_id: ElementId, recency: Recency) -> Call<Option<Element>> {
+    self.spawn(self.pid_of(element_id), move |a| {
+      a.parent(element_id, recency)
+    })
+  }
+
+  /// [`Allio::element_at`] on the pool.
+  pub fn element_at(&self, x: f64, y: f64) -> Call<Option<Element>> {
+    let pid = self.allio.window_at_point(x, y).map(|w| w.process_id);
+    self.spawn(pid, move |a| a.element_at(x, y))
+  }
+
+  /// [`Allio::window_root`] on the pool.
+  pub fn window_root(&self, window_id: WindowId) -> Call<Option<Element>> {
+    let pid = self.allio.window(window_id).map(|w| w.process_id);
+    self.spawn(pid, move |a| a.window_root(window_id))
+  }
+
+  /// [`Allio::set_value`] on the pool.
+  pub fn set_value(&self, element_id: ElementId, value: Value) -> Call<()> {
+    self.spawn(self.pid_of(element_id), move |a| {
+      a.set_value(element_id, &value)
+    })
+  }
+
+  /// [`Allio::perform_action`] on the pool.
+  pub fn perform_action(&self, element_id: ElementId, action: Action) -> Call<()> {
+    self.spawn(self.pid_of(element_id), move |a| {
+      a.perform_action(element_id, action)
+    })
+  }
+}
+
+#[cfg(test)]
+#[cfg(feature = "mock")]
+mod tests {
+  use super::*;
+  use crate::a11y::Role;
+  use crate::mock::{MockDesktop, MockElement};
+  use std::future::Future;
+  use std::pin::pin;
+  use std::sync::atomic::{AtomicBool, Ordering};
+  use std::task::{Context, Poll};
+  use std::time::Duration;
+
+  /// Minimal executor: poll on this thread, parking between wakes.
+  fn block_on<F: Future>(future: F) -> F::Output {
+    let waker = crate::wait::park_waker();
+    let mut cx = Context::from_waker(&waker);
+    let mut future = pin!(future);
+    loop {
+      if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+        return output;
+      }
+      std::thread::park_timeout(Duration::from_secs(1));
+    }
+  }
+
+  fn setup() -> (MockDesktop, AsyncAllio, WindowId) {
+    let desktop = MockDesktop::acquire();
+    let window = desktop.open_window("Notes", "Untitled");
+    desktop.add_element(window.node, MockElement::new(Role::TextField));
+    desktop.add_element(
+      window.node,
+      MockElement::new(Role::Button)
+        .label("Save")
+        .actions(&[Action::Press]),
+    );
+    let allio = desktop.start_allio().unwrap();
+    (desktop, AsyncAllio::new(allio).unwrap(), window.id)
+  }
+
+  #[test]
+  fn calls_resolve_on_the_pool() {
+    let (desktop, allio, window) = setup();
+    let root = block_on(allio.window_root(window)).unwrap().unwrap();
+    let children = block_on(allio.children(root.id, Recency::Current)).unwrap();
+    assert_eq!(children.len(), 2);
+    let field = children[0].id;
+    let parent = block_on(allio.parent(field, Recency::Any)).unwrap();
+    assert_eq!(parent.unwrap().id, root.id);
+
+    block_on(allio.set_value(field, Value::String("hello".into()))).unwrap();
+    let field = block_on(allio.get(field, Recency::Current)).unwrap();
+    assert_eq!(field.value, Some(Value::String("hello".into())));
+    block_on(allio.perform_action(children[1].id, Action::Press)).unwrap();
+    assert_eq!(desktop.performed_actions().len(), 1);
+
+    let at = block_on(allio.element_at(10.0, 10.0)).unwrap();
+    assert_eq!(at.unwrap().window_id, window);
+    assert!(matches!(
+      block_on(allio.get(ElementId::new(root.id.epoch(), 999_999), Recency::Any)),
+      Err(AllioError::ElementNotFound(_))
+    ));
+  }
+
+  #[test]
+  fn dropped_calls_are_cancelled() {
+    let (_desktop, allio, window) = setup();
+    let allio = AsyncAllio::with_workers(allio.allio().clone(), 1).unwrap();
+    let pid = allio.allio().window(window).map(|w| w.process_id);
+
+    // Hold the only worker, queue a call behind it, and drop the call.
+    let (release, held) = std::sync::mpsc::channel::<()>();
+    let blocker = allio.spawn(pid, move |_| {
+      held.recv().unwrap();
+      Ok(())
+    });
+    let ran = Arc::new(AtomicBool::new(false));
+    let flag = Arc::clone(&ran);
+    drop(allio.spawn(pid, move |_| {
+      flag.store(true, Ordering::SeqCst);
+      Ok(())
+    }));
+    release.send(()).unwrap();
+    block_on(blocker).unwrap();
+    block_on(allio.window_root(window)).unwrap();
+    assert!(!ran.load(Ordering::SeqCst));
+  }
+
+  #[test]
+  fn a_panicking_call_fails_without_stalling_its_process() {
+    let (_desktop, allio, window) = setup();
+    let pid = allio.allio().window(window).map(|w| w.process_id);
+    let panicked = allio.spawn(pid, |_| -> AllioResult<()> { panic!("call failed") });
+    assert!(matches!(block_on(panicked), Err(AllioError::Internal(_))));
+    assert!(block_on(allio.window_root(window)).unwrap().is_some());
+  }
+}
@@ -0,0 +1,262 @@
+/*!
+Worker pool for `AsyncAllio`.
+
+A fixed number of threads run queued jobs. Jobs for the same process share a
+lane and run one at a time, in submission order: apps answer accessibility
+requests on their main thread anyway, so concurrent calls into one app only
+queue up there, and writes to it should land in the order they were made.
+Jobs with no process run as soon as a worker is free.
+
+A job that panics is caught on its worker, which goes on to the next job and
+releases the job's lane. The queue is bounded: past `MAX_QUEUED` waiting jobs,
+`submit` refuses new ones instead of letting a stuck app pile up work.
+*/
+
+use parking_lot::{Condvar, Mutex};
+use std::collections::{HashMap, VecDeque};
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::Arc;
+
+use crate::types::{AllioError, AllioResult, ProcessId};
+
+type Job = Box<dyn FnOnce() + Send>;
+
+/// Jobs that can wait in the queue before `submit` refuses more.
+const MAX_QUEUED: usize = 1024;
+
+/// What a worker can pick up next.
+enum Ready {
+  /// A job with no process.
+  Free(Job),
+  /// The next job in a process's lane.
+  Lane(ProcessId),
+}
+
+#[derive(Default)]
+struct Queue {
+  /// Queued jobs per process. A lane stays here while one of its jobs runs,
+  /// and is in `ready` only while none does.
+  lanes: HashMap<ProcessId, VecDeque<Job>>,
+  ready: VecDeque<Ready>,
+  /// Jobs waiting in `lanes` and `ready`, not counting running ones.
+  queued: usize,
+  shutdown: bool,
+}
+
+#[derive(Default)]
+struct Shared {
+  queue: Mutex<Queue>,
+  work: Condvar,
+}
+
+/// Fixed-size thread pool with per-process lanes.
+pub(super) struct WorkerPool {
+  shared: Arc<Shared>,
+}
+
+impl WorkerPool {
+  /// Start `workers` threads. Fails if `workers` is 0 or a thread can't be
+  /// spawned, since calls would then never run.
+  pub(super) fn new(workers: usize) -> AllioResult<Self> {
+    if workers == 0 {
+      return Err(AllioError::Internal(
+        "worker pool needs at least one worker".into(),
+      ));
+    }
+    // Dropping `pool` on a failed spawn stops the workers already started.
+    let pool = Self {
+      shared: Arc::new(Shared::default()),
+    };
+    for i in 0..workers {
+      let shared = Arc::clone(&pool.shared);
+      std::thread::Builder::new()
+        .name(format!("allio-worker-{i}"))
+        .spawn(move || work(&shared))
+        .map_err(|e| AllioError::Internal(format!("failed to spawn allio worker: {e}")))?;
+    }
+    Ok(pool)
+  }
+
+  /// Queue a job, in `pid`'s lane if given. Fails, dropping the job, if
+  /// `MAX_QUEUED` jobs are already waiting.
+  pub(super) fn submit(&self, pid: Option<ProcessId>, job: Job) -> AllioResult<()> {
+    let mut queue = self.shared.queue.lock();
+    if queue.queued >= MAX_QUEUED {
+      return Err(AllioError::Internal(format!(
+        "worker pool queue is full ({MAX_QUEUED} calls waiting)"
+      )));
+    }
+    queue.queued += 1;
+    match pid {
+      None => queue.ready.push_back(Ready::Free(job)),
+      Some(pid) => {
+        if let Some(lane) = queue.lanes.get_mut(&pid) {
+          lane.push_back(job);
+          return Ok(());
+        }
+        queue.lanes.insert(pid, VecDeque::from([job]));
+        queue.ready.push_back(Ready::Lane(pid));
+      }
+    }
+    drop(queue);
+    self.shared.work.notify_one();
+    Ok(())
+  }
+}
+
+impl Drop for WorkerPool {
+  /// Workers exit after their current job. Queued jobs are dropped, which
+  /// fails their calls.
+  fn drop(&mut self) {
+    let pending = {
+      let mut queue = self.shared.queue.lock();
+      queue.shutdown = true;
+      (
+        std::mem::take(&mut queue.lanes),
+        std::mem::take(&mut queue.ready),
+      )
+    };
+    self.shared.work.notify_all();
+    drop(pending);
+  }
+}
+
+fn work(shared: &Shared) {
+  loop {
+    let (job, lane) = {
+      let mut queue = shared.queue.lock();
+      let next = loop {
+        if queue.shutdown {
+          return;
+        }
+        if let Some(next) = queue.ready.pop_front() {
+          break next;
+        }
+        shared.work.wait(&mut queue);
+      };
+      let picked = match next {
+        Ready::Free(job) => (job, None),
+        Ready::Lane(pid) => {
+          let Some(job) = queue.lanes.get_mut(&pid).and_then(VecDeque::pop_front) else {
+            queue.lanes.remove(&pid);
+            continue;
+          };
+          (job, Some(pid))
+        }
+      };
+      queue.queued -= 1;
+      picked
+    };
+
+    if panic::catch_unwind(AssertUnwindSafe(job)).is_err() {
+      log::error!("allio worker job panicked");
+    }
+
+    if let Some(pid) = lane {
+      let mut queue = shared.queue.lock();
+      if queue.lanes.get(&pid).is_some_and(|lane| !lane.is_empty()) {
+        queue.ready.push_back(Ready::Lane(pid));
+        drop(queue);
+        shared.work.notify_one();
+      } else {
+        queue.lanes.remove(&pid);
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::sync::mpsc;
+  use std::time::Duration;
+
+  #[test]
+  fn lanes_run_in_order_one_at_a_time() {
+    let pool = WorkerPool::new(4).unwrap();
+    let (tx, rx) = mpsc::channel();
+    for i in 0..8 {
+      let tx = tx.clone();
+      pool
+        .submit(
+          Some(ProcessId(1)),
+          Box::new(move || {
+            tx.send(("start", i)).unwrap();
+            std::thread::sleep(Duration::from_millis(2));
+            tx.send(("end", i)).unwrap();
+          }),
+        )
+        .unwrap();
+    }
+    let log: Vec<_> = rx.iter().take(16).collect();
+    let expected: Vec<_> = (0..8).flat_map(|i| [("start", i), ("end", i)]).collect();
+    assert_eq!(log, expected);
+  }
+
+  #[test]
+  fn other_lanes_and_free_jobs_are_not_held_up() {
+    let pool = WorkerPool::new(2).unwrap();
+    let (release, held) = mpsc::channel::<()>();
+    let (tx, rx) = mpsc::channel();
+    pool
+      .submit(Some(ProcessId(1)), Box::new(move || held.recv().unwrap()))
+      .unwrap();
+    let done = tx.clone();
+    pool
+      .submit(Some(ProcessId(1)), Box::new(move || done.send(1).unwrap()))
+      .unwrap();
+    let done = tx.clone();
+    pool
+      .submit(Some(ProcessId(2)), Box::new(move || done.send(2).unwrap()))
+      .unwrap();
+    pool
+      .submit(None, Box::new(move || tx.send(0).unwrap()))
+      .unwrap();
+
+    let mut first: Vec<_> = rx.iter().take(2).collect();
+    first.sort_unstable();
+    assert_eq!(first, [0, 2]);
+    release.send(()).unwrap();
+    assert_eq!(rx.recv().unwrap(), 1);
+  }
+
+  #[test]
+  fn a_panicking_job_releases_its_worker_and_lane() {
+    let pool = WorkerPool::new(1).unwrap();
+    let (tx, rx) = mpsc::channel();
+    pool
+      .submit(Some(ProcessId(1)), Box::new(|| panic!("job failed")))
+      .unwrap();
+    pool
+      .submit(Some(ProcessId(1)), Box::new(move || tx.send(1).unwrap()))
+      .unwrap();
+    assert_eq!(rx.recv_timeout(Duration::from_secs(5)), Ok(1));
+  }
+
+  #[test]
+  fn zero_workers_are_refused() {
+    assert!(matches!(WorkerPool::new(0), Err(AllioError::Internal(_))));
+  }
+
+  #[test]
+  fn a_full_queue_refuses_jobs() {
+    let pool = WorkerPool::new(1).unwrap();
+    let (release, held) = mpsc::channel::<()>();
+    let (started, running) = mpsc::channel();
+    pool
+      .submit(
+        None,
+        Box::new(move || {
+          started.send(()).unwrap();
+          held.recv().unwrap();
+        }),
+      )
+      .unwrap();
+    running.recv().unwrap();
+    for _ in 0..MAX_QUEUED {
+      pool.submit(Some(ProcessId(1)), Box::new(|| {})).unwrap();
+    }
+    assert!(pool.submit(None, Box::new(|| {})).is_err());
+    release.send(()).unwrap();
+  }
+}
@@ -4,49 +4,271 @@ Write operations that modify OS state through the platform layer.
 These are user-initiated actions that send commands to the accessibility API.
 */
 
+use std::time::{Duration, Instant};
+
 use super::Allio;
-use crate::a11y::Action;
-use crate::platform::PlatformHandle;
-use crate::types::{AllioError, AllioResult, ElementId};
+use crate::a11y::{Action, Role, Value};
+use crate::platform::{Handle, PlatformHandle};
+use crate::types::{AllioError, AllioResult, ElementId, Expect, Recency, Verification};
+use crate::wait;
+
+/// How long each attempt of a verified write waits for its outcome before
+/// writing again. One attempt per entry.
+const VERIFY_SETTLE: [Duration; 3] = [
+  Duration::from_millis(100),
+  Duration::from_millis(300),
+  Duration::from_millis(900),
+];
+
+/// How long a verified write that can't safely be repeated waits for its
+/// outcome: as long as all the attempts of one that can.
+const VERIFY_ONCE: [Duration; 1] = [Duration::from_millis(1300)];
 
 impl Allio {
   /// Set a typed value on an element.
   pub fn set_value(&self, element_id: ElementId, value: &crate::a11y::Value) -> AllioResult<()> {
     // Step 1: Extract what we need (quick read)
     let (handle, role) = self.read(|s| {
-      let e = s
-        .element(element_id)
-        .ok_or(AllioError::ElementNotFound(element_id))?;
+      let e = s.element(element_id).ok_or_else(|| s.missing(element_id))?;
       Ok((e.handle.clone(), e.role))
     })?;
 
-    // Step 2: Validate role is writable (no lock)
-    if !role.is_writable() {
-      return Err(AllioError::NotSupported(format!(
-        "Element with role '{role:?}' is not writable"
-      )));
-    }
-
-    // Step 3: Validate value type matches role's expected type
-    let expected = role.value_type();
-    let got = value.value_type();
-    if expected != got {
-      return Err(AllioError::TypeMismatch { expected, got });
-    }
+    // Step 2: Validate role and value type (no lock)
+    check_writable(role, value)?;
 
-    // Step 4: Platform call (NO LOCK)
+    // Step 3: Platform call (NO LOCK)
     handle.set_value(value)
   }
 
   /// Perform an action on an element.
   pub fn perform_action(&self, element_id: ElementId, action: Action) -> AllioResult<()> {
     let handle = self.read(|s| {
-      let e = s
-        .element(element_id)
-        .ok_or(AllioError::ElementNotFound(element_id))?;
+      let e = s.element(element_id).ok_or_else(|| s.missing(element_id))?;
       Ok(e.handle.clone())
     })?;
 
     handle.perform_action(action)
   }
+
+  /// Set a value, then read the element back until it shows the value,
+  /// setting it again if the app ignored the write.
+  ///
+  /// Fails with `AllioError::WriteNotApplied` if it never does.
+  pub fn set_value_verified(
+    &self,
+    element_id: ElementId,
+    value: &Value,
+  ) -> AllioResult<Verification> {
+    let expect = Expect::Value {
+      value: value.clone(),
+    };
+    self.verified(element_id, &expect, &VERIFY_SETTLE, || {
+      self.set_value(element_id, value)
+    })
+  }
+
+  /// Perform an action, then read the element back until `expect` holds,
+  /// performing it again if the app ignored it.
+  ///
+  /// Fails with `AllioError::WriteNotApplied` if it never does. Only actions
+  /// that are safe to repeat ([`Action::is_idempotent`], e.g. `Expand`) are
+  /// performed again; others, like a `Press` that may have toggled or
+  /// submitted something, are performed once and waited on longer.
+  pub fn perform_verified(
+    &self,
+    element_id: ElementId,
+    action: Action,
+    expect: &Expect,
+  ) -> AllioResult<Verification> {
+    let settles: &[Duration] = if action.is_idempotent() {
+      &VERIFY_SETTLE
+    } else {
+      &VERIFY_ONCE
+    };
+    self.verified(element_id, expect, settles, || {
+      self.perform_action(element_id, action)
+    })
+  }
+
+  /// Write, then wait for `expect` to hold, once per entry of `settles`.
+  fn verified(
+    &self,
+    element_id: ElementId,
+    expect: &Expect,
+    settles: &[Duration],
+    write: impl Fn() -> AllioResult<()>,
+  ) -> AllioResult<Verification> {
+    let before = self.get(element_id, Recency::Current)?;
+    let start = Instant::now();
+    let mut attempts = 0;
+    let mut now = before.clone();
+    for &settle in settles {
+      write()?;
+      attempts += 1;
+      let holds = |e: &Option<_>| e.as_ref().is_some_and(|e| expect.holds(&before, e));
+      // `holds` only accepts a present element, so a result is always `Some`.
+      let held = match self.wait_for(&wait::element(element_id), holds, settle) {
+        Ok(element) => element,
+        Err(AllioError::Timeout { .. }) => None,
+        Err(e) => return Err(e),
+      };
+      if let Some(element) = held {
+        return Ok(Verification {
+          element,
+          attempts,
+          elapsed_ms: u64::try_from(start.elapsed().as_millis()).unwrap_or(u64::MAX),
+        });
+      }
+      // Removed mid-verification: report that rather than a mismatch.
+      now = self.get(element_id, Recency::Any)?;
+    }
+    Err(AllioError::WriteNotApplied {
+      element_id,
+      attempts,
+      reason: expect.mismatch(&now),
+    })
+  }
+
+  /// Perform one action on many elements. Handles are read under a single lock.
+  pub(crate) fn perform_actions(&self, ids: &[ElementId], action: Action) -> Vec<AllioResult<()>> {
+    self
+      .handles(ids)
+      .into_iter()
+      .map(|entry| entry.and_then(|(handle, _)| handle.perform_action(action)))
+      .collect()
+  }
+
+  /// Set one value per element. Handles are read under a single lock.
+  pub(crate) fn set_values(&self, ids: &[ElementId], values: &[Value]) -> Vec<AllioResult<()>> {
+    self
+      .handles(ids)
+      .into_iter()
+      .zip(values)
+      .map(|(entry, value)| {
+        let (handle, role) = entry?;
+        check_writable(role, value)?;
+        handle.set_value(value)
+      })
+      .collect()
+  }
+
+  /// Handle and role for each element, in one read.
+  fn handles(&self, ids: &[ElementId]) -> Vec<AllioResult<(Handle, Role)>> {
+    self.read(|s| {
+      ids
+        .iter()
+        .map(|&id| {
+          s.element(id)
+            .map(|e| (e.handle.clone(), e.role))
+            .ok_or_else(|| s.missing(id))
+        })
+        .collect()
+    })
+  }
+}
+
+/// Validate that `value` can be written to an element with `role`.
+fn check_writable(role: Role, value: &Value) -> AllioResult<()> {
+  if !role.is_writable() {
+    return Err(AllioError::NotSupported(format!(
+      "Element with role '{role:?}' is not writable"
+    )));
+  }
+
+  let expected = role.value_type();
+  let got = value.value_type();
+  if expected != got {
+    return Err(AllioError::TypeMismatch { expected, got });
+  }
+  Ok(())
+}
+
+#[cfg(test)]
+#[cfg(feature = "mock")]
+mod tests {
+  use super::*;
+  use crate::mock::{MockDesktop, MockElement};
+  use crate::types::Element;
+
+  /// A window holding `elements`, loaded into the cache in order.
+  fn setup(elements: Vec<MockElement>) -> (MockDesktop, Allio, Vec<Element>) {
+    let desktop = MockDesktop::acquire();
+    let window = desktop.open_window("Notes", "Untitled");
+    for element in elements {
+      desktop.add_element(window.node, element);
+    }
+    let allio = desktop.start_allio().unwrap();
+    let root = allio.window_root(window.id).unwrap().unwrap().id;
+    let children = allio.children(root, Recency::Current).unwrap();
+    (desktop, allio, children)
+  }
+
+  #[test]
+  fn verified_writes_retry_until_applied() {
+    let (desktop, allio, elements) = setup(vec![
+      MockElement::new(Role::TextField).ignore_writes(1),
+      MockElement::new(Role::Checkbox)
+        .value(Value::Boolean(false))
+        .actions(&[Action::Press]),
+      MockElement::new(Role::Group)
+        .actions(&[Action::Expand])
+        .ignore_writes(2),
+    ]);
+    let [field, checkbox, group] = [elements[0].id, elements[1].id, elements[2].id];
+
+    let hello = Value::String("hello".into());
+    let verified = allio.set_value_verified(field, &hello).unwrap();
+    assert_eq!(verified.attempts, 2);
+    assert_eq!(verified.element.value, Some(hello));
+
+    let verified = allio
+      .perform_verified(checkbox, Action::Press, &Expect::ValueChanged)
+      .unwrap();
+    assert_eq!(verified.attempts, 1);
+    assert_eq!(verified.element.value, Some(Value::Boolean(true)));
+
+    let expanded = Expect::Expanded { expanded: true };
+    let verified = allio
+      .perform_verified(group, Action::Expand, &expanded)
+      .unwrap();
+    assert_eq!(verified.attempts, 3);
+    assert_eq!(desktop.performed_actions().len(), 4);
+  }
+
+  #[test]
+  fn ignored_writes_fail_after_every_attempt() {
+    let (_desktop, allio, elements) = setup(vec![
+      MockElement::new(Role::TextField).ignore_writes(u32::MAX)
+    ]);
+    let field = elements[0].id;
+
+    let result = allio.set_value_verified(field, &Value::String("hello".into()));
+    let Err(AllioError::WriteNotApplied {
+      element_id,
+      attempts,
+      reason,
+    }) = result
+    else {
+      panic!("expected WriteNotApplied, got {result:?}");
+    };
+    assert_eq!((element_id, attempts), (field, 3));
+    assert_eq!(reason, "expected value String(\"hello\"), found None");
+  }
+
+  #[test]
+  fn presses_are_never_repeated() {
+    let (desktop, allio, elements) = setup(vec![MockElement::new(Role::Checkbox)
+      .value(Value::Boolean(false))
+      .actions(&[Action::Press])
+      .ignore_writes(1)]);
+    let checkbox = elements[0].id;
+
+    let result = allio.perform_verified(checkbox, Action::Press, &Expect::ValueChanged);
+    assert!(matches!(
+      result,
+      Err(AllioError::WriteNotApplied { attempts: 1, .. })
+    ));
+    assert_eq!(desktop.performed_actions().len(), 1);
+    assert!(Action::Expand.is_idempotent() && !Action::Press.is_idempotent());
+  }
 }
@@ -8,7 +8,7 @@ and platform handles into cached registry entries.
 use super::registry::{CachedElement, Registry};
 use crate::a11y::Role;
 use crate::platform::{Handle, PlatformHandle};
-use crate::types::{Element, ElementId, ProcessId, Snapshot, WindowId};
+use crate::types::{Element, ElementId, ProcessId, WindowId};
 
 /// Build an Element from a `CachedElement` + tree relationships.
 pub(crate) fn build_element(registry: &Registry, id: ElementId) -> Option<Element> {
@@ -18,14 +18,22 @@ pub(crate) fn build_element(registry: &Registry, id: ElementId) -> Option<Elemen
   } else {
     registry.tree_parent(id)
   };
-  let children_slice = registry.tree_children(id);
-  let children = if children_slice.is_empty() && !registry.tree_has_children(id) {
-    None // Children not yet fetched
-  } else {
-    Some(children_slice.to_vec())
-  };
+  Some(to_element(
+    id,
+    elem,
+    parent_id,
+    registry.element_children(id),
+  ))
+}
 
-  Some(Element {
+/// Convert a `CachedElement` to the public type, given its tree relationships.
+pub(crate) fn to_element(
+  id: ElementId,
+  elem: &CachedElement,
+  parent_id: Option<ElementId>,
+  children: Option<Vec<ElementId>>,
+) -> Element {
+  Element {
     id,
     window_id: elem.window_id,
     pid: elem.pid,
@@ -51,15 +59,7 @@ pub(crate) fn build_element(registry: &Registry, id: ElementId) -> Option<Elemen
     actions: elem.actions.clone(),
     identifier: elem.identifier.clone(),
     is_fallback: elem.is_fallback,
-  })
-}
-
-/// Build all elements as public API types.
-pub(crate) fn build_all_elements(registry: &Registry) -> Vec<Element> {
-  registry
-    .elements()
-    .filter_map(|(id, _)| build_element(registry, id))
-    .collect()
+  }
 }
 
 /// Build a `CachedElement` from a platform handle.
@@ -81,38 +81,5 @@ pub(crate) fn build_entry_from_handle(
   // For root elements, don't store parent handle
   let parent_for_entry = if is_root { None } else { parent_handle };
 
-  CachedElement::from_attributes(
-    ElementId::new(),
-    window_id,
-    pid,
-    is_root,
-    handle,
-    parent_for_entry,
-    attrs,
-  )
-}
-
-/// Build a snapshot of current state.
-pub(crate) fn build_snapshot(registry: &Registry) -> Snapshot {
-  let (focused_element, selection) = registry
-    .focused_window()
-    .and_then(|wid| {
-      let window = registry.window(wid)?;
-      let process = registry.process(window.process_id)?;
-      let focused = process
-        .focused_element
-        .and_then(|eid| build_element(registry, eid));
-      Some((focused, process.last_selection.clone()))
-    })
-    .unwrap_or((None, None));
-
-  Snapshot {
-    windows: registry.windows().map(|w| w.info.clone()).collect(),
-    elements: build_all_elements(registry),
-    focused_window: registry.focused_window(),
-    focused_element,
-    selection,
-    z_order: registry.z_order().to_vec(),
-    mouse_position: registry.mouse_position(),
-  }
+  CachedElement::from_attributes(window_id, pid, is_root, handle, parent_for_entry, attrs)
 }
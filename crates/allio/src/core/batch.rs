@@ -0,0 +1,258 @@
+/*!
+Batched reads: many elements, or a whole subtree, in one call.
+
+Registry locks are taken once per phase rather than once per element, and the
+OS fetches in between fan out over rayon's pool. Loading a 500-row table with
+`fetch_subtree` costs one round of parallel fetches per tree level, instead of
+a sequential `children` round-trip per row.
+*/
+
+use rayon::prelude::*;
+
+use super::adapters::build_entry_from_handle;
+use super::registry::CachedElement;
+use super::{build_element, Allio, Registry};
+use crate::platform::{Handle, PlatformHandle};
+use crate::types::{AllioResult, Element, ElementId, ElementNode, ProcessId, Recency, WindowId};
+
+impl Allio {
+  /// Get many elements with one recency, in order.
+  ///
+  /// Fails with the first error if any ID can't be read;
+  /// [`Selection::get`](crate::Selection::get) reports each element separately.
+  #[must_use = "this returns a Result that may contain elements"]
+  pub fn get_many(&self, ids: &[ElementId], recency: Recency) -> AllioResult<Vec<Element>> {
+    self.get_each(ids, recency).into_iter().collect()
+  }
+
+  /// Get many elements with one recency, in order, with a result per ID.
+  pub(crate) fn get_each(&self, ids: &[ElementId], recency: Recency) -> Vec<AllioResult<Element>> {
+    let stale: Vec<(ElementId, Handle)> = self.read(|r| {
+      ids
+        .iter()
+        .filter_map(|&id| {
+          let e = r.element(id)?;
+          let refresh = match recency {
+            Recency::Any => false,
+            Recency::Current => true,
+            Recency::MaxAge(max_age) => e.is_stale(max_age),
+          };
+          refresh.then(|| (id, e.handle.clone()))
+        })
+        .collect()
+    });
+
+    if !stale.is_empty() {
+      let fresh: Vec<_> = stale
+        .into_par_iter()
+        .map(|(id, handle)| (id, handle.fetch_attributes()))
+        .collect();
+      self.write(|r| {
+        for (id, attrs) in fresh {
+          r.refresh_element(id, attrs);
+        }
+      });
+    }
+
+    self.read(|r| {
+      ids
+        .iter()
+        .map(|&id| build_element(r, id).ok_or_else(|| r.missing(id)))
+        .collect()
+    })
+  }
+
+  /// Get the subtree at `root` as a nested tree, down to `depth` levels below
+  /// it (`None` for all of it).
+  ///
+  /// `recency` applies to every element, as in [`Allio::get`] and [`Allio::children`]:
+  /// with `Recency::Any` nothing is fetched. Children are fetched a level at a
+  /// time, the whole level in parallel. Elements that vanish mid-fetch are left out.
+  #[must_use = "this returns a Result that may contain a tree"]
+  pub fn fetch_subtree(
+    &self,
+    root: ElementId,
+    depth: Option<usize>,
+    recency: Recency,
+  ) -> AllioResult<ElementNode> {
+    self.get(root, recency)?;
+    let mut level = vec![root];
+    let mut below = 0;
+    while !level.is_empty() && depth.is_none_or(|d| below < d) {
+      level = self.fetch_level(&level, recency);
+      below += 1;
+    }
+    self.read(|r| build_node(r, root, depth).ok_or_else(|| r.missing(root)))
+  }
+
+  /// Bring the children of `parents` up to `recency`. Returns all their children, in order.
+  fn fetch_level(&self, parents: &[ElementId], recency: Recency) -> Vec<ElementId> {
+    let to_fetch: Vec<(ElementId, Handle, WindowId, ProcessId)> = self.read(|r| {
+      parents
+        .iter()
+        .filter_map(|&id| {
+          let e = r.element(id)?;
+          let fetch = match recency {
+            Recency::Any => false,
+            Recency::Current => true,
+            Recency::MaxAge(max_age) => !r.tree_children_loaded(id) || e.is_stale(max_age),
+          };
+          fetch.then(|| (id, e.handle.clone(), e.window_id, e.pid))
+        })
+        .collect()
+    });
+
+    if !to_fetch.is_empty() {
+      let fetched: Vec<(ElementId, Vec<CachedElement>)> = to_fetch
+        .into_par_iter()
+        .map(|(id, handle, window_id, pid)| {
+          let children = handle
+            .fetch_children()
+            .into_par_iter()
+            .map(|child| build_entry_from_handle(child, window_id, pid))
+            .collect();
+          (id, children)
+        })
+        .collect();
+
+      let upserted: Vec<ElementId> = self.write(|r| {
+        let mut upserted = Vec::new();
+        for (id, entries) in fetched {
+          let child_ids: Vec<ElementId> = entries
+            .into_iter()
+            .filter_map(|entry| r.upsert_element(entry))
+            .collect();
+          upserted.extend_from_slice(&child_ids);
+          r.set_children(id, child_ids);
+        }
+        upserted
+      });
+      upserted.par_iter().for_each(|&id| self.ensure_watched(id));
+    }
+
+    self.read(|r| {
+      parents
+        .iter()
+        .flat_map(|&id| r.tree_children(id).iter().copied())
+        .collect()
+    })
+  }
+}
+
+/// The cached subtree at `id`, `depth` levels deep.
+fn build_node(r: &Registry, id: ElementId, depth: Option<usize>) -> Option<ElementNode> {
+  let element = build_element(r, id)?;
+  let children = if depth == Some(0) {
+    Vec::new()
+  } else {
+    let below = depth.map(|d| d.saturating_sub(1));
+    r.tree_children(id)
+      .iter()
+      .filter_map(|&child| build_node(r, child, below))
+      .collect()
+  };
+  Some(ElementNode { element, children })
+}
+
+#[cfg(test)]
+#[cfg(feature = "mock")]
+mod tests {
+  use super::*;
+  use crate::a11y::Role;
+  use crate::mock::{MockDesktop, MockElement, MockNodeId};
+  use crate::types::AllioError;
+
+  /// A window holding a table of three rows, each with one cell.
+  fn setup() -> (MockDesktop, Allio, ElementId, Vec<MockNodeId>) {
+    let desktop = MockDesktop::acquire();
+    let window = desktop.open_window("Sheets", "Budget");
+    let table = desktop
+      .add_element(window.node, MockElement::new(Role::Table))
+      .unwrap();
+    let rows = (0..3)
+      .map(|i| {
+        let row = desktop
+          .add_element(table, MockElement::new(Role::Row).label(format!("row {i}")))
+          .unwrap();
+        desktop.add_element(row, MockElement::new(Role::Cell).label(format!("cell {i}")));
+        row
+      })
+      .collect();
+
+    let allio = desktop.start_allio().unwrap();
+    let root = allio.window_root(window.id).unwrap().unwrap().id;
+    (desktop, allio, root, rows)
+  }
+
+  fn shape(node: &ElementNode) -> String {
+    let children: Vec<String> = node.children.iter().map(shape).collect();
+    let label = node.element.label.as_deref().unwrap_or_default();
+    if children.is_empty() {
+      format!("{:?}({label})", node.element.role)
+    } else {
+      format!("{:?}({label})[{}]", node.element.role, children.join(", "))
+    }
+  }
+
+  #[test]
+  fn fetch_subtree_loads_levels_to_depth() {
+    let (_desktop, allio, root, _) = setup();
+
+    // Nothing below the root is cached yet.
+    let cached = allio.fetch_subtree(root, None, Recency::Any).unwrap();
+    assert!(cached.children.is_empty());
+
+    let shallow = allio
+      .fetch_subtree(root, Some(2), Recency::Current)
+      .unwrap();
+    assert_eq!(
+      shape(&shallow),
+      "Window(Budget)[Table()[Row(row 0), Row(row 1), Row(row 2)]]"
+    );
+    let full = allio.fetch_subtree(root, None, Recency::Current).unwrap();
+    assert_eq!(
+      shape(&full),
+      "Window(Budget)[Table()[Row(row 0)[Cell(cell 0)], Row(row 1)[Cell(cell 1)], Row(row 2)[Cell(cell 2)]]]"
+    );
+    assert_eq!(allio.fetch_subtree(root, None, Recency::Any).unwrap(), full);
+
+    let labels: Vec<_> = full.flatten().into_iter().filter_map(|e| e.label).collect();
+    assert_eq!(
+      labels,
+      ["Budget", "row 0", "cell 0", "row 1", "cell 1", "row 2", "cell 2"]
+    );
+  }
+
+  #[test]
+  fn get_many_refreshes_in_order() {
+    let (desktop, allio, root, rows) = setup();
+    let table = allio
+      .fetch_subtree(root, Some(2), Recency::Current)
+      .unwrap();
+    let ids: Vec<ElementId> = table.children[0]
+      .children
+      .iter()
+      .rev()
+      .map(|row| row.element.id)
+      .collect();
+
+    desktop.update_element(rows[2], |e| e.label = Some("total".into()));
+    let label_of = |recency| -> Vec<Option<String>> {
+      allio
+        .get_many(&ids, recency)
+        .unwrap()
+        .into_iter()
+        .map(|e| e.label)
+        .collect()
+    };
+    assert_eq!(label_of(Recency::Any)[0].as_deref(), Some("row 2"));
+    assert_eq!(label_of(Recency::Current)[0].as_deref(), Some("total"));
+    assert_eq!(label_of(Recency::Any)[2].as_deref(), Some("row 0"));
+
+    let missing = ElementId::new(root.epoch(), 999_999);
+    assert!(matches!(
+      allio.get_many(&[ids[0], missing], Recency::Any),
+      Err(AllioError::ElementNotFound(id)) if id == missing
+    ));
+  }
+}
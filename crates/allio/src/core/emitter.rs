@@ -0,0 +1,47 @@
+/*!
+Event emission.
+
+Every event goes through one [`EventEmitter`], which stamps it with the next
+sequence number and a timestamp. Stamping and broadcasting happen under one
+lock, so receivers see sequence numbers in increasing order.
+*/
+
+use async_broadcast::Sender;
+use parking_lot::Mutex;
+
+use crate::types::{Event, SequencedEvent};
+
+/// Stamps and broadcasts events. Shared by `Allio` and its `Registry`.
+pub(crate) struct EventEmitter {
+  tx: Sender<SequencedEvent>,
+  /// Sequence number of the last emitted event (0 before the first).
+  last_seq: Mutex<u64>,
+}
+
+impl EventEmitter {
+  pub(crate) const fn new(tx: Sender<SequencedEvent>) -> Self {
+    Self {
+      tx,
+      last_seq: Mutex::new(0),
+    }
+  }
+
+  /// Stamp and broadcast an event.
+  pub(crate) fn emit(&self, event: Event) {
+    let mut last_seq = self.last_seq.lock();
+    *last_seq += 1;
+    if let Err(e) = self.tx.try_broadcast(SequencedEvent::new(*last_seq, event)) {
+      if e.is_full() {
+        log::error!(
+          "Event channel overflow - events are being dropped. \
+           Consider increasing EVENT_CHANNEL_CAPACITY or processing events faster."
+        );
+      }
+    }
+  }
+
+  /// Sequence number of the last emitted event (0 before the first).
+  pub(crate) fn last_seq(&self) -> u64 {
+    *self.last_seq.lock()
+  }
+}
@@ -123,7 +123,7 @@ impl Allio {
       let Some(entry) = r.element(element_id) else {
         return (false, ProcessId(0), None);
       };
-      let needs = entry.watch.is_none();
+      let needs = !r.has_element_watch(element_id);
       (needs, entry.pid, Some(entry.handle.clone()))
     });
 
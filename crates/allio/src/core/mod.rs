@@ -4,9 +4,11 @@ Core Allio instance - owns all accessibility state and event broadcasting.
 # Module Structure
 
 - `mod.rs` - Allio struct, construction, events, `EventHandler` impl
+- `emitter.rs` - stamps events with sequence numbers and broadcasts them
 - `registry/` - Registry (cache) with private fields + operations + event emission
 - `queries.rs` - `get()` with recency, lookups, discovery
-- `actions.rs` - `set_value()`, `perform_action()` (write to OS)
+- `batch.rs` - `get_many()`, `fetch_subtree()`: batched reads with parallel OS fetches
+- `actions.rs` - `set_value()`, `perform_action()` (write to OS), and their verified variants
 - `sync.rs` - bulk updates from polling loop
 - `handlers.rs` - notification handlers from OS events
 - `subscriptions.rs` - watch/unwatch
@@ -27,27 +29,30 @@ let element = allio.get(element_id, Recency::Current)?;   // From OS
 let children = allio.children(element.id, Recency::Current)?;
 
 let mut events = allio.subscribe();
-while let Ok(event) = events.recv().await {
-    // handle event
+while let Ok(stamped) = events.recv().await {
+    // handle stamped.event (stamped.seq orders events, stamped.timestamp_ms dates them)
 }
 ```
 */
 
 mod actions;
 pub(crate) mod adapters;
+mod batch;
+mod emitter;
 mod handlers;
 mod queries;
 pub(crate) mod registry;
 mod subscriptions;
 mod sync;
 
-pub(crate) use adapters::{build_element, build_snapshot};
+pub(crate) use adapters::build_element;
 pub(crate) use registry::Registry;
 
 use crate::platform::{CurrentPlatform, Platform};
 use crate::polling::{self, PollingHandle};
-use crate::types::{AllioError, AllioResult, Event};
-use async_broadcast::{InactiveReceiver, Sender};
+use crate::types::{AllioError, AllioResult, Event, SequencedEvent};
+use async_broadcast::InactiveReceiver;
+use emitter::EventEmitter;
 use parking_lot::{Mutex, RwLock};
 use std::sync::Arc;
 
@@ -62,8 +67,8 @@ const EVENT_CHANNEL_CAPACITY: usize = 5000;
 /// Clone is cheap (Arc bumps) - share freely across threads.
 pub struct Allio {
   pub(crate) state: Arc<RwLock<Registry>>,
-  events_tx: Sender<Event>,
-  events_keepalive: InactiveReceiver<Event>,
+  events: Arc<EventEmitter>,
+  events_keepalive: InactiveReceiver<SequencedEvent>,
   polling: Arc<Mutex<Option<PollingHandle>>>,
   screen_size: Arc<std::sync::OnceLock<(f64, f64)>>,
   observation: Arc<crate::observation::ObservationState>,
@@ -74,7 +79,7 @@ impl Clone for Allio {
   fn clone(&self) -> Self {
     Self {
       state: Arc::clone(&self.state),
-      events_tx: self.events_tx.clone(),
+      events: Arc::clone(&self.events),
       events_keepalive: self.events_keepalive.clone(),
       polling: Arc::clone(&self.polling),
       screen_size: Arc::clone(&self.screen_size),
@@ -188,12 +193,13 @@ impl Allio {
     let (mut tx, rx) = async_broadcast::broadcast(EVENT_CHANNEL_CAPACITY);
     tx.set_overflow(true); // Drop oldest messages when full
 
-    // State owns a clone of the sender for event emission
-    let state = Registry::new(tx.clone());
+    // State shares the emitter, so all events are sequenced together
+    let events = Arc::new(EventEmitter::new(tx));
+    let state = Registry::new(Arc::clone(&events));
 
     let allio = Allio {
       state: Arc::new(RwLock::new(state)),
-      events_tx: tx,
+      events,
       events_keepalive: rx.deactivate(),
       polling: Arc::new(Mutex::new(None)),
       screen_size: Arc::new(std::sync::OnceLock::new()),
@@ -213,7 +219,10 @@ impl Allio {
   }
 
   /// Subscribe to events from this instance.
-  pub fn subscribe(&self) -> async_broadcast::Receiver<Event> {
+  ///
+  /// Events arrive in sequence order. A gap in `seq` (or an `Overflowed` error)
+  /// means the receiver fell behind and events were dropped.
+  pub fn subscribe(&self) -> async_broadcast::Receiver<SequencedEvent> {
     self.events_keepalive.activate_cloned()
   }
 
@@ -236,11 +245,7 @@ impl Allio {
 
   /// Emit an event directly (used by observation system).
   pub(crate) fn emit_event(&self, event: Event) {
-    if let Err(e) = self.events_tx.try_broadcast(event) {
-      if e.is_full() {
-        log::error!("Event channel overflow in observation");
-      }
-    }
+    self.events.emit(event);
   }
 }
 
@@ -262,8 +267,18 @@ impl EventHandler for Allio {
       }
 
       ElementEvent::ChildrenChanged(element_id) => {
-        // Re-fetch children
-        drop(self.fetch_children(element_id, 1000));
+        // Re-fetch as many children as were loaded: all of them, or the
+        // leading pages. Unloaded children wait to be paged in.
+        let loaded = self.read(|r| {
+          if r.tree_children_loaded(element_id) {
+            usize::MAX
+          } else {
+            r.tree_children(element_id).len()
+          }
+        });
+        if loaded > 0 {
+          drop(self.fetch_children_page(element_id, 0, loaded));
+        }
       }
 
       ElementEvent::FocusChanged(focused_handle) => {
@@ -276,7 +291,9 @@ impl EventHandler for Allio {
         };
 
         // Cache element from handle and delegate to handler
-        let element_id = self.upsert_from_handle(focused_handle, window_id, pid);
+        let Some(element_id) = self.upsert_from_handle(focused_handle, window_id, pid) else {
+          return;
+        };
         self.handle_focus_changed(pid.0, element_id);
       }
 
@@ -294,7 +311,9 @@ impl EventHandler for Allio {
         };
 
         // Cache element from handle
-        let element_id = self.upsert_from_handle(handle, window_id, pid);
+        let Some(element_id) = self.upsert_from_handle(handle, window_id, pid) else {
+          return;
+        };
 
         // Delegate to existing handler
         self.handle_selection_changed(pid.0, window_id, element_id, text, range);
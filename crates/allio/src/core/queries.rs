@@ -5,7 +5,11 @@ Query methods for Allio.
 
 - `get(id, recency)` = unified element access with explicit recency
 - `children(id, recency)` = get children with recency control
+- `children_page(id, offset, limit, recency)` = one page of them
 - `parent(id, recency)` = get parent with recency control
+- `query(root, selector, recency)` = CSS-like selector over a subtree
+- `get_many(ids, recency)`, `fetch_subtree(root, depth, recency)` = batched reads (`batch.rs`)
+- `tombstone(id)` = when and why a removed element was removed
 - `get_*` = internal registry/state lookups (fast, no OS calls)
 - `fetch_*` = internal OS calls (deprecated in public API)
 
@@ -17,11 +21,15 @@ The `get` method takes a `Recency` parameter that explicitly controls staleness:
 - `Recency::MaxAge(duration)` - fetch if older than duration
 */
 
+use std::collections::VecDeque;
+
 use super::adapters::build_entry_from_handle;
 use super::Allio;
 use crate::platform::{CurrentPlatform, Handle, Platform};
+use crate::selector::Selector;
 use crate::types::{
-  AllioError, AllioResult, Element, ElementId, ProcessId, Recency, Window, WindowId,
+  AllioError, AllioResult, ChildrenPage, Element, ElementId, ProcessId, Recency, Tombstone, Window,
+  WindowId,
 };
 
 impl Allio {
@@ -49,17 +57,17 @@ impl Allio {
     self.read(|r| r.find_window_by_handle(&window_handle))
   }
 
-  /// Cache an element from a platform handle.
+  /// Cache an element from a platform handle. None if its window has closed.
   pub(crate) fn upsert_from_handle(
     &self,
     handle: Handle,
     window_id: WindowId,
     pid: ProcessId,
-  ) -> ElementId {
+  ) -> Option<ElementId> {
     let entry = build_entry_from_handle(handle, window_id, pid);
-    let element_id = self.write(|r| r.upsert_element(entry));
+    let element_id = self.write(|r| r.upsert_element(entry))?;
     self.ensure_watched(element_id);
-    element_id
+    Some(element_id)
   }
 }
 
@@ -70,18 +78,14 @@ impl Allio {
     match recency {
       Recency::Any => {
         // Fast path: just read from cache
-        self
-          .read(|r| super::build_element(r, element_id))
-          .ok_or(AllioError::ElementNotFound(element_id))
+        self.read(|r| super::build_element(r, element_id).ok_or_else(|| r.missing(element_id)))
       }
       Recency::Current => {
         // Always refresh from OS
         if self.read(|r| r.element(element_id).is_some()) {
           self.refresh_element(element_id)?;
         }
-        self
-          .read(|r| super::build_element(r, element_id))
-          .ok_or(AllioError::ElementNotFound(element_id))
+        self.read(|r| super::build_element(r, element_id).ok_or_else(|| r.missing(element_id)))
       }
       Recency::MaxAge(max_age) => {
         // Check if stale, refresh if needed
@@ -90,13 +94,26 @@ impl Allio {
         if needs_refresh {
           self.refresh_element(element_id)?;
         }
-        self
-          .read(|r| super::build_element(r, element_id))
-          .ok_or(AllioError::ElementNotFound(element_id))
+        self.read(|r| super::build_element(r, element_id).ok_or_else(|| r.missing(element_id)))
       }
     }
   }
 
+  /// Epoch of this instance's session. Every `ElementId` it assigns carries it
+  /// (see [`ElementId::epoch`]), until 2^32 - 1 have been assigned and it
+  /// moves on to further epochs of its own. IDs carrying another are rejected
+  /// with `AllioError::ForeignElementId`.
+  pub fn session(&self) -> u32 {
+    self.read(super::registry::Registry::session)
+  }
+
+  /// When and why an element was removed. `None` if it wasn't, or was too long
+  /// ago to be remembered. Lookups of a remembered element fail with
+  /// `AllioError::ElementRemoved` rather than `ElementNotFound`.
+  pub fn tombstone(&self, element_id: ElementId) -> Option<Tombstone> {
+    self.read(|r| r.tombstone(element_id))
+  }
+
   /// Get children of an element with specified recency.
   #[must_use = "this returns a Result that may contain elements"]
   pub fn children(&self, element_id: ElementId, recency: Recency) -> AllioResult<Vec<Element>> {
@@ -120,6 +137,47 @@ impl Allio {
     }
   }
 
+  /// Get a page of an element's children: up to `limit` of them, from `offset`.
+  ///
+  /// When `recency` calls for a fetch, the child list is read from the OS but
+  /// attributes are only fetched for children in the page, so paging through a
+  /// huge list stays cheap. Otherwise the page is cut from the cached children,
+  /// which are only those loaded so far: the total is then None unless all of
+  /// them were loaded.
+  #[must_use = "this returns a Result that may contain elements"]
+  pub fn children_page(
+    &self,
+    element_id: ElementId,
+    offset: usize,
+    limit: usize,
+    recency: Recency,
+  ) -> AllioResult<ChildrenPage> {
+    let fetch = match recency {
+      Recency::Any => false,
+      Recency::Current => true,
+      Recency::MaxAge(max_age) => {
+        self.read(|r| r.element(element_id).is_none_or(|e| e.is_stale(max_age)))
+      }
+    };
+    if fetch {
+      return self.fetch_children_page(element_id, offset, limit);
+    }
+    Ok(self.read(|r| {
+      let cached = r.tree_children(element_id);
+      let end = offset.saturating_add(limit).min(cached.len());
+      ChildrenPage {
+        children: cached
+          .get(offset.min(end)..end)
+          .unwrap_or_default()
+          .iter()
+          .filter_map(|id| super::build_element(r, *id))
+          .collect(),
+        total: r.tree_children_loaded(element_id).then_some(cached.len()),
+        next_offset: (end < cached.len()).then_some(end),
+      }
+    }))
+  }
+
   /// Get parent of an element with specified recency.
   /// Returns `Ok(None)` if element is root.
   #[must_use = "this returns a Result that may contain an element"]
@@ -143,6 +201,85 @@ impl Allio {
     }
   }
 
+  /// Is `id` the element `root` or one of its cached descendants? Cache only.
+  pub fn in_subtree(&self, root: ElementId, id: ElementId) -> bool {
+    self.read(|r| r.tree_contains(root, id))
+  }
+
+  /// Find elements in the subtree of `root` (inclusive) matching a CSS-like selector.
+  /// Results are in document order. See [`Selector`] for the syntax.
+  ///
+  /// `recency` controls how much of the subtree is fetched from the OS first:
+  /// - `Recency::Any` - match only what is cached
+  /// - `Recency::Current` - re-fetch the children of every node
+  /// - `Recency::MaxAge(duration)` - fetch children that are missing or stale
+  #[must_use = "this returns a Result that may contain elements"]
+  pub fn query(
+    &self,
+    root: ElementId,
+    selector: &str,
+    recency: Recency,
+  ) -> AllioResult<Vec<Element>> {
+    let selector = Selector::parse(selector)?;
+    self.get(root, recency)?;
+    if selector.uses_position() && recency != Recency::Any {
+      self.load_siblings(root);
+    }
+    self.load_subtree(root, recency)?;
+    Ok(self.read(|r| {
+      selector
+        .select(r, root)
+        .into_iter()
+        .filter_map(|id| super::build_element(r, id))
+        .collect()
+    }))
+  }
+
+  /// Load every sibling of `root`, so position pseudo-classes can match it.
+  /// Failures are logged: the root then matches no position.
+  fn load_siblings(&self, root: ElementId) {
+    let parent = self.read(|r| r.tree_parent(root));
+    let Some(parent) = parent.or_else(|| self.fetch_parent(root).ok().flatten().map(|p| p.id))
+    else {
+      return;
+    };
+    if self.read(|r| r.tree_children_loaded(parent)) {
+      return;
+    }
+    if let Err(e) = self.fetch_children(parent, usize::MAX) {
+      log::debug!("Siblings of query root {root} not loaded: {e}");
+    }
+  }
+
+  /// Fetch children below `root` as `recency` requires, breadth-first.
+  /// Failures below the root are logged and skip that branch.
+  pub(crate) fn load_subtree(&self, root: ElementId, recency: Recency) -> AllioResult<()> {
+    let mut queue = VecDeque::from([root]);
+    while let Some(id) = queue.pop_front() {
+      let needs_fetch = match recency {
+        Recency::Any => false,
+        Recency::Current => true,
+        Recency::MaxAge(max_age) => self.read(|r| {
+          !r.tree_children_loaded(id) || r.element(id).is_some_and(|e| e.is_stale(max_age))
+        }),
+      };
+      let children = if needs_fetch {
+        match self.fetch_children(id, usize::MAX) {
+          Ok(children) => children.into_iter().map(|child| child.id).collect(),
+          Err(e) if id == root => return Err(e),
+          Err(e) => {
+            log::debug!("query: skipping children of {id}: {e}");
+            continue;
+          }
+        }
+      } else {
+        self.read(|r| r.tree_children(id).to_vec())
+      };
+      queue.extend(children);
+    }
+    Ok(())
+  }
+
   /// Refresh element data from OS.
   pub(crate) fn refresh_element(&self, element_id: ElementId) -> AllioResult<Element> {
     use crate::platform::PlatformHandle;
@@ -150,20 +287,14 @@ impl Allio {
     let handle = self.read(|r| {
       r.element(element_id)
         .map(|e| e.handle.clone())
-        .ok_or(AllioError::ElementNotFound(element_id))
+        .ok_or_else(|| r.missing(element_id))
     })?;
 
     let attrs = handle.fetch_attributes();
 
-    self.write(|r| {
-      if let Some(elem) = r.elements.get_mut(&element_id) {
-        elem.refresh(attrs);
-      }
-    });
+    self.write(|r| r.refresh_element(element_id, attrs));
 
-    self
-      .read(|r| super::build_element(r, element_id))
-      .ok_or(AllioError::ElementNotFound(element_id))
+    self.read(|r| super::build_element(r, element_id).ok_or_else(|| r.missing(element_id)))
   }
 
   /// Get all windows.
@@ -187,13 +318,17 @@ impl Allio {
   }
 
   /// Get all elements.
+  ///
+  /// Converts a frozen copy of the cache, so writers aren't held up meanwhile.
   pub fn all_elements(&self) -> Vec<Element> {
-    self.read(super::adapters::build_all_elements)
+    self.read(super::registry::Registry::freeze).all_elements()
   }
 
   /// Get a snapshot of the current state.
+  ///
+  /// Built from a frozen copy of the cache, so writers aren't held up meanwhile.
   pub fn snapshot(&self) -> crate::types::Snapshot {
-    self.read(super::build_snapshot)
+    self.read(super::registry::Registry::freeze).snapshot()
   }
 
   /// Find window at a point.
@@ -221,9 +356,7 @@ impl Allio {
     element_id: ElementId,
   ) -> AllioResult<(Handle, WindowId, u32, bool)> {
     self.read(|s| {
-      let e = s
-        .element(element_id)
-        .ok_or(AllioError::ElementNotFound(element_id))?;
+      let e = s.element(element_id).ok_or_else(|| s.missing(element_id))?;
       Ok((e.handle.clone(), e.window_id, e.pid.0, e.is_root))
     })
   }
@@ -267,10 +400,12 @@ impl Allio {
       .fetch_element_at_position(x, y)
       .ok_or(AllioError::NoElementAtPosition { x, y })?;
 
-    let element_id = self.upsert_from_handle(element_handle, window_id, ProcessId(pid));
-    let mut element = self
-      .read(|r| super::build_element(r, element_id))
-      .ok_or(AllioError::ElementNotFound(element_id))?;
+    let Some(element_id) = self.upsert_from_handle(element_handle, window_id, ProcessId(pid))
+    else {
+      return Ok(None);
+    };
+    let mut element =
+      self.read(|r| super::build_element(r, element_id).ok_or_else(|| r.missing(element_id)))?;
 
     // Detect Chromium/Electron fallback container
     let is_fallback = matches!(element.role, Role::Group | Role::GenericGroup)
@@ -284,37 +419,80 @@ impl Allio {
     Ok(Some(element))
   }
 
-  /// Fetch and register children of element from OS.
+  /// Fetch and register the first `max_children` children of element from OS.
   pub(crate) fn fetch_children(
     &self,
     element_id: ElementId,
     max_children: usize,
   ) -> AllioResult<Vec<Element>> {
+    self
+      .fetch_children_page(element_id, 0, max_children)
+      .map(|page| page.children)
+  }
+
+  /// Fetch and register `limit` children of element from OS, starting at `offset`.
+  ///
+  /// Attributes are only fetched for children in the page. The element's cached
+  /// children become those in the page plus any others that were already cached,
+  /// in OS order; the rest stay unloaded until paged in, and until then the
+  /// element's children don't count as loaded.
+  pub(crate) fn fetch_children_page(
+    &self,
+    element_id: ElementId,
+    offset: usize,
+    limit: usize,
+  ) -> AllioResult<ChildrenPage> {
     use crate::platform::PlatformHandle;
 
     let (handle, window_id, pid, _is_root) = self.element_handle(element_id)?;
     let child_handles = handle.fetch_children();
+    let total = child_handles.len();
+    let end = offset.saturating_add(limit).min(total);
+    let page = offset.min(end)..end;
+    let whole = page.len() == total;
+
+    // IDs of cached children outside the page, in one read.
+    let known: Vec<Option<ElementId>> = self.read(|r| {
+      child_handles
+        .iter()
+        .enumerate()
+        .map(|(i, h)| (!page.contains(&i)).then(|| r.find_element(h)).flatten())
+        .collect()
+    });
 
-    if child_handles.is_empty() {
-      self.write(|r| r.set_children(element_id, vec![]));
-      return Ok(vec![]);
-    }
-
-    let cap = child_handles.len().min(max_children);
-    let mut children = Vec::with_capacity(cap);
-    let mut child_ids = Vec::with_capacity(cap);
-
-    for child_handle in child_handles.into_iter().take(max_children) {
-      // upsert_element will detect parent changes and reparent in the tree
-      let child_id = self.upsert_from_handle(child_handle, window_id, ProcessId(pid));
-      if let Some(child) = self.read(|r| super::build_element(r, child_id)) {
-        child_ids.push(child.id);
-        children.push(child);
+    let mut child_ids = Vec::with_capacity(page.len());
+    let mut page_ids = Vec::with_capacity(page.len());
+    for (i, (child_handle, known)) in child_handles.into_iter().zip(known).enumerate() {
+      if page.contains(&i) {
+        // upsert_element will detect parent changes and reparent in the tree
+        if let Some(child_id) = self.upsert_from_handle(child_handle, window_id, ProcessId(pid)) {
+          page_ids.push(child_id);
+          child_ids.push(child_id);
+        }
+      } else if let Some(child_id) = known {
+        child_ids.push(child_id);
       }
     }
 
-    self.write(|r| r.set_children(element_id, child_ids));
-    Ok(children)
+    let complete = whole || child_ids.len() == total;
+    self.write(|r| {
+      if complete {
+        r.set_children(element_id, child_ids);
+      } else {
+        r.set_some_children(element_id, child_ids);
+      }
+    });
+    let children = self.read(|r| {
+      page_ids
+        .iter()
+        .filter_map(|&id| super::build_element(r, id))
+        .collect()
+    });
+    Ok(ChildrenPage {
+      children,
+      total: Some(total),
+      next_offset: (end < total).then_some(end),
+    })
   }
 
   /// Fetch and register parent of element from OS. Returns None if element is root.
@@ -326,7 +504,9 @@ impl Allio {
       return Ok(None);
     };
 
-    let parent_id = self.upsert_from_handle(parent_handle, window_id, ProcessId(pid));
+    let Some(parent_id) = self.upsert_from_handle(parent_handle, window_id, ProcessId(pid)) else {
+      return Ok(None);
+    };
     Ok(self.read(|r| super::build_element(r, parent_id)))
   }
 
@@ -347,8 +527,11 @@ impl Allio {
       return Ok(None);
     };
 
-    let element_id =
-      self.upsert_from_handle(window_handle, window_id, ProcessId(window.process_id.0));
+    let Some(element_id) =
+      self.upsert_from_handle(window_handle, window_id, ProcessId(window.process_id.0))
+    else {
+      return Ok(None);
+    };
     self.write(|r| r.set_window_root(window_id, element_id));
 
     Ok(self.read(|r| super::build_element(r, element_id)))
@@ -3,7 +3,7 @@ Element operations for the Registry.
 
 CRUD: `upsert_element`, `update_element`, `remove_element`
 Query: element, elements, `find_element`
-Element-specific: `set_children`, `set_element_watch`, `take_element_watch`
+Element-specific: `set_children`, `set_element_watch`, `take_element_watch`, watcher counts
 
 ## Handle-Based Identity
 
@@ -13,7 +13,7 @@ This gives O(1) lookups with correct collision resolution - no more O(n) fallbac
 
 use super::{CachedElement, Registry};
 use crate::platform::{Handle, WatchHandle};
-use crate::types::{ElementId, Event};
+use crate::types::{ElementChange, ElementId, Event, RemovalReason};
 
 impl Registry {
   /// Insert or update an element by handle.
@@ -23,7 +23,13 @@ impl Registry {
   /// If the element exists with a DIFFERENT parent (platform reparented it),
   /// destroys the old element and its subtree, then creates a new element.
   /// Our API doesn't support reparenting - element IDs have stable parents.
-  pub(crate) fn upsert_element(&mut self, elem: CachedElement) -> ElementId {
+  ///
+  /// Returns None without caching anything if the element's window isn't cached:
+  /// it closed while the element was being fetched.
+  pub(crate) fn upsert_element(&mut self, elem: CachedElement) -> Option<ElementId> {
+    if !self.windows.contains_key(&elem.window_id) {
+      return None;
+    }
     let handle = elem.handle.clone();
     let parent_handle = elem.parent_handle.clone();
     let is_root = elem.is_root;
@@ -49,15 +55,13 @@ impl Registry {
         // Fall through to create new element below
       } else {
         // Same parent (or discovering parent for first time) - update in place
-        let mut fresh_elem = elem;
-        fresh_elem.id = existing_id;
-        self.update_element(existing_id, fresh_elem);
-        return existing_id;
+        self.update_element(existing_id, elem);
+        return Some(existing_id);
       }
     }
 
     // Create new element (either first time, or after reparent-destroy)
-    let element_id = elem.id;
+    let element_id = self.ids.next();
 
     self.handle_to_id.insert(handle.clone(), element_id);
     self.elements.insert(element_id, elem);
@@ -66,8 +70,10 @@ impl Registry {
     if !is_root {
       if let Some(ref ph) = parent_handle {
         if let Some(&parent_id) = self.handle_to_id.get(ph) {
+          let old = self.element_children(parent_id);
           self.tree.add_child(parent_id, element_id);
-          self.emit_element_changed(parent_id);
+          let new = self.element_children(parent_id);
+          self.emit_element_changed(parent_id, vec![ElementChange::Children { old, new }]);
         } else {
           self
             .waiting_for_parent
@@ -79,51 +85,72 @@ impl Registry {
     }
 
     // Resolve orphans waiting for this element
-    if let Some(orphans) = self.waiting_for_parent.remove(&handle) {
-      for orphan_id in orphans {
-        self.tree.add_child(element_id, orphan_id);
-        self.emit_element_changed(orphan_id);
-      }
+    let orphans = self.waiting_for_parent.remove(&handle).unwrap_or_default();
+    for &orphan_id in &orphans {
+      self.tree.add_child(element_id, orphan_id);
+      let change = ElementChange::ParentId {
+        old: None,
+        new: Some(element_id),
+      };
+      self.emit_element_changed(orphan_id, vec![change]);
     }
 
     self.emit_element_added(element_id);
-    element_id
+    self.note_added(element_id);
+    self.rematch_added(&orphans);
+    Some(element_id)
   }
 
-  /// Update element data. Preserves handle, watch, and updates `last_refreshed`.
-  /// Emits `ElementChanged` if semantic data differs.
+  /// Update element data. Preserves handle, watchers, and updates `last_refreshed`.
+  /// Emits `ElementChanged` with the semantic fields that differ.
   pub(crate) fn update_element(&mut self, id: ElementId, mut new_elem: CachedElement) {
     let Some(old_elem) = self.elements.get_mut(&id) else {
       return;
     };
 
-    let changed = old_elem != &new_elem;
+    let changes = old_elem.diff(&new_elem);
 
     // Preserve metadata from old entry
     new_elem.handle = old_elem.handle.clone();
-    new_elem.watch = old_elem.watch.take();
+    new_elem.watchers = old_elem.watchers;
     new_elem.last_refreshed = std::time::Instant::now();
 
     *old_elem = new_elem;
 
-    if changed {
-      self.emit_element_changed(id);
-    }
+    self.emit_element_changed(id, changes);
   }
 
-  /// Remove an element and all descendants.
+  /// Remove a destroyed element and all descendants, noting them for re-identification.
   pub(crate) fn remove_element(&mut self, id: ElementId) {
+    let Some(window_id) = self.elements.get(&id).map(|e| e.window_id) else {
+      self.remove_subtree(id, RemovalReason::Destroyed);
+      return;
+    };
+    let fingerprints = self.subtree_fingerprints(id);
+    self.remove_subtree(id, RemovalReason::Destroyed);
+    self.note_removed(window_id, fingerprints);
+  }
+
+  /// Remove an element and all descendants, leaving tombstones.
+  /// Descendants are buried with `reason.cascaded()`.
+  pub(super) fn remove_subtree(&mut self, id: ElementId, reason: RemovalReason) {
     let removed_ids = self.tree.remove_subtree(id);
 
     for removed_id in removed_ids {
-      self.remove_element_internal(removed_id);
+      let reason = if removed_id == id {
+        reason
+      } else {
+        reason.cascaded()
+      };
+      self.remove_element_internal(removed_id, reason);
     }
   }
 
-  fn remove_element_internal(&mut self, id: ElementId) {
-    let Some(mut elem) = self.elements.remove(&id) else {
+  fn remove_element_internal(&mut self, id: ElementId, reason: RemovalReason) {
+    let Some(elem) = self.elements.remove(&id) else {
       return;
     };
+    self.tombstones.bury(id, elem.window_id, reason);
 
     // Clean handle index
     self.handle_to_id.remove(&elem.handle);
@@ -138,9 +165,12 @@ impl Registry {
       }
     }
     self.waiting_for_parent.remove(&elem.handle);
-    elem.watch.take();
+    self.watches.remove(&id);
 
-    self.emit(Event::ElementRemoved { element_id: id });
+    self.emit(Event::ElementRemoved {
+      element_id: id,
+      reason,
+    });
   }
 
   /// Get element entry by ID.
@@ -160,6 +190,16 @@ impl Registry {
 
   /// Set children for an element. Emits `ElementChanged` if different.
   pub(crate) fn set_children(&mut self, id: ElementId, children: Vec<ElementId>) {
+    self.replace_children(id, children, true);
+  }
+
+  /// Cache some of an element's children, such as one page of them, without
+  /// counting its children as loaded. Emits `ElementChanged` if different.
+  pub(crate) fn set_some_children(&mut self, id: ElementId, children: Vec<ElementId>) {
+    self.replace_children(id, children, false);
+  }
+
+  fn replace_children(&mut self, id: ElementId, children: Vec<ElementId>, complete: bool) {
     if !self.elements.contains_key(&id) {
       return;
     }
@@ -169,8 +209,9 @@ impl Registry {
       .filter(|&cid| self.elements.contains_key(&cid))
       .collect();
 
-    let old_children = self.tree.children(id);
-    if old_children == valid_children {
+    let old_children = self.element_children(id);
+    if old_children.as_deref() == Some(valid_children.as_slice()) {
+      self.tree.set_loaded(id, complete);
       return;
     }
 
@@ -189,19 +230,47 @@ impl Registry {
       }
     }
 
-    self.tree.set_children(id, valid_children);
-    self.emit_element_changed(id);
+    self.tree.set_children(id, valid_children.clone());
+    self.tree.set_loaded(id, complete);
+    let change = ElementChange::Children {
+      old: old_children,
+      new: Some(valid_children),
+    };
+    self.emit_element_changed(id, vec![change]);
+    let linked = self.tree.children(id).to_vec();
+    self.rematch_added(&linked);
   }
 
   /// Set watch handle for element.
   pub(crate) fn set_element_watch(&mut self, id: ElementId, watch: WatchHandle) {
-    if let Some(elem) = self.elements.get_mut(&id) {
-      elem.watch = Some(watch);
+    if self.elements.contains_key(&id) {
+      self.watches.insert(id, watch);
     }
   }
 
   /// Take watch handle from element.
   pub(crate) fn take_element_watch(&mut self, id: ElementId) -> Option<WatchHandle> {
-    self.elements.get_mut(&id).and_then(|e| e.watch.take())
+    self.watches.remove(&id)
+  }
+
+  /// Check if the element has a watch handle.
+  pub(crate) fn has_element_watch(&self, id: ElementId) -> bool {
+    self.watches.contains_key(&id)
+  }
+
+  /// Count a watcher of an element. Returns the new count (0 if not found).
+  pub(crate) fn add_element_watcher(&mut self, id: ElementId) -> usize {
+    self.elements.get_mut(&id).map_or(0, |e| {
+      e.watchers += 1;
+      e.watchers
+    })
+  }
+
+  /// Uncount a watcher of an element. Returns the remaining count (0 if not found).
+  pub(crate) fn remove_element_watcher(&mut self, id: ElementId) -> usize {
+    self.elements.get_mut(&id).map_or(0, |e| {
+      e.watchers = e.watchers.saturating_sub(1);
+      e.watchers
+    })
   }
 }
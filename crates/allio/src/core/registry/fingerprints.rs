@@ -0,0 +1,361 @@
+/*!
+Re-identification of recreated elements.
+
+When an app rebuilds a view, its elements are destroyed and new ones created
+in their place, each under a new `ElementId`. Elements are fingerprinted by
+role, identifier, label and index, chained through their ancestors up to the
+window root. When a new element matches the fingerprint of one removed from
+the same window moments before, `ElementReplaced { old, new }` is emitted so
+clients can move anchors over.
+
+Removal and recreation arrive in either order (a sweep adds the new children
+before removing the old ones), so both sides are remembered briefly. Removals
+through `remove_window` are not remembered: the window is gone.
+*/
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::hash::{Hash, Hasher};
+use std::time::{Duration, Instant};
+
+use super::{CachedElement, Registry};
+use crate::types::{ElementId, Event, WindowId};
+
+/// How long a removed (or added) element waits for its counterpart.
+const MATCH_WINDOW: Duration = Duration::from_secs(2);
+
+/// Most removed (and added) elements remembered at once.
+const MAX_REMEMBERED: usize = 1024;
+
+/// What removed elements are matched by: their window and fingerprint.
+type Key = (WindowId, u64);
+
+/// Recently removed and added elements not yet matched.
+///
+/// Both are indexed, so matching costs a lookup per element. The queues keep
+/// arrival order for expiry; entries matched since are skipped as they expire.
+#[derive(Default)]
+pub(crate) struct Replacements {
+  /// Removed elements by window and fingerprint.
+  removed: HashMap<Key, Vec<ElementId>>,
+  removed_order: VecDeque<(Key, ElementId, Instant)>,
+  removed_len: usize,
+  /// Added elements by window. Fingerprinted when matched: the index may
+  /// still change after adding.
+  added: HashMap<WindowId, HashSet<ElementId>>,
+  added_order: VecDeque<(WindowId, ElementId, Instant)>,
+  added_len: usize,
+}
+
+impl Replacements {
+  fn prune(&mut self) {
+    let now = Instant::now();
+    let expired = |at: Instant| now.duration_since(at) > MATCH_WINDOW;
+    while let Some(&(key, id, at)) = self.removed_order.front() {
+      if !expired(at) && self.removed_len <= MAX_REMEMBERED {
+        break;
+      }
+      self.removed_order.pop_front();
+      self.take_removed(key, id);
+    }
+    while let Some(&(window_id, id, at)) = self.added_order.front() {
+      if !expired(at) && self.added_len <= MAX_REMEMBERED {
+        break;
+      }
+      self.added_order.pop_front();
+      self.take_added(window_id, id);
+    }
+  }
+
+  fn remember_removed(&mut self, key: Key, id: ElementId, at: Instant) {
+    self.removed.entry(key).or_default().push(id);
+    self.removed_order.push_back((key, id, at));
+    self.removed_len += 1;
+  }
+
+  fn remember_added(&mut self, window_id: WindowId, id: ElementId, at: Instant) {
+    if self.added.entry(window_id).or_default().insert(id) {
+      self.added_order.push_back((window_id, id, at));
+      self.added_len += 1;
+    }
+  }
+
+  /// Stop remembering a removed element. Returns whether it was remembered.
+  fn take_removed(&mut self, key: Key, id: ElementId) -> bool {
+    let Some(ids) = self.removed.get_mut(&key) else {
+      return false;
+    };
+    let Some(index) = ids.iter().position(|&r| r == id) else {
+      return false;
+    };
+    ids.swap_remove(index);
+    if ids.is_empty() {
+      self.removed.remove(&key);
+    }
+    self.removed_len -= 1;
+    true
+  }
+
+  /// Stop remembering an added element. Returns whether it was remembered.
+  fn take_added(&mut self, window_id: WindowId, id: ElementId) -> bool {
+    let Some(ids) = self.added.get_mut(&window_id) else {
+      return false;
+    };
+    if !ids.remove(&id) {
+      return false;
+    }
+    if ids.is_empty() {
+      self.added.remove(&window_id);
+    }
+    self.added_len -= 1;
+    true
+  }
+
+  /// Forget elements from a window that is gone.
+  pub(super) fn forget_window(&mut self, window_id: WindowId) {
+    let mut forgotten = 0;
+    self.removed.retain(|&(window, _), ids| {
+      if window == window_id {
+        forgotten += ids.len();
+      }
+      window != window_id
+    });
+    self.removed_len -= forgotten;
+    if let Some(ids) = self.added.remove(&window_id) {
+      self.added_len -= ids.len();
+    }
+  }
+}
+
+/// Fingerprint of an element, given its parent's fingerprint (None for roots).
+fn step(parent: Option<u64>, index: usize, elem: &CachedElement) -> u64 {
+  let mut hasher = DefaultHasher::new();
+  parent.hash(&mut hasher);
+  index.hash(&mut hasher);
+  elem.role.hash(&mut hasher);
+  elem.identifier.hash(&mut hasher);
+  elem.label.hash(&mut hasher);
+  hasher.finish()
+}
+
+impl Registry {
+  /// Structural fingerprint of an element. None unless it is linked to its window root.
+  pub(super) fn fingerprint(&self, id: ElementId) -> Option<u64> {
+    // Walk up to the root, then hash back down.
+    let mut chain = Vec::new();
+    let mut current = id;
+    loop {
+      let elem = self.elements.get(&current)?;
+      if elem.is_root {
+        chain.push((elem, 0));
+        break;
+      }
+      let parent = self.tree.parent(current)?;
+      let index = self
+        .tree
+        .children(parent)
+        .iter()
+        .position(|&c| c == current)?;
+      chain.push((elem, index));
+      current = parent;
+    }
+    chain.into_iter().rev().fold(None, |parent, (elem, index)| {
+      Some(step(parent, index, elem))
+    })
+  }
+
+  /// Fingerprints of an element and its descendants, for noting their removal.
+  pub(super) fn subtree_fingerprints(&self, root: ElementId) -> Vec<(ElementId, u64)> {
+    let mut out = Vec::new();
+    let Some(fingerprint) = self.fingerprint(root) else {
+      return out;
+    };
+    let mut stack = vec![(root, fingerprint)];
+    while let Some((id, fingerprint)) = stack.pop() {
+      out.push((id, fingerprint));
+      for (index, &child) in self.tree.children(id).iter().enumerate() {
+        if let Some(elem) = self.elements.get(&child) {
+          stack.push((child, step(Some(fingerprint), index, elem)));
+        }
+      }
+    }
+    out
+  }
+
+  /// Note removed elements (with their fingerprints from before removal), matching
+  /// them against recently added ones.
+  ///
+  /// A fingerprint shared by more than one removed or added element is
+  /// ambiguous: none of them is matched, rather than guessing.
+  pub(super) fn note_removed(&mut self, window_id: WindowId, removed: Vec<(ElementId, u64)>) {
+    if removed.is_empty() {
+      return;
+    }
+    self.replacements.prune();
+
+    let mut candidates: HashMap<u64, Vec<ElementId>> = HashMap::new();
+    for &id in self
+      .replacements
+      .added
+      .get(&window_id)
+      .into_iter()
+      .flatten()
+    {
+      if let Some(fingerprint) = self.fingerprint(id) {
+        candidates.entry(fingerprint).or_default().push(id);
+      }
+    }
+    let mut removed_count: HashMap<u64, usize> = HashMap::new();
+    for &(_, fingerprint) in &removed {
+      *removed_count.entry(fingerprint).or_default() += 1;
+    }
+
+    let now = Instant::now();
+    for (old, fingerprint) in removed {
+      let key = (window_id, fingerprint);
+      let unique = removed_count.get(&fingerprint) == Some(&1)
+        && !self.replacements.removed.contains_key(&key);
+      match candidates.get(&fingerprint).map(Vec::as_slice) {
+        Some(&[new]) if unique => {
+          self.replacements.take_added(window_id, new);
+          self.emit(Event::ElementReplaced { old, new });
+        }
+        _ => self.replacements.remember_removed(key, old, now),
+      }
+    }
+  }
+
+  /// Note a newly created element, matching it against recently removed ones.
+  pub(super) fn note_added(&mut self, id: ElementId) {
+    self.replacements.prune();
+    if self.match_added(id) {
+      return;
+    }
+    if let Some(window_id) = self.elements.get(&id).map(|e| e.window_id) {
+      self
+        .replacements
+        .remember_added(window_id, id, Instant::now());
+    }
+  }
+
+  /// Retry matching recently added elements, now that they may be linked or reindexed.
+  pub(super) fn rematch_added(&mut self, ids: &[ElementId]) {
+    for &id in ids {
+      let Some(window_id) = self.elements.get(&id).map(|e| e.window_id) else {
+        continue;
+      };
+      let pending = self
+        .replacements
+        .added
+        .get(&window_id)
+        .is_some_and(|ids| ids.contains(&id));
+      if pending && self.match_added(id) {
+        self.replacements.take_added(window_id, id);
+      }
+    }
+  }
+
+  /// Emit `ElementReplaced` if exactly one removed element matches this one.
+  /// Returns whether one did.
+  fn match_added(&mut self, new: ElementId) -> bool {
+    if self.replacements.removed.is_empty() {
+      return false;
+    }
+    let Some(window_id) = self.elements.get(&new).map(|e| e.window_id) else {
+      return false;
+    };
+    let Some(fingerprint) = self.fingerprint(new) else {
+      return false;
+    };
+    let key = (window_id, fingerprint);
+    let Some(&[old]) = self.replacements.removed.get(&key).map(Vec::as_slice) else {
+      return false;
+    };
+    self.replacements.take_removed(key, old);
+    self.emit(Event::ElementReplaced { old, new });
+    true
+  }
+}
+
+#[cfg(test)]
+#[cfg(feature = "mock")]
+mod tests {
+  use super::*;
+  use crate::a11y::Role;
+  use crate::mock::{MockDesktop, MockElement};
+  use crate::types::SequencedEvent;
+  use crate::Recency;
+
+  fn replaced(rx: &mut async_broadcast::Receiver<SequencedEvent>) -> Vec<(ElementId, ElementId)> {
+    std::iter::from_fn(|| rx.try_recv().ok())
+      .filter_map(|stamped| {
+        if let Event::ElementReplaced { old, new } = stamped.event {
+          Some((old, new))
+        } else {
+          None
+        }
+      })
+      .collect()
+  }
+
+  #[test]
+  fn ambiguous_removals_are_not_matched() {
+    let desktop = MockDesktop::acquire();
+    let window = desktop.open_window("Notes", "Untitled");
+    desktop.add_element(window.node, MockElement::new(Role::Button).label("Save"));
+    desktop.add_element(window.node, MockElement::new(Role::Button).label("Open"));
+    let allio = desktop.start_allio().unwrap();
+    let root = allio.window_root(window.id).unwrap().unwrap();
+    let children = allio.children(root.id, Recency::Current).unwrap();
+    let (save, open) = (children[0].id, children[1].id);
+    let fingerprint = |id| allio.read(|r| r.fingerprint(id)).unwrap();
+    let mut rx = allio.subscribe();
+
+    // Two removals share the new Save button's fingerprint: neither is its predecessor.
+    let (a, b, c) = (
+      ElementId::new(0, 1_000),
+      ElementId::new(0, 1_001),
+      ElementId::new(0, 1_002),
+    );
+    let save_fingerprint = fingerprint(save);
+    allio.write(|r| {
+      r.note_removed(
+        window.id,
+        vec![(a, save_fingerprint), (b, save_fingerprint)],
+      )
+    });
+    assert_eq!(replaced(&mut rx), []);
+
+    // One removal for Open is.
+    let open_fingerprint = fingerprint(open);
+    allio.write(|r| r.note_removed(window.id, vec![(c, open_fingerprint)]));
+    assert_eq!(replaced(&mut rx), [(c, open)]);
+  }
+
+  #[test]
+  fn replacements_stay_within_their_window() {
+    let desktop = MockDesktop::acquire();
+    // Same app and title, so elements in the same place fingerprint alike.
+    let first = desktop.open_window("Notes", "Untitled");
+    let second = desktop.open_window("Notes", "Untitled");
+    let button = desktop
+      .add_element(first.node, MockElement::new(Role::Button).label("Save"))
+      .unwrap();
+    let allio = desktop.start_allio().unwrap();
+    let first_root = allio.window_root(first.id).unwrap().unwrap();
+    let second_root = allio.window_root(second.id).unwrap().unwrap();
+    let old = allio.children(first_root.id, Recency::Current).unwrap()[0].id;
+    let fingerprint = allio.read(|r| r.fingerprint(old));
+    let mut rx = allio.subscribe();
+
+    desktop.remove_element(button);
+    assert!(allio
+      .children(first_root.id, Recency::Current)
+      .unwrap()
+      .is_empty());
+    desktop.add_element(second.node, MockElement::new(Role::Button).label("Save"));
+    let new = allio.children(second_root.id, Recency::Current).unwrap()[0].id;
+    assert_eq!(allio.read(|r| r.fingerprint(new)), fingerprint);
+    assert_eq!(replaced(&mut rx), []);
+  }
+}
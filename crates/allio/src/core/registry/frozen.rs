@@ -0,0 +1,172 @@
+/*!
+Frozen copies of the registry, for heavy readers.
+
+`snapshot()` and `all_elements()` convert every cached element. Doing that
+under the registry lock would stall polling and observation sweeps for as long
+as it takes, and every new WebSocket client asks for a snapshot. Instead they
+`freeze` the registry: windows, elements and the tree live in persistent maps,
+so copying them is O(1), and the conversion runs on the copy after the lock is
+released. While a frozen copy is alive, writers copy only the nodes they touch.
+*/
+
+use super::tree::ElementTree;
+use super::{CachedElement, CachedWindow, Registry};
+use crate::core::adapters::to_element;
+use crate::types::{Element, ElementId, Point, Snapshot, TextSelection, WindowId};
+
+/// Immutable copy of the state a `Snapshot` is built from.
+pub(crate) struct Frozen {
+  windows: im::HashMap<WindowId, CachedWindow>,
+  elements: im::HashMap<ElementId, CachedElement>,
+  tree: ElementTree,
+  focused_window: Option<WindowId>,
+  /// Focused element and selection of the focused window's process.
+  focused_element: Option<ElementId>,
+  selection: Option<TextSelection>,
+  z_order: Vec<WindowId>,
+  mouse_position: Option<Point>,
+  seq: u64,
+  session: u32,
+}
+
+impl Registry {
+  /// Copy the state for building snapshots, in O(1) (plus the z-order).
+  pub(crate) fn freeze(&self) -> Frozen {
+    let process = self
+      .focused_window()
+      .and_then(|wid| self.windows.get(&wid))
+      .and_then(|w| self.processes.get(&w.process_id));
+    Frozen {
+      windows: self.windows.clone(),
+      elements: self.elements.clone(),
+      tree: self.tree.clone(),
+      focused_window: self.focused_window(),
+      focused_element: process.and_then(|p| p.focused_element),
+      selection: process.and_then(|p| p.last_selection.clone()),
+      z_order: self.z_order().to_vec(),
+      mouse_position: self.mouse_position(),
+      seq: self.last_seq(),
+      session: self.session(),
+    }
+  }
+}
+
+impl Frozen {
+  fn element(&self, id: ElementId) -> Option<Element> {
+    let elem = self.elements.get(&id)?;
+    let parent_id = if elem.is_root {
+      None
+    } else {
+      self.tree.parent(id)
+    };
+    Some(to_element(
+      id,
+      elem,
+      parent_id,
+      self.tree.reported_children(id),
+    ))
+  }
+
+  /// All elements as public API types.
+  pub(crate) fn all_elements(&self) -> Vec<Element> {
+    let mut elements = Vec::with_capacity(self.elements.len());
+    elements.extend(self.elements.iter().map(|(&id, elem)| {
+      let parent_id = if elem.is_root {
+        None
+      } else {
+        self.tree.parent(id)
+      };
+      to_element(id, elem, parent_id, self.tree.reported_children(id))
+    }));
+    elements
+  }
+
+  pub(crate) fn snapshot(&self) -> Snapshot {
+    Snapshot {
+      windows: self.windows.values().map(|w| w.info.clone()).collect(),
+      elements: self.all_elements(),
+      focused_window: self.focused_window,
+      focused_element: self.focused_element.and_then(|id| self.element(id)),
+      selection: self.selection.clone(),
+      z_order: self.z_order.clone(),
+      mouse_position: self.mouse_position,
+      seq: self.seq,
+      session: self.session,
+    }
+  }
+}
+
+#[cfg(test)]
+#[cfg(feature = "mock")]
+mod tests {
+  use super::*;
+  use crate::a11y::{Role, Value};
+  use crate::mock::{eventually, MockDesktop, MockElement};
+  use crate::types::Recency;
+
+  #[test]
+  fn frozen_copies_ignore_later_writes() {
+    let desktop = MockDesktop::acquire();
+    let window = desktop.open_window("Notes", "Untitled");
+    desktop.add_element(window.node, MockElement::new(Role::Button).label("Save"));
+    let allio = desktop.start_allio().unwrap();
+    let root = allio.window_root(window.id).unwrap().unwrap().id;
+    let button = allio.children(root, Recency::Current).unwrap()[0].id;
+
+    let frozen = allio.read(Registry::freeze);
+    desktop.remove_window(window.id);
+    eventually(|| allio.window(window.id).is_none().then_some(()));
+
+    let before = frozen.snapshot();
+    assert_eq!(before.windows.len(), 1);
+    let saved = before.elements.iter().find(|e| e.id == button).unwrap();
+    assert_eq!(saved.parent_id, Some(root));
+    assert!(before.seq < allio.snapshot().seq);
+    assert!(allio.all_elements().is_empty());
+  }
+
+  #[test]
+  fn frozen_snapshots_never_change() {
+    let desktop = MockDesktop::acquire();
+    let window = desktop.open_window("Notes", "Untitled");
+    let group = desktop
+      .add_element(window.node, MockElement::new(Role::Group))
+      .unwrap();
+    let field = desktop
+      .add_element(group, MockElement::new(Role::TextField))
+      .unwrap();
+    let button = desktop
+      .add_element(group, MockElement::new(Role::Button).label("Save"))
+      .unwrap();
+    let allio = desktop.start_allio().unwrap();
+    let root = allio.window_root(window.id).unwrap().unwrap().id;
+    let group_id = allio.children(root, Recency::Current).unwrap()[0].id;
+    let field_id = allio.children(group_id, Recency::Current).unwrap()[0].id;
+    allio.watch(field_id).unwrap();
+
+    let frozen = allio.read(Registry::freeze);
+    let before = frozen.snapshot();
+    assert_eq!(before.elements.len(), 4);
+
+    // Touch elements, the tree and windows after freezing.
+    desktop.set_value(field, Value::String("edited".into()));
+    eventually(|| {
+      let value = allio.get(field_id, Recency::Any).unwrap().value;
+      (value == Some(Value::String("edited".into()))).then_some(())
+    });
+    desktop.remove_element(button);
+    desktop.add_element(group, MockElement::new(Role::Button).label("Open"));
+    let labels: Vec<_> = allio
+      .children(group_id, Recency::Current)
+      .unwrap()
+      .into_iter()
+      .map(|e| e.label)
+      .collect();
+    assert_eq!(labels, [None, Some("Open".into())]);
+    desktop.remove_window(window.id);
+    eventually(|| allio.window(window.id).is_none().then_some(()));
+
+    assert_eq!(frozen.snapshot(), before);
+    assert_ne!(allio.snapshot(), before);
+  }
+}
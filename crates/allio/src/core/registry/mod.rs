@@ -14,21 +14,31 @@ and emit events. This guarantees:
 - `windows.rs` - Window CRUD, queries, window-specific ops
 - `processes.rs` - Process CRUD, queries
 - `tree.rs` - `ElementTree` for parent/child relationships
+- `frozen.rs` - O(1) immutable copies for heavy readers (`snapshot`, `all_elements`)
+- `fingerprints.rs` - Re-identification of recreated elements (`ElementReplaced`)
+- `tombstones.rs` - Records of removed elements (`ElementRemoved` errors)
 */
 
 mod elements;
+mod fingerprints;
+mod frozen;
 mod processes;
+mod tombstones;
 mod tree;
 mod windows;
 
-use async_broadcast::Sender;
 use std::collections::HashMap;
+use std::sync::Arc;
 
+use super::emitter::EventEmitter;
 use crate::a11y::{Action, Role, Value};
 use crate::platform::{AppNotificationHandle, Handle, Observer, WatchHandle};
 use crate::types::{
-  Bounds, Element, ElementId, Event, Point, ProcessId, TextRange, TextSelection, Window, WindowId,
+  Bounds, Element, ElementChange, ElementId, ElementIds, Event, Point, ProcessId, TextRange,
+  TextSelection, Window, WindowId,
 };
+use fingerprints::Replacements;
+use tombstones::Tombstones;
 use tree::ElementTree;
 
 /// Result of attempting to set focused element.
@@ -50,6 +60,7 @@ pub(crate) struct CachedProcess {
 }
 
 /// Per-window state.
+#[derive(Clone)]
 pub(crate) struct CachedWindow {
   pub(crate) process_id: ProcessId,
   pub(crate) info: Window,
@@ -59,9 +70,9 @@ pub(crate) struct CachedWindow {
 }
 
 /// Per-element state in the registry.
+#[derive(Clone)]
 pub(crate) struct CachedElement {
   // === Identity & Hierarchy ===
-  pub(crate) id: ElementId,
   pub(crate) window_id: WindowId,
   pub(crate) pid: ProcessId,
   pub(crate) is_root: bool,
@@ -112,7 +123,7 @@ pub(crate) struct CachedElement {
   pub(crate) actions: Vec<Action>,
 
   // === Identity ===
-  /// Platform accessibility identifier (AXIdentifier on macOS).
+  /// Platform accessibility identifier (`AXIdentifier` on macOS).
   pub(crate) identifier: Option<String>,
 
   // === Hit Test Status ===
@@ -120,16 +131,16 @@ pub(crate) struct CachedElement {
   pub(crate) is_fallback: bool,
 
   // === Registry metadata ===
-  pub(crate) watch: Option<WatchHandle>,
+  /// Outstanding `watch` calls. Change notifications are registered while this is non-zero.
+  pub(crate) watchers: usize,
   /// When this element was last refreshed from the OS.
   pub(crate) last_refreshed: std::time::Instant,
 }
 
 impl PartialEq for CachedElement {
-  /// Compare semantic element data. Excludes registry metadata (handle, watch, `last_refreshed`).
+  /// Compare semantic element data. Excludes registry metadata (handle, watchers, `last_refreshed`).
   fn eq(&self, other: &Self) -> bool {
-    self.id == other.id
-      && self.window_id == other.window_id
+    self.window_id == other.window_id
       && self.pid == other.pid
       && self.is_root == other.is_root
       && self.role == other.role
@@ -157,7 +168,6 @@ impl PartialEq for CachedElement {
 impl CachedElement {
   /// Create a `CachedElement` from platform attributes.
   pub(crate) fn from_attributes(
-    id: ElementId,
     window_id: WindowId,
     pid: ProcessId,
     is_root: bool,
@@ -166,7 +176,6 @@ impl CachedElement {
     attrs: crate::platform::ElementAttributes,
   ) -> Self {
     Self {
-      id,
       window_id,
       pid,
       is_root,
@@ -191,7 +200,7 @@ impl CachedElement {
       actions: attrs.actions,
       identifier: attrs.identifier,
       is_fallback: false,
-      watch: None,
+      watchers: 0,
       last_refreshed: std::time::Instant::now(),
     }
   }
@@ -201,41 +210,80 @@ impl CachedElement {
     self.last_refreshed.elapsed() > max_age
   }
 
-  /// Refresh element data from platform attributes. Updates all semantic fields in place.
-  /// Preserves: id, handle, `parent_handle`, watch. Updates: `last_refreshed`.
-  pub(crate) fn refresh(&mut self, attrs: crate::platform::ElementAttributes) {
-    self.role = attrs.role;
-    self.platform_role = attrs.platform_role;
-    self.label = attrs.title;
-    self.description = attrs.description;
-    self.placeholder = attrs.placeholder;
-    self.url = attrs.url;
-    self.value = attrs.value;
-    self.bounds = attrs.bounds;
-    self.focused = attrs.focused;
-    self.disabled = attrs.disabled;
-    self.selected = attrs.selected;
-    self.expanded = attrs.expanded;
-    self.row_index = attrs.row_index;
-    self.column_index = attrs.column_index;
-    self.row_count = attrs.row_count;
-    self.column_count = attrs.column_count;
-    self.actions = attrs.actions;
-    self.identifier = attrs.identifier;
-    self.is_fallback = false;
-    self.last_refreshed = std::time::Instant::now();
+  /// Refresh element data from platform attributes. Updates all semantic fields in place
+  /// and returns the ones that changed.
+  /// Preserves: handle, `parent_handle`, watchers. Updates: `last_refreshed`.
+  pub(crate) fn refresh(
+    &mut self,
+    attrs: crate::platform::ElementAttributes,
+  ) -> Vec<ElementChange> {
+    let mut fresh = Self::from_attributes(
+      self.window_id,
+      self.pid,
+      self.is_root,
+      self.handle.clone(),
+      self.parent_handle.clone(),
+      attrs,
+    );
+    fresh.watchers = self.watchers;
+    let changes = self.diff(&fresh);
+    *self = fresh;
+    changes
+  }
+
+  /// Semantic fields that differ in `new`, with old and new values.
+  pub(crate) fn diff(&self, new: &Self) -> Vec<ElementChange> {
+    let mut changes = Vec::new();
+    macro_rules! diff_fields {
+      ($($field:ident => $change:ident),* $(,)?) => {
+        $(
+          if self.$field != new.$field {
+            changes.push(ElementChange::$change {
+              old: self.$field.clone(),
+              new: new.$field.clone(),
+            });
+          }
+        )*
+      };
+    }
+    diff_fields! {
+      role => Role,
+      platform_role => PlatformRole,
+      label => Label,
+      description => Description,
+      placeholder => Placeholder,
+      url => Url,
+      value => Value,
+      bounds => Bounds,
+      focused => Focused,
+      disabled => Disabled,
+      selected => Selected,
+      expanded => Expanded,
+      row_index => RowIndex,
+      column_index => ColumnIndex,
+      row_count => RowCount,
+      column_count => ColumnCount,
+      actions => Actions,
+      identifier => Identifier,
+      is_fallback => IsFallback,
+    }
+    changes
   }
 }
 
 /// Internal state storage with automatic event emission.
 pub(crate) struct Registry {
   // Event emission
-  events_tx: Sender<Event>,
+  events: Arc<EventEmitter>,
 
-  // Primary collections
+  // Primary collections. Windows and elements (and the tree) are persistent
+  // maps, so `freeze` copies them in O(1) for readers.
   pub(super) processes: HashMap<ProcessId, CachedProcess>,
-  pub(super) windows: HashMap<WindowId, CachedWindow>,
-  pub(super) elements: HashMap<ElementId, CachedElement>,
+  pub(super) windows: im::HashMap<WindowId, CachedWindow>,
+  pub(super) elements: im::HashMap<ElementId, CachedElement>,
+  /// Destruction and change watches, by element. Kept out of `elements`: they
+  /// unregister from the OS on drop, so must not be shared with frozen copies.
+  pub(super) watches: HashMap<ElementId, WatchHandle>,
 
   // Tree structure - single source of truth for relationships
   pub(super) tree: ElementTree,
@@ -245,6 +293,12 @@ pub(crate) struct Registry {
   pub(super) waiting_for_parent: HashMap<Handle, Vec<ElementId>>,
   /// Window handle → `WindowId` index for O(1) lookup from element's `AXWindow` handle.
   pub(super) window_handle_to_id: HashMap<Handle, WindowId>,
+  /// Recently removed and added elements, for re-identification.
+  pub(super) replacements: Replacements,
+  /// Recently removed elements, for telling stale IDs from bogus ones.
+  pub(super) tombstones: Tombstones,
+  /// Allocates IDs for new elements, tagged with this session's epoch.
+  pub(super) ids: ElementIds,
 
   // Focus/UI state
   focused_window: Option<WindowId>,
@@ -253,16 +307,20 @@ pub(crate) struct Registry {
 }
 
 impl Registry {
-  pub(crate) fn new(events_tx: Sender<Event>) -> Self {
+  pub(crate) fn new(events: Arc<EventEmitter>) -> Self {
     Self {
-      events_tx,
+      events,
       processes: HashMap::new(),
-      windows: HashMap::new(),
-      elements: HashMap::new(),
+      windows: im::HashMap::new(),
+      elements: im::HashMap::new(),
+      watches: HashMap::new(),
       tree: ElementTree::new(),
       handle_to_id: HashMap::new(),
       waiting_for_parent: HashMap::new(),
       window_handle_to_id: HashMap::new(),
+      replacements: Replacements::default(),
+      tombstones: Tombstones::default(),
+      ids: ElementIds::new(),
       focused_window: None,
       z_order: Vec::new(),
       mouse_position: None,
@@ -271,14 +329,17 @@ impl Registry {
 
   /// Emit an event.
   pub(super) fn emit(&self, event: Event) {
-    if let Err(e) = self.events_tx.try_broadcast(event) {
-      if e.is_full() {
-        log::error!(
-          "Event channel overflow - events are being dropped. \
-           Consider increasing EVENT_CHANNEL_CAPACITY or processing events faster."
-        );
-      }
-    }
+    self.events.emit(event);
+  }
+
+  /// Epoch tagging this session's element IDs.
+  pub(crate) fn session(&self) -> u32 {
+    self.ids.epoch()
+  }
+
+  /// Sequence number of the last emitted event.
+  pub(crate) fn last_seq(&self) -> u64 {
+    self.events.last_seq()
   }
 
   /// Emit `ElementAdded` event (used by elements.rs).
@@ -288,13 +349,21 @@ impl Registry {
     }
   }
 
-  /// Emit `ElementChanged` event.
-  pub(crate) fn emit_element_changed(&self, id: ElementId) {
-    if let Some(element) = super::adapters::build_element(self, id) {
-      self.emit(Event::ElementChanged { element });
+  /// Emit `ElementChanged` event, unless nothing changed.
+  pub(crate) fn emit_element_changed(&self, id: ElementId, changes: Vec<ElementChange>) {
+    if !changes.is_empty() {
+      self.emit(Event::ElementChanged {
+        element_id: id,
+        changes,
+      });
     }
   }
 
+  /// Children as reported on `Element`: None until they have been fetched.
+  pub(crate) fn element_children(&self, id: ElementId) -> Option<Vec<ElementId>> {
+    self.tree.reported_children(id)
+  }
+
   /// Get parent from tree.
   pub(crate) fn tree_parent(&self, id: ElementId) -> Option<ElementId> {
     self.tree.parent(id)
@@ -305,9 +374,21 @@ impl Registry {
     self.tree.children(id)
   }
 
-  /// Check if element has children tracked in tree.
-  pub(crate) fn tree_has_children(&self, id: ElementId) -> bool {
-    self.tree.has_children(id)
+  /// Check if `id` is `root` or one of its cached descendants.
+  pub(crate) fn tree_contains(&self, root: ElementId, id: ElementId) -> bool {
+    let mut current = Some(id);
+    while let Some(candidate) = current {
+      if candidate == root {
+        return true;
+      }
+      current = self.tree.parent(candidate);
+    }
+    false
+  }
+
+  /// Check if the element's children have been fetched (possibly none).
+  pub(crate) fn tree_children_loaded(&self, id: ElementId) -> bool {
+    self.tree.children_loaded(id)
   }
 
   /// Refresh an element's attributes and return whether it changed.
@@ -319,28 +400,10 @@ impl Registry {
   ) -> Option<bool> {
     let elem = self.elements.get_mut(&id)?;
 
-    // Check for meaningful change
-    let old_value = elem.value.clone();
-    let old_label = elem.label.clone();
-    let old_bounds = elem.bounds;
-    let old_focused = elem.focused;
-    let old_selected = elem.selected;
-    let old_expanded = elem.expanded;
-
-    elem.refresh(attrs);
-
-    let changed = elem.value != old_value
-      || elem.label != old_label
-      || elem.bounds != old_bounds
-      || elem.focused != old_focused
-      || elem.selected != old_selected
-      || elem.expanded != old_expanded;
-
-    if changed {
-      self.emit_element_changed(id);
-    }
-
-    Some(changed)
+    let changes = elem.refresh(attrs);
+    let refreshed = !changes.is_empty();
+    self.emit_element_changed(id, changes);
+    Some(refreshed)
   }
 }
 
@@ -445,3 +508,152 @@ impl Registry {
     None
   }
 }
+
+#[cfg(all(test, feature = "mock"))]
+impl Registry {
+  /// Check the indexes, tree and orphan queue agree with the cached elements.
+  pub(crate) fn check_consistency(&self) -> Result<(), String> {
+    if self.handle_to_id.len() != self.elements.len() {
+      return Err(format!(
+        "{} handles indexed for {} elements",
+        self.handle_to_id.len(),
+        self.elements.len()
+      ));
+    }
+    for (&id, elem) in &self.elements {
+      if self.handle_to_id.get(&elem.handle) != Some(&id) {
+        return Err(format!("element {id} is not indexed by its handle"));
+      }
+      if !self.windows.contains_key(&elem.window_id) {
+        return Err(format!("element {id} outlived window {}", elem.window_id));
+      }
+    }
+    self.tree.check(|id| self.elements.contains_key(&id))?;
+    for (parent_handle, waiting) in &self.waiting_for_parent {
+      if self.handle_to_id.contains_key(parent_handle) {
+        return Err(format!("{waiting:?} wait for a parent that is cached"));
+      }
+      for &id in waiting {
+        let elem = self
+          .elements
+          .get(&id)
+          .ok_or_else(|| format!("removed element {id} waits for a parent"))?;
+        if elem.parent_handle.as_ref() != Some(parent_handle) || self.tree.parent(id).is_some() {
+          return Err(format!("element {id} waits for a parent it doesn't have"));
+        }
+      }
+    }
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+#[cfg(feature = "mock")]
+mod tests {
+  use super::*;
+  use crate::mock::{MockDesktop, MockElement};
+  use crate::platform::ElementAttributes;
+  use crate::Recency;
+
+  /// A cached button, and attributes matching it.
+  fn button() -> (MockDesktop, CachedElement, ElementAttributes) {
+    let desktop = MockDesktop::acquire();
+    let window = desktop.open_window("Notes", "Untitled");
+    desktop.add_element(window.node, MockElement::new(Role::Button).label("Save"));
+    let allio = desktop.start_allio().unwrap();
+    let root = allio.window_root(window.id).unwrap().unwrap();
+    let id = allio.children(root.id, Recency::Current).unwrap()[0].id;
+    let elem = allio.read(|r| r.element(id).cloned()).unwrap();
+    let attrs = ElementAttributes {
+      role: elem.role,
+      platform_role: elem.platform_role.clone(),
+      title: elem.label.clone(),
+      value: elem.value.clone(),
+      description: elem.description.clone(),
+      placeholder: elem.placeholder.clone(),
+      url: elem.url.clone(),
+      bounds: elem.bounds,
+      focused: elem.focused,
+      disabled: elem.disabled,
+      selected: elem.selected,
+      expanded: elem.expanded,
+      row_index: elem.row_index,
+      column_index: elem.column_index,
+      row_count: elem.row_count,
+      column_count: elem.column_count,
+      actions: elem.actions.clone(),
+      identifier: elem.identifier.clone(),
+    };
+    (desktop, elem, attrs)
+  }
+
+  /// The `field` tag each change serializes with.
+  fn fields(changes: &[ElementChange]) -> Vec<String> {
+    changes
+      .iter()
+      .map(|c| {
+        serde_json::to_value(c).unwrap()["field"]
+          .as_str()
+          .unwrap()
+          .to_owned()
+      })
+      .collect()
+  }
+
+  #[test]
+  fn unchanged_elements_report_nothing() {
+    let (_desktop, elem, attrs) = button();
+    assert!(elem.diff(&elem.clone()).is_empty());
+    let mut refreshed = elem.clone();
+    assert!(refreshed.refresh(attrs).is_empty());
+    assert!(refreshed == elem);
+  }
+
+  #[test]
+  fn each_field_reports_its_own_change() {
+    let (_desktop, elem, attrs) = button();
+    type Edit = fn(&mut ElementAttributes);
+    let edits: [(&str, Edit); 18] = [
+      ("role", |a| a.role = Role::Link),
+      ("platform_role", |a| a.platform_role = "AXLink".into()),
+      ("label", |a| a.title = Some("Open".into())),
+      ("description", |a| a.description = Some("help".into())),
+      ("placeholder", |a| a.placeholder = Some("hint".into())),
+      ("url", |a| a.url = Some("https://example.com".into())),
+      ("value", |a| a.value = Some(Value::Number(1.0))),
+      ("bounds", |a| {
+        a.bounds = Some(Bounds {
+          x: 1.0,
+          y: 2.0,
+          w: 3.0,
+          h: 4.0,
+        });
+      }),
+      ("focused", |a| a.focused = Some(true)),
+      ("disabled", |a| a.disabled = true),
+      ("selected", |a| a.selected = Some(true)),
+      ("expanded", |a| a.expanded = Some(true)),
+      ("row_index", |a| a.row_index = Some(1)),
+      ("column_index", |a| a.column_index = Some(2)),
+      ("row_count", |a| a.row_count = Some(3)),
+      ("column_count", |a| a.column_count = Some(4)),
+      ("actions", |a| a.actions = vec![Action::Increment]),
+      ("identifier", |a| a.identifier = Some("save".into())),
+    ];
+    for (field, edit) in edits {
+      let mut changed = attrs.clone();
+      edit(&mut changed);
+      let mut refreshed = elem.clone();
+      let changes = refreshed.refresh(changed);
+      assert_eq!(fields(&changes), [field]);
+      // `diff` agrees, and reverses.
+      assert_eq!(elem.diff(&refreshed), changes);
+      assert_eq!(fields(&refreshed.diff(&elem)), [field]);
+    }
+
+    // Refreshing clears the fallback flag.
+    let mut fallback = elem.clone();
+    fallback.is_fallback = true;
+    assert_eq!(fields(&fallback.refresh(attrs)), ["is_fallback"]);
+  }
+}
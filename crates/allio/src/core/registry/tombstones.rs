@@ -0,0 +1,126 @@
+/*!
+Tombstones for removed elements.
+
+A bounded record of recently removed elements, so a lookup of a removed
+element can report `ElementRemoved` (a stale reference) rather than
+`ElementNotFound` (an ID that never existed). IDs are never reused (see
+`ElementIds`), so a tombstone stays true until it is evicted.
+
+Closing a window or quitting an app usually destroys its elements (and fires
+their `Destroyed` notifications) just before polling sees the window gone.
+Tombstones laid in the moments before are then reattributed to the window or
+process.
+*/
+
+use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, Instant};
+
+use super::Registry;
+use crate::types::{AllioError, ElementId, RemovalReason, Tombstone, WindowId};
+
+/// Most tombstones kept. The oldest are evicted first.
+const MAX_TOMBSTONES: usize = 4096;
+
+/// How long before a window is removed its elements' tombstones are reattributed.
+const REATTRIBUTE_WINDOW: Duration = Duration::from_secs(1);
+
+/// Recently removed elements, oldest first.
+#[derive(Default)]
+pub(crate) struct Tombstones {
+  by_id: HashMap<ElementId, Tombstone>,
+  order: VecDeque<ElementId>,
+}
+
+impl Tombstones {
+  pub(super) fn bury(&mut self, id: ElementId, window_id: WindowId, reason: RemovalReason) {
+    let tombstone = Tombstone {
+      reason,
+      window_id,
+      removed_at: Instant::now(),
+    };
+    if self.by_id.insert(id, tombstone).is_none() {
+      self.order.push_back(id);
+    }
+    while self.order.len() > MAX_TOMBSTONES {
+      if let Some(evicted) = self.order.pop_front() {
+        self.by_id.remove(&evicted);
+      }
+    }
+  }
+
+  /// Attribute the window's recent removals to it closing (or its process exiting).
+  pub(super) fn reattribute(&mut self, window_id: WindowId, reason: RemovalReason) {
+    let Some(since) = Instant::now().checked_sub(REATTRIBUTE_WINDOW) else {
+      return;
+    };
+    // Newest first, stopping at the first tombstone older than the window.
+    for id in self.order.iter().rev() {
+      let Some(tombstone) = self.by_id.get_mut(id) else {
+        continue;
+      };
+      if tombstone.removed_at < since {
+        break;
+      }
+      if tombstone.window_id == window_id {
+        tombstone.reason = reason;
+      }
+    }
+  }
+
+  pub(super) fn get(&self, id: ElementId) -> Option<Tombstone> {
+    self.by_id.get(&id).copied()
+  }
+}
+
+impl Registry {
+  /// Tombstone of a removed element, if it is still remembered.
+  pub(crate) fn tombstone(&self, id: ElementId) -> Option<Tombstone> {
+    self.tombstones.get(id)
+  }
+
+  /// Error for an ID with no element: `ForeignElementId` if another session
+  /// assigned it, `ElementRemoved` if it has a tombstone, otherwise `ElementNotFound`.
+  pub(crate) fn missing(&self, id: ElementId) -> AllioError {
+    if !self.ids.owns(id) {
+      return AllioError::ForeignElementId(id);
+    }
+    match self.tombstones.get(id) {
+      Some(tombstone) => AllioError::ElementRemoved {
+        id,
+        reason: tombstone.reason,
+      },
+      None => AllioError::ElementNotFound(id),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn evicts_oldest_beyond_capacity() {
+    let mut tombstones = Tombstones::default();
+    let max = u64::try_from(MAX_TOMBSTONES).unwrap();
+    for id in 0..=max {
+      tombstones.bury(ElementId(id), WindowId(1), RemovalReason::Destroyed);
+    }
+    assert!(tombstones.get(ElementId(0)).is_none());
+    assert!(tombstones.get(ElementId(1)).is_some());
+    assert!(tombstones.get(ElementId(max)).is_some());
+  }
+
+  #[test]
+  fn reattributes_only_the_closed_window() {
+    let mut tombstones = Tombstones::default();
+    tombstones.bury(ElementId(1), WindowId(1), RemovalReason::Destroyed);
+    tombstones.bury(ElementId(2), WindowId(1), RemovalReason::Cascade);
+    tombstones.bury(ElementId(3), WindowId(2), RemovalReason::Destroyed);
+
+    tombstones.reattribute(WindowId(1), RemovalReason::ProcessExited);
+    let reason = |id| tombstones.get(ElementId(id)).unwrap().reason;
+    assert_eq!(reason(1), RemovalReason::ProcessExited);
+    assert_eq!(reason(2), RemovalReason::ProcessExited);
+    assert_eq!(reason(3), RemovalReason::Destroyed);
+  }
+}
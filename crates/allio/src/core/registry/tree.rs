@@ -14,11 +14,16 @@ All mutations go through methods that maintain bidirectional link invariants.
 */
 
 use crate::types::ElementId;
-use std::collections::HashMap;
+use im::{HashMap, HashSet};
 
+/// Persistent maps, so cloning the tree is O(1).
+#[derive(Clone)]
 pub(crate) struct ElementTree {
   parent_of: HashMap<ElementId, ElementId>,
   children_of: HashMap<ElementId, Vec<ElementId>>,
+  /// Elements whose full child list was set. Others list only the children
+  /// linked one at a time, as they were cached.
+  loaded: HashSet<ElementId>,
 }
 
 impl ElementTree {
@@ -26,6 +31,7 @@ impl ElementTree {
     Self {
       parent_of: HashMap::new(),
       children_of: HashMap::new(),
+      loaded: HashSet::new(),
     }
   }
 
@@ -39,12 +45,19 @@ impl ElementTree {
     self.children_of.get(&id).map_or(&[], Vec::as_slice)
   }
 
-  /// Check if element has any children registered.
-  pub(super) fn has_children(&self, id: ElementId) -> bool {
+  /// Children as reported on `Element`: None until they have been fetched.
+  pub(super) fn reported_children(&self, id: ElementId) -> Option<Vec<ElementId>> {
     self
       .children_of
       .get(&id)
-      .is_some_and(|children| !children.is_empty())
+      .filter(|children| !children.is_empty())
+      .cloned()
+  }
+
+  /// Check if children have been loaded for an element (possibly as an empty list).
+  /// Children linked with `add_child` alone don't count.
+  pub(super) fn children_loaded(&self, id: ElementId) -> bool {
+    self.loaded.contains(&id)
   }
 
   /// Set children for a parent, replacing any existing children.
@@ -78,6 +91,17 @@ impl ElementTree {
       self.parent_of.insert(child_id, parent);
     }
     self.children_of.insert(parent, children);
+    self.loaded.insert(parent);
+  }
+
+  /// Mark a parent's children as loaded or not, e.g. after caching only a
+  /// page of them.
+  pub(super) fn set_loaded(&mut self, parent: ElementId, loaded: bool) {
+    if loaded {
+      self.loaded.insert(parent);
+    } else {
+      self.loaded.remove(&parent);
+    }
   }
 
   /// Link a child to a parent.
@@ -123,19 +147,49 @@ impl ElementTree {
       if let Some(children) = self.children_of.remove(&id) {
         queue.extend(children);
       }
+      self.loaded.remove(&id);
 
       removed.push(id);
     }
 
     removed
   }
+
+  /// Check links are bidirectional and only between elements that `exists`.
+  #[cfg(all(test, feature = "mock"))]
+  pub(super) fn check(&self, exists: impl Fn(ElementId) -> bool) -> Result<(), String> {
+    for (&child, &parent) in &self.parent_of {
+      if !exists(child) || !exists(parent) {
+        return Err(format!("link {parent} -> {child} to a removed element"));
+      }
+      if !self.children(parent).contains(&child) {
+        return Err(format!(
+          "{child} has parent {parent}, which doesn't list it"
+        ));
+      }
+    }
+    for (&parent, children) in &self.children_of {
+      if !exists(parent) {
+        return Err(format!("children listed for removed element {parent}"));
+      }
+      if let Some(&child) = children.iter().find(|&&c| self.parent(c) != Some(parent)) {
+        return Err(format!(
+          "{parent} lists {child}, whose parent is not {parent}"
+        ));
+      }
+    }
+    if let Some(&id) = self.loaded.iter().find(|&&id| !exists(id)) {
+      return Err(format!("children loaded for removed element {id}"));
+    }
+    Ok(())
+  }
 }
 
 #[cfg(test)]
 mod tests {
   use super::*;
 
-  fn id(n: u32) -> ElementId {
+  fn id(n: u64) -> ElementId {
     ElementId(n)
   }
 
@@ -148,6 +202,10 @@ mod tests {
     assert_eq!(tree.parent(id(2)), Some(id(1)));
     assert_eq!(tree.parent(id(3)), Some(id(1)));
     assert_eq!(tree.children(id(1)), &[id(2), id(3)]);
+    // Linked one at a time, so other children may be missing.
+    assert!(!tree.children_loaded(id(1)));
+    tree.set_children(id(1), vec![id(2), id(3)]);
+    assert!(tree.children_loaded(id(1)));
   }
 
   #[test]
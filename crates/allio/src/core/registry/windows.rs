@@ -8,7 +8,7 @@ Window-specific: `set_window_handle`, `window_root`, `set_window_root`
 
 use super::{CachedWindow, Registry};
 use crate::platform::Handle;
-use crate::types::{ElementId, Event, ProcessId, Window, WindowId};
+use crate::types::{ElementId, Event, ProcessId, RemovalReason, Window, WindowId};
 
 impl Registry {
   /// Insert a window if it doesn't exist. Emits `WindowAdded`.
@@ -61,8 +61,8 @@ impl Registry {
     self.emit(Event::WindowChanged { window: info });
   }
 
-  /// Remove a window and all its elements.
-  pub(crate) fn remove_window(&mut self, id: WindowId) {
+  /// Remove a window and all its elements, which are buried with `reason`.
+  pub(crate) fn remove_window(&mut self, id: WindowId, reason: RemovalReason) {
     let element_ids: Vec<ElementId> = self
       .elements
       .iter()
@@ -71,8 +71,10 @@ impl Registry {
       .collect();
 
     for element_id in element_ids {
-      self.remove_element(element_id);
+      self.remove_subtree(element_id, reason);
     }
+    self.replacements.forget_window(id);
+    self.tombstones.reattribute(id, reason);
 
     if let Some(window) = self.windows.remove(&id) {
       if let Some(ref handle) = window.handle {
@@ -2,21 +2,35 @@
 Watch/unwatch subscription methods for Allio.
 
 Uses take/replace pattern to avoid holding lock during OS calls.
+Watches are reference-counted: notifications are registered by the first
+`watch` of an element and removed by the `unwatch` that balances the last one.
+
+While one caller has the watch handle out, others find nothing to take and
+only change the count; the holder re-checks the count before putting the
+handle back and registers or removes again until the two agree.
 */
 
 use super::Allio;
 use crate::a11y::Notification;
-use crate::types::{AllioError, AllioResult, ElementId};
+use crate::platform::WatchHandle;
+use crate::types::{AllioResult, ElementId};
 
 impl Allio {
   /// Watch an element for change notifications (value, title, children, etc).
+  ///
+  /// Each call must be balanced by an [`Allio::unwatch`].
   pub fn watch(&self, element_id: ElementId) -> AllioResult<()> {
     // Step 1: Get role and take watch handle (quick write, releases lock)
     let (notifs, watch_handle) = self.write(|s| {
       let role = s
         .element(element_id)
         .map(|e| e.role)
-        .ok_or(AllioError::ElementNotFound(element_id))?;
+        .ok_or_else(|| s.missing(element_id))?;
+
+      // Already watched: notifications are registered.
+      if s.add_element_watcher(element_id) > 1 {
+        return Ok((Vec::new(), None));
+      }
 
       let notifs = Notification::for_watching(role);
       if notifs.is_empty() {
@@ -27,36 +41,29 @@ impl Allio {
       Ok((notifs, watch))
     })?;
 
-    // Step 2: OS operations (NO LOCK)
-    let Some(mut watch) = watch_handle else {
-      if !notifs.is_empty() {
-        log::warn!("Element {element_id} has no watch handle");
-      }
-      return Ok(());
-    };
-
-    let added = watch.add(&notifs);
-    if added < notifs.len() {
-      log::warn!(
-        "Element {element_id}: only {added}/{} notifications registered",
-        notifs.len()
-      );
+    // Step 2: OS operations (NO LOCK). Without a handle, whoever holds it
+    // registers for us when putting it back.
+    if let Some(watch) = watch_handle {
+      self.sync_watch(element_id, &notifs, watch, true);
     }
-
-    // Step 3: Put watch back (quick write)
-    self.write(|s| s.set_element_watch(element_id, watch));
-
     Ok(())
   }
 
   /// Stop watching an element for change notifications.
+  ///
+  /// Notifications stay registered while other `watch` calls are outstanding.
   pub fn unwatch(&self, element_id: ElementId) -> AllioResult<()> {
     // Step 1: Get role and take watch handle (quick write, releases lock)
     let (notifs, watch_handle) = self.write(|s| {
       let role = s
         .element(element_id)
         .map(|e| e.role)
-        .ok_or(AllioError::ElementNotFound(element_id))?;
+        .ok_or_else(|| s.missing(element_id))?;
+
+      // Still watched by someone else.
+      if s.remove_element_watcher(element_id) > 0 {
+        return Ok((Vec::new(), None));
+      }
 
       let notifs = Notification::for_watching(role);
       let watch = s.take_element_watch(element_id);
@@ -64,15 +71,55 @@ impl Allio {
     })?;
 
     // Step 2: OS operations (NO LOCK)
-    let Some(mut watch) = watch_handle else {
-      return Ok(());
-    };
+    if let Some(watch) = watch_handle {
+      self.sync_watch(element_id, &notifs, watch, false);
+    }
+    Ok(())
+  }
 
-    watch.remove(&notifs);
+  /// Check if an element has outstanding `watch` calls.
+  pub fn is_watched(&self, element_id: ElementId) -> bool {
+    self.read(|s| s.element(element_id).is_some_and(|e| e.watchers > 0))
+  }
 
-    // Step 3: Put watch back (quick write)
-    self.write(|s| s.set_element_watch(element_id, watch));
+  /// Register (or remove) `notifs`, then put the handle back once the
+  /// element's watcher count agrees with what is registered.
+  fn sync_watch(
+    &self,
+    element_id: ElementId,
+    notifs: &[Notification],
+    mut watch: WatchHandle,
+    mut register: bool,
+  ) {
+    loop {
+      if register {
+        let added = watch.add(notifs);
+        if added < notifs.len() {
+          log::warn!(
+            "Element {element_id}: only {added}/{} notifications registered",
+            notifs.len()
+          );
+        }
+      } else {
+        watch.remove(notifs);
+      }
 
-    Ok(())
+      // Step 3: Put watch back (quick write), unless the count moved meanwhile.
+      // A removed element drops the handle.
+      let moved = self.write(|s| {
+        let watched = s.element(element_id).map(|e| e.watchers > 0);
+        if watched.is_none_or(|watched| watched == register) {
+          s.set_element_watch(element_id, watch);
+          None
+        } else {
+          Some(watch)
+        }
+      });
+      let Some(taken) = moved else {
+        return;
+      };
+      watch = taken;
+      register = !register;
+    }
   }
 }
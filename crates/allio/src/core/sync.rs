@@ -7,8 +7,8 @@ through individual element queries.
 
 use super::Allio;
 use crate::platform::{CurrentPlatform, Platform};
-use crate::types::{Window, WindowId};
-use std::collections::HashSet;
+use crate::types::{ProcessId, RemovalReason, Window, WindowId};
+use std::collections::{HashMap, HashSet};
 
 impl Allio {
   /// Sync windows from polling. Handles add/update/remove.
@@ -40,13 +40,41 @@ impl Allio {
       })
       .collect();
 
+    // Windows no longer present, and whether their process exited (an OS call, so no lock)
+    let to_remove: Vec<(WindowId, ProcessId)> = if skip_removal {
+      Vec::new()
+    } else {
+      self.read(|s| {
+        s.window_ids()
+          .filter(|id| !new_ids.contains(id))
+          .filter_map(|id| Some((id, s.window(id)?.process_id)))
+          .collect()
+      })
+    };
+    let remaining_pids: HashSet<ProcessId> = windows_with_handles
+      .iter()
+      .map(|(w, _)| w.process_id)
+      .collect();
+    let mut exited_pids: HashMap<ProcessId, bool> = HashMap::new();
+    let removals: Vec<(WindowId, RemovalReason)> = to_remove
+      .into_iter()
+      .map(|(window_id, pid)| {
+        let exited = *exited_pids.entry(pid).or_insert_with(|| {
+          !remaining_pids.contains(&pid) && !CurrentPlatform::is_process_running(pid.0)
+        });
+        let reason = if exited {
+          RemovalReason::ProcessExited
+        } else {
+          RemovalReason::WindowClosed
+        };
+        (window_id, reason)
+      })
+      .collect();
+
     let new_process_pids = self.write(|s| {
       // Remove windows no longer present
-      if !skip_removal {
-        let to_remove: Vec<WindowId> = s.window_ids().filter(|id| !new_ids.contains(id)).collect();
-        for window_id in to_remove {
-          s.remove_window(window_id);
-        }
+      for (window_id, reason) in removals {
+        s.remove_window(window_id, reason);
       }
 
       // Add/update windows
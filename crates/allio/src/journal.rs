@@ -0,0 +1,477 @@
+/*!
+Event journals: record an event stream to disk and replay it later.
+
+A journal is newline-delimited JSON. The first line holds the format version and
+the [`Snapshot`] recording started from; every following line is one
+[`SequencedEvent`], serialized exactly as `allio-ws` sends it.
+
+A [`Replayer`] stands in for `Allio` when feeding a journal to consumers: it
+broadcasts the recorded events with their original sequence numbers and
+timestamps, and keeps a snapshot of the state they describe. Replaying the same
+journal always produces the same events and the same final state.
+
+```ignore
+use allio::journal::{JournalReader, Recorder, Replayer, Speed};
+
+// Record until stopped
+let recorder = Recorder::start(&allio, BufWriter::new(File::create("session.jsonl")?))?;
+// ...
+recorder.stop()?;
+
+// Replay at 4x speed
+let journal = JournalReader::new(BufReader::new(File::open("session.jsonl")?))?;
+let replayer = Replayer::new(journal.snapshot().clone());
+let mut events = replayer.subscribe();
+replayer.play(journal, Speed::Factor(4.0))?;
+```
+*/
+
+use crate::types::{
+  Element, ElementId, Event, Point, SequencedEvent, Snapshot, TextSelection, Window, WindowId,
+};
+use crate::Allio;
+use async_broadcast::{InactiveReceiver, Receiver, Sender, TryRecvError};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io::{self, BufRead, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+/// Journal format version, written in the first line.
+pub const VERSION: u32 = 1;
+
+const EVENT_CHANNEL_CAPACITY: usize = 5000;
+
+/// How often the recorder checks for a stop request while no events arrive.
+const RECORDER_IDLE: Duration = Duration::from_millis(10);
+
+/// First line of a journal.
+#[derive(Serialize, Deserialize)]
+struct Header {
+  version: u32,
+  snapshot: Snapshot,
+}
+
+/// Writes a journal: the initial snapshot, then one event per line.
+#[derive(Debug)]
+pub struct JournalWriter<W: Write> {
+  out: W,
+  /// Sequence number of the snapshot; older events are already reflected in it.
+  since: u64,
+}
+
+impl<W: Write> JournalWriter<W> {
+  /// Start a journal from `snapshot`.
+  pub fn new(mut out: W, snapshot: Snapshot) -> io::Result<Self> {
+    let since = snapshot.seq;
+    serde_json::to_writer(
+      &mut out,
+      &Header {
+        version: VERSION,
+        snapshot,
+      },
+    )?;
+    out.write_all(b"\n")?;
+    Ok(Self { out, since })
+  }
+
+  /// Append an event. Events already reflected in the snapshot are skipped.
+  pub fn write(&mut self, event: &SequencedEvent) -> io::Result<()> {
+    if event.seq <= self.since {
+      return Ok(());
+    }
+    serde_json::to_writer(&mut self.out, event)?;
+    self.out.write_all(b"\n")
+  }
+
+  /// Flush buffered events to the underlying writer.
+  pub fn flush(&mut self) -> io::Result<()> {
+    self.out.flush()
+  }
+
+  /// Flush and return the underlying writer.
+  pub fn into_inner(mut self) -> io::Result<W> {
+    self.out.flush()?;
+    Ok(self.out)
+  }
+}
+
+/// Reads a journal: the snapshot up front, then events as an iterator.
+#[derive(Debug)]
+pub struct JournalReader<R: BufRead> {
+  lines: io::Lines<R>,
+  snapshot: Snapshot,
+}
+
+impl<R: BufRead> JournalReader<R> {
+  /// Read the header. Fails if the journal is empty or has an unknown version.
+  pub fn new(input: R) -> io::Result<Self> {
+    let mut lines = input.lines();
+    let first = lines
+      .next()
+      .ok_or_else(|| io::Error::new(io::ErrorKind::UnexpectedEof, "empty journal"))??;
+    let header: Header = serde_json::from_str(&first)?;
+    if header.version != VERSION {
+      return Err(io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("unsupported journal version {}", header.version),
+      ));
+    }
+    Ok(Self {
+      lines,
+      snapshot: header.snapshot,
+    })
+  }
+
+  /// State when recording started.
+  pub const fn snapshot(&self) -> &Snapshot {
+    &self.snapshot
+  }
+}
+
+impl<R: BufRead> Iterator for JournalReader<R> {
+  type Item = io::Result<SequencedEvent>;
+
+  fn next(&mut self) -> Option<Self::Item> {
+    loop {
+      let line = match self.lines.next()? {
+        Ok(line) => line,
+        Err(e) => return Some(Err(e)),
+      };
+      if !line.trim().is_empty() {
+        return Some(serde_json::from_str(&line).map_err(io::Error::from));
+      }
+    }
+  }
+}
+
+/// Records an `Allio` instance's events to a journal on a background thread.
+pub struct Recorder<W: Write + Send + 'static> {
+  stop: Arc<AtomicBool>,
+  thread: JoinHandle<io::Result<JournalWriter<W>>>,
+}
+
+impl<W: Write + Send + 'static> std::fmt::Debug for Recorder<W> {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.debug_struct("Recorder").finish_non_exhaustive()
+  }
+}
+
+impl<W: Write + Send + 'static> Recorder<W> {
+  /// Write the current snapshot and record every event after it until stopped.
+  pub fn start(allio: &Allio, out: W) -> io::Result<Self> {
+    // Subscribe before the snapshot, so nothing falls between the two.
+    let mut events = allio.subscribe();
+    let mut journal = JournalWriter::new(out, allio.snapshot())?;
+    let stop = Arc::new(AtomicBool::new(false));
+    let stopped = Arc::clone(&stop);
+
+    let thread = std::thread::spawn(move || loop {
+      match events.try_recv() {
+        Ok(event) => journal.write(&event)?,
+        // The gap in sequence numbers shows where events were lost.
+        Err(TryRecvError::Overflowed(n)) => {
+          log::warn!("[journal] Recorder lagged, dropped {n} events");
+        }
+        Err(TryRecvError::Empty) if !stopped.load(Ordering::Relaxed) => {
+          std::thread::sleep(RECORDER_IDLE);
+        }
+        Err(TryRecvError::Empty | TryRecvError::Closed) => return Ok(journal),
+      }
+    });
+    Ok(Self { stop, thread })
+  }
+
+  /// Write any pending events, stop recording, and return the flushed writer.
+  pub fn stop(self) -> io::Result<W> {
+    self.stop.store(true, Ordering::Relaxed);
+    self
+      .thread
+      .join()
+      .map_err(|_| io::Error::other("recorder thread panicked"))??
+      .into_inner()
+  }
+}
+
+/// Replay pace.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Speed {
+  /// Keep the recorded gaps between events.
+  Original,
+  /// Divide the recorded gaps by this factor (`2.0` plays twice as fast).
+  Factor(f64),
+  /// No gaps: emit events as fast as consumers accept them.
+  Unpaced,
+}
+
+impl Speed {
+  /// Time to wait for a recorded gap of `gap_ms`.
+  fn delay(self, gap_ms: u64) -> Duration {
+    let gap = Duration::from_millis(gap_ms);
+    match self {
+      Self::Original => gap,
+      Self::Factor(factor) => {
+        Duration::try_from_secs_f64(gap.as_secs_f64() / factor).unwrap_or(Duration::ZERO)
+      }
+      Self::Unpaced => Duration::ZERO,
+    }
+  }
+}
+
+/// State described by a replayed event stream, keyed for cheap updates.
+#[derive(Debug)]
+struct ReplayState {
+  windows: HashMap<WindowId, Window>,
+  elements: HashMap<ElementId, Element>,
+  focused_window: Option<WindowId>,
+  focused_element: Option<Element>,
+  selection: Option<TextSelection>,
+  mouse_position: Option<Point>,
+  seq: u64,
+  session: u32,
+}
+
+impl ReplayState {
+  fn new(snapshot: Snapshot) -> Self {
+    Self {
+      windows: snapshot.windows.into_iter().map(|w| (w.id, w)).collect(),
+      elements: snapshot.elements.into_iter().map(|e| (e.id, e)).collect(),
+      focused_window: snapshot.focused_window,
+      focused_element: snapshot.focused_element,
+      selection: snapshot.selection,
+      mouse_position: snapshot.mouse_position,
+      seq: snapshot.seq,
+      session: snapshot.session,
+    }
+  }
+
+  /// Apply an event the way a client applies it to its cache.
+  fn apply(&mut self, event: &Event) {
+    match event {
+      Event::SyncInit(snapshot) => *self = Self::new(snapshot.clone()),
+      Event::WindowAdded { window } | Event::WindowChanged { window } => {
+        self.windows.insert(window.id, window.clone());
+      }
+      Event::WindowRemoved { window_id } => {
+        self.windows.remove(window_id);
+        self.elements.retain(|_, e| e.window_id != *window_id);
+      }
+      Event::ElementAdded { element } => {
+        self.elements.insert(element.id, element.clone());
+      }
+      Event::ElementChanged {
+        element_id,
+        changes,
+      } => {
+        if let Some(element) = self.elements.get_mut(element_id) {
+          element.apply(changes);
+        }
+        if let Some(element) = self
+          .focused_element
+          .as_mut()
+          .filter(|e| e.id == *element_id)
+        {
+          element.apply(changes);
+        }
+      }
+      Event::ElementRemoved { element_id, .. } => {
+        self.elements.remove(element_id);
+      }
+      Event::FocusWindow { window_id } => self.focused_window = *window_id,
+      Event::FocusElement { element, .. } => {
+        self.elements.insert(element.id, element.clone());
+        self.focused_element = Some(element.clone());
+      }
+      Event::SelectionChanged {
+        element_id,
+        text,
+        range,
+        ..
+      } => {
+        self.selection = (!text.is_empty()).then(|| TextSelection {
+          element_id: *element_id,
+          text: text.clone(),
+          range: *range,
+        });
+      }
+      Event::MousePosition(point) => self.mouse_position = Some(*point),
+      Event::ElementReplaced { .. } | Event::SubtreeChanged { .. } => {}
+    }
+  }
+
+  /// Windows by ID and elements by ID, so equal states give equal snapshots.
+  fn snapshot(&self) -> Snapshot {
+    let mut windows: Vec<Window> = self.windows.values().cloned().collect();
+    windows.sort_by_key(|w| w.id.0);
+    let mut elements: Vec<Element> = self.elements.values().cloned().collect();
+    elements.sort_by_key(|e| e.id.0);
+    let mut z_order: Vec<&Window> = self.windows.values().collect();
+    z_order.sort_by_key(|w| (w.z_index, w.id.0));
+    Snapshot {
+      z_order: z_order.into_iter().map(|w| w.id).collect(),
+      windows,
+      elements,
+      focused_window: self.focused_window,
+      focused_element: self.focused_element.clone(),
+      selection: self.selection.clone(),
+      mouse_position: self.mouse_position,
+      seq: self.seq,
+      session: self.session,
+    }
+  }
+}
+
+/// Stands in for `Allio` when replaying a journal: broadcasts recorded events
+/// and tracks the state they describe.
+///
+/// Clone is cheap (Arc bumps), so one clone can play while others serve state.
+#[derive(Clone)]
+pub struct Replayer {
+  state: Arc<Mutex<ReplayState>>,
+  events: Sender<SequencedEvent>,
+  events_keepalive: InactiveReceiver<SequencedEvent>,
+}
+
+impl std::fmt::Debug for Replayer {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.debug_struct("Replayer").finish_non_exhaustive()
+  }
+}
+
+impl Replayer {
+  /// Start from the state a journal was recorded from.
+  pub fn new(snapshot: Snapshot) -> Self {
+    let (mut tx, rx) = async_broadcast::broadcast(EVENT_CHANNEL_CAPACITY);
+    tx.set_overflow(true);
+    Self {
+      state: Arc::new(Mutex::new(ReplayState::new(snapshot))),
+      events: tx,
+      events_keepalive: rx.deactivate(),
+    }
+  }
+
+  /// Subscribe to replayed events, as with [`Allio::subscribe`].
+  pub fn subscribe(&self) -> Receiver<SequencedEvent> {
+    self.events_keepalive.activate_cloned()
+  }
+
+  /// State after the events replayed so far.
+  pub fn snapshot(&self) -> Snapshot {
+    self.state.lock().snapshot()
+  }
+
+  /// Replay events at `speed`, blocking until they run out.
+  ///
+  /// Each event updates the state before it is broadcast, so a subscriber that
+  /// receives it can read a snapshot that already reflects it.
+  pub fn play(
+    &self,
+    events: impl IntoIterator<Item = io::Result<SequencedEvent>>,
+    speed: Speed,
+  ) -> io::Result<()> {
+    let mut previous: Option<u64> = None;
+    for event in events {
+      let event = event?;
+      if let Some(previous) = previous {
+        let delay = speed.delay(event.timestamp_ms.saturating_sub(previous));
+        if !delay.is_zero() {
+          std::thread::sleep(delay);
+        }
+      }
+      previous = Some(event.timestamp_ms);
+
+      {
+        let mut state = self.state.lock();
+        state.apply(&event.event);
+        state.seq = event.seq;
+      }
+      drop(self.events.try_broadcast(event));
+    }
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+#[cfg(feature = "mock")]
+mod tests {
+  use super::*;
+  use crate::a11y::{Role, Value};
+  use crate::mock::{MockDesktop, MockElement};
+  use crate::Recency;
+
+  #[test]
+  fn recorded_journal_replays_to_the_same_state() {
+    let desktop = MockDesktop::acquire();
+    let window = desktop.open_window("Notes", "Untitled");
+    let field = desktop
+      .add_element(
+        window.node,
+        MockElement::new(Role::TextField).value(Value::String("draft".into())),
+      )
+      .unwrap();
+    let allio = desktop.start_allio().unwrap();
+
+    let recorder = Recorder::start(&allio, Vec::new()).unwrap();
+    let root = allio.window_root(window.id).unwrap().unwrap();
+    let text = allio.children(root.id, Recency::Current).unwrap().remove(0);
+    desktop.set_value(field, Value::String("final".into()));
+    allio.get(text.id, Recency::Current).unwrap();
+    let expected = allio.snapshot();
+    let bytes = recorder.stop().unwrap();
+
+    let journal = JournalReader::new(bytes.as_slice()).unwrap();
+    let start = journal.snapshot().seq;
+    assert!(journal.snapshot().elements.iter().all(|e| e.id != text.id));
+    let replayer = Replayer::new(journal.snapshot().clone());
+    let mut events = replayer.subscribe();
+    replayer.play(journal, Speed::Unpaced).unwrap();
+
+    let state = replayer.snapshot();
+    assert_eq!(state.seq, expected.seq);
+    let mut expected_elements = expected.elements;
+    expected_elements.sort_by_key(|e| e.id.0);
+    assert_eq!(state.elements, expected_elements);
+    assert_eq!(state.windows, expected.windows);
+
+    // Subscribers see the recorded events with their original stamps.
+    let first = events.try_recv().unwrap();
+    assert_eq!(first.seq, start + 1);
+    assert!(matches!(first.event, Event::ElementAdded { .. }));
+  }
+
+  #[test]
+  fn rejects_unknown_versions() {
+    let snapshot = Snapshot {
+      windows: vec![],
+      elements: vec![],
+      focused_window: None,
+      focused_element: None,
+      selection: None,
+      z_order: vec![],
+      mouse_position: None,
+      seq: 7,
+      session: 1,
+    };
+    let bytes = JournalWriter::new(Vec::new(), snapshot)
+      .unwrap()
+      .into_inner()
+      .unwrap();
+    let line = String::from_utf8(bytes)
+      .unwrap()
+      .replace("\"version\":1", "\"version\":99");
+    let error = JournalReader::new(line.as_bytes()).unwrap_err();
+    assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    assert!(JournalReader::new(&b""[..]).is_err());
+  }
+
+  #[test]
+  fn factor_divides_gaps() {
+    assert_eq!(Speed::Original.delay(400), Duration::from_millis(400));
+    assert_eq!(Speed::Factor(4.0).delay(400), Duration::from_millis(100));
+    assert_eq!(Speed::Factor(0.0).delay(400), Duration::ZERO);
+    assert_eq!(Speed::Unpaced.delay(400), Duration::ZERO);
+  }
+}
@@ -17,10 +17,13 @@ let element = allio.get(element_id, Recency::max_age_ms(100))?; // Refresh if st
 let children = allio.children(element.id, Recency::Current)?;
 let parent = allio.parent(element.id, Recency::Any)?;
 
+// CSS-like queries, fetching missing subtrees as the recency requires
+let todos = allio.query(root_id, "list > listitem[label^=\"Todo\"]", Recency::max_age_ms(500))?;
+
 // Subscribe to events
 let mut events = allio.subscribe();
-while let Ok(event) = events.recv().await {
-    // handle event
+while let Ok(stamped) = events.recv().await {
+    // handle stamped.event (stamped.seq orders events, stamped.timestamp_ms dates them)
 }
 
 // Polling stops when allio is dropped
@@ -28,15 +31,52 @@ drop(allio);
 ```
 */
 
+#[cfg(feature = "async")]
+mod async_allio;
 mod core;
 mod observation;
+mod path;
 mod platform;
 mod polling;
+mod selection;
+mod selector;
+mod view;
 
 pub mod a11y;
+pub mod journal;
+pub mod wait;
 
 mod types;
 pub use types::*;
 
+#[cfg(feature = "async")]
+pub use crate::async_allio::{AsyncAllio, Call};
 pub use crate::core::{Allio, AllioBuilder};
 pub use crate::observation::{ObservationHandle, ObserveConfig};
+pub use crate::path::{ElementPath, PathStep};
+pub use crate::selection::Selection;
+pub use crate::selector::Selector;
+pub use crate::view::{View, ViewNode};
+
+/// Record platform calls and callbacks, for replay without the OS
+/// (enabled by the `capture` feature, and by `mock`).
+#[cfg(feature = "capture")]
+pub mod capture {
+  pub use crate::platform::capture::{start, stop, Capture};
+}
+
+/// Inject platform faults (latency, errors, stale handles, lost or spurious
+/// notifications), for testing how Allio copes with misbehaving apps
+/// (enabled by the `faults` feature, and by `mock`).
+#[cfg(feature = "faults")]
+pub mod faults {
+  pub use crate::platform::faults::{clear, inject, Faults, Injection};
+}
+
+/// Scriptable in-memory platform for tests (enabled by the `mock` feature).
+#[cfg(feature = "mock")]
+pub mod mock {
+  pub use crate::platform::mock::desktop::{
+    eventually, MockDesktop, MockElement, MockNodeId, MockWindow,
+  };
+}
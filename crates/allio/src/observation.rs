@@ -22,6 +22,10 @@ let handle = allio.observe(element_id, ObserveConfig { depth: Some(3), ..default
 
 handle.dispose(); // Or let it drop
 ```
+
+Observations are reference-counted: observing an already observed root returns
+another handle to the same observation (which keeps its original config), and
+the observation stops when the last handle is dropped.
 */
 
 use parking_lot::Mutex;
@@ -83,7 +87,7 @@ pub(crate) struct SweepChanges {
 }
 
 impl SweepChanges {
-  fn is_empty(&self) -> bool {
+  const fn is_empty(&self) -> bool {
     self.added.is_empty() && self.removed.is_empty() && self.modified.is_empty()
   }
 
@@ -94,15 +98,27 @@ impl SweepChanges {
   }
 }
 
-/// Handle to an observed subtree. Stops observation on drop.
-#[derive(Debug)]
+/// Handle to an observed subtree. Stops observation when the last handle drops.
 pub struct ObservationHandle {
-  root_id: ElementId,
+  subtree: Arc<ObservedSubtree>,
   allio: Allio,
 }
 
+impl std::fmt::Debug for ObservationHandle {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.debug_struct("ObservationHandle")
+      .field("root_id", &self.subtree.root_id)
+      .finish_non_exhaustive()
+  }
+}
+
 impl ObservationHandle {
-  /// Stop observing this subtree.
+  /// Root of the observed subtree.
+  pub fn root_id(&self) -> ElementId {
+    self.subtree.root_id
+  }
+
+  /// Release this handle. Observation stops if it was the last one.
   pub fn dispose(self) {
     // Drop will handle cleanup
   }
@@ -110,14 +126,33 @@ impl ObservationHandle {
 
 impl Drop for ObservationHandle {
   fn drop(&mut self) {
-    self.allio.unobserve(self.root_id);
+    let mut subtrees = self.allio.observation_state().subtrees.lock();
+    let root_id = self.subtree.root_id;
+    // The observation may have been stopped (and restarted) by `unobserve`.
+    let Some(observed) = subtrees
+      .get_mut(&root_id)
+      .filter(|o| Arc::ptr_eq(&o.subtree, &self.subtree))
+    else {
+      return;
+    };
+    observed.handles -= 1;
+    if observed.handles == 0 {
+      subtrees.remove(&root_id);
+      log::debug!("Stopped observing subtree {root_id}");
+    }
   }
 }
 
+/// An observed subtree and the number of handles to it.
+pub(crate) struct Observed {
+  pub(crate) subtree: Arc<ObservedSubtree>,
+  pub(crate) handles: usize,
+}
+
 /// Shared state for all observations.
 pub(crate) struct ObservationState {
   /// Map of observed subtrees by root element ID.
-  pub(crate) subtrees: Mutex<HashMap<ElementId, Arc<ObservedSubtree>>>,
+  pub(crate) subtrees: Mutex<HashMap<ElementId, Observed>>,
 }
 
 impl ObservationState {
@@ -149,7 +184,7 @@ pub(crate) fn start_observation_thread(allio: Allio) -> ObservationThreadHandle
   let stop_signal_clone = Arc::clone(&stop_signal);
 
   let thread = thread::spawn(move || {
-    observation_loop(allio, &stop_signal_clone);
+    observation_loop(&allio, &stop_signal_clone);
   });
 
   ObservationThreadHandle {
@@ -159,12 +194,18 @@ pub(crate) fn start_observation_thread(allio: Allio) -> ObservationThreadHandle
 }
 
 /// Main observation loop - checks subtrees and spawns sweeps.
-fn observation_loop(allio: Allio, stop_signal: &AtomicBool) {
-  let pool = rayon::ThreadPoolBuilder::new()
+fn observation_loop(allio: &Allio, stop_signal: &AtomicBool) {
+  let pool = match rayon::ThreadPoolBuilder::new()
     .num_threads(4)
     .thread_name(|i| format!("allio-sweep-{i}"))
     .build()
-    .expect("Failed to create rayon thread pool");
+  {
+    Ok(pool) => pool,
+    Err(e) => {
+      log::error!("Failed to create rayon thread pool, observation disabled: {e}");
+      return;
+    }
+  };
 
   while !stop_signal.load(Ordering::SeqCst) {
     thread::sleep(Duration::from_millis(CHECK_INTERVAL_MS));
@@ -175,7 +216,7 @@ fn observation_loop(allio: Allio, stop_signal: &AtomicBool) {
       .subtrees
       .lock()
       .values()
-      .cloned()
+      .map(|o| Arc::clone(&o.subtree))
       .collect();
 
     for subtree in subtrees {
@@ -278,8 +319,7 @@ fn sweep_element_recursive(
   // Fetch current attributes from OS
   let attrs = handle.fetch_attributes();
 
-  // Check if element is dead (no role = invalid element)
-  if attrs.role == crate::a11y::Role::Unknown && attrs.platform_role.is_empty() {
+  if attrs.is_dead() {
     // Element is dead - remove from cache
     allio.write(|r| r.remove_element(element_id));
     obs.changes.lock().removed.push(element_id);
@@ -315,7 +355,10 @@ fn sweep_element_recursive(
       // New child discovered - add to cache
       let entry =
         crate::core::adapters::build_entry_from_handle(child_handle.clone(), window_id, pid);
-      let new_id = allio.write(|r| r.upsert_element(entry));
+      let Some(new_id) = allio.write(|r| r.upsert_element(entry)) else {
+        // The window closed mid-sweep.
+        return;
+      };
       obs.changes.lock().added.push(new_id);
       new_id
     };
@@ -353,15 +396,25 @@ impl Allio {
   /// The subtree will be polled periodically (default: every 100ms after each sweep completes).
   /// Changes emit both element-level events and a single `subtree:changed` event per cycle.
   ///
-  /// Returns a handle that stops observation when dropped.
+  /// Returns a handle that stops observation when dropped. If `root_id` is already
+  /// observed, the handle shares that observation and `config` is ignored.
   pub fn observe(
     &self,
     root_id: ElementId,
     config: ObserveConfig,
   ) -> crate::types::AllioResult<ObservationHandle> {
     // Verify element exists
-    if !self.read(|r| r.element(root_id).is_some()) {
-      return Err(crate::types::AllioError::ElementNotFound(root_id));
+    if let Some(missing) = self.read(|r| r.element(root_id).is_none().then(|| r.missing(root_id))) {
+      return Err(missing);
+    }
+
+    let mut subtrees = self.observation_state().subtrees.lock();
+    if let Some(observed) = subtrees.get_mut(&root_id) {
+      observed.handles += 1;
+      return Ok(ObservationHandle {
+        subtree: Arc::clone(&observed.subtree),
+        allio: self.clone(),
+      });
     }
 
     let subtree = Arc::new(ObservedSubtree {
@@ -371,15 +424,22 @@ impl Allio {
         .wait_between
         .unwrap_or(Duration::from_millis(DEFAULT_WAIT_BETWEEN_MS)),
       in_progress: AtomicBool::new(false),
-      last_completed: Mutex::new(Instant::now() - Duration::from_secs(1)), // Trigger immediate first sweep
+      // Trigger immediate first sweep
+      last_completed: Mutex::new(
+        Instant::now()
+          .checked_sub(Duration::from_secs(1))
+          .unwrap_or_else(Instant::now),
+      ),
       changes: Mutex::new(SweepChanges::default()),
     });
 
-    self
-      .observation_state()
-      .subtrees
-      .lock()
-      .insert(root_id, subtree);
+    subtrees.insert(
+      root_id,
+      Observed {
+        subtree: Arc::clone(&subtree),
+        handles: 1,
+      },
+    );
 
     log::debug!(
       "Started observing subtree {} (depth: {:?})",
@@ -388,15 +448,15 @@ impl Allio {
     );
 
     Ok(ObservationHandle {
-      root_id,
+      subtree,
       allio: self.clone(),
     })
   }
 
-  /// Stop observing a subtree.
+  /// Stop observing a subtree, regardless of outstanding handles.
   pub fn unobserve(&self, root_id: ElementId) {
     self.observation_state().subtrees.lock().remove(&root_id);
-    log::debug!("Stopped observing subtree {}", root_id);
+    log::debug!("Stopped observing subtree {root_id}");
   }
 
   /// Check if a subtree is being observed.
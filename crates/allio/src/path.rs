@@ -0,0 +1,353 @@
+/*!
+Portable element paths.
+
+`ElementId`s are assigned by each `Allio` instance and mean nothing to the
+next one, so saved automations and overlay configurations can't hold on to
+them across restarts. An [`ElementPath`] describes where an element sits
+instead: the app, the window title, then one step per level below the window
+root.
+
+Each step records the element's role, its platform identifier (if any), and
+its index among the siblings that share both. [`Allio::resolve`] walks the
+live tree along those steps to find the element again.
+
+```ignore
+let path = allio.path_of(button.id)?;
+save(&serde_json::to_string(&path)?);
+
+// Later, in another run:
+let path: ElementPath = serde_json::from_str(&load())?;
+let button = allio.resolve(&path, Recency::Current)?;
+```
+
+Windows are matched by app name and exact title. When the title has changed
+(a document was renamed, say), an app's sole window is taken instead.
+*/
+
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+
+use crate::a11y::Role;
+use crate::core::Allio;
+use crate::types::{AllioError, AllioResult, Element, ElementId, Recency, Window};
+
+/// Where an element sits, in terms that survive restarts.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct ElementPath {
+  /// Name of the app owning the window.
+  pub app: String,
+  /// Window title.
+  pub window: String,
+  /// Steps from the window root down to the element. Empty for the root itself.
+  pub steps: Vec<PathStep>,
+}
+
+/// One level of an [`ElementPath`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct PathStep {
+  /// Role of the element at this level.
+  pub role: Role,
+  /// Platform accessibility identifier, when the app sets one.
+  pub identifier: Option<String>,
+  /// Index among siblings with the same role and identifier.
+  pub index: usize,
+}
+
+impl PathStep {
+  fn matches(&self, role: Role, identifier: Option<&str>) -> bool {
+    self.role == role && self.identifier.as_deref() == identifier
+  }
+}
+
+impl Allio {
+  /// Portable path to an element, for finding it again with [`Allio::resolve`].
+  ///
+  /// Uses the cache where it can. Missing parents and sibling lists are
+  /// fetched from the OS.
+  #[must_use = "this returns a Result that may contain a path"]
+  pub fn path_of(&self, element_id: ElementId) -> AllioResult<ElementPath> {
+    let mut steps = Vec::new();
+    let mut current = self.get(element_id, Recency::Any)?;
+    while !current.is_root {
+      let parent = match current.parent_id {
+        Some(parent_id) => self.get(parent_id, Recency::Any)?,
+        None => self.fetch_parent(current.id)?.ok_or_else(|| {
+          AllioError::NotSupported(format!(
+            "element {} is outside its window's tree",
+            current.id
+          ))
+        })?,
+      };
+      steps.push(self.step_to(&parent, &current)?);
+      current = parent;
+    }
+    steps.reverse();
+
+    let window = self
+      .window(current.window_id)
+      .ok_or(AllioError::WindowNotFound(current.window_id))?;
+    Ok(ElementPath {
+      app: window.app_name,
+      window: window.title,
+      steps,
+    })
+  }
+
+  /// Find the element at `path` in the live tree. `Ok(None)` if the window or
+  /// any step along the path can't be found.
+  ///
+  /// `recency` controls which children are fetched from the OS at each step,
+  /// as in [`Allio::query`]. Use `Recency::Current` for a path saved by an
+  /// earlier run, whose elements aren't cached yet.
+  #[must_use = "this returns a Result that may contain an element"]
+  pub fn resolve(&self, path: &ElementPath, recency: Recency) -> AllioResult<Option<Element>> {
+    let Some(window) = self.window_for_path(path) else {
+      return Ok(None);
+    };
+    let Some(root) = self.window_root(window.id)? else {
+      return Ok(None);
+    };
+
+    let mut current = root.id;
+    for step in &path.steps {
+      let children = self.children_for_step(current, recency)?;
+      let next = self.read(|r| {
+        children
+          .into_iter()
+          .filter(|&id| {
+            r.element(id)
+              .is_some_and(|e| step.matches(e.role, e.identifier.as_deref()))
+          })
+          .nth(step.index)
+      });
+      let Some(next) = next else {
+        return Ok(None);
+      };
+      current = next;
+    }
+    self.get(current, Recency::Any).map(Some)
+  }
+
+  /// The step from `parent` down to `child`.
+  fn step_to(&self, parent: &Element, child: &Element) -> AllioResult<PathStep> {
+    // Only a fully loaded sibling list counts siblings that were never cached.
+    let cached = self.read(|r| {
+      r.tree_children_loaded(parent.id)
+        .then(|| r.tree_children(parent.id).to_vec())
+    });
+    let siblings = match cached {
+      Some(cached) if cached.contains(&child.id) => cached,
+      _ => self
+        .fetch_children(parent.id, usize::MAX)?
+        .into_iter()
+        .map(|e| e.id)
+        .collect(),
+    };
+
+    let identifier = child.identifier.as_deref();
+    let index = self.read(|r| {
+      siblings
+        .iter()
+        .take_while(|&&id| id != child.id)
+        .filter(|&&id| {
+          r.element(id)
+            .is_some_and(|e| e.role == child.role && e.identifier.as_deref() == identifier)
+        })
+        .count()
+    });
+    Ok(PathStep {
+      role: child.role,
+      identifier: child.identifier.clone(),
+      index,
+    })
+  }
+
+  /// The window a path starts from: same app and title, or the app's only window.
+  fn window_for_path(&self, path: &ElementPath) -> Option<Window> {
+    let windows: Vec<Window> = self
+      .all_windows()
+      .into_iter()
+      .filter(|w| w.app_name == path.app)
+      .collect();
+    if let Some(window) = windows.iter().find(|w| w.title == path.window) {
+      return Some(window.clone());
+    }
+    match <[Window; 1]>::try_from(windows) {
+      Ok([only]) => Some(only),
+      Err(_) => None,
+    }
+  }
+
+  /// Children of `id`, fetched from the OS as `recency` requires.
+  fn children_for_step(&self, id: ElementId, recency: Recency) -> AllioResult<Vec<ElementId>> {
+    let needs_fetch = match recency {
+      Recency::Any => false,
+      Recency::Current => true,
+      Recency::MaxAge(max_age) => self.read(|r| {
+        !r.tree_children_loaded(id) || r.element(id).is_some_and(|e| e.is_stale(max_age))
+      }),
+    };
+    if needs_fetch {
+      Ok(
+        self
+          .fetch_children(id, usize::MAX)?
+          .into_iter()
+          .map(|e| e.id)
+          .collect(),
+      )
+    } else {
+      Ok(self.read(|r| r.tree_children(id).to_vec()))
+    }
+  }
+}
+
+#[cfg(test)]
+#[cfg(feature = "mock")]
+mod tests {
+  use super::*;
+  use crate::mock::{MockDesktop, MockElement, MockWindow};
+  use crate::types::{Bounds, WindowId};
+
+  /// window > [toolbar > [button "New", button "Open"], list#files > [item "a", item "b"]]
+  fn build(desktop: &MockDesktop, title: &str) -> MockWindow {
+    let window = desktop.open_window("Finder", title);
+    let toolbar = desktop
+      .add_element(window.node, MockElement::new(Role::Toolbar))
+      .unwrap();
+    for label in ["New", "Open"] {
+      desktop.add_element(toolbar, MockElement::new(Role::Button).label(label));
+    }
+    let list = desktop
+      .add_element(
+        window.node,
+        MockElement::new(Role::List).identifier("files"),
+      )
+      .unwrap();
+    for label in ["a", "b"] {
+      desktop.add_element(list, MockElement::new(Role::ListItem).label(label));
+    }
+    window
+  }
+
+  /// Element of `allio` standing for a desktop node, found from the window root.
+  fn find(allio: &Allio, window: WindowId, label: &str) -> Element {
+    let root = allio.window_root(window).unwrap().unwrap();
+    let query = format!("[label=\"{label}\"]");
+    allio
+      .query(root.id, &query, Recency::Current)
+      .unwrap()
+      .remove(0)
+  }
+
+  #[test]
+  fn paths_round_trip() {
+    let desktop = MockDesktop::acquire();
+    let window = build(&desktop, "Downloads");
+    let allio = desktop.start_allio().unwrap();
+
+    let open = find(&allio, window.id, "Open");
+    let path = allio.path_of(open.id).unwrap();
+    assert_eq!(path.app, "Finder");
+    assert_eq!(path.window, "Downloads");
+    let steps: Vec<_> = path.steps.iter().map(|s| (s.role, s.index)).collect();
+    assert_eq!(steps, [(Role::Toolbar, 0), (Role::Button, 1)]);
+    assert_eq!(
+      allio.resolve(&path, Recency::Any).unwrap().unwrap().id,
+      open.id
+    );
+
+    let root = allio.window_root(window.id).unwrap().unwrap();
+    let root_path = allio.path_of(root.id).unwrap();
+    assert!(root_path.steps.is_empty());
+    assert_eq!(
+      allio.resolve(&root_path, Recency::Any).unwrap().unwrap().id,
+      root.id
+    );
+
+    let item = find(&allio, window.id, "b");
+    let path = allio.path_of(item.id).unwrap();
+    assert_eq!(path.steps[0].identifier.as_deref(), Some("files"));
+    assert_eq!(
+      allio.resolve(&path, Recency::Any).unwrap().unwrap().id,
+      item.id
+    );
+  }
+
+  #[test]
+  fn resolves_paths_from_an_earlier_run() {
+    let desktop = MockDesktop::acquire();
+    let window = build(&desktop, "Downloads");
+    let allio = desktop.start_allio().unwrap();
+    let path = allio.path_of(find(&allio, window.id, "Open").id).unwrap();
+    let saved = serde_json::to_string(&path).unwrap();
+    drop(allio);
+
+    // The app restarts: same structure, new window, nothing cached.
+    desktop.remove_window(window.id);
+    let window = build(&desktop, "Downloads");
+    let allio = desktop.start_allio().unwrap();
+
+    let path: ElementPath = serde_json::from_str(&saved).unwrap();
+    let found = allio.resolve(&path, Recency::Current).unwrap().unwrap();
+    assert_eq!(found.label.as_deref(), Some("Open"));
+    assert_eq!(found.window_id, window.id);
+  }
+
+  #[test]
+  fn counts_siblings_that_are_not_cached() {
+    let desktop = MockDesktop::acquire();
+    let window = desktop.open_window("Finder", "Downloads");
+    let bar = |x, w| Bounds {
+      x,
+      y: 0.0,
+      w,
+      h: 20.0,
+    };
+    let toolbar = desktop
+      .add_element(
+        window.node,
+        MockElement::new(Role::Toolbar).bounds(bar(0.0, 200.0)),
+      )
+      .unwrap();
+    for (x, label) in [(0.0, "New"), (100.0, "Open")] {
+      let bounds = bar(x, 100.0);
+      desktop.add_element(
+        toolbar,
+        MockElement::new(Role::Button).label(label).bounds(bounds),
+      );
+    }
+    let allio = desktop.start_allio().unwrap();
+
+    // Hit testing caches "Open" without its sibling "New".
+    let open = allio.element_at(150.0, 10.0).unwrap().unwrap();
+    assert_eq!(open.label.as_deref(), Some("Open"));
+    let path = allio.path_of(open.id).unwrap();
+    let steps: Vec<_> = path.steps.iter().map(|s| (s.role, s.index)).collect();
+    assert_eq!(steps, [(Role::Toolbar, 0), (Role::Button, 1)]);
+  }
+
+  #[test]
+  fn falls_back_to_the_only_window_and_misses_cleanly() {
+    let desktop = MockDesktop::acquire();
+    let window = build(&desktop, "Downloads");
+    let allio = desktop.start_allio().unwrap();
+    let mut path = allio.path_of(find(&allio, window.id, "Open").id).unwrap();
+
+    path.window = "Documents".into();
+    let found = allio.resolve(&path, Recency::Current).unwrap().unwrap();
+    assert_eq!(found.label.as_deref(), Some("Open"));
+
+    // A second window makes the title ambiguous again.
+    let other = desktop.open_window("Finder", "Desktop");
+    desktop.wait_for_window(&allio, other.id);
+    assert!(allio.resolve(&path, Recency::Current).unwrap().is_none());
+
+    path.window = "Downloads".into();
+    path.steps[1].index = 2;
+    assert!(allio.resolve(&path, Recency::Current).unwrap().is_none());
+    path.app = "Mail".into();
+    assert!(allio.resolve(&path, Recency::Current).unwrap().is_none());
+  }
+}
@@ -0,0 +1,524 @@
+/*!
+Platform call capture.
+
+With the `capture` feature, `Captured` wraps the backend (via
+`faults::Faulty`, with the `faults` feature) and is what core code sees as
+`CurrentPlatform`.
+It forwards every call unchanged. While a capture is running (see [`start`]),
+it also writes each call with its result, and each observer callback, to the
+capture as one JSON line.
+
+Handles are recorded by an ID assigned the first time the capture sees them,
+so a replay can tell them apart without the OS. Watch callbacks carry an
+`ElementId`, which only means something to the `Allio` that assigned it, so
+they are recorded against the watched handle instead. Start capturing before
+creating `Allio` to record every watch.
+
+The mock backend replays captures (`MockDesktop::replay`), so registry and
+observation logic can be tested against real apps on any OS.
+*/
+
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io::{self, BufRead, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use super::FaultyBackend as Backend;
+use super::traits::{
+  AppNotificationHandle, DisplayLinkHandle, ElementAttributes, ElementEvent, EventHandler,
+  Platform, PlatformHandle, PlatformObserver, WatchHandle,
+};
+use crate::a11y::{Action, Notification, Value};
+use crate::types::{AllioResult, ElementId, Point, Window, WindowId};
+
+type BackendHandle = <Backend as Platform>::Handle;
+type BackendObserver = <Backend as Platform>::Observer;
+
+/// A handle as recorded: capture-assigned ID plus PID.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub(crate) struct HandleRef {
+  pub(crate) id: u64,
+  pub(crate) pid: u32,
+}
+
+/// One recorded platform call (with its result) or observer callback.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "call", rename_all = "snake_case")]
+pub(crate) enum Entry {
+  HasPermissions {
+    granted: bool,
+  },
+  FetchWindows {
+    windows: Vec<Window>,
+  },
+  FetchScreenSize {
+    width: f64,
+    height: f64,
+  },
+  FetchMousePosition {
+    position: Point,
+  },
+  FetchWindowHandle {
+    window_id: WindowId,
+    handle: Option<HandleRef>,
+  },
+  CreateObserver {
+    pid: u32,
+    result: AllioResult<()>,
+  },
+  AppElement {
+    pid: u32,
+    handle: HandleRef,
+  },
+  IsProcessRunning {
+    pid: u32,
+    running: bool,
+  },
+  FetchChildren {
+    handle: u64,
+    children: Vec<HandleRef>,
+  },
+  FetchParent {
+    handle: u64,
+    parent: Option<HandleRef>,
+  },
+  FetchAttributes {
+    handle: u64,
+    attributes: Box<ElementAttributes>,
+  },
+  FetchElementAtPosition {
+    handle: u64,
+    x: f64,
+    y: f64,
+    element: Option<HandleRef>,
+  },
+  Window {
+    handle: u64,
+    window: Option<HandleRef>,
+  },
+  SetValue {
+    handle: u64,
+    value: Value,
+    result: AllioResult<()>,
+  },
+  PerformAction {
+    handle: u64,
+    action: Action,
+    result: AllioResult<()>,
+  },
+  SubscribeApp {
+    pid: u32,
+    result: AllioResult<()>,
+  },
+  CreateWatch {
+    handle: u64,
+    notifications: Vec<Notification>,
+    result: AllioResult<()>,
+  },
+  Callback {
+    #[serde(flatten)]
+    callback: Callback,
+  },
+}
+
+/// An observer callback, with handles in place of element IDs.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub(crate) enum Callback {
+  Destroyed {
+    handle: u64,
+  },
+  Changed {
+    handle: u64,
+    notification: Notification,
+  },
+  ChildrenChanged {
+    handle: u64,
+  },
+  FocusChanged {
+    handle: HandleRef,
+  },
+  SelectionChanged {
+    handle: HandleRef,
+    text: String,
+    range: Option<(u32, u32)>,
+  },
+}
+
+/// A recorded capture, read back for replay.
+#[derive(Debug, Clone, Default)]
+pub struct Capture {
+  pub(crate) entries: Vec<Entry>,
+}
+
+impl Capture {
+  /// Read a capture written by [`start`].
+  pub fn read(input: impl BufRead) -> io::Result<Self> {
+    let mut entries = Vec::new();
+    for line in input.lines() {
+      let line = line?;
+      if !line.trim().is_empty() {
+        entries.push(serde_json::from_str(&line)?);
+      }
+    }
+    Ok(Self { entries })
+  }
+
+  /// Number of recorded calls and callbacks.
+  pub const fn len(&self) -> usize {
+    self.entries.len()
+  }
+
+  /// Whether nothing was recorded.
+  pub const fn is_empty(&self) -> bool {
+    self.entries.is_empty()
+  }
+}
+
+/// Where a running capture writes, and what it has seen so far.
+struct Sink {
+  out: Box<dyn Write + Send>,
+  ids: HashMap<BackendHandle, u64>,
+  /// Watched elements, so watch callbacks can be recorded against handles.
+  watched: HashMap<ElementId, u64>,
+}
+
+impl Sink {
+  fn id(&mut self, handle: &BackendHandle) -> u64 {
+    let next = self.ids.len() as u64 + 1;
+    *self.ids.entry(handle.clone()).or_insert(next)
+  }
+
+  fn handle_ref(&mut self, handle: &BackendHandle) -> HandleRef {
+    HandleRef {
+      id: self.id(handle),
+      pid: handle.pid(),
+    }
+  }
+}
+
+/// Checked before locking, so calls cost one atomic load while not capturing.
+static CAPTURING: AtomicBool = AtomicBool::new(false);
+static SINK: Mutex<Option<Sink>> = Mutex::new(None);
+
+/// Start capturing platform calls and callbacks to `out`, one JSON line each.
+///
+/// Captures are process-wide. Starting again replaces the running capture.
+/// Use a buffered writer: calls are written as they complete, from every thread.
+pub fn start(out: impl Write + Send + 'static) {
+  *SINK.lock() = Some(Sink {
+    out: Box::new(out),
+    ids: HashMap::new(),
+    watched: HashMap::new(),
+  });
+  CAPTURING.store(true, Ordering::SeqCst);
+}
+
+/// Stop capturing and flush the capture.
+pub fn stop() -> io::Result<()> {
+  CAPTURING.store(false, Ordering::SeqCst);
+  SINK
+    .lock()
+    .take()
+    .map_or(Ok(()), |mut sink| sink.out.flush())
+}
+
+/// Record an entry if capturing. `entry` only runs while capturing.
+fn record(entry: impl FnOnce(&mut Sink) -> Entry) {
+  record_some(|sink| Some(entry(sink)));
+}
+
+/// Record an entry if capturing and `entry` returns one.
+fn record_some(entry: impl FnOnce(&mut Sink) -> Option<Entry>) {
+  if !CAPTURING.load(Ordering::Relaxed) {
+    return;
+  }
+  let mut guard = SINK.lock();
+  let Some(sink) = guard.as_mut() else {
+    return;
+  };
+  let Some(entry) = entry(sink) else {
+    return;
+  };
+  let written = serde_json::to_writer(&mut sink.out, &entry)
+    .map_err(io::Error::from)
+    .and_then(|()| sink.out.write_all(b"\n"));
+  if let Err(e) = written {
+    log::error!("[capture] Failed to write, stopping capture: {e}");
+    CAPTURING.store(false, Ordering::SeqCst);
+    *guard = None;
+  }
+}
+
+fn unit<T>(result: &AllioResult<T>) -> AllioResult<()> {
+  result.as_ref().map(|_| ()).map_err(Clone::clone)
+}
+
+/// The backend, with calls recorded while capturing.
+pub(crate) struct Captured;
+
+/// A backend handle. Compares and hashes like the handle it wraps.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(crate) struct CapturedHandle(pub(crate) BackendHandle);
+
+/// A backend observer whose callbacks are recorded while capturing.
+#[derive(Clone)]
+pub(crate) struct CapturedObserver(BackendObserver);
+
+/// Forwards backend callbacks to core, recording them on the way.
+struct Relay<C>(Arc<C>);
+
+impl<C: EventHandler<Handle = CapturedHandle>> EventHandler for Relay<C> {
+  type Handle = BackendHandle;
+
+  fn on_element_event(&self, event: ElementEvent<BackendHandle>) {
+    record_some(|sink| {
+      // Watches made before the capture started have no recorded handle, so a
+      // replay couldn't deliver their callbacks. Leave them out.
+      let watched = |id| {
+        let handle = sink.watched.get(id).copied();
+        if handle.is_none() {
+          log::debug!("[capture] Skipping callback for unwatched element {id}");
+        }
+        handle
+      };
+      let callback = match &event {
+        ElementEvent::Destroyed(id) => Callback::Destroyed {
+          handle: watched(id)?,
+        },
+        ElementEvent::Changed(id, notification) => Callback::Changed {
+          handle: watched(id)?,
+          notification: *notification,
+        },
+        ElementEvent::ChildrenChanged(id) => Callback::ChildrenChanged {
+          handle: watched(id)?,
+        },
+        ElementEvent::FocusChanged(handle) => Callback::FocusChanged {
+          handle: sink.handle_ref(handle),
+        },
+        ElementEvent::SelectionChanged {
+          handle,
+          text,
+          range,
+        } => Callback::SelectionChanged {
+          handle: sink.handle_ref(handle),
+          text: text.clone(),
+          range: *range,
+        },
+      };
+      Some(Entry::Callback { callback })
+    });
+
+    self.0.on_element_event(match event {
+      ElementEvent::Destroyed(id) => ElementEvent::Destroyed(id),
+      ElementEvent::Changed(id, notification) => ElementEvent::Changed(id, notification),
+      ElementEvent::ChildrenChanged(id) => ElementEvent::ChildrenChanged(id),
+      ElementEvent::FocusChanged(handle) => ElementEvent::FocusChanged(CapturedHandle(handle)),
+      ElementEvent::SelectionChanged {
+        handle,
+        text,
+        range,
+      } => ElementEvent::SelectionChanged {
+        handle: CapturedHandle(handle),
+        text,
+        range,
+      },
+    });
+  }
+}
+
+impl Platform for Captured {
+  type Handle = CapturedHandle;
+  type Observer = CapturedObserver;
+
+  fn has_permissions() -> bool {
+    let granted = Backend::has_permissions();
+    record(|_| Entry::HasPermissions { granted });
+    granted
+  }
+
+  fn fetch_windows(exclude_pid: Option<u32>) -> Vec<Window> {
+    let windows = Backend::fetch_windows(exclude_pid);
+    record(|_| Entry::FetchWindows {
+      windows: windows.clone(),
+    });
+    windows
+  }
+
+  fn fetch_screen_size() -> (f64, f64) {
+    let (width, height) = Backend::fetch_screen_size();
+    record(|_| Entry::FetchScreenSize { width, height });
+    (width, height)
+  }
+
+  fn fetch_mouse_position() -> Point {
+    let position = Backend::fetch_mouse_position();
+    record(|_| Entry::FetchMousePosition { position });
+    position
+  }
+
+  fn fetch_window_handle(window: &Window) -> Option<Self::Handle> {
+    let handle = Backend::fetch_window_handle(window);
+    record(|sink| Entry::FetchWindowHandle {
+      window_id: window.id,
+      handle: handle.as_ref().map(|h| sink.handle_ref(h)),
+    });
+    handle.map(CapturedHandle)
+  }
+
+  fn create_observer<C: EventHandler<Handle = Self::Handle>>(
+    pid: u32,
+    callbacks: Arc<C>,
+  ) -> AllioResult<Self::Observer> {
+    let observer = Backend::create_observer(pid, Arc::new(Relay(callbacks)));
+    record(|_| Entry::CreateObserver {
+      pid,
+      result: unit(&observer),
+    });
+    observer.map(CapturedObserver)
+  }
+
+  fn start_display_link<F: Fn() + Send + Sync + 'static>(callback: F) -> Option<DisplayLinkHandle> {
+    Backend::start_display_link(callback)
+  }
+
+  fn enable_accessibility_for_pid(pid: u32) {
+    Backend::enable_accessibility_for_pid(pid);
+  }
+
+  fn app_element(pid: u32) -> Self::Handle {
+    let handle = Backend::app_element(pid);
+    record(|sink| Entry::AppElement {
+      pid,
+      handle: sink.handle_ref(&handle),
+    });
+    CapturedHandle(handle)
+  }
+
+  fn is_process_running(pid: u32) -> bool {
+    let running = Backend::is_process_running(pid);
+    record(|_| Entry::IsProcessRunning { pid, running });
+    running
+  }
+}
+
+impl PlatformHandle for CapturedHandle {
+  fn pid(&self) -> u32 {
+    self.0.pid()
+  }
+
+  fn fetch_children(&self) -> Vec<Self> {
+    let children = self.0.fetch_children();
+    record(|sink| Entry::FetchChildren {
+      handle: sink.id(&self.0),
+      children: children.iter().map(|c| sink.handle_ref(c)).collect(),
+    });
+    children.into_iter().map(Self).collect()
+  }
+
+  fn fetch_parent(&self) -> Option<Self> {
+    let parent = self.0.fetch_parent();
+    record(|sink| Entry::FetchParent {
+      handle: sink.id(&self.0),
+      parent: parent.as_ref().map(|p| sink.handle_ref(p)),
+    });
+    parent.map(Self)
+  }
+
+  fn set_value(&self, value: &Value) -> AllioResult<()> {
+    let result = self.0.set_value(value);
+    record(|sink| Entry::SetValue {
+      handle: sink.id(&self.0),
+      value: value.clone(),
+      result: result.clone(),
+    });
+    result
+  }
+
+  fn perform_action(&self, action: Action) -> AllioResult<()> {
+    let result = self.0.perform_action(action);
+    record(|sink| Entry::PerformAction {
+      handle: sink.id(&self.0),
+      action,
+      result: result.clone(),
+    });
+    result
+  }
+
+  fn fetch_attributes(&self) -> ElementAttributes {
+    let attributes = self.0.fetch_attributes();
+    record(|sink| Entry::FetchAttributes {
+      handle: sink.id(&self.0),
+      attributes: Box::new(attributes.clone()),
+    });
+    attributes
+  }
+
+  fn fetch_element_at_position(&self, x: f64, y: f64) -> Option<Self> {
+    let element = self.0.fetch_element_at_position(x, y);
+    record(|sink| Entry::FetchElementAtPosition {
+      handle: sink.id(&self.0),
+      x,
+      y,
+      element: element.as_ref().map(|e| sink.handle_ref(e)),
+    });
+    element.map(Self)
+  }
+
+  fn window(&self) -> Option<Self> {
+    let window = self.0.window();
+    record(|sink| Entry::Window {
+      handle: sink.id(&self.0),
+      window: window.as_ref().map(|w| sink.handle_ref(w)),
+    });
+    window.map(Self)
+  }
+}
+
+impl PlatformObserver for CapturedObserver {
+  type Handle = CapturedHandle;
+
+  fn subscribe_app_notifications<C: EventHandler<Handle = Self::Handle>>(
+    &self,
+    pid: u32,
+    callbacks: Arc<C>,
+  ) -> AllioResult<AppNotificationHandle> {
+    let subscription = self
+      .0
+      .subscribe_app_notifications(pid, Arc::new(Relay(callbacks)));
+    record(|_| Entry::SubscribeApp {
+      pid,
+      result: unit(&subscription),
+    });
+    subscription
+  }
+
+  fn create_watch<C: EventHandler<Handle = Self::Handle>>(
+    &self,
+    handle: &Self::Handle,
+    element_id: ElementId,
+    initial_notifications: &[Notification],
+    callbacks: Arc<C>,
+  ) -> AllioResult<WatchHandle> {
+    let watch = self.0.create_watch(
+      &handle.0,
+      element_id,
+      initial_notifications,
+      Arc::new(Relay(callbacks)),
+    );
+    record(|sink| {
+      let id = sink.id(&handle.0);
+      if watch.is_ok() {
+        sink.watched.insert(element_id, id);
+      }
+      Entry::CreateWatch {
+        handle: id,
+        notifications: initial_notifications.to_vec(),
+        result: unit(&watch),
+      }
+    });
+    watch
+  }
+}
@@ -0,0 +1,429 @@
+/*!
+Platform fault injection.
+
+`Faulty` wraps the backend underneath `capture::Captured`. It forwards every
+call unchanged until faults are injected (see [`inject`]); then it misbehaves
+the way real apps do, Chromium and Electron especially: calls are slow or fail
+transiently, handles go stale under live elements, watches report `Destroyed`
+for elements that still exist or lose notifications, and children come back
+in a different order.
+
+Faults are drawn from a seeded generator, so the same sequence of calls
+misbehaves the same way. Captures record calls as `Faulty` answered them, so a
+run that went wrong can be replayed exactly, faults included.
+*/
+
+use parking_lot::Mutex;
+use std::collections::HashSet;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use super::traits::{
+  AppNotificationHandle, DisplayLinkHandle, ElementAttributes, ElementEvent, EventHandler,
+  Platform, PlatformHandle, PlatformObserver, WatchHandle,
+};
+use super::Backend;
+use crate::a11y::{Action, Notification, Value};
+use crate::types::{AllioError, AllioResult, ElementId, Point, Window};
+
+type BackendHandle = <Backend as Platform>::Handle;
+type BackendObserver = <Backend as Platform>::Observer;
+
+/// Reason given by injected errors.
+const INJECTED: &str = "injected fault";
+
+/// Faults to inject. Rates are probabilities (`0.0..=1.0`) drawn per call or callback.
+///
+/// The default injects nothing.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Faults {
+  /// Seed for drawing faults.
+  pub seed: u64,
+  /// Every call is delayed by up to this long.
+  pub latency: Duration,
+  /// Calls fail as if the app didn't answer: writes and subscriptions return
+  /// an error, reads come back empty.
+  pub error_rate: f64,
+  /// Handles go stale when used, answering like a destroyed element from then on.
+  pub stale_rate: f64,
+  /// Element callbacks are preceded by a `Destroyed` for an element that still exists.
+  pub spurious_destroyed_rate: f64,
+  /// `fetch_children` returns the children shuffled.
+  pub reorder_rate: f64,
+  /// Callbacks are dropped.
+  pub drop_rate: f64,
+}
+
+/// Injected faults and what they have done so far.
+struct Injector {
+  faults: Faults,
+  /// `SplitMix64` state.
+  state: u64,
+  stale: HashSet<BackendHandle>,
+}
+
+impl Injector {
+  fn new(faults: Faults) -> Self {
+    Self {
+      faults,
+      state: faults.seed,
+      stale: HashSet::new(),
+    }
+  }
+
+  const fn next(&mut self) -> u64 {
+    self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    let mut z = self.state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+  }
+
+  /// Whether a fault with this rate happens now.
+  fn roll(&mut self, rate: f64) -> bool {
+    if rate <= 0.0 {
+      return false;
+    }
+    let draw = u32::try_from(self.next() >> 32).unwrap_or(u32::MAX);
+    f64::from(draw) / 4_294_967_296.0 < rate
+  }
+
+  fn delay(&mut self) -> Duration {
+    let max = u64::try_from(self.faults.latency.as_micros()).unwrap_or(u64::MAX);
+    if max == 0 {
+      return Duration::ZERO;
+    }
+    Duration::from_micros(self.next() % max.saturating_add(1))
+  }
+
+  /// Shuffle in place (Fisher-Yates).
+  fn shuffle<T>(&mut self, items: &mut [T]) {
+    for i in (1..items.len()).rev() {
+      let j = usize::try_from(self.next() % (i as u64 + 1)).unwrap_or(i);
+      items.swap(i, j);
+    }
+  }
+}
+
+/// Checked before locking, so calls cost one atomic load while nothing is injected.
+static INJECTING: AtomicBool = AtomicBool::new(false);
+static INJECTOR: Mutex<Option<Injector>> = Mutex::new(None);
+
+/// Start injecting `faults` into every platform call and callback, until the
+/// returned guard is dropped.
+///
+/// Injection is process-wide. Injecting again replaces the faults, reseeds,
+/// and forgets which handles went stale.
+pub fn inject(faults: Faults) -> Injection {
+  *INJECTOR.lock() = Some(Injector::new(faults));
+  INJECTING.store(true, Ordering::SeqCst);
+  Injection(())
+}
+
+/// Injected faults: dropping it clears them, so a failing test doesn't leak
+/// faults into the next.
+#[must_use = "faults are cleared when this is dropped"]
+#[derive(Debug)]
+pub struct Injection(());
+
+impl Drop for Injection {
+  fn drop(&mut self) {
+    clear();
+  }
+}
+
+/// Stop injecting faults. Stale handles work again.
+pub fn clear() {
+  INJECTING.store(false, Ordering::SeqCst);
+  let injector = INJECTOR.lock().take();
+  drop(injector);
+}
+
+/// Draw from the injector, if faults are injected.
+fn draw<R>(f: impl FnOnce(&mut Injector) -> R) -> Option<R> {
+  if !INJECTING.load(Ordering::Relaxed) {
+    return None;
+  }
+  INJECTOR.lock().as_mut().map(f)
+}
+
+/// Delay the call by the injected latency.
+fn stall() {
+  if let Some(delay) = draw(Injector::delay).filter(|d| !d.is_zero()) {
+    std::thread::sleep(delay);
+  }
+}
+
+/// Delay the call, then draw whether it fails.
+fn call_fails() -> bool {
+  stall();
+  draw(|i| i.roll(i.faults.error_rate)).unwrap_or(false)
+}
+
+/// Delay a call on `handle`, then draw whether it fails. Stale handles always fail.
+fn handle_call_fails(handle: &BackendHandle) -> bool {
+  stall();
+  draw(|i| {
+    if !i.stale.contains(handle) && i.roll(i.faults.stale_rate) {
+      i.stale.insert(handle.clone());
+    }
+    i.stale.contains(handle) || i.roll(i.faults.error_rate)
+  })
+  .unwrap_or(false)
+}
+
+fn observer_error() -> AllioError {
+  AllioError::ObserverError(INJECTED.into())
+}
+
+/// The backend, misbehaving while faults are injected.
+pub(crate) struct Faulty;
+
+/// A backend handle. Compares and hashes like the handle it wraps.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(crate) struct FaultyHandle(pub(crate) BackendHandle);
+
+/// A backend observer whose callbacks are dropped or preceded by spurious
+/// `Destroyed` while faults are injected.
+#[derive(Clone)]
+pub(crate) struct FaultyObserver(BackendObserver);
+
+/// Forwards backend callbacks to the layer above, misbehaving on the way.
+struct Relay<C>(Arc<C>);
+
+impl<C: EventHandler<Handle = FaultyHandle>> EventHandler for Relay<C> {
+  type Handle = BackendHandle;
+
+  fn on_element_event(&self, event: ElementEvent<BackendHandle>) {
+    let (dropped, spurious) = draw(|i| {
+      (
+        i.roll(i.faults.drop_rate),
+        i.roll(i.faults.spurious_destroyed_rate),
+      )
+    })
+    .unwrap_or_default();
+    if dropped {
+      return;
+    }
+
+    let event = match event {
+      ElementEvent::Destroyed(id) => ElementEvent::Destroyed(id),
+      ElementEvent::Changed(id, notification) => {
+        if spurious {
+          self.0.on_element_event(ElementEvent::Destroyed(id));
+        }
+        ElementEvent::Changed(id, notification)
+      }
+      ElementEvent::ChildrenChanged(id) => {
+        if spurious {
+          self.0.on_element_event(ElementEvent::Destroyed(id));
+        }
+        ElementEvent::ChildrenChanged(id)
+      }
+      ElementEvent::FocusChanged(handle) => ElementEvent::FocusChanged(FaultyHandle(handle)),
+      ElementEvent::SelectionChanged {
+        handle,
+        text,
+        range,
+      } => ElementEvent::SelectionChanged {
+        handle: FaultyHandle(handle),
+        text,
+        range,
+      },
+    };
+    self.0.on_element_event(event);
+  }
+}
+
+impl Platform for Faulty {
+  type Handle = FaultyHandle;
+  type Observer = FaultyObserver;
+
+  fn has_permissions() -> bool {
+    Backend::has_permissions()
+  }
+
+  fn fetch_windows(exclude_pid: Option<u32>) -> Vec<Window> {
+    if call_fails() {
+      return Vec::new();
+    }
+    Backend::fetch_windows(exclude_pid)
+  }
+
+  fn fetch_screen_size() -> (f64, f64) {
+    stall();
+    Backend::fetch_screen_size()
+  }
+
+  fn fetch_mouse_position() -> Point {
+    stall();
+    Backend::fetch_mouse_position()
+  }
+
+  fn fetch_window_handle(window: &Window) -> Option<Self::Handle> {
+    if call_fails() {
+      return None;
+    }
+    Backend::fetch_window_handle(window).map(FaultyHandle)
+  }
+
+  fn create_observer<C: EventHandler<Handle = Self::Handle>>(
+    pid: u32,
+    callbacks: Arc<C>,
+  ) -> AllioResult<Self::Observer> {
+    if call_fails() {
+      return Err(observer_error());
+    }
+    Backend::create_observer(pid, Arc::new(Relay(callbacks))).map(FaultyObserver)
+  }
+
+  fn start_display_link<F: Fn() + Send + Sync + 'static>(callback: F) -> Option<DisplayLinkHandle> {
+    Backend::start_display_link(callback)
+  }
+
+  fn enable_accessibility_for_pid(pid: u32) {
+    Backend::enable_accessibility_for_pid(pid);
+  }
+
+  fn app_element(pid: u32) -> Self::Handle {
+    stall();
+    FaultyHandle(Backend::app_element(pid))
+  }
+
+  fn is_process_running(pid: u32) -> bool {
+    stall();
+    Backend::is_process_running(pid)
+  }
+}
+
+impl PlatformHandle for FaultyHandle {
+  fn pid(&self) -> u32 {
+    self.0.pid()
+  }
+
+  fn fetch_children(&self) -> Vec<Self> {
+    if handle_call_fails(&self.0) {
+      return Vec::new();
+    }
+    let mut children = self.0.fetch_children();
+    draw(|i| {
+      if i.roll(i.faults.reorder_rate) {
+        i.shuffle(&mut children);
+      }
+    });
+    children.into_iter().map(Self).collect()
+  }
+
+  fn fetch_parent(&self) -> Option<Self> {
+    if handle_call_fails(&self.0) {
+      return None;
+    }
+    self.0.fetch_parent().map(Self)
+  }
+
+  fn set_value(&self, value: &Value) -> AllioResult<()> {
+    if handle_call_fails(&self.0) {
+      return Err(AllioError::SetValueFailed {
+        reason: INJECTED.into(),
+      });
+    }
+    self.0.set_value(value)
+  }
+
+  fn perform_action(&self, action: Action) -> AllioResult<()> {
+    if handle_call_fails(&self.0) {
+      return Err(AllioError::ActionFailed {
+        action,
+        reason: INJECTED.into(),
+      });
+    }
+    self.0.perform_action(action)
+  }
+
+  fn fetch_attributes(&self) -> ElementAttributes {
+    if handle_call_fails(&self.0) {
+      return ElementAttributes::default();
+    }
+    self.0.fetch_attributes()
+  }
+
+  fn fetch_element_at_position(&self, x: f64, y: f64) -> Option<Self> {
+    if handle_call_fails(&self.0) {
+      return None;
+    }
+    self.0.fetch_element_at_position(x, y).map(Self)
+  }
+
+  fn window(&self) -> Option<Self> {
+    if handle_call_fails(&self.0) {
+      return None;
+    }
+    self.0.window().map(Self)
+  }
+}
+
+impl PlatformObserver for FaultyObserver {
+  type Handle = FaultyHandle;
+
+  fn subscribe_app_notifications<C: EventHandler<Handle = Self::Handle>>(
+    &self,
+    pid: u32,
+    callbacks: Arc<C>,
+  ) -> AllioResult<AppNotificationHandle> {
+    if call_fails() {
+      return Err(observer_error());
+    }
+    self
+      .0
+      .subscribe_app_notifications(pid, Arc::new(Relay(callbacks)))
+  }
+
+  fn create_watch<C: EventHandler<Handle = Self::Handle>>(
+    &self,
+    handle: &Self::Handle,
+    element_id: ElementId,
+    initial_notifications: &[Notification],
+    callbacks: Arc<C>,
+  ) -> AllioResult<WatchHandle> {
+    if handle_call_fails(&handle.0) {
+      return Err(observer_error());
+    }
+    self.0.create_watch(
+      &handle.0,
+      element_id,
+      initial_notifications,
+      Arc::new(Relay(callbacks)),
+    )
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn draws_are_seeded() {
+    let faults = Faults {
+      seed: 7,
+      error_rate: 0.5,
+      ..Faults::default()
+    };
+    let draws = |faults| {
+      let mut injector = Injector::new(faults);
+      (0..64).map(|_| injector.roll(0.5)).collect::<Vec<_>>()
+    };
+    assert_eq!(draws(faults), draws(faults));
+    assert_ne!(draws(faults), draws(Faults { seed: 8, ..faults }));
+    assert!(draws(faults).contains(&true) && draws(faults).contains(&false));
+
+    let mut injector = Injector::new(faults);
+    assert!(!injector.roll(0.0));
+    assert!((0..64).all(|_| injector.roll(1.0)));
+    assert_eq!(injector.delay(), Duration::ZERO);
+    let mut items: Vec<u32> = (0..16).collect();
+    injector.shuffle(&mut items);
+    items.sort_unstable();
+    assert_eq!(items, (0..16).collect::<Vec<_>>());
+  }
+}
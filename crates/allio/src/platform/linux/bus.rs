@@ -0,0 +1,250 @@
+/*!
+D-Bus plumbing for AT-SPI2.
+
+AT-SPI lives on a dedicated accessibility bus, separate from the session bus.
+Its address comes from `AT_SPI_BUS_ADDRESS`, or from `org.a11y.Bus.GetAddress`
+on the session bus. One blocking connection is shared by the whole process.
+*/
+
+use parking_lot::Mutex;
+use std::collections::HashMap;
+use std::sync::{Arc, LazyLock, OnceLock};
+use std::time::Duration;
+
+use serde::Serialize;
+use zbus::blocking::Connection;
+use zbus::zvariant::{
+  DynamicDeserialize, DynamicType, ObjectPath, OwnedObjectPath, OwnedValue, Value,
+};
+
+/// Well-known name of the AT-SPI registry daemon.
+pub(super) const REGISTRY_BUS: &str = "org.a11y.atspi.Registry";
+/// Path of the desktop (on the registry) and of each application's root object.
+pub(super) const ROOT_PATH: &str = "/org/a11y/atspi/accessible/root";
+/// Path AT-SPI uses for "no object" (e.g. the parent of the desktop).
+pub(super) const NULL_PATH: &str = "/org/a11y/atspi/null";
+/// Path of the registry object that tracks event listeners.
+pub(super) const REGISTRY_PATH: &str = "/org/a11y/atspi/registry";
+
+/// AT-SPI D-Bus interface names.
+pub(super) mod iface {
+  pub(in crate::platform::linux) const ACCESSIBLE: &str = "org.a11y.atspi.Accessible";
+  pub(in crate::platform::linux) const ACTION: &str = "org.a11y.atspi.Action";
+  pub(in crate::platform::linux) const COMPONENT: &str = "org.a11y.atspi.Component";
+  pub(in crate::platform::linux) const EDITABLE_TEXT: &str = "org.a11y.atspi.EditableText";
+  pub(in crate::platform::linux) const TABLE: &str = "org.a11y.atspi.Table";
+  pub(in crate::platform::linux) const TABLE_CELL: &str = "org.a11y.atspi.TableCell";
+  pub(in crate::platform::linux) const TEXT: &str = "org.a11y.atspi.Text";
+  pub(in crate::platform::linux) const VALUE: &str = "org.a11y.atspi.Value";
+  pub(in crate::platform::linux) const REGISTRY: &str = "org.a11y.atspi.Registry";
+  pub(in crate::platform::linux) const EVENT_OBJECT: &str = "org.a11y.atspi.Event.Object";
+  pub(in crate::platform::linux) const EVENT_MOUSE: &str = "org.a11y.atspi.Event.Mouse";
+  pub(in crate::platform::linux) const PROPERTIES: &str = "org.freedesktop.DBus.Properties";
+}
+
+/// `ATSPI_COORD_TYPE_SCREEN`: coordinates relative to the screen.
+pub(super) const COORD_TYPE_SCREEN: u32 = 0;
+
+/// Timeout for calls into apps. A hung app must not stall polling.
+const METHOD_TIMEOUT: Duration = Duration::from_millis(500);
+
+static CONNECTION: OnceLock<Option<Connection>> = OnceLock::new();
+
+/// Process IDs of connections on the accessibility bus, by unique name.
+static PIDS: LazyLock<Mutex<HashMap<Arc<str>, u32>>> = LazyLock::new(Mutex::default);
+
+/// The shared accessibility bus connection. None if the bus is unreachable.
+pub(super) fn connection() -> Option<&'static Connection> {
+  CONNECTION
+    .get_or_init(|| match connect() {
+      Ok(conn) => Some(conn),
+      Err(e) => {
+        log::warn!("Failed to connect to the AT-SPI bus: {e}");
+        None
+      }
+    })
+    .as_ref()
+}
+
+fn connect() -> zbus::Result<Connection> {
+  let address = match std::env::var("AT_SPI_BUS_ADDRESS") {
+    Ok(address) if !address.is_empty() => address,
+    _ => {
+      let session = Connection::session()?;
+      let reply = session.call_method(
+        Some("org.a11y.Bus"),
+        "/org/a11y/bus",
+        Some("org.a11y.Bus"),
+        "GetAddress",
+        &(),
+      )?;
+      reply.body().deserialize::<String>()?
+    }
+  };
+  open(&address)
+}
+
+/// Open a connection to a bus address with the AT-SPI call timeout.
+pub(super) fn open(address: &str) -> zbus::Result<Connection> {
+  zbus::blocking::connection::Builder::address(address)?
+    .method_timeout(METHOD_TIMEOUT)
+    .build()
+}
+
+/// Point the shared connection at a private bus. Must run before first use.
+#[cfg(test)]
+pub(super) fn use_connection(conn: Connection) -> bool {
+  CONNECTION.set(Some(conn)).is_ok()
+}
+
+/// Ask toolkits to expose their accessibility trees.
+///
+/// GTK, Qt and Chromium check `org.a11y.Status.IsEnabled` on the session bus.
+/// Best effort: set once per process, failures are logged.
+pub(super) fn enable_toolkit_accessibility() {
+  static ENABLED: OnceLock<()> = OnceLock::new();
+  ENABLED.get_or_init(|| {
+    let result = Connection::session().and_then(|session| {
+      session.call_method(
+        Some("org.a11y.Bus"),
+        "/org/a11y/bus",
+        Some(iface::PROPERTIES),
+        "Set",
+        &("org.a11y.Status", "IsEnabled", Value::from(true)),
+      )
+    });
+    if let Err(e) = result {
+      log::debug!("Could not set org.a11y.Status.IsEnabled: {e}");
+    }
+  });
+}
+
+/// Tell the registry we listen for an event, so toolkits start emitting it.
+pub(super) fn register_event(event: &str) {
+  let Some(conn) = connection() else {
+    return;
+  };
+  // AT-SPI >= 2.46 takes (event, properties, app_bus_name); older versions just (event).
+  let result = conn
+    .call_method(
+      Some(REGISTRY_BUS),
+      REGISTRY_PATH,
+      Some(iface::REGISTRY),
+      "RegisterEvent",
+      &(event, Vec::<&str>::new(), ""),
+    )
+    .or_else(|_| {
+      conn.call_method(
+        Some(REGISTRY_BUS),
+        REGISTRY_PATH,
+        Some(iface::REGISTRY),
+        "RegisterEvent",
+        &(event,),
+      )
+    });
+  if let Err(e) = result {
+    log::debug!("RegisterEvent({event}) failed: {e}");
+  }
+}
+
+/// Process ID owning a bus connection. Cached per unique name.
+pub(super) fn pid_of(bus: &Arc<str>) -> Option<u32> {
+  if let Some(pid) = PIDS.lock().get(bus) {
+    return Some(*pid);
+  }
+  let reply = connection()?
+    .call_method(
+      Some("org.freedesktop.DBus"),
+      "/org/freedesktop/DBus",
+      Some("org.freedesktop.DBus"),
+      "GetConnectionUnixProcessID",
+      &(&**bus,),
+    )
+    .ok()?;
+  let pid = reply.body().deserialize::<u32>().ok()?;
+  PIDS.lock().insert(Arc::clone(bus), pid);
+  Some(pid)
+}
+
+/// Forget cached PIDs for connections that are no longer on the bus.
+pub(super) fn retain_pids(live: impl Fn(&str) -> bool) {
+  PIDS.lock().retain(|bus, _| live(bus));
+}
+
+/// Reference to an accessible object: owning connection and object path.
+/// This is AT-SPI's `(so)` wire type. Clone is cheap (reference-counted).
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(super) struct ObjectRef {
+  pub(super) bus: Arc<str>,
+  pub(super) path: OwnedObjectPath,
+}
+
+impl ObjectRef {
+  pub(super) fn new(bus: &str, path: &str) -> Option<Self> {
+    Some(Self {
+      bus: Arc::from(bus),
+      path: OwnedObjectPath::try_from(path).ok()?,
+    })
+  }
+
+  /// Root object of an application.
+  pub(super) fn app_root(bus: Arc<str>) -> Self {
+    Self {
+      bus,
+      path: ObjectPath::from_static_str_unchecked(ROOT_PATH).into(),
+    }
+  }
+
+  /// The registry's desktop object, whose children are the applications.
+  pub(super) fn desktop() -> Self {
+    Self::app_root(Arc::from(REGISTRY_BUS))
+  }
+
+  /// Decode a `(so)` pair. None for the null object.
+  pub(super) fn from_wire((bus, path): (String, OwnedObjectPath)) -> Option<Self> {
+    if path.as_str() == NULL_PATH || bus.is_empty() {
+      return None;
+    }
+    Some(Self {
+      bus: Arc::from(bus),
+      path,
+    })
+  }
+
+  /// Whether this is an application's root object.
+  pub(super) fn is_app_root(&self) -> bool {
+    self.path.as_str() == ROOT_PATH && &*self.bus != REGISTRY_BUS
+  }
+
+  /// Call a method and decode the reply.
+  pub(super) fn call<B, R>(&self, iface: &str, method: &str, body: &B) -> zbus::Result<R>
+  where
+    B: Serialize + DynamicType,
+    R: for<'d> DynamicDeserialize<'d>,
+  {
+    let conn = connection().ok_or_else(|| zbus::Error::Failure("no AT-SPI bus".into()))?;
+    let reply = conn.call_method(Some(&*self.bus), &self.path, Some(iface), method, body)?;
+    reply.body().deserialize()
+  }
+
+  /// Read a D-Bus property.
+  pub(super) fn property<T>(&self, iface: &str, name: &str) -> Option<T>
+  where
+    T: TryFrom<OwnedValue>,
+  {
+    let value: OwnedValue = self.call(iface::PROPERTIES, "Get", &(iface, name)).ok()?;
+    T::try_from(value).ok()
+  }
+
+  /// Read all D-Bus properties of an interface.
+  pub(super) fn properties(&self, iface: &str) -> HashMap<String, OwnedValue> {
+    self
+      .call(iface::PROPERTIES, "GetAll", &(iface,))
+      .unwrap_or_default()
+  }
+
+  /// Write a D-Bus property.
+  pub(super) fn set_property(&self, iface: &str, name: &str, value: Value<'_>) -> zbus::Result<()> {
+    self.call(iface::PROPERTIES, "Set", &(iface, name, value))
+  }
+}
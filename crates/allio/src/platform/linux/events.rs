@@ -0,0 +1,371 @@
+/*!
+AT-SPI event delivery.
+
+AT-SPI broadcasts events as D-Bus signals on the accessibility bus. One
+listener thread receives them and routes each one the way the macOS observer
+callback does: element-level notifications go to the watches registered for
+the source object, focus/selection go to the app-level subscriptions of the
+source's application.
+
+Handles:
+- Watches via `WatchHandleInner` RAII (unregister on drop)
+- App-level subscriptions via `AppNotificationHandleInner` RAII
+- Mouse position, from `mouse:abs` events (X11 only)
+
+Callbacks always run with the routing table unlocked, so they may call back
+into the platform.
+*/
+
+use parking_lot::Mutex;
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, LazyLock, OnceLock};
+
+use zbus::blocking::MessageIterator;
+use zbus::message::Type as MessageType;
+use zbus::zvariant::OwnedValue;
+use zbus::MatchRule;
+
+use super::bus::{self, iface, ObjectRef};
+use super::handles::{object_from_variant, ElementHandle};
+use super::mapping::{atspi_event, notification_from_atspi, REGISTERED_EVENTS};
+use crate::a11y::Notification;
+use crate::platform::{ElementEvent, EventHandler};
+use crate::types::{AllioError, AllioResult, ElementId, Point};
+
+/// Signals buffered while callbacks run, before the oldest are dropped.
+const EVENT_QUEUE: usize = 1024;
+
+static NEXT_SUBSCRIPTION_ID: AtomicU64 = AtomicU64::new(1);
+
+static ROUTER: LazyLock<Mutex<Router>> = LazyLock::new(Mutex::default);
+
+static MOUSE_POSITION: Mutex<Option<Point>> = Mutex::new(None);
+
+/// Type-erased callbacks, so subscriptions can live in the global router.
+pub(crate) trait Callbacks: Send + Sync {
+  fn on_element_event(&self, event: ElementEvent<ElementHandle>);
+}
+
+/// Wrapper that erases the generic type parameter.
+pub(crate) struct CallbacksWrapper<C: EventHandler<Handle = ElementHandle>>(pub(crate) Arc<C>);
+
+impl<C: EventHandler<Handle = ElementHandle>> Callbacks for CallbacksWrapper<C> {
+  fn on_element_event(&self, event: ElementEvent<ElementHandle>) {
+    self.0.on_element_event(event);
+  }
+}
+
+type Delivery = (Arc<dyn Callbacks>, ElementEvent<ElementHandle>);
+
+struct Watch {
+  object: ObjectRef,
+  element_id: ElementId,
+  notifications: HashSet<Notification>,
+  callbacks: Arc<dyn Callbacks>,
+}
+
+struct AppSubscription {
+  bus: Arc<str>,
+  pid: u32,
+  callbacks: Arc<dyn Callbacks>,
+}
+
+#[derive(Default)]
+struct Router {
+  watches: HashMap<u64, Watch>,
+  by_object: HashMap<ObjectRef, HashSet<u64>>,
+  apps: HashMap<u64, AppSubscription>,
+}
+
+impl Router {
+  fn add_watch(&mut self, watch: Watch) -> u64 {
+    let id = NEXT_SUBSCRIPTION_ID.fetch_add(1, Ordering::Relaxed);
+    self
+      .by_object
+      .entry(watch.object.clone())
+      .or_default()
+      .insert(id);
+    self.watches.insert(id, watch);
+    id
+  }
+
+  fn remove_watch(&mut self, id: u64) -> Option<Arc<dyn Callbacks>> {
+    let watch = self.watches.remove(&id)?;
+    if let Some(ids) = self.by_object.get_mut(&watch.object) {
+      ids.remove(&id);
+      if ids.is_empty() {
+        self.by_object.remove(&watch.object);
+      }
+    }
+    Some(watch.callbacks)
+  }
+
+  /// Queue an element-level notification for the watches on `object`.
+  fn notify(&self, object: &ObjectRef, notif: Notification, out: &mut Vec<Delivery>) {
+    let Some(ids) = self.by_object.get(object) else {
+      return;
+    };
+    for watch in ids.iter().filter_map(|id| self.watches.get(id)) {
+      if !watch.notifications.contains(&notif) {
+        continue;
+      }
+      let event = match notif {
+        Notification::ValueChanged | Notification::TitleChanged => {
+          ElementEvent::Changed(watch.element_id, notif)
+        }
+        Notification::Destroyed => ElementEvent::Destroyed(watch.element_id),
+        Notification::ChildrenChanged => ElementEvent::ChildrenChanged(watch.element_id),
+        Notification::FocusChanged
+        | Notification::SelectionChanged
+        | Notification::BoundsChanged => continue,
+      };
+      out.push((Arc::clone(&watch.callbacks), event));
+    }
+  }
+
+  /// App-level subscribers for the application owning `bus`.
+  fn app_subscribers(&self, bus: &str) -> Vec<(Arc<dyn Callbacks>, u32)> {
+    self
+      .apps
+      .values()
+      .filter(|s| &*s.bus == bus)
+      .map(|s| (Arc::clone(&s.callbacks), s.pid))
+      .collect()
+  }
+}
+
+/// Register a watch for an element's notifications.
+pub(super) fn create_watch(
+  handle: &ElementHandle,
+  element_id: ElementId,
+  initial_notifications: &[Notification],
+  callbacks: Arc<dyn Callbacks>,
+) -> AllioResult<WatchHandleInner> {
+  ensure_listener()?;
+  let id = ROUTER.lock().add_watch(Watch {
+    object: handle.object.clone(),
+    element_id,
+    notifications: initial_notifications.iter().copied().collect(),
+    callbacks,
+  });
+  Ok(WatchHandleInner { id })
+}
+
+/// Subscribe to focus and selection changes for an application.
+pub(super) fn subscribe_app(
+  bus: Arc<str>,
+  pid: u32,
+  callbacks: Arc<dyn Callbacks>,
+) -> AllioResult<AppNotificationHandleInner> {
+  ensure_listener()?;
+  let id = NEXT_SUBSCRIPTION_ID.fetch_add(1, Ordering::Relaxed);
+  ROUTER.lock().apps.insert(
+    id,
+    AppSubscription {
+      bus,
+      pid,
+      callbacks,
+    },
+  );
+  Ok(AppNotificationHandleInner { id })
+}
+
+/// Last pointer position reported by the registry. Starts listening on first call.
+pub(super) fn mouse_position() -> Option<Point> {
+  static LISTENING: OnceLock<()> = OnceLock::new();
+  LISTENING.get_or_init(|| {
+    if let Err(e) = start_mouse_listener() {
+      log::debug!("AT-SPI mouse events unavailable: {e}");
+    }
+  });
+  *MOUSE_POSITION.lock()
+}
+
+fn ensure_listener() -> AllioResult<()> {
+  static STARTED: OnceLock<bool> = OnceLock::new();
+  let started = *STARTED.get_or_init(|| match start_object_listener() {
+    Ok(()) => true,
+    Err(e) => {
+      log::warn!("Failed to start AT-SPI event listener: {e}");
+      false
+    }
+  });
+  if started {
+    Ok(())
+  } else {
+    Err(AllioError::ObserverError(
+      "AT-SPI event listener is not running".into(),
+    ))
+  }
+}
+
+fn listen(
+  interface: &'static str,
+  thread_name: &str,
+  handler: impl Fn(&zbus::Message) + Send + 'static,
+) -> zbus::Result<()> {
+  let conn = bus::connection().ok_or_else(|| zbus::Error::Failure("no AT-SPI bus".into()))?;
+  let rule = MatchRule::builder()
+    .msg_type(MessageType::Signal)
+    .interface(interface)?
+    .build();
+  let messages = MessageIterator::for_match_rule(rule, conn, Some(EVENT_QUEUE))?;
+  std::thread::Builder::new()
+    .name(thread_name.into())
+    .spawn(move || {
+      for message in messages.flatten() {
+        handler(&message);
+      }
+    })
+    .map_err(|e| zbus::Error::Failure(e.to_string()))?;
+  Ok(())
+}
+
+fn start_object_listener() -> zbus::Result<()> {
+  listen(iface::EVENT_OBJECT, "allio-atspi-events", route)?;
+  for event in REGISTERED_EVENTS {
+    bus::register_event(event);
+  }
+  Ok(())
+}
+
+fn start_mouse_listener() -> zbus::Result<()> {
+  listen(iface::EVENT_MOUSE, "allio-atspi-mouse", |message| {
+    let header = message.header();
+    if header.member().map(zbus::names::MemberName::as_str) != Some("Abs") {
+      return;
+    }
+    if let Some((_, x, y, _)) = parse_body(message) {
+      *MOUSE_POSITION.lock() = Some(Point::new(f64::from(x), f64::from(y)));
+    }
+  })?;
+  bus::register_event("mouse:abs");
+  Ok(())
+}
+
+/// Event body: (kind, detail1, detail2, `any_data`). Trailing properties are ignored.
+type EventBody = (String, i32, i32, OwnedValue);
+
+fn parse_body(message: &zbus::Message) -> Option<EventBody> {
+  let body = message.body();
+  body
+    .deserialize::<(String, i32, i32, OwnedValue, HashMap<String, OwnedValue>)>()
+    .map(|(kind, detail1, detail2, any_data, _)| (kind, detail1, detail2, any_data))
+    .or_else(|_| body.deserialize::<EventBody>())
+    .ok()
+}
+
+/// Route one `org.a11y.atspi.Event.Object` signal.
+fn route(message: &zbus::Message) {
+  let header = message.header();
+  let (Some(sender), Some(path), Some(member)) = (header.sender(), header.path(), header.member())
+  else {
+    return;
+  };
+  let Some(source) = ObjectRef::new(sender.as_str(), path.as_str()) else {
+    return;
+  };
+  let Some((kind, detail1, _, any_data)) = parse_body(message) else {
+    return;
+  };
+  let Some(notif) = notification_from_atspi(member.as_str(), &kind, detail1) else {
+    return;
+  };
+
+  match notif {
+    Notification::FocusChanged => {
+      deliver_app_event(&source, |handle| ElementEvent::FocusChanged(handle.clone()));
+    }
+    Notification::SelectionChanged => {
+      let probe = ElementHandle::new(source.clone(), 0);
+      let (text, range) = probe
+        .text_selection()
+        .map_or((String::new(), None), |(text, range)| (text, Some(range)));
+      deliver_app_event(&source, |handle| ElementEvent::SelectionChanged {
+        handle: handle.clone(),
+        text: text.clone(),
+        range,
+      });
+    }
+    Notification::Destroyed
+    | Notification::ValueChanged
+    | Notification::TitleChanged
+    | Notification::BoundsChanged
+    | Notification::ChildrenChanged => {
+      let mut pending = Vec::new();
+      {
+        let router = ROUTER.lock();
+        // A removed child is gone: tell its watchers before the parent's.
+        if member.as_str() == atspi_event::CHILDREN_CHANGED && kind.starts_with("remove") {
+          if let Some(child) = object_from_variant(&any_data) {
+            router.notify(&child, Notification::Destroyed, &mut pending);
+          }
+        }
+        router.notify(&source, notif, &mut pending);
+      }
+      for (callbacks, event) in pending {
+        callbacks.on_element_event(event);
+      }
+    }
+  }
+}
+
+fn deliver_app_event(
+  source: &ObjectRef,
+  event: impl Fn(&ElementHandle) -> ElementEvent<ElementHandle>,
+) {
+  let subscribers = ROUTER.lock().app_subscribers(&source.bus);
+  for (callbacks, pid) in subscribers {
+    let handle = ElementHandle::new(source.clone(), pid);
+    callbacks.on_element_event(event(&handle));
+  }
+}
+
+/// Watch registration in the event router. Unregisters on drop.
+pub(crate) struct WatchHandleInner {
+  id: u64,
+}
+
+impl WatchHandleInner {
+  /// Add notifications to the watch set.
+  pub(crate) fn add(&mut self, notifs: &[Notification]) -> usize {
+    let mut router = ROUTER.lock();
+    let Some(watch) = router.watches.get_mut(&self.id) else {
+      return 0;
+    };
+    notifs
+      .iter()
+      .filter(|n| watch.notifications.insert(**n))
+      .count()
+  }
+
+  /// Remove notifications from the watch set.
+  pub(crate) fn remove(&mut self, notifs: &[Notification]) {
+    if let Some(watch) = ROUTER.lock().watches.get_mut(&self.id) {
+      for notif in notifs {
+        watch.notifications.remove(notif);
+      }
+    }
+  }
+}
+
+impl Drop for WatchHandleInner {
+  fn drop(&mut self) {
+    // Callbacks are released outside the lock: they may own the last `Allio` clone.
+    let callbacks = ROUTER.lock().remove_watch(self.id);
+    drop(callbacks);
+  }
+}
+
+/// App-level subscription in the event router. Unregisters on drop.
+pub(crate) struct AppNotificationHandleInner {
+  id: u64,
+}
+
+impl Drop for AppNotificationHandleInner {
+  fn drop(&mut self) {
+    let subscription = ROUTER.lock().apps.remove(&self.id);
+    drop(subscription);
+  }
+}
@@ -0,0 +1,429 @@
+/*!
+AT-SPI element and observer handles.
+
+An element is addressed by the connection that owns it and its object path.
+Every read is a D-Bus round trip, so attributes are fetched in as few calls as
+the protocol allows (`GetAll` for properties, one call per optional interface).
+*/
+
+#![allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use zbus::zvariant::{OwnedObjectPath, OwnedValue, Value as DBusValue};
+
+use super::bus::{self, iface, ObjectRef, COORD_TYPE_SCREEN};
+use super::mapping::{action_from_atspi, is_plain_container, role_from_atspi, State, StateSet};
+use super::window_list;
+use crate::a11y::{Action, Role, Value, ValueType};
+use crate::platform::ElementAttributes;
+use crate::types::Bounds;
+
+/// Deepest hierarchy walked when hit testing or looking for the window.
+const MAX_DEPTH: usize = 64;
+
+/// `ATSPI_SCROLL_ANYWHERE`: scroll the minimum needed to show the object.
+const SCROLL_ANYWHERE: u32 = 6;
+
+/// Wire type of an object reference, `(so)`.
+type WireRef = (String, OwnedObjectPath);
+
+/// Handle to an accessible object. Clone is cheap (reference-counted).
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(crate) struct ElementHandle {
+  pub(super) object: ObjectRef,
+  pub(super) pid: u32,
+}
+
+impl ElementHandle {
+  pub(super) const fn new(object: ObjectRef, pid: u32) -> Self {
+    Self { object, pid }
+  }
+
+  /// Root object of the application with this PID.
+  /// Falls back to the desktop if the application is not on the bus.
+  pub(super) fn app(pid: u32) -> Self {
+    let object = window_list::app_bus(pid).map_or_else(ObjectRef::desktop, ObjectRef::app_root);
+    Self::new(object, pid)
+  }
+
+  /// Handle for a referenced object, resolving its PID if it lives in another process.
+  fn related(&self, object: ObjectRef) -> Self {
+    let pid = if object.bus == self.object.bus {
+      self.pid
+    } else {
+      bus::pid_of(&object.bus).unwrap_or(self.pid)
+    };
+    Self::new(object, pid)
+  }
+
+  pub(super) fn get_children(&self) -> Vec<Self> {
+    let children: Vec<WireRef> = self
+      .object
+      .call(iface::ACCESSIBLE, "GetChildren", &())
+      .unwrap_or_default();
+    children
+      .into_iter()
+      .filter_map(ObjectRef::from_wire)
+      .map(|object| self.related(object))
+      .collect()
+  }
+
+  /// Parent element. None for application roots (their parent is the desktop).
+  pub(super) fn get_parent(&self) -> Option<Self> {
+    if self.object.is_app_root() {
+      return None;
+    }
+    let parent: WireRef = self.object.property(iface::ACCESSIBLE, "Parent")?;
+    let parent = ObjectRef::from_wire(parent)?;
+    if &*parent.bus == bus::REGISTRY_BUS {
+      return None;
+    }
+    Some(self.related(parent))
+  }
+
+  pub(super) fn states(&self) -> Option<StateSet> {
+    let words: Vec<u32> = self.object.call(iface::ACCESSIBLE, "GetState", &()).ok()?;
+    Some(StateSet::from_words(&words))
+  }
+
+  pub(super) fn name(&self) -> Option<String> {
+    self
+      .object
+      .property::<String>(iface::ACCESSIBLE, "Name")
+      .filter(|s| !s.is_empty())
+  }
+
+  fn interfaces(&self) -> Vec<String> {
+    self
+      .object
+      .call(iface::ACCESSIBLE, "GetInterfaces", &())
+      .unwrap_or_default()
+  }
+
+  pub(super) fn extents(&self) -> Option<Bounds> {
+    let (x, y, w, h): (i32, i32, i32, i32) = self
+      .object
+      .call(iface::COMPONENT, "GetExtents", &(COORD_TYPE_SCREEN,))
+      .ok()?;
+    if w < 0 || h < 0 {
+      return None;
+    }
+    Some(Bounds {
+      x: f64::from(x),
+      y: f64::from(y),
+      w: f64::from(w),
+      h: f64::from(h),
+    })
+  }
+
+  /// Action names, in the order `DoAction` indexes them.
+  fn action_names(&self) -> Vec<String> {
+    let actions: Vec<(String, String, String)> = self
+      .object
+      .call(iface::ACTION, "GetActions", &())
+      .unwrap_or_default();
+    actions.into_iter().map(|(name, _, _)| name).collect()
+  }
+
+  /// Full text content of a Text object.
+  pub(super) fn text(&self) -> Option<String> {
+    self
+      .object
+      .call(iface::TEXT, "GetText", &(0i32, -1i32))
+      .ok()
+  }
+
+  /// First selected text range and its text. None if nothing is selected.
+  pub(super) fn text_selection(&self) -> Option<(String, (u32, u32))> {
+    let count: i32 = self.object.call(iface::TEXT, "GetNSelections", &()).ok()?;
+    if count < 1 {
+      return None;
+    }
+    let (start, end): (i32, i32) = self
+      .object
+      .call(iface::TEXT, "GetSelection", &(0i32,))
+      .ok()?;
+    let text: String = self
+      .object
+      .call(iface::TEXT, "GetText", &(start, end))
+      .ok()?;
+    Some((text, (start.max(0) as u32, end.max(0) as u32)))
+  }
+
+  pub(in crate::platform) fn fetch_attributes_internal(&self) -> ElementAttributes {
+    // GetRole fails for defunct objects: report them like a dead macOS element.
+    let Ok(role_code) = self
+      .object
+      .call::<_, u32>(iface::ACCESSIBLE, "GetRole", &())
+    else {
+      return ElementAttributes::default();
+    };
+    let states = self.states().unwrap_or_default();
+    let interfaces = self.interfaces();
+    let has = |name: &str| interfaces.iter().any(|i| i == name);
+
+    let props = self.object.properties(iface::ACCESSIBLE);
+    let prop_str = |name: &str| -> Option<String> {
+      props
+        .get(name)
+        .and_then(|v| String::try_from(v.try_clone().ok()?).ok())
+        .filter(|s| !s.is_empty())
+    };
+    let attributes: HashMap<String, String> = self
+      .object
+      .call(iface::ACCESSIBLE, "GetAttributes", &())
+      .unwrap_or_default();
+    let attribute = |name: &str| attributes.get(name).filter(|s| !s.is_empty()).cloned();
+
+    let title = prop_str("Name");
+    let mut role = role_from_atspi(role_code, states);
+
+    let value = match role.value_type() {
+      ValueType::String if has(iface::TEXT) => self.text().map(Value::String),
+      ValueType::Boolean => Some(Value::Boolean(states.contains(State::Checked))),
+      ValueType::Number if has(iface::VALUE) => self
+        .object
+        .property::<f64>(iface::VALUE, "CurrentValue")
+        .map(Value::Number),
+      ValueType::String | ValueType::Number | ValueType::Color | ValueType::None => None,
+    };
+
+    // Plain panel with no label/value → GenericGroup (for pruning)
+    if role == Role::Group && is_plain_container(role_code) && title.is_none() && value.is_none() {
+      role = Role::GenericGroup;
+    }
+
+    let platform_role = self
+      .object
+      .call::<_, String>(iface::ACCESSIBLE, "GetRoleName", &())
+      .unwrap_or_else(|_| format!("role {role_code}"));
+
+    let actions = self.supported_actions(
+      has(iface::ACTION),
+      has(iface::VALUE) && role.value_type() == ValueType::Number,
+      states,
+    );
+
+    let (row_index, column_index) = if has(iface::TABLE_CELL) {
+      self
+        .object
+        .property::<(i32, i32)>(iface::TABLE_CELL, "Position")
+        .map_or((None, None), |(row, col)| (to_index(row), to_index(col)))
+    } else {
+      (None, None)
+    };
+    let (row_count, column_count) = if has(iface::TABLE) {
+      (
+        self
+          .object
+          .property::<i32>(iface::TABLE, "NRows")
+          .and_then(to_index),
+        self
+          .object
+          .property::<i32>(iface::TABLE, "NColumns")
+          .and_then(to_index),
+      )
+    } else {
+      (None, None)
+    };
+
+    ElementAttributes {
+      role,
+      platform_role,
+      title,
+      value,
+      description: prop_str("Description"),
+      placeholder: attribute("placeholder-text"),
+      url: None,
+      bounds: if has(iface::COMPONENT) {
+        self.extents()
+      } else {
+        None
+      },
+      focused: states
+        .contains(State::Focusable)
+        .then(|| states.contains(State::Focused)),
+      disabled: !states.contains(State::Enabled),
+      selected: states
+        .contains(State::Selectable)
+        .then(|| states.contains(State::Selected)),
+      expanded: states
+        .contains(State::Expandable)
+        .then(|| states.contains(State::Expanded)),
+      row_index,
+      column_index,
+      row_count,
+      column_count,
+      actions,
+      identifier: prop_str("AccessibleId").or_else(|| attribute("id")),
+    }
+  }
+
+  /// Actions from the Action interface, plus Increment/Decrement for numeric values.
+  fn supported_actions(&self, has_action: bool, steppable: bool, states: StateSet) -> Vec<Action> {
+    let mut actions: Vec<Action> = if has_action {
+      self
+        .action_names()
+        .iter()
+        .filter_map(|name| action_from_atspi(name, states))
+        .collect()
+    } else {
+      Vec::new()
+    };
+    if steppable {
+      actions.extend([Action::Increment, Action::Decrement]);
+    }
+    let mut seen = Vec::with_capacity(actions.len());
+    actions.retain(|a| {
+      let new = !seen.contains(a);
+      seen.push(*a);
+      new
+    });
+    actions
+  }
+
+  pub(super) fn set_typed_value(&self, value: &Value) -> Result<(), String> {
+    match value {
+      Value::String(s) => {
+        let ok: bool = self
+          .object
+          .call(iface::EDITABLE_TEXT, "SetTextContents", &(s.as_str(),))
+          .map_err(|e| e.to_string())?;
+        ok.then_some(())
+          .ok_or_else(|| "SetTextContents was rejected".to_string())
+      }
+      Value::Number(n) => self
+        .object
+        .set_property(iface::VALUE, "CurrentValue", DBusValue::from(*n))
+        .map_err(|e| e.to_string()),
+      Value::Boolean(b) => {
+        let states = self
+          .states()
+          .ok_or_else(|| "element no longer exists".to_string())?;
+        if states.contains(State::Checked) == *b {
+          return Ok(());
+        }
+        // AT-SPI has no writable checked state: toggle it like a user would.
+        self.perform(Action::Press)
+      }
+      Value::Color(_) => Err("color values cannot be set through AT-SPI".to_string()),
+    }
+  }
+
+  pub(super) fn perform(&self, action: Action) -> Result<(), String> {
+    let states = self.states().unwrap_or_default();
+    let index = self
+      .action_names()
+      .iter()
+      .position(|name| action_from_atspi(name, states) == Some(action));
+
+    let ok: bool = match (index, action) {
+      (Some(index), _) => {
+        let index = i32::try_from(index).map_err(|e| e.to_string())?;
+        self.object.call(iface::ACTION, "DoAction", &(index,))
+      }
+      (None, Action::Raise) => self.object.call(iface::COMPONENT, "GrabFocus", &()),
+      (None, Action::ScrollToVisible) => {
+        self
+          .object
+          .call(iface::COMPONENT, "ScrollTo", &(SCROLL_ANYWHERE,))
+      }
+      (None, Action::Increment | Action::Decrement) => return self.step_value(action),
+      (None, _) => return Err(format!("element does not support {action:?}")),
+    }
+    .map_err(|e| e.to_string())?;
+
+    ok.then_some(())
+      .ok_or_else(|| format!("{action:?} was rejected"))
+  }
+
+  /// Increment/decrement through the Value interface, for elements without named actions.
+  fn step_value(&self, action: Action) -> Result<(), String> {
+    let current = self
+      .object
+      .property::<f64>(iface::VALUE, "CurrentValue")
+      .ok_or_else(|| format!("element does not support {action:?}"))?;
+    let step = self
+      .object
+      .property::<f64>(iface::VALUE, "MinimumIncrement")
+      .filter(|s| *s > 0.0)
+      .unwrap_or(1.0);
+    let target = if action == Action::Increment {
+      current + step
+    } else {
+      current - step
+    };
+    self
+      .object
+      .set_property(iface::VALUE, "CurrentValue", DBusValue::from(target))
+      .map_err(|e| e.to_string())
+  }
+
+  /// Deepest element at a screen point. Starts from the window under the point
+  /// when called on an application.
+  pub(super) fn element_at_position(&self, x: f64, y: f64) -> Option<Self> {
+    let (x, y) = (x.round() as i32, y.round() as i32);
+    let mut current = if self.object.is_app_root() {
+      self.get_children().into_iter().find(|w| {
+        w.extents()
+          .is_some_and(|b| b.contains(crate::types::Point::new(f64::from(x), f64::from(y))))
+      })?
+    } else {
+      self.clone()
+    };
+
+    for _ in 0..MAX_DEPTH {
+      let Ok(hit) = self.object_at(&current, x, y) else {
+        break;
+      };
+      let Some(hit) = hit else {
+        break;
+      };
+      if hit == current {
+        break;
+      }
+      current = hit;
+    }
+    Some(current)
+  }
+
+  fn object_at(&self, within: &Self, x: i32, y: i32) -> zbus::Result<Option<Self>> {
+    let hit: WireRef = within.object.call(
+      iface::COMPONENT,
+      "GetAccessibleAtPoint",
+      &(x, y, COORD_TYPE_SCREEN),
+    )?;
+    Ok(ObjectRef::from_wire(hit).map(|object| self.related(object)))
+  }
+
+  /// The top-level window containing this element (the ancestor just below the app).
+  pub(super) fn containing_window(&self) -> Option<Self> {
+    let mut current = self.clone();
+    for _ in 0..MAX_DEPTH {
+      let parent = current.get_parent()?;
+      if parent.object.is_app_root() {
+        return Some(current);
+      }
+      current = parent;
+    }
+    None
+  }
+}
+
+fn to_index(n: i32) -> Option<usize> {
+  usize::try_from(n).ok()
+}
+
+/// Decode an event's `any_data` as an object reference, e.g. the child in `children-changed`.
+pub(super) fn object_from_variant(value: &OwnedValue) -> Option<ObjectRef> {
+  let wire = WireRef::try_from(value.try_clone().ok()?).ok()?;
+  ObjectRef::from_wire(wire)
+}
+
+/// Observer for one application. Subscriptions live in the event router.
+#[derive(Debug, Clone)]
+pub(crate) struct ObserverHandle {
+  pub(super) bus: Arc<str>,
+}
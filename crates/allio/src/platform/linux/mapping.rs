@@ -0,0 +1,443 @@
+/*!
+Mappings between Allio accessibility types and AT-SPI2 roles, states, action
+names and events.
+
+AT-SPI roles and states are numeric (`AtspiRole`, `AtspiStateType` in
+`atspi-constants.h`). Action names are free-form strings chosen by the toolkit,
+so several spellings map to the same action.
+*/
+
+use crate::a11y::{Action, Notification, Role};
+
+/// AT-SPI role codes (`ATSPI_ROLE_*`).
+pub(super) mod atspi_role {
+  pub(in crate::platform::linux) const ALERT: u32 = 2;
+  pub(in crate::platform::linux) const CANVAS: u32 = 6;
+  pub(in crate::platform::linux) const CHECK_BOX: u32 = 7;
+  pub(in crate::platform::linux) const CHECK_MENU_ITEM: u32 = 8;
+  pub(in crate::platform::linux) const COLOR_CHOOSER: u32 = 9;
+  pub(in crate::platform::linux) const COLUMN_HEADER: u32 = 10;
+  pub(in crate::platform::linux) const COMBO_BOX: u32 = 11;
+  pub(in crate::platform::linux) const DIAL: u32 = 15;
+  pub(in crate::platform::linux) const DIALOG: u32 = 16;
+  pub(in crate::platform::linux) const FILE_CHOOSER: u32 = 19;
+  pub(in crate::platform::linux) const FILLER: u32 = 20;
+  pub(in crate::platform::linux) const FRAME: u32 = 23;
+  pub(in crate::platform::linux) const GLASS_PANE: u32 = 24;
+  pub(in crate::platform::linux) const ICON: u32 = 26;
+  pub(in crate::platform::linux) const IMAGE: u32 = 27;
+  pub(in crate::platform::linux) const INTERNAL_FRAME: u32 = 28;
+  pub(in crate::platform::linux) const LABEL: u32 = 29;
+  pub(in crate::platform::linux) const LAYERED_PANE: u32 = 30;
+  pub(in crate::platform::linux) const LIST: u32 = 31;
+  pub(in crate::platform::linux) const LIST_ITEM: u32 = 32;
+  pub(in crate::platform::linux) const MENU: u32 = 33;
+  pub(in crate::platform::linux) const MENU_BAR: u32 = 34;
+  pub(in crate::platform::linux) const MENU_ITEM: u32 = 35;
+  pub(in crate::platform::linux) const PAGE_TAB: u32 = 37;
+  pub(in crate::platform::linux) const PAGE_TAB_LIST: u32 = 38;
+  pub(in crate::platform::linux) const PANEL: u32 = 39;
+  pub(in crate::platform::linux) const PASSWORD_TEXT: u32 = 40;
+  pub(in crate::platform::linux) const POPUP_MENU: u32 = 41;
+  pub(in crate::platform::linux) const PROGRESS_BAR: u32 = 42;
+  pub(in crate::platform::linux) const PUSH_BUTTON: u32 = 43;
+  pub(in crate::platform::linux) const RADIO_BUTTON: u32 = 44;
+  pub(in crate::platform::linux) const RADIO_MENU_ITEM: u32 = 45;
+  pub(in crate::platform::linux) const ROOT_PANE: u32 = 46;
+  pub(in crate::platform::linux) const ROW_HEADER: u32 = 47;
+  pub(in crate::platform::linux) const SCROLL_BAR: u32 = 48;
+  pub(in crate::platform::linux) const SCROLL_PANE: u32 = 49;
+  pub(in crate::platform::linux) const SEPARATOR: u32 = 50;
+  pub(in crate::platform::linux) const SLIDER: u32 = 51;
+  pub(in crate::platform::linux) const SPIN_BUTTON: u32 = 52;
+  pub(in crate::platform::linux) const SPLIT_PANE: u32 = 53;
+  pub(in crate::platform::linux) const STATUS_BAR: u32 = 54;
+  pub(in crate::platform::linux) const TABLE: u32 = 55;
+  pub(in crate::platform::linux) const TABLE_CELL: u32 = 56;
+  pub(in crate::platform::linux) const TABLE_COLUMN_HEADER: u32 = 57;
+  pub(in crate::platform::linux) const TABLE_ROW_HEADER: u32 = 58;
+  pub(in crate::platform::linux) const TEAROFF_MENU_ITEM: u32 = 59;
+  pub(in crate::platform::linux) const TERMINAL: u32 = 60;
+  pub(in crate::platform::linux) const TEXT: u32 = 61;
+  pub(in crate::platform::linux) const TOGGLE_BUTTON: u32 = 62;
+  pub(in crate::platform::linux) const TOOL_BAR: u32 = 63;
+  pub(in crate::platform::linux) const TOOL_TIP: u32 = 64;
+  pub(in crate::platform::linux) const TREE: u32 = 65;
+  pub(in crate::platform::linux) const TREE_TABLE: u32 = 66;
+  pub(in crate::platform::linux) const UNKNOWN: u32 = 67;
+  pub(in crate::platform::linux) const VIEWPORT: u32 = 68;
+  pub(in crate::platform::linux) const WINDOW: u32 = 69;
+  pub(in crate::platform::linux) const PARAGRAPH: u32 = 73;
+  pub(in crate::platform::linux) const APPLICATION: u32 = 75;
+  pub(in crate::platform::linux) const AUTOCOMPLETE: u32 = 76;
+  pub(in crate::platform::linux) const ENTRY: u32 = 79;
+  pub(in crate::platform::linux) const CAPTION: u32 = 81;
+  pub(in crate::platform::linux) const DOCUMENT_FRAME: u32 = 82;
+  pub(in crate::platform::linux) const HEADING: u32 = 83;
+  pub(in crate::platform::linux) const SECTION: u32 = 85;
+  pub(in crate::platform::linux) const REDUNDANT_OBJECT: u32 = 86;
+  pub(in crate::platform::linux) const FORM: u32 = 87;
+  pub(in crate::platform::linux) const LINK: u32 = 88;
+  pub(in crate::platform::linux) const TABLE_ROW: u32 = 90;
+  pub(in crate::platform::linux) const TREE_ITEM: u32 = 91;
+  pub(in crate::platform::linux) const DOCUMENT_SPREADSHEET: u32 = 92;
+  pub(in crate::platform::linux) const DOCUMENT_PRESENTATION: u32 = 93;
+  pub(in crate::platform::linux) const DOCUMENT_TEXT: u32 = 94;
+  pub(in crate::platform::linux) const DOCUMENT_WEB: u32 = 95;
+  pub(in crate::platform::linux) const DOCUMENT_EMAIL: u32 = 96;
+  pub(in crate::platform::linux) const LIST_BOX: u32 = 98;
+  pub(in crate::platform::linux) const GROUPING: u32 = 99;
+  pub(in crate::platform::linux) const LEVEL_BAR: u32 = 103;
+  pub(in crate::platform::linux) const TITLE_BAR: u32 = 104;
+  pub(in crate::platform::linux) const ARTICLE: u32 = 109;
+  pub(in crate::platform::linux) const LANDMARK: u32 = 110;
+  pub(in crate::platform::linux) const STATIC: u32 = 116;
+  pub(in crate::platform::linux) const PUSH_BUTTON_MENU: u32 = 129;
+  pub(in crate::platform::linux) const SWITCH: u32 = 130;
+}
+
+/// AT-SPI state bits (`ATSPI_STATE_*`), as positions in the 64-bit state set.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum State {
+  Active = 1,
+  Checked = 4,
+  Enabled = 8,
+  Expandable = 9,
+  Expanded = 10,
+  Focusable = 11,
+  Focused = 12,
+  Iconified = 15,
+  MultiLine = 17,
+  Selectable = 22,
+  Selected = 23,
+  Showing = 25,
+  Visible = 30,
+}
+
+/// Set of AT-SPI states, decoded from the `au` returned by `GetState`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(super) struct StateSet(u64);
+
+impl StateSet {
+  /// Decode from the two 32-bit words AT-SPI sends (low word first).
+  pub(super) fn from_words(words: &[u32]) -> Self {
+    let low = words.first().copied().unwrap_or(0);
+    let high = words.get(1).copied().unwrap_or(0);
+    Self(u64::from(low) | (u64::from(high) << 32))
+  }
+
+  /// Encode as the two 32-bit words AT-SPI sends.
+  #[cfg(test)]
+  #[allow(clippy::cast_possible_truncation)]
+  pub(super) const fn to_words(self) -> [u32; 2] {
+    [self.0 as u32, (self.0 >> 32) as u32]
+  }
+
+  pub(super) const fn contains(self, state: State) -> bool {
+    self.0 & (1 << state as u64) != 0
+  }
+
+  #[cfg(test)]
+  pub(super) const fn with(self, state: State) -> Self {
+    Self(self.0 | (1 << state as u64))
+  }
+
+  #[cfg(test)]
+  pub(super) const fn without(self, state: State) -> Self {
+    Self(self.0 & !(1 << state as u64))
+  }
+}
+
+/// Convert an AT-SPI role code to our Role.
+///
+/// `TEXT` is used for both single- and multi-line inputs, so the state set decides.
+pub(super) fn role_from_atspi(code: u32, states: StateSet) -> Role {
+  use atspi_role as r;
+  match code {
+    // Structural
+    r::APPLICATION => Role::Application,
+    r::FRAME | r::WINDOW | r::DIALOG | r::ALERT | r::FILE_CHOOSER => Role::Window,
+    r::DOCUMENT_FRAME
+    | r::DOCUMENT_WEB
+    | r::DOCUMENT_TEXT
+    | r::DOCUMENT_EMAIL
+    | r::DOCUMENT_SPREADSHEET
+    | r::DOCUMENT_PRESENTATION => Role::Document,
+    r::PANEL | r::FILLER | r::SECTION | r::GROUPING | r::FORM | r::ARTICLE | r::LANDMARK => {
+      Role::Group
+    }
+    r::SCROLL_PANE | r::VIEWPORT => Role::ScrollArea,
+    r::TOOL_BAR => Role::Toolbar,
+
+    // Navigation
+    r::MENU | r::POPUP_MENU => Role::Menu,
+    r::MENU_BAR => Role::MenuBar,
+    r::MENU_ITEM | r::CHECK_MENU_ITEM | r::RADIO_MENU_ITEM | r::TEAROFF_MENU_ITEM => Role::MenuItem,
+    r::PAGE_TAB => Role::Tab,
+    r::PAGE_TAB_LIST => Role::TabList,
+
+    // Collections
+    r::LIST | r::LIST_BOX => Role::List,
+    r::LIST_ITEM => Role::ListItem,
+    r::TABLE | r::TREE_TABLE => Role::Table,
+    r::TABLE_ROW => Role::Row,
+    r::TABLE_CELL
+    | r::COLUMN_HEADER
+    | r::ROW_HEADER
+    | r::TABLE_COLUMN_HEADER
+    | r::TABLE_ROW_HEADER => Role::Cell,
+    r::TREE => Role::Tree,
+    r::TREE_ITEM => Role::TreeItem,
+
+    // Interactive
+    r::PUSH_BUTTON | r::TOGGLE_BUTTON | r::PUSH_BUTTON_MENU => Role::Button,
+    r::LINK => Role::Link,
+    r::TEXT if states.contains(State::MultiLine) => Role::TextArea,
+    r::TEXT | r::ENTRY | r::PASSWORD_TEXT | r::TERMINAL => Role::TextField,
+    r::COMBO_BOX | r::AUTOCOMPLETE => Role::ComboBox,
+    r::CHECK_BOX => Role::Checkbox,
+    r::SWITCH => Role::Switch,
+    r::RADIO_BUTTON => Role::RadioButton,
+    r::SLIDER | r::DIAL => Role::Slider,
+    r::SPIN_BUTTON => Role::Stepper,
+    r::PROGRESS_BAR | r::LEVEL_BAR => Role::ProgressBar,
+    r::COLOR_CHOOSER => Role::ColorWell,
+
+    // Static content
+    r::LABEL | r::STATIC | r::PARAGRAPH | r::CAPTION => Role::StaticText,
+    r::HEADING => Role::Heading,
+    r::IMAGE | r::ICON | r::CANVAS => Role::Image,
+    r::SEPARATOR => Role::Separator,
+
+    // Known non-semantic LEAFY elements → GenericElement.
+    r::SCROLL_BAR | r::STATUS_BAR | r::TITLE_BAR | r::TOOL_TIP | r::REDUNDANT_OBJECT => {
+      Role::GenericElement
+    }
+
+    // Known non-semantic CONTAINER elements → GenericGroup
+    r::ROOT_PANE | r::LAYERED_PANE | r::GLASS_PANE | r::SPLIT_PANE | r::INTERNAL_FRAME => {
+      Role::GenericGroup
+    }
+
+    r::UNKNOWN => Role::Unknown,
+
+    _ => {
+      log::debug!("Unmapped AT-SPI role: {code}");
+      Role::Unknown
+    }
+  }
+}
+
+/// Whether a role code is a plain layout container (candidate for `GenericGroup`).
+pub(super) const fn is_plain_container(code: u32) -> bool {
+  matches!(
+    code,
+    atspi_role::PANEL | atspi_role::FILLER | atspi_role::SECTION
+  )
+}
+
+/// AT-SPI action names, as GTK, Qt and Chromium spell them.
+mod atspi_action {
+  pub(super) const CLICK: &str = "click";
+  pub(super) const PRESS: &str = "press";
+  pub(super) const ACTIVATE: &str = "activate";
+  pub(super) const TOGGLE: &str = "toggle";
+  pub(super) const JUMP: &str = "jump";
+  pub(super) const SHOW_MENU: &str = "showmenu";
+  pub(super) const MENU: &str = "menu";
+  pub(super) const POPUP: &str = "popup";
+  pub(super) const INCREMENT: &str = "increment";
+  pub(super) const DECREMENT: &str = "decrement";
+  pub(super) const CONFIRM: &str = "confirm";
+  pub(super) const CANCEL: &str = "cancel";
+  pub(super) const RAISE: &str = "raise";
+  pub(super) const SELECT: &str = "select";
+  pub(super) const EXPAND: &str = "expand";
+  pub(super) const COLLAPSE: &str = "collapse";
+  pub(super) const EXPAND_OR_CONTRACT: &str = "expand or contract";
+  pub(super) const EXPAND_OR_COLLAPSE: &str = "expand or collapse";
+  pub(super) const SCROLL_TO: &str = "scroll to";
+}
+
+/// Convert an AT-SPI action name to our Action.
+///
+/// Toggle-style expand actions resolve to whichever direction applies now.
+pub(super) fn action_from_atspi(name: &str, states: StateSet) -> Option<Action> {
+  use atspi_action as a;
+  match name.to_ascii_lowercase().as_str() {
+    a::CLICK | a::PRESS | a::ACTIVATE | a::TOGGLE | a::JUMP => Some(Action::Press),
+    a::SHOW_MENU | a::MENU | a::POPUP => Some(Action::ShowMenu),
+    a::INCREMENT => Some(Action::Increment),
+    a::DECREMENT => Some(Action::Decrement),
+    a::CONFIRM => Some(Action::Confirm),
+    a::CANCEL => Some(Action::Cancel),
+    a::RAISE => Some(Action::Raise),
+    a::SELECT => Some(Action::Pick),
+    a::EXPAND => Some(Action::Expand),
+    a::COLLAPSE => Some(Action::Collapse),
+    a::EXPAND_OR_CONTRACT | a::EXPAND_OR_COLLAPSE => Some(if states.contains(State::Expanded) {
+      Action::Collapse
+    } else {
+      Action::Expand
+    }),
+    a::SCROLL_TO => Some(Action::ScrollToVisible),
+    _ => None,
+  }
+}
+
+/// AT-SPI event members on `org.a11y.atspi.Event.Object`.
+pub(super) mod atspi_event {
+  pub(in crate::platform::linux) const PROPERTY_CHANGE: &str = "PropertyChange";
+  pub(in crate::platform::linux) const STATE_CHANGED: &str = "StateChanged";
+  pub(in crate::platform::linux) const CHILDREN_CHANGED: &str = "ChildrenChanged";
+  pub(in crate::platform::linux) const TEXT_CHANGED: &str = "TextChanged";
+  pub(in crate::platform::linux) const TEXT_SELECTION_CHANGED: &str = "TextSelectionChanged";
+  pub(in crate::platform::linux) const BOUNDS_CHANGED: &str = "BoundsChanged";
+}
+
+/// Event names registered with the AT-SPI registry, so toolkits emit them.
+pub(super) const REGISTERED_EVENTS: &[&str] = &[
+  "object:property-change",
+  "object:state-changed",
+  "object:children-changed",
+  "object:text-changed",
+  "object:text-selection-changed",
+  "object:bounds-changed",
+];
+
+/// Convert an AT-SPI object event to our Notification.
+///
+/// `kind` is the event's first string (e.g. `accessible-name`, `focused`, `add/system`)
+/// and `detail1` its first integer (1 when a state was set, 0 when cleared).
+pub(super) fn notification_from_atspi(
+  member: &str,
+  kind: &str,
+  detail1: i32,
+) -> Option<Notification> {
+  use atspi_event as e;
+  match (member, kind) {
+    (e::PROPERTY_CHANGE, "accessible-name") => Some(Notification::TitleChanged),
+    (e::PROPERTY_CHANGE, "accessible-value") | (e::TEXT_CHANGED, _) => {
+      Some(Notification::ValueChanged)
+    }
+    (e::STATE_CHANGED, "checked") => Some(Notification::ValueChanged),
+    (e::STATE_CHANGED, "defunct") if detail1 != 0 => Some(Notification::Destroyed),
+    (e::STATE_CHANGED, "focused") if detail1 != 0 => Some(Notification::FocusChanged),
+    (e::CHILDREN_CHANGED, _) => Some(Notification::ChildrenChanged),
+    (e::TEXT_SELECTION_CHANGED, _) => Some(Notification::SelectionChanged),
+    (e::BOUNDS_CHANGED, _) => Some(Notification::BoundsChanged),
+    _ => None,
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn state_set_words() {
+    let states = StateSet::default().with(State::Focused);
+    assert_eq!(StateSet::from_words(&states.to_words()), states);
+    assert!(states.contains(State::Focused));
+    // High word carries states 32..63 (bit 41 is ATSPI_STATE_CHECKABLE)
+    assert_eq!(StateSet::from_words(&[0, 1 << 9]), StateSet(1 << 41));
+    assert!(!states.contains(State::Checked));
+    assert!(!states.without(State::Focused).contains(State::Focused));
+  }
+
+  #[test]
+  fn state_set_short_input() {
+    assert_eq!(StateSet::from_words(&[]), StateSet::default());
+    assert!(StateSet::from_words(&[1 << 12]).contains(State::Focused));
+  }
+
+  #[test]
+  fn role_mapping() {
+    let none = StateSet::default();
+    assert_eq!(role_from_atspi(atspi_role::PUSH_BUTTON, none), Role::Button);
+    assert_eq!(role_from_atspi(atspi_role::FRAME, none), Role::Window);
+    assert_eq!(role_from_atspi(atspi_role::ENTRY, none), Role::TextField);
+    assert_eq!(role_from_atspi(atspi_role::SWITCH, none), Role::Switch);
+    assert_eq!(
+      role_from_atspi(atspi_role::SCROLL_BAR, none),
+      Role::GenericElement
+    );
+  }
+
+  #[test]
+  fn text_role_depends_on_multi_line() {
+    let multi = StateSet::default().with(State::MultiLine);
+    assert_eq!(
+      role_from_atspi(atspi_role::TEXT, StateSet::default()),
+      Role::TextField
+    );
+    assert_eq!(role_from_atspi(atspi_role::TEXT, multi), Role::TextArea);
+  }
+
+  #[test]
+  fn unknown_role() {
+    assert_eq!(role_from_atspi(9999, StateSet::default()), Role::Unknown);
+  }
+
+  #[test]
+  fn action_spellings() {
+    let none = StateSet::default();
+    for name in ["click", "press", "activate", "toggle", "Click"] {
+      assert_eq!(action_from_atspi(name, none), Some(Action::Press), "{name}");
+    }
+    assert_eq!(action_from_atspi("showmenu", none), Some(Action::ShowMenu));
+    assert_eq!(action_from_atspi("frobnicate", none), None);
+  }
+
+  #[test]
+  fn expand_toggle_follows_state() {
+    let collapsed = StateSet::default().with(State::Expandable);
+    let expanded = collapsed.with(State::Expanded);
+    assert_eq!(
+      action_from_atspi("expand or contract", collapsed),
+      Some(Action::Expand)
+    );
+    assert_eq!(
+      action_from_atspi("expand or contract", expanded),
+      Some(Action::Collapse)
+    );
+  }
+
+  #[test]
+  fn event_mapping() {
+    use atspi_event as e;
+    assert_eq!(
+      notification_from_atspi(e::PROPERTY_CHANGE, "accessible-name", 0),
+      Some(Notification::TitleChanged)
+    );
+    assert_eq!(
+      notification_from_atspi(e::TEXT_CHANGED, "insert/system", 0),
+      Some(Notification::ValueChanged)
+    );
+    assert_eq!(
+      notification_from_atspi(e::CHILDREN_CHANGED, "remove", 0),
+      Some(Notification::ChildrenChanged)
+    );
+    assert_eq!(
+      notification_from_atspi(e::STATE_CHANGED, "focused", 1),
+      Some(Notification::FocusChanged)
+    );
+  }
+
+  #[test]
+  fn cleared_states_are_ignored() {
+    use atspi_event as e;
+    assert_eq!(
+      notification_from_atspi(e::STATE_CHANGED, "focused", 0),
+      None
+    );
+    assert_eq!(
+      notification_from_atspi(e::STATE_CHANGED, "defunct", 0),
+      None
+    );
+    assert_eq!(
+      notification_from_atspi(e::PROPERTY_CHANGE, "accessible-parent", 0),
+      None
+    );
+  }
+}
@@ -0,0 +1,491 @@
+/*!
+Linux platform implementation over AT-SPI2.
+
+Implements the platform traits defined in `platform/mod.rs` by talking to the
+accessibility bus over D-Bus. All AT-SPI specifics (object references, role
+codes, state sets, event signals) stay within this module.
+
+AT-SPI has no permission prompt: access is granted when the accessibility bus
+is reachable. There is no display link, so polling uses the timer fallback.
+*/
+
+// === Internal modules ===
+mod bus;
+mod events;
+mod handles;
+mod mapping;
+mod window_list;
+
+#[cfg(test)]
+mod test_app;
+
+// === Re-exports for internal use ===
+pub(super) use handles::ElementHandle;
+#[cfg(not(feature = "mock"))]
+pub(super) use handles::ObserverHandle;
+
+// === Trait Implementations ===
+
+use crate::a11y::{Action, Value};
+use crate::platform::traits::{ElementAttributes, PlatformHandle};
+use crate::types::{AllioError, AllioResult};
+
+#[cfg(not(feature = "mock"))]
+use std::sync::Arc;
+
+#[cfg(not(feature = "mock"))]
+use crate::{
+  a11y::Notification,
+  platform::traits::{
+    AppNotificationHandle, DisplayLinkHandle, EventHandler, Platform, PlatformObserver, WatchHandle,
+  },
+  types::{ElementId, Point},
+};
+
+/// Linux (AT-SPI2) platform implementation.
+#[cfg(not(feature = "mock"))]
+pub(crate) struct Linux;
+
+#[cfg(not(feature = "mock"))]
+impl Platform for Linux {
+  type Handle = ElementHandle;
+  type Observer = ObserverHandle;
+
+  fn has_permissions() -> bool {
+    bus::connection().is_some()
+  }
+
+  fn fetch_windows(_exclude_pid: Option<u32>) -> Vec<crate::types::Window> {
+    // Note: exclude_pid filtering happens in polling.rs, not here
+    window_list::enumerate_windows()
+  }
+
+  fn fetch_screen_size() -> (f64, f64) {
+    window_list::screen_size()
+  }
+
+  fn fetch_mouse_position() -> Point {
+    events::mouse_position().unwrap_or_else(|| Point::new(0.0, 0.0))
+  }
+
+  fn fetch_window_handle(window: &crate::types::Window) -> Option<Self::Handle> {
+    window_list::window_handle(window.id)
+  }
+
+  fn create_observer<C: EventHandler<Handle = Self::Handle>>(
+    pid: u32,
+    _callbacks: Arc<C>,
+  ) -> AllioResult<Self::Observer> {
+    let bus = window_list::app_bus(pid)
+      .ok_or_else(|| AllioError::ObserverError(format!("No AT-SPI application for PID {pid}")))?;
+    Ok(ObserverHandle { bus })
+  }
+
+  fn start_display_link<F: Fn() + Send + Sync + 'static>(
+    _callback: F,
+  ) -> Option<DisplayLinkHandle> {
+    None
+  }
+
+  fn enable_accessibility_for_pid(_pid: u32) {
+    // Toolkit accessibility is a session-wide switch on Linux, not per process.
+    bus::enable_toolkit_accessibility();
+  }
+
+  fn app_element(pid: u32) -> Self::Handle {
+    ElementHandle::app(pid)
+  }
+
+  fn is_process_running(pid: u32) -> bool {
+    std::path::Path::new(&format!("/proc/{pid}")).exists()
+  }
+}
+
+impl PlatformHandle for ElementHandle {
+  fn pid(&self) -> u32 {
+    self.pid
+  }
+
+  fn fetch_children(&self) -> Vec<Self> {
+    self.get_children()
+  }
+
+  fn fetch_parent(&self) -> Option<Self> {
+    self.get_parent()
+  }
+
+  fn set_value(&self, value: &Value) -> AllioResult<()> {
+    self
+      .set_typed_value(value)
+      .map_err(|reason| AllioError::SetValueFailed { reason })
+  }
+
+  fn perform_action(&self, action: Action) -> AllioResult<()> {
+    self
+      .perform(action)
+      .map_err(|reason| AllioError::ActionFailed { action, reason })
+  }
+
+  fn fetch_attributes(&self) -> ElementAttributes {
+    self.fetch_attributes_internal()
+  }
+
+  fn fetch_element_at_position(&self, x: f64, y: f64) -> Option<Self> {
+    self.element_at_position(x, y)
+  }
+
+  fn window(&self) -> Option<Self> {
+    self.containing_window()
+  }
+}
+
+#[cfg(not(feature = "mock"))]
+impl PlatformObserver for ObserverHandle {
+  type Handle = ElementHandle;
+
+  fn subscribe_app_notifications<C: EventHandler<Handle = Self::Handle>>(
+    &self,
+    pid: u32,
+    callbacks: Arc<C>,
+  ) -> AllioResult<AppNotificationHandle> {
+    let callbacks: Arc<dyn events::Callbacks> = Arc::new(events::CallbacksWrapper(callbacks));
+    let inner = events::subscribe_app(Arc::clone(&self.bus), pid, callbacks)?;
+    Ok(AppNotificationHandle { _inner: inner })
+  }
+
+  fn create_watch<C: EventHandler<Handle = Self::Handle>>(
+    &self,
+    handle: &Self::Handle,
+    element_id: ElementId,
+    initial_notifications: &[Notification],
+    callbacks: Arc<C>,
+  ) -> AllioResult<WatchHandle> {
+    let callbacks: Arc<dyn events::Callbacks> = Arc::new(events::CallbacksWrapper(callbacks));
+    let inner = events::create_watch(handle, element_id, initial_notifications, callbacks)?;
+    Ok(WatchHandle { inner })
+  }
+}
+
+// === Type Exports ===
+
+pub(crate) type WatchHandleInner = events::WatchHandleInner;
+pub(crate) type AppNotificationHandleInner = events::AppNotificationHandleInner;
+
+/// Never constructed: AT-SPI has no display link.
+pub(crate) enum DisplayLinkHandleInner {}
+
+impl DisplayLinkHandleInner {
+  // Not const, to keep the signature the other backends have.
+  #[allow(clippy::missing_const_for_fn)]
+  pub(crate) fn stop(&self) {
+    match *self {}
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::events::{self, Callbacks};
+  use super::mapping::{atspi_role as role, State};
+  use super::test_app::{Node, TestApp, SCREEN};
+  use super::{window_list, ElementHandle};
+  use crate::a11y::{Action, Notification, Role, Value};
+  use crate::platform::{ElementEvent, PlatformHandle};
+  use crate::types::{AllioError, ElementId};
+  use parking_lot::Mutex;
+  use std::sync::mpsc::{channel, Receiver, Sender};
+  use std::sync::Arc;
+  use std::time::Duration;
+  use zbus::zvariant::Value as DBusValue;
+
+  const TIMEOUT: Duration = Duration::from_secs(2);
+
+  struct Recorder(Mutex<Sender<ElementEvent<ElementHandle>>>);
+
+  impl Callbacks for Recorder {
+    fn on_element_event(&self, event: ElementEvent<ElementHandle>) {
+      self.0.lock().send(event).ok();
+    }
+  }
+
+  fn recorder() -> (Arc<dyn Callbacks>, Receiver<ElementEvent<ElementHandle>>) {
+    let (tx, rx) = channel();
+    (Arc::new(Recorder(Mutex::new(tx))), rx)
+  }
+
+  fn next_event(rx: &Receiver<ElementEvent<ElementHandle>>) -> ElementEvent<ElementHandle> {
+    rx.recv_timeout(TIMEOUT)
+      .expect("timed out waiting for event")
+  }
+
+  /// An editor window with a form, a hidden window and a tooltip-sized one.
+  fn desktop() -> Node {
+    Node::new(role::APPLICATION, "gedit")
+      .child(
+        Node::new(role::FRAME, "Editor")
+          .state(State::Active)
+          .bounds(100, 100, 800, 600)
+          .child(
+            Node::new(role::PANEL, "")
+              .bounds(100, 100, 400, 300)
+              .child(
+                Node::new(role::ENTRY, "Name")
+                  .state(State::Focusable)
+                  .state(State::Focused)
+                  .bounds(110, 110, 200, 30)
+                  .text("hello world")
+                  .attribute("placeholder-text", "Your name")
+                  .attribute("id", "name-field"),
+              )
+              .child(
+                Node::new(role::CHECK_BOX, "Wrap")
+                  .bounds(110, 150, 100, 20)
+                  .actions(&["toggle"]),
+              )
+              .child(
+                Node::new(role::SLIDER, "Zoom")
+                  .bounds(110, 180, 200, 20)
+                  .value(1.0),
+              ),
+          )
+          .child(
+            Node::new(role::PUSH_BUTTON, "Save")
+              .bounds(600, 600, 80, 30)
+              .actions(&["click"]),
+          )
+          .child(Node::new(role::PUSH_BUTTON, "Disabled").without(State::Enabled)),
+      )
+      .child(Node::new(role::DIALOG, "Find").bounds(200, 200, 300, 200))
+      .child(
+        Node::new(role::FRAME, "Minimized")
+          .state(State::Iconified)
+          .bounds(0, 0, 500, 500),
+      )
+      .child(Node::new(role::WINDOW, "Tooltip").bounds(0, 0, 40, 20))
+  }
+
+  #[test]
+  #[ignore = "needs dbus-daemon; run with --ignored"]
+  fn enumerates_on_screen_windows_active_first() {
+    let _app = TestApp::acquire(desktop());
+    let windows = window_list::enumerate_windows();
+    let titles: Vec<&str> = windows.iter().map(|w| w.title.as_str()).collect();
+    assert_eq!(titles, ["Editor", "Find"]);
+
+    let editor = &windows[0];
+    assert!(editor.focused);
+    assert_eq!(editor.z_index, 0);
+    assert_eq!(editor.app_name, "gedit");
+    assert_eq!(u32::from(editor.process_id), std::process::id());
+    assert_eq!((editor.bounds.x, editor.bounds.w), (100.0, 800.0));
+    assert!(!windows[1].focused);
+
+    // IDs are stable across enumerations
+    let again = window_list::enumerate_windows();
+    assert_eq!(again[0].id, editor.id);
+    let handle = window_list::window_handle(editor.id).unwrap();
+    assert_eq!(handle.fetch_attributes().title.as_deref(), Some("Editor"));
+
+    let (w, h) = window_list::screen_size();
+    assert_eq!((w, h), (f64::from(SCREEN.0), f64::from(SCREEN.1)));
+  }
+
+  #[test]
+  #[ignore = "needs dbus-daemon; run with --ignored"]
+  fn traverses_tree_and_maps_attributes() {
+    let app = TestApp::acquire(desktop());
+    let window = app.handle("Editor");
+    let children = window.fetch_children();
+    assert_eq!(children.len(), 3);
+    assert_eq!(children[0].fetch_parent(), Some(window.clone()));
+    assert_eq!(
+      window.fetch_parent(),
+      Some(ElementHandle::app(std::process::id()))
+    );
+    assert_eq!(ElementHandle::app(std::process::id()).fetch_parent(), None);
+
+    let panel = children[0].fetch_attributes();
+    assert_eq!(panel.role, Role::GenericGroup);
+
+    let entry = app.handle("Name");
+    assert_eq!(entry.window(), Some(window));
+    let attrs = entry.fetch_attributes();
+    assert_eq!(attrs.role, Role::TextField);
+    assert_eq!(attrs.title.as_deref(), Some("Name"));
+    assert_eq!(attrs.value, Some(Value::String("hello world".into())));
+    assert_eq!(attrs.placeholder.as_deref(), Some("Your name"));
+    assert_eq!(attrs.identifier.as_deref(), Some("name-field"));
+    assert_eq!(attrs.focused, Some(true));
+    assert_eq!(attrs.platform_role, format!("role-{}", role::ENTRY));
+    assert!(!attrs.disabled);
+
+    let checkbox = app.handle("Wrap").fetch_attributes();
+    assert_eq!(checkbox.role, Role::Checkbox);
+    assert_eq!(checkbox.value, Some(Value::Boolean(false)));
+    assert_eq!(checkbox.actions, [Action::Press]);
+
+    let slider = app.handle("Zoom").fetch_attributes();
+    assert_eq!(slider.role, Role::Slider);
+    assert_eq!(slider.value, Some(Value::Number(1.0)));
+    assert_eq!(slider.actions, [Action::Increment, Action::Decrement]);
+
+    assert!(app.handle("Disabled").fetch_attributes().disabled);
+  }
+
+  #[test]
+  #[ignore = "needs dbus-daemon; run with --ignored"]
+  fn writes_values_and_performs_actions() {
+    let app = TestApp::acquire(desktop());
+    app
+      .handle("Name")
+      .set_value(&Value::String("Ada".into()))
+      .unwrap();
+    assert_eq!(app.node("Name").text.as_deref(), Some("Ada"));
+
+    let slider = app.handle("Zoom");
+    slider.set_value(&Value::Number(2.0)).unwrap();
+    assert_eq!(app.node("Zoom").value, Some(2.0));
+    slider.perform_action(Action::Increment).unwrap();
+    assert_eq!(app.node("Zoom").value, Some(2.5));
+
+    // Booleans toggle through the element's action, and only when needed
+    let checkbox = app.handle("Wrap");
+    checkbox.set_value(&Value::Boolean(true)).unwrap();
+    checkbox.set_value(&Value::Boolean(true)).unwrap();
+    assert!(app.node("Wrap").states.contains(State::Checked));
+
+    app.handle("Save").perform_action(Action::Press).unwrap();
+    app.handle("Save").perform_action(Action::Raise).unwrap();
+    assert_eq!(app.log(), ["Wrap:toggle", "Save:click", "Save:grab-focus"]);
+
+    let err = app
+      .handle("Save")
+      .perform_action(Action::Expand)
+      .unwrap_err();
+    assert!(matches!(
+      err,
+      AllioError::ActionFailed {
+        action: Action::Expand,
+        ..
+      }
+    ));
+    let err = app
+      .handle("Save")
+      .set_value(&Value::String("x".into()))
+      .unwrap_err();
+    assert!(matches!(err, AllioError::SetValueFailed { .. }));
+  }
+
+  #[test]
+  #[ignore = "needs dbus-daemon; run with --ignored"]
+  fn element_at_position_finds_deepest_element() {
+    let app = TestApp::acquire(desktop());
+    let root = ElementHandle::app(std::process::id());
+    assert_eq!(
+      root.fetch_element_at_position(150.0, 160.0),
+      Some(app.handle("Wrap"))
+    );
+    assert_eq!(
+      root.fetch_element_at_position(620.0, 610.0),
+      Some(app.handle("Save"))
+    );
+    // Inside a window but over no child
+    assert_eq!(
+      root.fetch_element_at_position(800.0, 200.0),
+      Some(app.handle("Editor"))
+    );
+    assert_eq!(root.fetch_element_at_position(1200.0, 790.0), None);
+  }
+
+  #[test]
+  #[ignore = "needs dbus-daemon; run with --ignored"]
+  fn watches_receive_element_notifications() {
+    let app = TestApp::acquire(desktop());
+    let (callbacks, rx) = recorder();
+    let entry_id = ElementId::new(1, 1);
+    let panel_id = ElementId::new(1, 2);
+    let _entry = events::create_watch(
+      &app.handle("Name"),
+      entry_id,
+      &[Notification::ValueChanged, Notification::Destroyed],
+      Arc::clone(&callbacks),
+    )
+    .unwrap();
+    let mut panel = events::create_watch(
+      &app.handle("Save").fetch_parent().unwrap().fetch_children()[0],
+      panel_id,
+      &[],
+      callbacks,
+    )
+    .unwrap();
+    assert!(app
+      .registered_events()
+      .iter()
+      .any(|e| e == "object:children-changed"));
+
+    // Not watched: dropped
+    app.emit(
+      "Name",
+      "PropertyChange",
+      "accessible-name",
+      0,
+      DBusValue::from(""),
+    );
+    app.emit("Name", "TextChanged", "insert", 0, DBusValue::from("x"));
+    assert!(matches!(
+      next_event(&rx),
+      ElementEvent::Changed(id, Notification::ValueChanged) if id == entry_id
+    ));
+
+    // Removal reaches the child's watch before the parent's
+    panel.add(&[Notification::ChildrenChanged]);
+    app.remove("Name");
+    assert!(matches!(next_event(&rx), ElementEvent::Destroyed(id) if id == entry_id));
+    assert!(matches!(next_event(&rx), ElementEvent::ChildrenChanged(id) if id == panel_id));
+
+    drop(panel);
+    app.remove("Wrap");
+    assert!(rx.recv_timeout(Duration::from_millis(200)).is_err());
+  }
+
+  #[test]
+  #[ignore = "needs dbus-daemon; run with --ignored"]
+  fn app_subscriptions_receive_focus_and_selection() {
+    let app = TestApp::acquire(desktop());
+    let pid = std::process::id();
+    let bus = window_list::app_bus(pid).unwrap();
+    let (callbacks, rx) = recorder();
+    let subscription = events::subscribe_app(bus, pid, callbacks).unwrap();
+
+    app.emit("Save", "StateChanged", "focused", 0, DBusValue::from(0i32));
+    app.emit("Save", "StateChanged", "focused", 1, DBusValue::from(0i32));
+    assert!(matches!(next_event(&rx), ElementEvent::FocusChanged(h) if h == app.handle("Save")));
+
+    app.emit("Name", "TextSelectionChanged", "", 0, DBusValue::from(""));
+    let ElementEvent::SelectionChanged {
+      handle,
+      text,
+      range,
+    } = next_event(&rx)
+    else {
+      panic!("expected SelectionChanged");
+    };
+    assert_eq!(handle, app.handle("Name"));
+    assert_eq!(text, "hello");
+    assert_eq!(range, Some((0, 5)));
+
+    drop(subscription);
+    app.emit("Save", "StateChanged", "focused", 1, DBusValue::from(0i32));
+    assert!(rx.recv_timeout(Duration::from_millis(200)).is_err());
+  }
+
+  #[test]
+  #[ignore = "needs dbus-daemon; run with --ignored"]
+  fn removed_elements_report_no_attributes() {
+    let app = TestApp::acquire(desktop());
+    let save = app.handle("Save");
+    app.remove("Save");
+    let attrs = save.fetch_attributes();
+    assert_eq!(attrs.role, Role::default());
+    assert!(save.fetch_children().is_empty());
+  }
+}
@@ -0,0 +1,747 @@
+/*!
+Stand-in accessibility stack for tests.
+
+Starts a private `dbus-daemon`, plays the AT-SPI registry on it and serves one
+scriptable application, so the backend runs against real D-Bus traffic without
+a desktop session. The backend's shared connection is pointed at this bus.
+
+Tests are serialized through `TestApp::acquire` (the bus and the app are
+process-global). They are `#[ignore]`d since they need `dbus-daemon`; run them
+with `cargo test -- --ignored` where it is installed.
+*/
+
+// D-Bus method signatures are fixed by AT-SPI, whether or not the stand-in needs them.
+#![allow(clippy::unused_self, clippy::used_underscore_binding)]
+
+use parking_lot::{Mutex, MutexGuard};
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader};
+use std::process::{Child, Command, Stdio};
+use std::sync::{Arc, LazyLock};
+
+use zbus::blocking::connection::Builder;
+use zbus::blocking::Connection;
+use zbus::zvariant::{OwnedObjectPath, Value};
+
+use super::bus::{self, iface, ObjectRef, NULL_PATH, REGISTRY_BUS, REGISTRY_PATH, ROOT_PATH};
+use super::handles::ElementHandle;
+use super::mapping::{atspi_role, State, StateSet};
+
+/// Screen size reported by the stand-in desktop.
+pub(super) const SCREEN: (i32, i32) = (1280, 800);
+
+type WireRef = (String, OwnedObjectPath);
+
+/// Node of the scripted accessibility tree.
+#[derive(Debug, Clone)]
+pub(super) struct Node {
+  pub(super) role: u32,
+  pub(super) name: String,
+  pub(super) states: StateSet,
+  pub(super) bounds: (i32, i32, i32, i32),
+  pub(super) text: Option<String>,
+  pub(super) value: Option<f64>,
+  pub(super) actions: Vec<&'static str>,
+  pub(super) attributes: HashMap<String, String>,
+  children: Vec<Node>,
+}
+
+impl Node {
+  pub(super) fn new(role: u32, name: &str) -> Self {
+    Self {
+      role,
+      name: name.into(),
+      states: StateSet::default()
+        .with(State::Enabled)
+        .with(State::Showing)
+        .with(State::Visible),
+      bounds: (0, 0, 0, 0),
+      text: None,
+      value: None,
+      actions: Vec::new(),
+      attributes: HashMap::new(),
+      children: Vec::new(),
+    }
+  }
+
+  pub(super) fn state(mut self, state: State) -> Self {
+    self.states = self.states.with(state);
+    self
+  }
+
+  pub(super) fn without(mut self, state: State) -> Self {
+    self.states = self.states.without(state);
+    self
+  }
+
+  pub(super) const fn bounds(mut self, x: i32, y: i32, w: i32, h: i32) -> Self {
+    self.bounds = (x, y, w, h);
+    self
+  }
+
+  pub(super) fn text(mut self, text: &str) -> Self {
+    self.text = Some(text.into());
+    self
+  }
+
+  pub(super) const fn value(mut self, value: f64) -> Self {
+    self.value = Some(value);
+    self
+  }
+
+  pub(super) fn actions(mut self, actions: &[&'static str]) -> Self {
+    self.actions = actions.to_vec();
+    self
+  }
+
+  pub(super) fn attribute(mut self, key: &str, value: &str) -> Self {
+    self.attributes.insert(key.into(), value.into());
+    self
+  }
+
+  pub(super) fn child(mut self, child: Self) -> Self {
+    self.children.push(child);
+    self
+  }
+
+  fn contains(&self, x: i32, y: i32) -> bool {
+    let (bx, by, w, h) = self.bounds;
+    x >= bx && y >= by && x < bx + w && y < by + h
+  }
+
+  fn interfaces(&self) -> Vec<String> {
+    let mut interfaces = vec![iface::ACCESSIBLE, iface::COMPONENT];
+    if !self.actions.is_empty() {
+      interfaces.push(iface::ACTION);
+    }
+    if self.text.is_some() {
+      interfaces.extend([iface::TEXT, iface::EDITABLE_TEXT]);
+    }
+    if self.value.is_some() {
+      interfaces.push(iface::VALUE);
+    }
+    interfaces.into_iter().map(String::from).collect()
+  }
+}
+
+/// Flattened tree. Node 0 is the application root.
+#[derive(Default)]
+struct Tree {
+  bus: String,
+  /// Path prefix, unique per loaded tree so stale handles never resolve.
+  generation: u32,
+  nodes: Vec<Node>,
+  parents: Vec<Option<usize>>,
+  children: Vec<Vec<usize>>,
+  removed: Vec<bool>,
+  /// Actions performed, as `name:action`.
+  log: Vec<String>,
+}
+
+impl Tree {
+  fn path(&self, index: usize) -> OwnedObjectPath {
+    let path = if index == 0 {
+      ROOT_PATH.to_string()
+    } else {
+      format!("/org/a11y/atspi/accessible/g{}/{index}", self.generation)
+    };
+    OwnedObjectPath::try_from(path).unwrap()
+  }
+
+  fn wire(&self, index: usize) -> WireRef {
+    (self.bus.clone(), self.path(index))
+  }
+
+  fn flatten(&mut self, mut node: Node, parent: Option<usize>) -> usize {
+    let index = self.nodes.len();
+    let children = std::mem::take(&mut node.children);
+    self.nodes.push(node);
+    self.parents.push(parent);
+    self.children.push(Vec::new());
+    self.removed.push(false);
+    for child in children {
+      let child = self.flatten(child, Some(index));
+      self.children[index].push(child);
+    }
+    index
+  }
+
+  fn find(&self, name: &str) -> usize {
+    (0..self.nodes.len())
+      .find(|i| !self.removed[*i] && self.nodes[*i].name == name)
+      .unwrap_or_else(|| panic!("no node named {name:?}"))
+  }
+}
+
+type SharedTree = Arc<Mutex<Tree>>;
+
+struct AccessibleIface {
+  tree: SharedTree,
+  index: usize,
+}
+
+#[zbus::interface(name = "org.a11y.atspi.Accessible")]
+impl AccessibleIface {
+  fn get_children(&self) -> Vec<WireRef> {
+    let tree = self.tree.lock();
+    tree.children[self.index]
+      .iter()
+      .map(|c| tree.wire(*c))
+      .collect()
+  }
+
+  fn get_role(&self) -> u32 {
+    self.tree.lock().nodes[self.index].role
+  }
+
+  fn get_role_name(&self) -> String {
+    format!("role-{}", self.tree.lock().nodes[self.index].role)
+  }
+
+  fn get_state(&self) -> Vec<u32> {
+    self.tree.lock().nodes[self.index]
+      .states
+      .to_words()
+      .to_vec()
+  }
+
+  fn get_interfaces(&self) -> Vec<String> {
+    self.tree.lock().nodes[self.index].interfaces()
+  }
+
+  fn get_attributes(&self) -> HashMap<String, String> {
+    self.tree.lock().nodes[self.index].attributes.clone()
+  }
+
+  #[zbus(property)]
+  fn name(&self) -> String {
+    self.tree.lock().nodes[self.index].name.clone()
+  }
+
+  #[zbus(property)]
+  fn description(&self) -> String {
+    String::new()
+  }
+
+  #[zbus(property)]
+  fn parent(&self) -> WireRef {
+    let tree = self.tree.lock();
+    match tree.parents[self.index] {
+      Some(parent) => tree.wire(parent),
+      None => (
+        REGISTRY_BUS.into(),
+        OwnedObjectPath::try_from(ROOT_PATH).unwrap(),
+      ),
+    }
+  }
+}
+
+struct ComponentIface {
+  tree: SharedTree,
+  index: usize,
+}
+
+#[zbus::interface(name = "org.a11y.atspi.Component")]
+impl ComponentIface {
+  fn get_extents(&self, _coord_type: u32) -> (i32, i32, i32, i32) {
+    self.tree.lock().nodes[self.index].bounds
+  }
+
+  fn get_accessible_at_point(&self, x: i32, y: i32, _coord_type: u32) -> WireRef {
+    let tree = self.tree.lock();
+    tree.children[self.index]
+      .iter()
+      .rev()
+      .find(|c| tree.nodes[**c].contains(x, y))
+      .map_or_else(
+        || (String::new(), OwnedObjectPath::try_from(NULL_PATH).unwrap()),
+        |c| tree.wire(*c),
+      )
+  }
+
+  fn grab_focus(&self) -> bool {
+    let mut tree = self.tree.lock();
+    let entry = format!("{}:grab-focus", tree.nodes[self.index].name);
+    tree.log.push(entry);
+    true
+  }
+}
+
+struct ActionIface {
+  tree: SharedTree,
+  index: usize,
+}
+
+#[zbus::interface(name = "org.a11y.atspi.Action")]
+impl ActionIface {
+  fn get_actions(&self) -> Vec<(String, String, String)> {
+    self.tree.lock().nodes[self.index]
+      .actions
+      .iter()
+      .map(|a| ((*a).to_string(), String::new(), String::new()))
+      .collect()
+  }
+
+  fn do_action(&self, index: i32) -> bool {
+    let mut tree = self.tree.lock();
+    let node = &mut tree.nodes[self.index];
+    let Some(action) = usize::try_from(index)
+      .ok()
+      .and_then(|i| node.actions.get(i))
+    else {
+      return false;
+    };
+    let entry = format!("{}:{action}", node.name);
+    if node.role == atspi_role::CHECK_BOX {
+      node.states = if node.states.contains(State::Checked) {
+        node.states.without(State::Checked)
+      } else {
+        node.states.with(State::Checked)
+      };
+    }
+    tree.log.push(entry);
+    true
+  }
+}
+
+struct TextIface {
+  tree: SharedTree,
+  index: usize,
+}
+
+#[zbus::interface(name = "org.a11y.atspi.Text")]
+impl TextIface {
+  fn get_text(&self, start: i32, end: i32) -> String {
+    let text = self.tree.lock().nodes[self.index]
+      .text
+      .clone()
+      .unwrap_or_default();
+    let start = usize::try_from(start).unwrap_or(0);
+    let end = usize::try_from(end).unwrap_or(text.len()).min(text.len());
+    text.get(start..end).unwrap_or_default().to_string()
+  }
+
+  fn get_n_selections(&self) -> i32 {
+    1
+  }
+
+  fn get_selection(&self, _index: i32) -> (i32, i32) {
+    (0, 5)
+  }
+}
+
+struct EditableTextIface {
+  tree: SharedTree,
+  index: usize,
+}
+
+#[zbus::interface(name = "org.a11y.atspi.EditableText")]
+impl EditableTextIface {
+  fn set_text_contents(&self, text: String) -> bool {
+    self.tree.lock().nodes[self.index].text = Some(text);
+    true
+  }
+}
+
+struct ValueIface {
+  tree: SharedTree,
+  index: usize,
+}
+
+#[zbus::interface(name = "org.a11y.atspi.Value")]
+impl ValueIface {
+  #[zbus(property)]
+  fn current_value(&self) -> f64 {
+    self.tree.lock().nodes[self.index].value.unwrap_or_default()
+  }
+
+  #[zbus(property)]
+  fn set_current_value(&mut self, value: f64) {
+    self.tree.lock().nodes[self.index].value = Some(value);
+  }
+
+  #[zbus(property)]
+  fn minimum_increment(&self) -> f64 {
+    0.5
+  }
+}
+
+/// The registry's desktop: one child, the stand-in application.
+struct DesktopIface {
+  app: WireRef,
+}
+
+#[zbus::interface(name = "org.a11y.atspi.Accessible")]
+impl DesktopIface {
+  fn get_children(&self) -> Vec<WireRef> {
+    vec![self.app.clone()]
+  }
+}
+
+struct DesktopComponentIface;
+
+#[zbus::interface(name = "org.a11y.atspi.Component")]
+impl DesktopComponentIface {
+  fn get_extents(&self, _coord_type: u32) -> (i32, i32, i32, i32) {
+    (0, 0, SCREEN.0, SCREEN.1)
+  }
+}
+
+struct RegistryIface {
+  events: Arc<Mutex<Vec<String>>>,
+}
+
+#[zbus::interface(name = "org.a11y.atspi.Registry")]
+impl RegistryIface {
+  fn register_event(&self, event: String, _properties: Vec<String>, _app: String) {
+    self.events.lock().push(event);
+  }
+}
+
+/// Shared stand-in stack. Lives for the whole test process.
+pub(super) struct TestApp {
+  conn: Connection,
+  tree: SharedTree,
+  registered_events: Arc<Mutex<Vec<String>>>,
+  _registry: Connection,
+  _daemon: Child,
+}
+
+static APP: LazyLock<Result<Mutex<TestApp>, String>> =
+  LazyLock::new(|| TestApp::start().map(Mutex::new));
+
+impl TestApp {
+  /// Exclusive access to the stand-in app, loaded with `root` as its tree.
+  /// Panics if the stack couldn't be started.
+  pub(super) fn acquire(root: Node) -> MutexGuard<'static, Self> {
+    let mut app = match APP.as_ref() {
+      Ok(app) => app.lock(),
+      Err(e) => panic!("AT-SPI stand-in failed to start: {e}"),
+    };
+    app.load(root);
+    app
+  }
+
+  fn start() -> Result<Self, String> {
+    let (daemon, address) = spawn_daemon()?;
+    let fail = |what: &'static str| move |e: zbus::Error| format!("{what}: {e}");
+
+    let client = bus::open(&address).map_err(fail("client"))?;
+    if !bus::use_connection(client) {
+      return Err("AT-SPI connection was already initialized".into());
+    }
+
+    // Objects are registered while building: zbus (tokio flavor) can only start
+    // the object server from inside its runtime.
+    let tree: SharedTree = Arc::default();
+    let conn = serve(Builder::address(address.as_str()), ROOT_PATH, &tree, 0)
+      .and_then(Builder::build)
+      .map_err(fail("app"))?;
+    let bus_name = conn
+      .unique_name()
+      .map(ToString::to_string)
+      .unwrap_or_default();
+    tree.lock().bus.clone_from(&bus_name);
+
+    let registered_events = Arc::new(Mutex::new(Vec::new()));
+    let app_root = (bus_name, OwnedObjectPath::try_from(ROOT_PATH).unwrap());
+    let registry = Builder::address(address.as_str())
+      .and_then(|b| b.serve_at(ROOT_PATH, DesktopIface { app: app_root }))
+      .and_then(|b| b.serve_at(ROOT_PATH, DesktopComponentIface))
+      .and_then(|b| {
+        b.serve_at(
+          REGISTRY_PATH,
+          RegistryIface {
+            events: Arc::clone(&registered_events),
+          },
+        )
+      })
+      .and_then(|b| b.name(REGISTRY_BUS))
+      .and_then(Builder::build)
+      .map_err(fail("registry"))?;
+
+    Ok(Self {
+      conn,
+      tree,
+      registered_events,
+      _registry: registry,
+      _daemon: daemon,
+    })
+  }
+
+  /// Replace the application's tree, unregistering the previous objects.
+  /// Each node only serves the interfaces it advertises. The root object stays
+  /// registered: it always serves node 0.
+  fn load(&mut self, root: Node) {
+    let old: Vec<OwnedObjectPath> = {
+      let tree = self.tree.lock();
+      (1..tree.nodes.len())
+        .filter(|i| !tree.removed[*i])
+        .map(|i| tree.path(i))
+        .collect()
+    };
+    for path in old {
+      unregister(&self.conn, &path);
+    }
+
+    let count = {
+      let mut tree = self.tree.lock();
+      let generation = tree.generation + 1;
+      *tree = Tree {
+        bus: std::mem::take(&mut tree.bus),
+        generation,
+        ..Tree::default()
+      };
+      tree.flatten(root, None);
+      tree.nodes.len()
+    };
+
+    let server = self.conn.object_server();
+    for index in 1..count {
+      let (path, interfaces) = {
+        let tree = self.tree.lock();
+        (tree.path(index), tree.nodes[index].interfaces())
+      };
+      let tree = || Arc::clone(&self.tree);
+      for name in interfaces {
+        match name.as_str() {
+          iface::ACCESSIBLE => server.at(
+            &path,
+            AccessibleIface {
+              tree: tree(),
+              index,
+            },
+          ),
+          iface::COMPONENT => server.at(
+            &path,
+            ComponentIface {
+              tree: tree(),
+              index,
+            },
+          ),
+          iface::ACTION => server.at(
+            &path,
+            ActionIface {
+              tree: tree(),
+              index,
+            },
+          ),
+          iface::TEXT => server.at(
+            &path,
+            TextIface {
+              tree: tree(),
+              index,
+            },
+          ),
+          iface::EDITABLE_TEXT => server.at(
+            &path,
+            EditableTextIface {
+              tree: tree(),
+              index,
+            },
+          ),
+          iface::VALUE => server.at(
+            &path,
+            ValueIface {
+              tree: tree(),
+              index,
+            },
+          ),
+          other => panic!("unexpected interface {other}"),
+        }
+        .unwrap();
+      }
+    }
+  }
+
+  /// Handle for the live node named `name`.
+  pub(super) fn handle(&self, name: &str) -> ElementHandle {
+    let tree = self.tree.lock();
+    let (bus, path) = tree.wire(tree.find(name));
+    ElementHandle::new(
+      ObjectRef::new(&bus, path.as_str()).unwrap(),
+      std::process::id(),
+    )
+  }
+
+  /// Read a node.
+  pub(super) fn node(&self, name: &str) -> Node {
+    let tree = self.tree.lock();
+    tree.nodes[tree.find(name)].clone()
+  }
+
+  /// Actions performed so far, as `name:action`.
+  pub(super) fn log(&self) -> Vec<String> {
+    self.tree.lock().log.clone()
+  }
+
+  /// Events registered with the registry so far.
+  pub(super) fn registered_events(&self) -> Vec<String> {
+    self.registered_events.lock().clone()
+  }
+
+  /// Send an `org.a11y.atspi.Event.Object` signal from a node.
+  pub(super) fn emit(
+    &self,
+    name: &str,
+    member: &str,
+    kind: &str,
+    detail1: i32,
+    any_data: Value<'_>,
+  ) {
+    let path = {
+      let tree = self.tree.lock();
+      tree.path(tree.find(name))
+    };
+    let properties: HashMap<String, Value<'_>> = HashMap::new();
+    self
+      .conn
+      .emit_signal(
+        None::<&str>,
+        &path,
+        iface::EVENT_OBJECT,
+        member,
+        &(kind, detail1, 0i32, any_data, properties),
+      )
+      .unwrap();
+  }
+
+  /// Detach a node from its parent and take it off the bus, then announce it
+  /// like a toolkit does (`children-changed::remove` on the parent).
+  pub(super) fn remove(&self, name: &str) {
+    let (parent, child, paths) = {
+      let mut tree = self.tree.lock();
+      let index = tree.find(name);
+      let parent = tree.parents[index].unwrap();
+      tree.children[parent].retain(|c| *c != index);
+      let mut stack = vec![index];
+      let mut paths = Vec::new();
+      while let Some(i) = stack.pop() {
+        tree.removed[i] = true;
+        paths.push(tree.path(i));
+        stack.extend(tree.children[i].iter().copied());
+      }
+      (tree.nodes[parent].name.clone(), tree.wire(index), paths)
+    };
+    for path in &paths {
+      unregister(&self.conn, path);
+    }
+    self.emit(&parent, "ChildrenChanged", "remove", 0, Value::from(child));
+  }
+}
+
+/// Add the node interfaces for `index` to a connection being built.
+fn serve<'a>(
+  builder: zbus::Result<Builder<'a>>,
+  path: &'a str,
+  tree: &SharedTree,
+  index: usize,
+) -> zbus::Result<Builder<'a>> {
+  let tree = || Arc::clone(tree);
+  builder?
+    .serve_at(
+      path,
+      AccessibleIface {
+        tree: tree(),
+        index,
+      },
+    )?
+    .serve_at(
+      path,
+      ComponentIface {
+        tree: tree(),
+        index,
+      },
+    )?
+    .serve_at(
+      path,
+      ActionIface {
+        tree: tree(),
+        index,
+      },
+    )?
+    .serve_at(
+      path,
+      TextIface {
+        tree: tree(),
+        index,
+      },
+    )?
+    .serve_at(
+      path,
+      EditableTextIface {
+        tree: tree(),
+        index,
+      },
+    )?
+    .serve_at(
+      path,
+      ValueIface {
+        tree: tree(),
+        index,
+      },
+    )
+}
+
+fn unregister(conn: &Connection, path: &OwnedObjectPath) {
+  let server = conn.object_server();
+  // Interfaces a node never had are reported as errors: nothing to undo.
+  server.remove::<AccessibleIface, _>(path).ok();
+  server.remove::<ComponentIface, _>(path).ok();
+  server.remove::<ActionIface, _>(path).ok();
+  server.remove::<TextIface, _>(path).ok();
+  server.remove::<EditableTextIface, _>(path).ok();
+  server.remove::<ValueIface, _>(path).ok();
+}
+
+/// Start a private bus. Returns the daemon and its address.
+///
+/// The daemon runs under a shell that kills it (and removes its directory) once
+/// our end of its stdin closes, so it does not outlive the test process.
+fn spawn_daemon() -> Result<(Child, String), String> {
+  let dir = std::env::temp_dir().join(format!("allio-atspi-{}", std::process::id()));
+  std::fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+  let config = dir.join("bus.conf");
+  std::fs::write(
+    &config,
+    format!(
+      r#"<!DOCTYPE busconfig PUBLIC "-//freedesktop//DTD D-Bus Bus Configuration 1.0//EN"
+ "http://www.freedesktop.org/standards/dbus/1.0/busconfig.dtd">
+<busconfig>
+  <type>session</type>
+  <listen>unix:dir={}</listen>
+  <auth>EXTERNAL</auth>
+  <policy context="default">
+    <allow send_destination="*" eavesdrop="true"/>
+    <allow eavesdrop="true"/>
+    <allow own="*"/>
+  </policy>
+</busconfig>"#,
+      dir.display()
+    ),
+  )
+  .map_err(|e| e.to_string())?;
+
+  let mut child = Command::new("sh")
+    .arg("-c")
+    .arg(r#"dbus-daemon --nofork --print-address=1 --config-file="$1/bus.conf" <&- & pid=$!; cat >/dev/null; kill $pid; rm -rf "$1""#)
+    .arg("sh")
+    .arg(&dir)
+    .stdin(Stdio::piped())
+    .stdout(Stdio::piped())
+    .stderr(Stdio::null())
+    .spawn()
+    .map_err(|e| format!("dbus-daemon: {e}"))?;
+
+  let stdout = child.stdout.take().ok_or("dbus-daemon: no stdout")?;
+  let mut address = String::new();
+  BufReader::new(stdout)
+    .read_line(&mut address)
+    .map_err(|e| e.to_string())?;
+  let address = address.trim().to_string();
+  if address.is_empty() {
+    child.kill().ok();
+    return Err("dbus-daemon is not available".into());
+  }
+  Ok((child, address))
+}
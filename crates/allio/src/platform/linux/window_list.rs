@@ -0,0 +1,169 @@
+/*! Window enumeration for AT-SPI.
+
+Windows are the showing top-level children of each application registered on
+the accessibility bus. AT-SPI has no window IDs or z-order, so IDs are assigned
+here (never reused) and the active window is reported frontmost.
+
+On Wayland, toolkits report window-relative coordinates, so every window
+appears at the origin.
+*/
+
+use parking_lot::Mutex;
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, LazyLock};
+
+use super::bus::{self, iface, ObjectRef};
+use super::handles::ElementHandle;
+use super::mapping::{State, StateSet};
+use crate::types::{Bounds, ProcessId, Window, WindowId};
+
+/// Windows smaller than this are popups/tooltips, not windows (same cutoff as macOS).
+const MIN_WINDOW_SIZE: f64 = 50.0;
+
+/// Fallback when neither the desktop nor any window reports a size.
+const DEFAULT_SCREEN_SIZE: (f64, f64) = (1920.0, 1080.0);
+
+#[derive(Default)]
+struct WindowTable {
+  next_id: u32,
+  ids: HashMap<ObjectRef, WindowId>,
+  handles: HashMap<WindowId, ElementHandle>,
+  /// Union of the last enumerated window bounds (screen size fallback).
+  extent: Option<(f64, f64)>,
+}
+
+impl WindowTable {
+  fn id_for(&mut self, object: &ObjectRef) -> WindowId {
+    if let Some(id) = self.ids.get(object) {
+      return *id;
+    }
+    self.next_id += 1;
+    let id = WindowId::from(self.next_id);
+    self.ids.insert(object.clone(), id);
+    id
+  }
+}
+
+static WINDOWS: LazyLock<Mutex<WindowTable>> = LazyLock::new(Mutex::default);
+
+/// Application bus names by PID.
+static APPS: LazyLock<Mutex<HashMap<u32, Arc<str>>>> = LazyLock::new(Mutex::default);
+
+/// Registered applications: (root object, pid). Refreshes the PID → bus map.
+fn applications() -> Vec<ElementHandle> {
+  let children: Vec<(String, zbus::zvariant::OwnedObjectPath)> = ObjectRef::desktop()
+    .call(iface::ACCESSIBLE, "GetChildren", &())
+    .unwrap_or_default();
+
+  let apps: Vec<ElementHandle> = children
+    .into_iter()
+    .filter_map(ObjectRef::from_wire)
+    .filter_map(|object| {
+      let pid = bus::pid_of(&object.bus)?;
+      Some(ElementHandle::new(object, pid))
+    })
+    .collect();
+
+  let live: HashSet<&str> = apps.iter().map(|a| &*a.object.bus).collect();
+  bus::retain_pids(|b| live.contains(b));
+  *APPS.lock() = apps
+    .iter()
+    .map(|a| (a.pid, Arc::clone(&a.object.bus)))
+    .collect();
+  apps
+}
+
+/// Bus name of the application with this PID.
+pub(super) fn app_bus(pid: u32) -> Option<Arc<str>> {
+  if let Some(bus) = APPS.lock().get(&pid) {
+    return Some(Arc::clone(bus));
+  }
+  applications()
+    .into_iter()
+    .find(|a| a.pid == pid)
+    .map(|a| a.object.bus)
+}
+
+/// Enumerate showing top-level windows, active window first.
+pub(super) fn enumerate_windows() -> Vec<Window> {
+  let mut found: Vec<(Window, ElementHandle, bool)> = Vec::new();
+
+  for app in applications() {
+    let app_name = app.name().unwrap_or_default();
+    for window in app.get_children() {
+      let Some(states) = window.states() else {
+        continue;
+      };
+      if !is_on_screen(states) {
+        continue;
+      }
+      let Some(bounds) = window.extents() else {
+        continue;
+      };
+      if bounds.w < MIN_WINDOW_SIZE || bounds.h < MIN_WINDOW_SIZE {
+        continue;
+      }
+      let info = Window {
+        id: WindowId::from(0),
+        title: window.name().unwrap_or_default(),
+        app_name: app_name.clone(),
+        bounds,
+        focused: false,
+        process_id: ProcessId::from(app.pid),
+        z_index: 0,
+      };
+      found.push((info, window, states.contains(State::Active)));
+    }
+  }
+
+  // No z-order in AT-SPI: active window in front, the rest in registry order.
+  found.sort_by_key(|(_, _, active)| !active);
+
+  let mut table = WINDOWS.lock();
+  table.handles.clear();
+  let mut extent: Option<(f64, f64)> = None;
+  let mut seen = HashSet::new();
+  let mut windows = Vec::with_capacity(found.len());
+
+  for (z_index, (mut info, handle, active)) in (0u32..).zip(found) {
+    info.id = table.id_for(&handle.object);
+    info.focused = active && z_index == 0;
+    info.z_index = z_index;
+    extent = Some(union_extent(extent, &info.bounds));
+    seen.insert(handle.object.clone());
+    table.handles.insert(info.id, handle);
+    windows.push(info);
+  }
+
+  table.ids.retain(|object, _| seen.contains(object));
+  table.extent = extent;
+  windows
+}
+
+const fn is_on_screen(states: StateSet) -> bool {
+  states.contains(State::Showing)
+    && states.contains(State::Visible)
+    && !states.contains(State::Iconified)
+}
+
+fn union_extent(extent: Option<(f64, f64)>, bounds: &Bounds) -> (f64, f64) {
+  let (w, h) = extent.unwrap_or((0.0, 0.0));
+  (w.max(bounds.x + bounds.w), h.max(bounds.y + bounds.h))
+}
+
+/// Accessibility handle for a window from the last enumeration.
+pub(super) fn window_handle(id: WindowId) -> Option<ElementHandle> {
+  WINDOWS.lock().handles.get(&id).cloned()
+}
+
+/// Screen size: the desktop's extents when the registry reports them,
+/// otherwise the area covered by the last enumerated windows.
+pub(super) fn screen_size() -> (f64, f64) {
+  let desktop_extents = ElementHandle::new(ObjectRef::desktop(), 0)
+    .extents()
+    .filter(|b| b.w > 0.0 && b.h > 0.0);
+  if let Some(bounds) = desktop_extents {
+    return (bounds.w, bounds.h);
+  }
+  WINDOWS.lock().extent.unwrap_or(DEFAULT_SCREEN_SIZE)
+}
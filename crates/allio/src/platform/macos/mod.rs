@@ -82,6 +82,10 @@ impl Platform for MacOS {
   fn app_element(pid: u32) -> Self::Handle {
     ElementHandle::new(util::app_element(pid))
   }
+
+  fn is_process_running(pid: u32) -> bool {
+    window_list::is_running(pid)
+  }
 }
 
 impl PlatformHandle for ElementHandle {
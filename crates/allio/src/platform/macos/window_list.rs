@@ -121,6 +121,11 @@ fn enumerate_windows_inner() -> Vec<Window> {
   windows
 }
 
+/// Whether the app with this PID is running (and not terminated).
+pub(crate) fn is_running(process_id: u32) -> bool {
+  get_running_application(process_id).is_some_and(|app| !app.isTerminated())
+}
+
 fn get_bundle_identifier(app: &NSRunningApplication) -> Option<String> {
   app.bundleIdentifier().map(|s| s.to_string())
 }
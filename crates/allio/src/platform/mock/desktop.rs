@@ -0,0 +1,1069 @@
+/*!
+Scriptable in-memory desktop backing the mock platform.
+
+State is process-global because `Platform` methods are static. Tests take
+exclusive access with [`MockDesktop::acquire`], which also resets the desktop.
+
+Mutations notify subscribers the way a real app would: changing a value fires
+`ValueChanged` to watchers of that node, removing a node fires `Destroyed` for
+every watched node in the subtree, focusing fires the app-level `FocusChanged`.
+Callbacks always run after the desktop lock is released, so they may call back
+into the platform.
+*/
+
+use parking_lot::{Mutex, MutexGuard};
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, LazyLock};
+use std::time::{Duration, Instant};
+
+use super::replay::{Key, Replay};
+use super::MockHandle;
+use crate::a11y::{Action, Notification, Role, Value};
+use crate::platform::capture::{Callback, Capture, Entry};
+use crate::platform::{ElementAttributes, ElementEvent, EventHandler};
+use crate::types::{
+  AllioError, AllioResult, Bounds, ElementId, Point, ProcessId, Window, WindowId,
+};
+use crate::Allio;
+
+const DEFAULT_SCREEN_SIZE: (f64, f64) = (1920.0, 1080.0);
+
+/// Next node ID. Never reset, so stale handles from a previous desktop can't alias.
+static NEXT_NODE_ID: AtomicU64 = AtomicU64::new(1);
+
+/// Next watch/subscription ID.
+static NEXT_SUBSCRIPTION_ID: AtomicU64 = AtomicU64::new(1);
+
+static DESKTOP: LazyLock<Mutex<Desktop>> = LazyLock::new(|| Mutex::new(Desktop::default()));
+
+/// Serializes tests that script the desktop.
+static EXCLUSIVE: Mutex<()> = Mutex::new(());
+
+/// Run a closure with the desktop locked. Never invoke callbacks inside it.
+pub(crate) fn with_desktop<R>(f: impl FnOnce(&mut Desktop) -> R) -> R {
+  f(&mut DESKTOP.lock())
+}
+
+/// Run a mutation, then deliver the notifications it queued (lock released).
+fn mutate<R>(f: impl FnOnce(&mut Desktop, &mut Vec<Delivery>) -> R) -> R {
+  let mut pending = Vec::new();
+  let result = with_desktop(|d| f(d, &mut pending));
+  for (callbacks, event) in pending {
+    callbacks.on_element_event(event);
+  }
+  result
+}
+
+/// Answer a call from the loaded capture, if any, delivering the callbacks
+/// recorded before it first. None if nothing is loaded or the call wasn't recorded.
+pub(crate) fn replayed<R>(key: Key, answer: impl FnOnce(&Entry) -> Option<R>) -> Option<R> {
+  mutate(|d, out| {
+    let replay = d.replay.as_mut()?;
+    let due = replay.due_callbacks();
+    let result = replay.answer(key).and_then(answer);
+    for callback in due {
+      d.deliver(callback, out);
+    }
+    result
+  })
+}
+
+/// Type-erased callbacks, so subscriptions can live in the global desktop.
+pub(crate) trait Callbacks: Send + Sync {
+  fn on_element_event(&self, event: ElementEvent<MockHandle>);
+}
+
+/// Wrapper that erases the generic type parameter.
+pub(crate) struct CallbacksWrapper<C: EventHandler<Handle = MockHandle>>(pub(crate) Arc<C>);
+
+impl<C: EventHandler<Handle = MockHandle>> Callbacks for CallbacksWrapper<C> {
+  fn on_element_event(&self, event: ElementEvent<MockHandle>) {
+    self.0.on_element_event(event);
+  }
+}
+
+type Delivery = (Arc<dyn Callbacks>, ElementEvent<MockHandle>);
+
+/// Identifier of a node (app, window or element) in the mock desktop.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct MockNodeId(u64);
+
+impl MockNodeId {
+  /// A node that never exists.
+  pub(crate) const DANGLING: Self = Self(0);
+
+  fn next() -> Self {
+    Self(NEXT_NODE_ID.fetch_add(1, Ordering::Relaxed))
+  }
+
+  /// The node standing in for a handle of a replayed capture.
+  pub(crate) const fn captured(id: u64) -> Self {
+    Self(id)
+  }
+
+  pub(crate) const fn raw(self) -> u64 {
+    self.0
+  }
+}
+
+/// A window added to the mock desktop.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MockWindow {
+  /// Window ID as reported by the window list.
+  pub id: WindowId,
+  /// The window's accessibility element (role `Window`).
+  pub node: MockNodeId,
+}
+
+/// Attributes of a mock element, as the platform would report them.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MockElement {
+  /// Semantic role.
+  pub role: Role,
+  /// Platform role string. Defaults to the role name.
+  pub platform_role: String,
+  /// Title/label.
+  pub label: Option<String>,
+  /// Current value.
+  pub value: Option<Value>,
+  /// Description/help text.
+  pub description: Option<String>,
+  /// Placeholder text.
+  pub placeholder: Option<String>,
+  /// URL for links and documents.
+  pub url: Option<String>,
+  /// Screen bounds. Used for hit testing.
+  pub bounds: Option<Bounds>,
+  /// Focus state.
+  pub focused: Option<bool>,
+  /// Whether the element is disabled.
+  pub disabled: bool,
+  /// Selection state for items in lists/tables.
+  pub selected: Option<bool>,
+  /// Expansion state for tree nodes, disclosure triangles.
+  pub expanded: Option<bool>,
+  /// Row index for cells/rows in tables.
+  pub row_index: Option<usize>,
+  /// Column index for cells in tables.
+  pub column_index: Option<usize>,
+  /// Total row count (for table containers).
+  pub row_count: Option<usize>,
+  /// Total column count (for table containers).
+  pub column_count: Option<usize>,
+  /// Supported actions. Actions not listed here fail.
+  pub actions: Vec<Action>,
+  /// Platform accessibility identifier.
+  pub identifier: Option<String>,
+  /// Writes and actions to accept but ignore, like a busy app dropping them.
+  /// Each ignored one counts down.
+  pub ignored_writes: u32,
+}
+
+impl MockElement {
+  /// Create an element with a role and default attributes.
+  pub fn new(role: Role) -> Self {
+    Self {
+      role,
+      platform_role: format!("{role:?}"),
+      ..Self::default()
+    }
+  }
+
+  /// Set the label.
+  #[must_use]
+  pub fn label(mut self, label: impl Into<String>) -> Self {
+    self.label = Some(label.into());
+    self
+  }
+
+  /// Set the value.
+  #[must_use]
+  pub fn value(mut self, value: Value) -> Self {
+    self.value = Some(value);
+    self
+  }
+
+  /// Set the bounds.
+  #[must_use]
+  pub const fn bounds(mut self, bounds: Bounds) -> Self {
+    self.bounds = Some(bounds);
+    self
+  }
+
+  /// Set the identifier.
+  #[must_use]
+  pub fn identifier(mut self, identifier: impl Into<String>) -> Self {
+    self.identifier = Some(identifier.into());
+    self
+  }
+
+  /// Set the supported actions.
+  #[must_use]
+  pub fn actions(mut self, actions: &[Action]) -> Self {
+    self.actions = actions.to_vec();
+    self
+  }
+
+  /// Accept but ignore the next `count` writes and actions.
+  #[must_use]
+  pub const fn ignore_writes(mut self, count: u32) -> Self {
+    self.ignored_writes = count;
+    self
+  }
+
+  fn to_attributes(&self) -> ElementAttributes {
+    ElementAttributes {
+      role: self.role,
+      platform_role: self.platform_role.clone(),
+      title: self.label.clone(),
+      value: self.value.clone(),
+      description: self.description.clone(),
+      placeholder: self.placeholder.clone(),
+      url: self.url.clone(),
+      bounds: self.bounds,
+      focused: self.focused,
+      disabled: self.disabled,
+      selected: self.selected,
+      expanded: self.expanded,
+      row_index: self.row_index,
+      column_index: self.column_index,
+      row_count: self.row_count,
+      column_count: self.column_count,
+      actions: self.actions.clone(),
+      identifier: self.identifier.clone(),
+    }
+  }
+}
+
+struct Node {
+  pid: u32,
+  parent: Option<MockNodeId>,
+  children: Vec<MockNodeId>,
+  element: MockElement,
+  /// Text selection reported with `SelectionChanged`.
+  selection: Option<(String, Option<(u32, u32)>)>,
+}
+
+struct WindowEntry {
+  info: Window,
+  node: MockNodeId,
+}
+
+struct Watch {
+  node: MockNodeId,
+  element_id: ElementId,
+  notifications: HashSet<Notification>,
+  callbacks: Arc<dyn Callbacks>,
+}
+
+struct AppSubscription {
+  pid: u32,
+  callbacks: Arc<dyn Callbacks>,
+}
+
+/// The in-memory desktop.
+pub(crate) struct Desktop {
+  pub(crate) permissions: bool,
+  pub(crate) screen_size: (f64, f64),
+  pub(crate) mouse_position: Point,
+  nodes: HashMap<MockNodeId, Node>,
+  apps: HashMap<u32, MockNodeId>,
+  /// Front to back.
+  windows: Vec<WindowEntry>,
+  focused_window: Option<WindowId>,
+  next_window_id: u32,
+  watches: HashMap<u64, Watch>,
+  app_subscriptions: HashMap<u64, AppSubscription>,
+  performed_actions: Vec<(MockNodeId, Action)>,
+  /// How long changing a watch's notifications takes, outside the lock.
+  pub(crate) watch_delay: Duration,
+  /// Capture being replayed, answering calls in place of the desktop.
+  replay: Option<Replay>,
+}
+
+impl Default for Desktop {
+  fn default() -> Self {
+    Self {
+      permissions: true,
+      screen_size: DEFAULT_SCREEN_SIZE,
+      mouse_position: Point::new(0.0, 0.0),
+      nodes: HashMap::new(),
+      apps: HashMap::new(),
+      windows: Vec::new(),
+      focused_window: None,
+      next_window_id: 1,
+      watches: HashMap::new(),
+      app_subscriptions: HashMap::new(),
+      performed_actions: Vec::new(),
+      watch_delay: Duration::ZERO,
+      replay: None,
+    }
+  }
+}
+
+// === Platform-facing queries ===
+
+impl Desktop {
+  pub(crate) fn window_list(&self) -> Vec<Window> {
+    self
+      .windows
+      .iter()
+      .zip(0u32..)
+      .map(|(entry, z_index)| Window {
+        z_index,
+        focused: self.focused_window == Some(entry.info.id),
+        ..entry.info.clone()
+      })
+      .collect()
+  }
+
+  pub(crate) fn window_node(&self, id: WindowId) -> Option<MockNodeId> {
+    self
+      .windows
+      .iter()
+      .find(|w| w.info.id == id)
+      .map(|w| w.node)
+  }
+
+  pub(crate) fn app_node(&self, pid: u32) -> Option<MockNodeId> {
+    self.apps.get(&pid).copied()
+  }
+
+  pub(crate) fn handle(&self, node: MockNodeId) -> Option<MockHandle> {
+    self
+      .nodes
+      .get(&node)
+      .map(|n| MockHandle { node, pid: n.pid })
+  }
+
+  pub(crate) fn parent(&self, node: MockNodeId) -> Option<MockNodeId> {
+    self.nodes.get(&node).and_then(|n| n.parent)
+  }
+
+  pub(crate) fn children_handles(&self, node: MockNodeId) -> Vec<MockHandle> {
+    self
+      .nodes
+      .get(&node)
+      .map(|n| n.children.iter().filter_map(|c| self.handle(*c)).collect())
+      .unwrap_or_default()
+  }
+
+  /// Attributes of a node. Destroyed nodes report defaults (no role), like dead `AXUIElement`s.
+  pub(crate) fn attributes(&self, node: MockNodeId) -> ElementAttributes {
+    self
+      .nodes
+      .get(&node)
+      .map(|n| n.element.to_attributes())
+      .unwrap_or_default()
+  }
+
+  /// Nearest ancestor-or-self with role `Window`.
+  pub(crate) fn containing_window(&self, node: MockNodeId) -> Option<MockNodeId> {
+    let mut current = Some(node);
+    while let Some(id) = current {
+      let n = self.nodes.get(&id)?;
+      if n.element.role == Role::Window {
+        return Some(id);
+      }
+      current = n.parent;
+    }
+    None
+  }
+
+  /// Deepest node of a process containing the point, searching front-most windows first.
+  pub(crate) fn hit_test(&self, pid: u32, point: Point) -> Option<MockNodeId> {
+    let window = self
+      .windows
+      .iter()
+      .find(|w| w.info.process_id.0 == pid && w.info.bounds.contains(point))?;
+
+    let mut hit = window.node;
+    // Later children are drawn on top
+    while let Some(child) = self.nodes.get(&hit).and_then(|node| {
+      node.children.iter().rev().find(|c| {
+        self
+          .nodes
+          .get(c)
+          .and_then(|n| n.element.bounds)
+          .is_some_and(|b| b.contains(point))
+      })
+    }) {
+      hit = *child;
+    }
+    Some(hit)
+  }
+}
+
+// === Subscriptions ===
+
+impl Desktop {
+  pub(crate) fn subscribe_app(&mut self, pid: u32, callbacks: Arc<dyn Callbacks>) -> u64 {
+    let id = NEXT_SUBSCRIPTION_ID.fetch_add(1, Ordering::Relaxed);
+    self
+      .app_subscriptions
+      .insert(id, AppSubscription { pid, callbacks });
+    id
+  }
+
+  /// Returns the removed subscription so it is dropped outside the lock.
+  #[must_use]
+  pub(crate) fn unsubscribe_app(&mut self, id: u64) -> Option<Arc<dyn Callbacks>> {
+    self.app_subscriptions.remove(&id).map(|s| s.callbacks)
+  }
+
+  /// Register a watch. Returns None if the node doesn't exist.
+  pub(crate) fn add_watch(
+    &mut self,
+    node: MockNodeId,
+    element_id: ElementId,
+    notifications: HashSet<Notification>,
+    callbacks: Arc<dyn Callbacks>,
+  ) -> Option<u64> {
+    if !self.nodes.contains_key(&node) {
+      return None;
+    }
+    Some(self.insert_watch(node, element_id, notifications, callbacks))
+  }
+
+  /// Register a watch without checking the node, for replayed handles.
+  pub(crate) fn insert_watch(
+    &mut self,
+    node: MockNodeId,
+    element_id: ElementId,
+    notifications: HashSet<Notification>,
+    callbacks: Arc<dyn Callbacks>,
+  ) -> u64 {
+    let id = NEXT_SUBSCRIPTION_ID.fetch_add(1, Ordering::Relaxed);
+    self.watches.insert(
+      id,
+      Watch {
+        node,
+        element_id,
+        notifications,
+        callbacks,
+      },
+    );
+    id
+  }
+
+  pub(crate) fn extend_watch(&mut self, id: u64, notifs: &[Notification]) -> usize {
+    let Some(watch) = self.watches.get_mut(&id) else {
+      return 0;
+    };
+    notifs
+      .iter()
+      .filter(|n| watch.notifications.insert(**n))
+      .count()
+  }
+
+  pub(crate) fn shrink_watch(&mut self, id: u64, notifs: &[Notification]) {
+    if let Some(watch) = self.watches.get_mut(&id) {
+      for notif in notifs {
+        watch.notifications.remove(notif);
+      }
+    }
+  }
+
+  /// Returns the removed watch so it is dropped outside the lock.
+  #[must_use]
+  pub(crate) fn remove_watch(&mut self, id: u64) -> Option<Arc<dyn Callbacks>> {
+    self.watches.remove(&id).map(|w| w.callbacks)
+  }
+
+  /// Queue a notification for a node, routed like the macOS observer callback.
+  fn notify(&self, node: MockNodeId, notif: Notification, out: &mut Vec<Delivery>) {
+    if notif.is_app_level() {
+      self.notify_app(node, notif, out);
+      return;
+    }
+
+    for watch in self.watches.values() {
+      if watch.node != node || !watch.notifications.contains(&notif) {
+        continue;
+      }
+      let event = match notif {
+        Notification::ValueChanged | Notification::TitleChanged => {
+          ElementEvent::Changed(watch.element_id, notif)
+        }
+        Notification::Destroyed => ElementEvent::Destroyed(watch.element_id),
+        Notification::ChildrenChanged => ElementEvent::ChildrenChanged(watch.element_id),
+        Notification::FocusChanged
+        | Notification::SelectionChanged
+        | Notification::BoundsChanged => continue,
+      };
+      out.push((Arc::clone(&watch.callbacks), event));
+    }
+  }
+
+  fn notify_app(&self, node: MockNodeId, notif: Notification, out: &mut Vec<Delivery>) {
+    let Some(n) = self.nodes.get(&node) else {
+      return;
+    };
+    let handle = MockHandle { node, pid: n.pid };
+
+    for sub in self.app_subscriptions.values() {
+      if sub.pid != n.pid {
+        continue;
+      }
+      let event = match notif {
+        Notification::FocusChanged => ElementEvent::FocusChanged(handle.clone()),
+        Notification::SelectionChanged => {
+          let (text, range) = n.selection.clone().unwrap_or_default();
+          ElementEvent::SelectionChanged {
+            handle: handle.clone(),
+            text,
+            range,
+          }
+        }
+        Notification::Destroyed
+        | Notification::ValueChanged
+        | Notification::TitleChanged
+        | Notification::BoundsChanged
+        | Notification::ChildrenChanged => continue,
+      };
+      out.push((Arc::clone(&sub.callbacks), event));
+    }
+  }
+
+  /// Queue a replayed callback to the watches or app subscriptions it was recorded for.
+  fn deliver(&self, callback: Callback, out: &mut Vec<Delivery>) {
+    let (node, notif) = match callback {
+      Callback::Destroyed { handle } => (handle, Notification::Destroyed),
+      Callback::Changed {
+        handle,
+        notification,
+      } => (handle, notification),
+      Callback::ChildrenChanged { handle } => (handle, Notification::ChildrenChanged),
+      Callback::FocusChanged { handle } => {
+        let handle = MockHandle {
+          node: MockNodeId::captured(handle.id),
+          pid: handle.pid,
+        };
+        for sub in self
+          .app_subscriptions
+          .values()
+          .filter(|s| s.pid == handle.pid)
+        {
+          out.push((
+            Arc::clone(&sub.callbacks),
+            ElementEvent::FocusChanged(handle.clone()),
+          ));
+        }
+        return;
+      }
+      Callback::SelectionChanged {
+        handle,
+        text,
+        range,
+      } => {
+        let handle = MockHandle {
+          node: MockNodeId::captured(handle.id),
+          pid: handle.pid,
+        };
+        for sub in self
+          .app_subscriptions
+          .values()
+          .filter(|s| s.pid == handle.pid)
+        {
+          let event = ElementEvent::SelectionChanged {
+            handle: handle.clone(),
+            text: text.clone(),
+            range,
+          };
+          out.push((Arc::clone(&sub.callbacks), event));
+        }
+        return;
+      }
+    };
+
+    let node = MockNodeId::captured(node);
+    for watch in self.watches.values() {
+      if watch.node != node || !watch.notifications.contains(&notif) {
+        continue;
+      }
+      let event = match notif {
+        Notification::Destroyed => ElementEvent::Destroyed(watch.element_id),
+        Notification::ChildrenChanged => ElementEvent::ChildrenChanged(watch.element_id),
+        Notification::ValueChanged
+        | Notification::TitleChanged
+        | Notification::FocusChanged
+        | Notification::SelectionChanged
+        | Notification::BoundsChanged => ElementEvent::Changed(watch.element_id, notif),
+      };
+      out.push((Arc::clone(&watch.callbacks), event));
+    }
+  }
+}
+
+// === Mutations ===
+
+impl Desktop {
+  fn insert_node(
+    &mut self,
+    pid: u32,
+    parent: Option<MockNodeId>,
+    index: Option<usize>,
+    element: MockElement,
+  ) -> MockNodeId {
+    let id = MockNodeId::next();
+    self.nodes.insert(
+      id,
+      Node {
+        pid,
+        parent,
+        children: Vec::new(),
+        element,
+        selection: None,
+      },
+    );
+    if let Some(parent) = parent.and_then(|p| self.nodes.get_mut(&p)) {
+      let index = index
+        .unwrap_or(parent.children.len())
+        .min(parent.children.len());
+      parent.children.insert(index, id);
+    }
+    id
+  }
+
+  fn ensure_app(&mut self, pid: u32, app_name: &str) -> MockNodeId {
+    if let Some(node) = self.app_node(pid) {
+      return node;
+    }
+    let node = self.insert_node(
+      pid,
+      None,
+      None,
+      MockElement::new(Role::Application).label(app_name),
+    );
+    self.apps.insert(pid, node);
+    node
+  }
+
+  /// Remove a node and its subtree, queueing `Destroyed` for watched nodes.
+  fn remove_subtree(&mut self, root: MockNodeId, out: &mut Vec<Delivery>) {
+    if let Some(parent) = self.parent(root).and_then(|p| self.nodes.get_mut(&p)) {
+      parent.children.retain(|c| *c != root);
+    }
+
+    let mut queue = vec![root];
+    while let Some(id) = queue.pop() {
+      self.notify(id, Notification::Destroyed, out);
+      if let Some(node) = self.nodes.remove(&id) {
+        queue.extend(node.children);
+      }
+    }
+  }
+
+  fn update(
+    &mut self,
+    node: MockNodeId,
+    f: impl FnOnce(&mut MockElement),
+    out: &mut Vec<Delivery>,
+  ) -> bool {
+    let Some(n) = self.nodes.get_mut(&node) else {
+      return false;
+    };
+    let before = n.element.clone();
+    f(&mut n.element);
+    let after = n.element.clone();
+
+    if before.value != after.value {
+      self.notify(node, Notification::ValueChanged, out);
+    }
+    if before.label != after.label {
+      self.notify(node, Notification::TitleChanged, out);
+    }
+    if before.bounds != after.bounds {
+      self.notify(node, Notification::BoundsChanged, out);
+    }
+    true
+  }
+}
+
+/// Write a value from the platform layer, as an app accepting the write would.
+pub(crate) fn write_value(node: MockNodeId, value: &Value) -> AllioResult<()> {
+  let applied = mutate(|d, out| {
+    d.update(
+      node,
+      |e| {
+        if e.ignored_writes > 0 {
+          e.ignored_writes -= 1;
+        } else {
+          e.value = Some(value.clone());
+        }
+      },
+      out,
+    )
+  });
+  if applied {
+    Ok(())
+  } else {
+    Err(AllioError::SetValueFailed {
+      reason: "element no longer exists".into(),
+    })
+  }
+}
+
+/// Perform an action from the platform layer. Toggles, expands and collapses like a real app.
+pub(crate) fn perform_action(node: MockNodeId, action: Action) -> AllioResult<()> {
+  mutate(|d, out| {
+    let Some(n) = d.nodes.get(&node) else {
+      return Err(AllioError::ActionFailed {
+        action,
+        reason: "element no longer exists".into(),
+      });
+    };
+    if !n.element.actions.contains(&action) {
+      return Err(AllioError::ActionFailed {
+        action,
+        reason: "action not supported".into(),
+      });
+    }
+
+    d.performed_actions.push((node, action));
+    d.update(
+      node,
+      |e| match (action, e.role) {
+        _ if e.ignored_writes > 0 => e.ignored_writes -= 1,
+        (Action::Press, Role::Checkbox | Role::Switch) => {
+          let checked = e.value.as_ref().and_then(Value::as_bool).unwrap_or(false);
+          e.value = Some(Value::Boolean(!checked));
+        }
+        (Action::Press, Role::RadioButton) => e.value = Some(Value::Boolean(true)),
+        (Action::Expand, _) => e.expanded = Some(true),
+        (Action::Collapse, _) => e.expanded = Some(false),
+        _ => {}
+      },
+      out,
+    );
+    Ok(())
+  })
+}
+
+/// Poll until `f` returns `Some`, panicking after 5 seconds.
+pub fn eventually<T>(mut f: impl FnMut() -> Option<T>) -> T {
+  let deadline = Instant::now() + Duration::from_secs(5);
+  loop {
+    if let Some(value) = f() {
+      return value;
+    }
+    assert!(Instant::now() < deadline, "timed out");
+    std::thread::sleep(Duration::from_millis(5));
+  }
+}
+
+/// Exclusive, scriptable access to the mock desktop.
+///
+/// Acquiring resets the desktop to empty (no windows, permissions granted,
+/// 1920x1080 screen). Holding the guard serializes tests that share it.
+///
+/// # Example
+///
+/// ```ignore
+/// let desktop = MockDesktop::acquire();
+/// let window = desktop.open_window("Notes", "Untitled");
+/// let field = desktop.add_element(window.node, MockElement::new(Role::TextField));
+///
+/// let allio = desktop.start_allio()?;
+/// desktop.set_value(field, Value::String("hello".into())); // fires ValueChanged
+/// ```
+#[derive(Debug)]
+#[must_use = "the desktop is only exclusive while the guard is held"]
+pub struct MockDesktop {
+  _exclusive: MutexGuard<'static, ()>,
+}
+
+impl MockDesktop {
+  /// Bounds of windows opened with [`MockDesktop::open_window`].
+  pub const WINDOW_BOUNDS: Bounds = Bounds {
+    x: 0.0,
+    y: 0.0,
+    w: 800.0,
+    h: 600.0,
+  };
+
+  /// Take exclusive access and reset the desktop.
+  pub fn acquire() -> Self {
+    let exclusive = EXCLUSIVE.lock();
+    // Drop the old desktop outside the lock: its callbacks may own the last `Allio` clone.
+    let old = with_desktop(std::mem::take);
+    drop(old);
+    Self {
+      _exclusive: exclusive,
+    }
+  }
+
+  /// Grant or revoke accessibility permissions.
+  pub fn set_permissions(&self, granted: bool) {
+    with_desktop(|d| d.permissions = granted);
+  }
+
+  /// Set the main screen size.
+  pub fn set_screen_size(&self, width: f64, height: f64) {
+    with_desktop(|d| d.screen_size = (width, height));
+  }
+
+  /// Make adding or removing a watch's notifications take `delay`, like a slow app.
+  pub fn set_watch_delay(&self, delay: Duration) {
+    with_desktop(|d| d.watch_delay = delay);
+  }
+
+  /// Move the mouse.
+  pub fn set_mouse_position(&self, position: Point) {
+    with_desktop(|d| d.mouse_position = position);
+  }
+
+  /// Add a window in front of all others. Creates the application element on first use.
+  pub fn add_window(&self, pid: u32, app_name: &str, title: &str, bounds: Bounds) -> MockWindow {
+    with_desktop(|d| {
+      let app = d.ensure_app(pid, app_name);
+      let node = d.insert_node(
+        pid,
+        Some(app),
+        None,
+        MockElement::new(Role::Window).label(title).bounds(bounds),
+      );
+      let id = WindowId(d.next_window_id);
+      d.next_window_id += 1;
+      d.windows.insert(
+        0,
+        WindowEntry {
+          info: Window {
+            id,
+            title: title.to_string(),
+            app_name: app_name.to_string(),
+            bounds,
+            focused: false,
+            process_id: ProcessId(pid),
+            z_index: 0,
+          },
+          node,
+        },
+      );
+      MockWindow { id, node }
+    })
+  }
+
+  /// Add an 800x600 window for process 100 at the origin.
+  pub fn open_window(&self, app_name: &str, title: &str) -> MockWindow {
+    self.add_window(100, app_name, title, Self::WINDOW_BOUNDS)
+  }
+
+  /// Start an [`Allio`] and block until it has polled every window added to the
+  /// desktop. Replayed windows aren't waited for; use [`MockDesktop::wait_for_window`].
+  pub fn start_allio(&self) -> AllioResult<Allio> {
+    let allio = Allio::new()?;
+    let ids: Vec<WindowId> = with_desktop(|d| d.windows.iter().map(|w| w.info.id).collect());
+    for id in ids {
+      self.wait_for_window(&allio, id);
+    }
+    Ok(allio)
+  }
+
+  /// Block until `allio` has polled the window, panicking after 5 seconds.
+  pub fn wait_for_window(&self, allio: &Allio, id: WindowId) {
+    eventually(|| allio.window(id));
+  }
+
+  /// Close a window, destroying its element subtree.
+  pub fn remove_window(&self, id: WindowId) {
+    mutate(|d, out| {
+      let Some(index) = d.windows.iter().position(|w| w.info.id == id) else {
+        return;
+      };
+      let entry = d.windows.remove(index);
+      d.remove_subtree(entry.node, out);
+      if d.focused_window == Some(id) {
+        d.focused_window = None;
+      }
+    });
+  }
+
+  /// Quit an application: close its windows and destroy its application element.
+  pub fn quit_app(&self, pid: u32) {
+    mutate(|d, out| {
+      d.windows.retain(|w| w.info.process_id != ProcessId(pid));
+      if d
+        .focused_window
+        .is_some_and(|id| d.windows.iter().all(|w| w.info.id != id))
+      {
+        d.focused_window = None;
+      }
+      if let Some(app) = d.apps.remove(&pid) {
+        d.remove_subtree(app, out);
+      }
+    });
+  }
+
+  /// Focus a window and bring it to the front.
+  pub fn focus_window(&self, id: WindowId) {
+    with_desktop(|d| {
+      let Some(index) = d.windows.iter().position(|w| w.info.id == id) else {
+        return;
+      };
+      let entry = d.windows.remove(index);
+      d.windows.insert(0, entry);
+      d.focused_window = Some(id);
+    });
+  }
+
+  /// Move or resize a window (and its window element).
+  pub fn set_window_bounds(&self, id: WindowId, bounds: Bounds) {
+    let node = with_desktop(|d| {
+      let entry = d.windows.iter_mut().find(|w| w.info.id == id)?;
+      entry.info.bounds = bounds;
+      Some(entry.node)
+    });
+    if let Some(node) = node {
+      self.update_element(node, |e| e.bounds = Some(bounds));
+    }
+  }
+
+  /// Retitle a window (and its window element).
+  pub fn set_window_title(&self, id: WindowId, title: &str) {
+    let node = with_desktop(|d| {
+      let entry = d.windows.iter_mut().find(|w| w.info.id == id)?;
+      entry.info.title = title.to_string();
+      Some(entry.node)
+    });
+    if let Some(node) = node {
+      self.update_element(node, |e| e.label = Some(title.to_string()));
+    }
+  }
+
+  /// Append a child element. Fires `ChildrenChanged` on the parent.
+  ///
+  /// Returns `None` if the parent doesn't exist.
+  pub fn add_element(&self, parent: MockNodeId, element: MockElement) -> Option<MockNodeId> {
+    self.insert_element(parent, usize::MAX, element)
+  }
+
+  /// Insert a child element at an index (clamped). Fires `ChildrenChanged` on the parent.
+  ///
+  /// Returns `None` if the parent doesn't exist.
+  pub fn insert_element(
+    &self,
+    parent: MockNodeId,
+    index: usize,
+    element: MockElement,
+  ) -> Option<MockNodeId> {
+    mutate(|d, out| {
+      let pid = d.nodes.get(&parent)?.pid;
+      let id = d.insert_node(pid, Some(parent), Some(index), element);
+      d.notify(parent, Notification::ChildrenChanged, out);
+      Some(id)
+    })
+  }
+
+  /// Destroy an element and its subtree. Fires `Destroyed` and `ChildrenChanged` on the parent.
+  pub fn remove_element(&self, node: MockNodeId) {
+    mutate(|d, out| {
+      let parent = d.parent(node);
+      d.remove_subtree(node, out);
+      if let Some(parent) = parent {
+        d.notify(parent, Notification::ChildrenChanged, out);
+      }
+    });
+  }
+
+  /// Modify an element's attributes. Fires `ValueChanged`/`TitleChanged` for what changed.
+  ///
+  /// Returns false if the node doesn't exist.
+  pub fn update_element(&self, node: MockNodeId, f: impl FnOnce(&mut MockElement)) -> bool {
+    mutate(|d, out| d.update(node, f, out))
+  }
+
+  /// Set an element's value, as if the user edited it. Fires `ValueChanged`.
+  pub fn set_value(&self, node: MockNodeId, value: Value) -> bool {
+    self.update_element(node, |e| e.value = Some(value))
+  }
+
+  /// Move keyboard focus to an element. Fires the app-level `FocusChanged`.
+  pub fn focus(&self, node: MockNodeId) {
+    mutate(|d, out| {
+      let Some(pid) = d.nodes.get(&node).map(|n| n.pid) else {
+        return;
+      };
+      for n in d.nodes.values_mut().filter(|n| n.pid == pid) {
+        if n.element.focused == Some(true) {
+          n.element.focused = Some(false);
+        }
+      }
+      if let Some(n) = d.nodes.get_mut(&node) {
+        n.element.focused = Some(true);
+      }
+      d.notify(node, Notification::FocusChanged, out);
+    });
+  }
+
+  /// Select text in an element. Fires the app-level `SelectionChanged`.
+  pub fn select_text(&self, node: MockNodeId, text: &str, range: Option<(u32, u32)>) {
+    mutate(|d, out| {
+      let Some(n) = d.nodes.get_mut(&node) else {
+        return;
+      };
+      n.selection = Some((text.to_string(), range));
+      d.notify(node, Notification::SelectionChanged, out);
+    });
+  }
+
+  /// Fire a raw notification for a node without changing anything.
+  ///
+  /// Delivered only if subscribed, exactly like an OS notification.
+  pub fn notify(&self, node: MockNodeId, notification: Notification) {
+    mutate(|d, out| d.notify(node, notification, out));
+  }
+
+  /// Current attributes of a node.
+  pub fn element(&self, node: MockNodeId) -> Option<MockElement> {
+    with_desktop(|d| d.nodes.get(&node).map(|n| n.element.clone()))
+  }
+
+  /// Children of a node, in order.
+  pub fn children(&self, node: MockNodeId) -> Vec<MockNodeId> {
+    with_desktop(|d| {
+      d.nodes
+        .get(&node)
+        .map(|n| n.children.clone())
+        .unwrap_or_default()
+    })
+  }
+
+  /// Whether a node has any element-level watch for a notification.
+  pub fn is_watched(&self, node: MockNodeId, notification: Notification) -> bool {
+    with_desktop(|d| {
+      d.watches
+        .values()
+        .any(|w| w.node == node && w.notifications.contains(&notification))
+    })
+  }
+
+  /// Replay a platform capture: calls it recorded are answered from it, and its
+  /// callbacks are delivered as the calls before them are replayed.
+  ///
+  /// Load it before creating `Allio`. Handles in the capture stand for nodes
+  /// that don't exist here, so don't script elements alongside a replay.
+  pub fn replay(&self, capture: Capture) {
+    with_desktop(|d| d.replay = Some(Replay::new(capture)));
+  }
+
+  /// Deliver every replayed callback not yet delivered, whatever calls remain.
+  pub fn finish_replay(&self) {
+    mutate(|d, out| {
+      let remaining = d
+        .replay
+        .as_mut()
+        .map(Replay::remaining_callbacks)
+        .unwrap_or_default();
+      for callback in remaining {
+        d.deliver(callback, out);
+      }
+    });
+  }
+
+  /// Actions performed through the platform layer, in order.
+  pub fn performed_actions(&self) -> Vec<(MockNodeId, Action)> {
+    with_desktop(|d| d.performed_actions.clone())
+  }
+}
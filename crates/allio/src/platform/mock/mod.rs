@@ -0,0 +1,1171 @@
+/*!
+In-memory mock platform implementation.
+
+Implements the platform traits over a scriptable desktop (see `desktop.rs`)
+instead of the OS. Used for tests.
+
+A platform capture loaded with `MockDesktop::replay` takes precedence: calls it
+recorded are answered from it (see `replay.rs`), anything else falls back to
+the desktop.
+
+Notifications are delivered synchronously on the thread that mutates the
+desktop, mirroring what the macOS observer callback would send:
+element-level notifications go to watches, focus/selection go to app-level
+subscriptions.
+*/
+
+pub(crate) mod desktop;
+pub(crate) mod replay;
+
+use std::collections::HashSet;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::a11y::{Action, Notification, Value};
+use crate::platform::capture::{Entry, HandleRef};
+use crate::platform::traits::{
+  AppNotificationHandle, DisplayLinkHandle, ElementAttributes, EventHandler, Platform,
+  PlatformHandle, PlatformObserver, WatchHandle,
+};
+use crate::types::{AllioError, AllioResult, ElementId, Point, Window};
+use desktop::{replayed, with_desktop, Callbacks, MockNodeId};
+use replay::Key;
+
+/// Interval of the emulated display link (60Hz).
+const DISPLAY_LINK_INTERVAL: Duration = Duration::from_micros(16_667);
+
+/// Answer from the replayed capture if the next entry for `$key` matches
+/// `$entry`; `None` falls back to the scripted desktop.
+macro_rules! replay {
+  ($key:expr, |$entry:pat_param| $answer:expr) => {
+    replayed($key, |entry| {
+      let $entry = entry else {
+        return None;
+      };
+      Some($answer)
+    })
+  };
+}
+
+/// Mock platform implementation.
+pub(crate) struct Mock;
+
+/// Handle to a node in the mock desktop. Clone is cheap (two integers).
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(crate) struct MockHandle {
+  pub(crate) node: MockNodeId,
+  pub(crate) pid: u32,
+}
+
+impl From<HandleRef> for MockHandle {
+  fn from(handle: HandleRef) -> Self {
+    Self {
+      node: MockNodeId::captured(handle.id),
+      pid: handle.pid,
+    }
+  }
+}
+
+/// Observer for a mock process. Subscriptions live in the desktop.
+#[derive(Debug, Clone)]
+pub(crate) struct MockObserver {
+  pid: u32,
+}
+
+impl Platform for Mock {
+  type Handle = MockHandle;
+  type Observer = MockObserver;
+
+  fn has_permissions() -> bool {
+    replay!(Key::HasPermissions, |Entry::HasPermissions { granted }| *granted)
+    .unwrap_or_else(|| with_desktop(|d| d.permissions))
+  }
+
+  fn fetch_windows(_exclude_pid: Option<u32>) -> Vec<Window> {
+    replay!(Key::FetchWindows, |Entry::FetchWindows { windows }| windows.clone())
+    // Note: exclude_pid filtering happens in polling.rs, not here
+    .unwrap_or_else(|| with_desktop(|d| d.window_list()))
+  }
+
+  fn fetch_screen_size() -> (f64, f64) {
+    replay!(
+      Key::FetchScreenSize,
+      |Entry::FetchScreenSize { width, height }| (*width, *height)
+    )
+    .unwrap_or_else(|| with_desktop(|d| d.screen_size))
+  }
+
+  fn fetch_mouse_position() -> Point {
+    replay!(
+      Key::FetchMousePosition,
+      |Entry::FetchMousePosition { position }| *position
+    )
+    .unwrap_or_else(|| with_desktop(|d| d.mouse_position))
+  }
+
+  fn fetch_window_handle(window: &Window) -> Option<Self::Handle> {
+    replay!(
+      Key::FetchWindowHandle(window.id),
+      |Entry::FetchWindowHandle { handle, .. }| handle.map(MockHandle::from)
+    )
+    .unwrap_or_else(|| with_desktop(|d| d.window_node(window.id).and_then(|node| d.handle(node))))
+  }
+
+  fn create_observer<C: EventHandler<Handle = Self::Handle>>(
+    pid: u32,
+    _callbacks: Arc<C>,
+  ) -> AllioResult<Self::Observer> {
+    let recorded = replay!(
+      Key::CreateObserver(pid),
+      |Entry::CreateObserver { result, .. }| result.clone()
+    );
+    if let Some(result) = recorded {
+      return result.map(|()| MockObserver { pid });
+    }
+    if with_desktop(|d| d.app_node(pid).is_none()) {
+      return Err(AllioError::ObserverError(format!(
+        "No mock application for PID {pid}"
+      )));
+    }
+    Ok(MockObserver { pid })
+  }
+
+  fn start_display_link<F: Fn() + Send + Sync + 'static>(callback: F) -> Option<DisplayLinkHandle> {
+    let stopped = Arc::new(AtomicBool::new(false));
+    let stopped_clone = Arc::clone(&stopped);
+    std::thread::spawn(move || {
+      while !stopped_clone.load(Ordering::SeqCst) {
+        callback();
+        std::thread::sleep(DISPLAY_LINK_INTERVAL);
+      }
+    });
+    Some(DisplayLinkHandle {
+      inner: DisplayLinkHandleInner { stopped },
+    })
+  }
+
+  fn enable_accessibility_for_pid(_pid: u32) {}
+
+  fn app_element(pid: u32) -> Self::Handle {
+    let recorded = replay!(
+      Key::AppElement(pid),
+      |Entry::AppElement { handle, .. }| MockHandle::from(*handle)
+    );
+    recorded.unwrap_or_else(|| MockHandle {
+      node: with_desktop(|d| d.app_node(pid)).unwrap_or(MockNodeId::DANGLING),
+      pid,
+    })
+  }
+
+  fn is_process_running(pid: u32) -> bool {
+    replay!(
+      Key::IsProcessRunning(pid),
+      |Entry::IsProcessRunning { running, .. }| *running
+    )
+    .unwrap_or_else(|| with_desktop(|d| d.app_node(pid).is_some()))
+  }
+}
+
+impl PlatformHandle for MockHandle {
+  fn pid(&self) -> u32 {
+    self.pid
+  }
+
+  fn fetch_children(&self) -> Vec<Self> {
+    replay!(
+      Key::FetchChildren(self.node.raw()),
+      |Entry::FetchChildren { children, .. }| children.iter().copied().map(Self::from).collect()
+    )
+    .unwrap_or_else(|| with_desktop(|d| d.children_handles(self.node)))
+  }
+
+  fn fetch_parent(&self) -> Option<Self> {
+    replay!(
+      Key::FetchParent(self.node.raw()),
+      |Entry::FetchParent { parent, .. }| parent.map(Self::from)
+    )
+    .unwrap_or_else(|| with_desktop(|d| d.parent(self.node).and_then(|p| d.handle(p))))
+  }
+
+  fn set_value(&self, value: &Value) -> AllioResult<()> {
+    // Replayed writes return the recorded result; the app's reaction is in the callbacks.
+    replay!(
+      Key::SetValue(self.node.raw()),
+      |Entry::SetValue { result, .. }| result.clone()
+    )
+    .unwrap_or_else(|| desktop::write_value(self.node, value))
+  }
+
+  fn perform_action(&self, action: Action) -> AllioResult<()> {
+    replay!(
+      Key::PerformAction(self.node.raw(), action),
+      |Entry::PerformAction { result, .. }| result.clone()
+    )
+    .unwrap_or_else(|| desktop::perform_action(self.node, action))
+  }
+
+  fn fetch_attributes(&self) -> ElementAttributes {
+    replay!(
+      Key::FetchAttributes(self.node.raw()),
+      |Entry::FetchAttributes { attributes, .. }| ElementAttributes::clone(attributes)
+    )
+    .unwrap_or_else(|| with_desktop(|d| d.attributes(self.node)))
+  }
+
+  fn fetch_element_at_position(&self, x: f64, y: f64) -> Option<Self> {
+    let key = Key::FetchElementAtPosition(self.node.raw(), x.to_bits(), y.to_bits());
+    replay!(
+      key,
+      |Entry::FetchElementAtPosition { element, .. }| element.map(Self::from)
+    )
+    .unwrap_or_else(|| {
+      with_desktop(|d| {
+        d.hit_test(self.pid, Point::new(x, y))
+          .and_then(|n| d.handle(n))
+      })
+    })
+  }
+
+  fn window(&self) -> Option<Self> {
+    replay!(
+      Key::Window(self.node.raw()),
+      |Entry::Window { window, .. }| window.map(Self::from)
+    )
+    .unwrap_or_else(|| with_desktop(|d| d.containing_window(self.node).and_then(|n| d.handle(n))))
+  }
+}
+
+impl PlatformObserver for MockObserver {
+  type Handle = MockHandle;
+
+  fn subscribe_app_notifications<C: EventHandler<Handle = Self::Handle>>(
+    &self,
+    pid: u32,
+    callbacks: Arc<C>,
+  ) -> AllioResult<AppNotificationHandle> {
+    debug_assert_eq!(pid, self.pid);
+    let recorded = replay!(
+      Key::SubscribeApp(pid),
+      |Entry::SubscribeApp { result, .. }| result.clone()
+    );
+    recorded.transpose()?;
+    let callbacks: Arc<dyn Callbacks> = Arc::new(desktop::CallbacksWrapper(callbacks));
+    let id = with_desktop(|d| d.subscribe_app(pid, callbacks));
+    Ok(AppNotificationHandle {
+      _inner: AppNotificationHandleInner { id },
+    })
+  }
+
+  fn create_watch<C: EventHandler<Handle = Self::Handle>>(
+    &self,
+    handle: &Self::Handle,
+    element_id: ElementId,
+    initial_notifications: &[Notification],
+    callbacks: Arc<C>,
+  ) -> AllioResult<WatchHandle> {
+    let callbacks: Arc<dyn Callbacks> = Arc::new(desktop::CallbacksWrapper(callbacks));
+    let notifications: HashSet<Notification> = initial_notifications.iter().copied().collect();
+    let recorded = replay!(
+      Key::CreateWatch(handle.node.raw()),
+      |Entry::CreateWatch { result, .. }| result.clone()
+    );
+    let id = if recorded.transpose()?.is_some() {
+      with_desktop(|d| d.insert_watch(handle.node, element_id, notifications, callbacks))
+    } else {
+      with_desktop(|d| d.add_watch(handle.node, element_id, notifications, callbacks)).ok_or_else(
+        || {
+          AllioError::ObserverError(format!(
+            "Failed to register notifications {initial_notifications:?} for element {element_id}"
+          ))
+        },
+      )?
+    };
+    Ok(WatchHandle {
+      inner: WatchHandleInner { id },
+    })
+  }
+}
+
+/// Watch registration in the mock desktop. Unregisters on drop.
+pub(crate) struct WatchHandleInner {
+  id: u64,
+}
+
+impl WatchHandleInner {
+  /// Add notifications to the watch set.
+  pub(crate) fn add(&mut self, notifs: &[Notification]) -> usize {
+    std::thread::sleep(with_desktop(|d| d.watch_delay));
+    with_desktop(|d| d.extend_watch(self.id, notifs))
+  }
+
+  /// Remove notifications from the watch set.
+  pub(crate) fn remove(&mut self, notifs: &[Notification]) {
+    std::thread::sleep(with_desktop(|d| d.watch_delay));
+    with_desktop(|d| d.shrink_watch(self.id, notifs));
+  }
+}
+
+impl Drop for WatchHandleInner {
+  fn drop(&mut self) {
+    drop(with_desktop(|d| d.remove_watch(self.id)));
+  }
+}
+
+/// App-level subscription in the mock desktop. Unregisters on drop.
+pub(crate) struct AppNotificationHandleInner {
+  id: u64,
+}
+
+impl Drop for AppNotificationHandleInner {
+  fn drop(&mut self) {
+    drop(with_desktop(|d| d.unsubscribe_app(self.id)));
+  }
+}
+
+/// Emulated display link: a 60Hz thread. Stops on `stop()`.
+pub(crate) struct DisplayLinkHandleInner {
+  stopped: Arc<AtomicBool>,
+}
+
+impl DisplayLinkHandleInner {
+  pub(crate) fn stop(&self) {
+    self.stopped.store(true, Ordering::SeqCst);
+  }
+}
+
+impl Drop for DisplayLinkHandleInner {
+  fn drop(&mut self) {
+    self.stop();
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::desktop::{MockDesktop, MockElement};
+  use crate::a11y::{Action, Notification, Role, Value};
+  use crate::capture::Capture;
+  use crate::core::Registry;
+  use crate::platform::capture::Entry;
+  use crate::faults::Faults;
+  use crate::types::{
+    AllioError, Bounds, ElementChange, ElementId, Event, RemovalReason, SequencedEvent,
+  };
+  use crate::mock::eventually;
+  use crate::{Allio, ObserveConfig, Recency};
+  use std::sync::Arc;
+  use std::time::Duration;
+
+  const fn rect(x: f64, y: f64, w: f64, h: f64) -> Bounds {
+    Bounds { x, y, w, h }
+  }
+
+  fn next_event(
+    rx: &mut async_broadcast::Receiver<SequencedEvent>,
+    mut pred: impl FnMut(&Event) -> bool,
+  ) -> Event {
+    eventually(|| {
+      while let Ok(stamped) = rx.try_recv() {
+        if pred(&stamped.event) {
+          return Some(stamped.event);
+        }
+      }
+      None
+    })
+  }
+
+  #[test]
+  fn permission_denied_without_permissions() {
+    let desktop = MockDesktop::acquire();
+    desktop.set_permissions(false);
+    assert!(matches!(Allio::new(), Err(AllioError::PermissionDenied)));
+  }
+
+  #[test]
+  fn polls_windows_and_builds_tree() {
+    let desktop = MockDesktop::acquire();
+    let window = desktop.open_window("Notes", "Untitled");
+    desktop.add_element(window.node, MockElement::new(Role::Button).label("Save"));
+    desktop.add_element(window.node, MockElement::new(Role::TextField));
+
+    let allio = desktop.start_allio().unwrap();
+    assert_eq!(allio.window(window.id).unwrap().title, "Untitled");
+
+    let root = allio.window_root(window.id).unwrap().unwrap();
+    assert_eq!(root.role, Role::Window);
+    let children = allio.children(root.id, Recency::Current).unwrap();
+    assert_eq!(children.len(), 2);
+    assert_eq!(children[0].label.as_deref(), Some("Save"));
+    assert_eq!(children[1].role, Role::TextField);
+  }
+
+  #[test]
+  fn children_are_paged() {
+    let desktop = MockDesktop::acquire();
+    let window = desktop.open_window("Mail", "Inbox");
+    for i in 0..25 {
+      desktop.add_element(
+        window.node,
+        MockElement::new(Role::Row).label(format!("message {i}")),
+      );
+    }
+    let allio = desktop.start_allio().unwrap();
+    let root = allio.window_root(window.id).unwrap().unwrap();
+
+    let first = allio
+      .children_page(root.id, 0, 10, Recency::Current)
+      .unwrap();
+    assert_eq!((first.children.len(), first.total), (10, Some(25)));
+    assert_eq!(first.next_offset, Some(10));
+    assert_eq!(first.children[0].label.as_deref(), Some("message 0"));
+    // Nothing outside the page was loaded.
+    assert_eq!(allio.all_elements().len(), 11);
+
+    let last = allio
+      .children_page(root.id, 20, 10, Recency::Current)
+      .unwrap();
+    assert_eq!(last.children.len(), 5);
+    assert_eq!(last.next_offset, None);
+    assert_eq!(last.children[4].label.as_deref(), Some("message 24"));
+    let past_end = allio
+      .children_page(root.id, 30, 10, Recency::Current)
+      .unwrap();
+    assert!(past_end.children.is_empty());
+
+    // Both pages stay cached, in order, but the cache doesn't know the total.
+    let cached = allio.children_page(root.id, 0, 100, Recency::Any).unwrap();
+    assert_eq!((cached.children.len(), cached.total), (15, None));
+    let labels: Vec<_> = cached.children.iter().map(|e| e.label.as_deref()).collect();
+    assert_eq!(labels[9..11], [Some("message 9"), Some("message 20")]);
+
+    // Once every child was fetched, it does.
+    allio.children(root.id, Recency::Current).unwrap();
+    let cached = allio.children_page(root.id, 0, 10, Recency::Any).unwrap();
+    assert_eq!(cached.total, Some(25));
+  }
+
+  #[test]
+  fn changed_children_refetch_what_was_loaded() {
+    use crate::platform::{ElementEvent, EventHandler};
+
+    let desktop = MockDesktop::acquire();
+    let window = desktop.open_window("Mail", "Inbox");
+    for i in 0..25 {
+      desktop.add_element(
+        window.node,
+        MockElement::new(Role::Row).label(format!("message {i}")),
+      );
+    }
+    let allio = desktop.start_allio().unwrap();
+    let root = allio.window_root(window.id).unwrap().unwrap();
+    let cached = |allio: &Allio| allio.children_page(root.id, 0, 100, Recency::Any).unwrap();
+
+    // A loaded page is re-fetched, and nothing past it.
+    allio
+      .children_page(root.id, 0, 10, Recency::Current)
+      .unwrap();
+    desktop.insert_element(window.node, 0, MockElement::new(Role::Row).label("new"));
+    allio.on_element_event(ElementEvent::ChildrenChanged(root.id));
+    let page = cached(&allio);
+    assert_eq!((page.children.len(), page.total), (11, None));
+    assert_eq!(page.children[0].label.as_deref(), Some("new"));
+    assert_eq!(allio.all_elements().len(), 12);
+
+    // Loaded children stay loaded.
+    allio.children(root.id, Recency::Current).unwrap();
+    desktop.add_element(window.node, MockElement::new(Role::Row).label("last"));
+    allio.on_element_event(ElementEvent::ChildrenChanged(root.id));
+    let page = cached(&allio);
+    assert_eq!(page.total, Some(27));
+    assert_eq!(page.children[26].label.as_deref(), Some("last"));
+  }
+
+  #[test]
+  fn window_removal_is_polled() {
+    let desktop = MockDesktop::acquire();
+    let window = desktop.open_window("Notes", "Untitled");
+    let allio = Allio::new().unwrap();
+    let mut rx = allio.subscribe();
+    desktop.wait_for_window(&allio, window.id);
+
+    desktop.remove_window(window.id);
+    next_event(
+      &mut rx,
+      |e| matches!(e, Event::WindowRemoved { window_id } if *window_id == window.id),
+    );
+  }
+
+  #[test]
+  fn events_are_sequenced_and_snapshots_record_the_last_seq() {
+    let desktop = MockDesktop::acquire();
+    let window = desktop.open_window("Notes", "Untitled");
+    desktop.add_element(window.node, MockElement::new(Role::Button));
+    let allio = Allio::new().unwrap();
+    let mut rx = allio.subscribe();
+    desktop.wait_for_window(&allio, window.id);
+    let root = allio.window_root(window.id).unwrap().unwrap();
+    allio.children(root.id, Recency::Current).unwrap();
+
+    let mut seqs = Vec::new();
+    while let Ok(stamped) = rx.try_recv() {
+      assert!(stamped.timestamp_ms > 0);
+      seqs.push(stamped.seq);
+    }
+    assert!(seqs.len() >= 3, "expected window and element events");
+    assert!(seqs.windows(2).all(|w| w[1] == w[0] + 1), "{seqs:?}");
+    assert!(allio.snapshot().seq >= *seqs.last().unwrap());
+
+    let json = serde_json::to_value(SequencedEvent::sync_init(allio.snapshot())).unwrap();
+    assert_eq!(json["event"], "sync:init");
+    assert_eq!(json["seq"], json["data"]["seq"]);
+  }
+
+  #[test]
+  fn watched_value_change_emits_element_changed() {
+    let desktop = MockDesktop::acquire();
+    let window = desktop.open_window("Notes", "Untitled");
+    let field = desktop
+      .add_element(window.node, MockElement::new(Role::TextField))
+      .unwrap();
+
+    let allio = Allio::new().unwrap();
+    let mut rx = allio.subscribe();
+    desktop.wait_for_window(&allio, window.id);
+    let root = allio.window_root(window.id).unwrap().unwrap();
+    let field_id = allio.children(root.id, Recency::Current).unwrap()[0].id;
+    allio.watch(field_id).unwrap();
+
+    desktop.set_value(field, Value::String("hello".into()));
+    let Event::ElementChanged { changes, .. } = next_event(
+      &mut rx,
+      |e| matches!(e, Event::ElementChanged { element_id, .. } if *element_id == field_id),
+    ) else {
+      unreachable!()
+    };
+    // Only the value moved, and the change carries both sides.
+    assert_eq!(
+      changes,
+      vec![ElementChange::Value {
+        old: None,
+        new: Some(Value::String("hello".into())),
+      }]
+    );
+    let json = serde_json::to_value(&changes[0]).unwrap();
+    assert_eq!(json["field"], "value");
+
+    // Applying the changes to a stale copy brings it up to date.
+    let mut stale = allio.get(field_id, Recency::Any).unwrap();
+    stale.value = None;
+    stale.apply(&changes);
+    assert_eq!(stale, allio.get(field_id, Recency::Any).unwrap());
+  }
+
+  #[test]
+  fn observations_and_watches_are_reference_counted() {
+    let desktop = MockDesktop::acquire();
+    let window = desktop.open_window("Notes", "Untitled");
+    desktop.add_element(window.node, MockElement::new(Role::TextField));
+    let allio = desktop.start_allio().unwrap();
+    let root = allio.window_root(window.id).unwrap().unwrap();
+    let field_id = allio.children(root.id, Recency::Current).unwrap()[0].id;
+
+    let first = allio.observe(root.id, ObserveConfig::default()).unwrap();
+    let second = allio.observe(root.id, ObserveConfig::default()).unwrap();
+    assert_eq!(second.root_id(), root.id);
+    drop(first);
+    assert!(allio.is_observed(root.id));
+    second.dispose();
+    assert!(!allio.is_observed(root.id));
+
+    // A handle outliving a forced `unobserve` leaves a later observation alone.
+    let stale = allio.observe(root.id, ObserveConfig::default()).unwrap();
+    allio.unobserve(root.id);
+    let fresh = allio.observe(root.id, ObserveConfig::default()).unwrap();
+    drop(stale);
+    assert!(allio.is_observed(root.id));
+    drop(fresh);
+
+    allio.watch(field_id).unwrap();
+    allio.watch(field_id).unwrap();
+    allio.unwatch(field_id).unwrap();
+    assert!(allio.is_watched(field_id));
+    allio.unwatch(field_id).unwrap();
+    assert!(!allio.is_watched(field_id));
+  }
+
+  #[test]
+  fn racing_watch_and_unwatch_settle_on_the_count() {
+    let desktop = MockDesktop::acquire();
+    let window = desktop.open_window("Notes", "Untitled");
+    let field = desktop
+      .add_element(window.node, MockElement::new(Role::TextField))
+      .unwrap();
+    let allio = desktop.start_allio().unwrap();
+    let root = allio.window_root(window.id).unwrap().unwrap();
+    let field_id = allio.children(root.id, Recency::Current).unwrap()[0].id;
+    let watched = || desktop.is_watched(field, Notification::ValueChanged);
+    desktop.set_watch_delay(Duration::from_millis(100));
+
+    // Unwatch while the first watch is still registering.
+    let racing = allio.clone();
+    let watching = std::thread::spawn(move || racing.watch(field_id).unwrap());
+    std::thread::sleep(Duration::from_millis(20));
+    allio.unwatch(field_id).unwrap();
+    watching.join().unwrap();
+    assert!(!allio.is_watched(field_id));
+    assert!(!watched());
+
+    // Watch while the last unwatch is still removing.
+    allio.watch(field_id).unwrap();
+    let racing = allio.clone();
+    let unwatching = std::thread::spawn(move || racing.unwatch(field_id).unwrap());
+    std::thread::sleep(Duration::from_millis(20));
+    allio.watch(field_id).unwrap();
+    unwatching.join().unwrap();
+    assert!(allio.is_watched(field_id));
+    assert!(watched());
+  }
+
+  #[test]
+  fn destroyed_element_emits_element_removed() {
+    let desktop = MockDesktop::acquire();
+    let window = desktop.open_window("Notes", "Untitled");
+    let button = desktop
+      .add_element(window.node, MockElement::new(Role::Button))
+      .unwrap();
+
+    let allio = Allio::new().unwrap();
+    let mut rx = allio.subscribe();
+    desktop.wait_for_window(&allio, window.id);
+    let root = allio.window_root(window.id).unwrap().unwrap();
+    let button_id = allio.children(root.id, Recency::Current).unwrap()[0].id;
+
+    desktop.remove_element(button);
+    next_event(
+      &mut rx,
+      |e| matches!(e, Event::ElementRemoved { element_id, .. } if *element_id == button_id),
+    );
+    assert!(allio.get(button_id, Recency::Any).is_err());
+  }
+
+  #[test]
+  fn rebuilt_elements_are_reported_as_replaced() {
+    let desktop = MockDesktop::acquire();
+    let window = desktop.open_window("Notes", "Untitled");
+    let build = |label: &str| {
+      let group = desktop
+        .add_element(
+          window.node,
+          MockElement::new(Role::Group).identifier("toolbar"),
+        )
+        .unwrap();
+      desktop.add_element(group, MockElement::new(Role::Button).label(label));
+      group
+    };
+    let group = build("Save");
+
+    let allio = Allio::new().unwrap();
+    let mut rx = allio.subscribe();
+    desktop.wait_for_window(&allio, window.id);
+    let root = allio.window_root(window.id).unwrap().unwrap();
+    let old_group = allio.children(root.id, Recency::Current).unwrap()[0].id;
+    let old_button = allio.children(old_group, Recency::Current).unwrap()[0].id;
+
+    // The app rebuilds the toolbar: same structure, new elements.
+    desktop.remove_element(group);
+    next_event(
+      &mut rx,
+      |e| matches!(e, Event::ElementRemoved { element_id, .. } if *element_id == old_button),
+    );
+    let group = build("Save");
+    let new_group = allio.children(root.id, Recency::Current).unwrap()[0].id;
+    let new_button = allio.children(new_group, Recency::Current).unwrap()[0].id;
+    assert_ne!((new_group, new_button), (old_group, old_button));
+    for (old, new) in [(old_group, new_group), (old_button, new_button)] {
+      let replaced = next_event(&mut rx, |e| matches!(e, Event::ElementReplaced { .. }));
+      assert_eq!(replaced, Event::ElementReplaced { old, new });
+    }
+
+    // A different button in its place is not a replacement.
+    desktop.remove_element(group);
+    build("Cancel");
+    let group = allio.children(root.id, Recency::Current).unwrap()[0].id;
+    allio.children(group, Recency::Current).unwrap();
+    let replaced: Vec<_> = std::iter::from_fn(|| rx.try_recv().ok())
+      .filter_map(|stamped| {
+        if let Event::ElementReplaced { old, .. } = stamped.event {
+          Some(old)
+        } else {
+          None
+        }
+      })
+      .collect();
+    assert_eq!(replaced, vec![new_group]);
+  }
+
+  #[test]
+  fn removed_elements_leave_tombstones() {
+    let desktop = MockDesktop::acquire();
+    let notes = desktop.open_window("Notes", "Untitled");
+    let group = desktop
+      .add_element(notes.node, MockElement::new(Role::Group))
+      .unwrap();
+    desktop.add_element(group, MockElement::new(Role::Button).label("Save"));
+    let draft = desktop.open_window("Notes", "Draft");
+    let mail = desktop.add_window(200, "Mail", "Inbox", MockDesktop::WINDOW_BOUNDS);
+    desktop.add_element(mail.node, MockElement::new(Role::List));
+
+    let allio = Allio::new().unwrap();
+    let mut rx = allio.subscribe();
+    for window in [&notes, &draft, &mail] {
+      desktop.wait_for_window(&allio, window.id);
+    }
+    let first_child = |id| allio.children(id, Recency::Current).unwrap()[0].id;
+    let notes_root = allio.window_root(notes.id).unwrap().unwrap().id;
+    let group_id = first_child(notes_root);
+    let button_id = first_child(group_id);
+    let draft_root = allio.window_root(draft.id).unwrap().unwrap().id;
+    let mail_root = allio.window_root(mail.id).unwrap().unwrap().id;
+    let list_id = first_child(mail_root);
+
+    let reason = |id| {
+      if let Err(AllioError::ElementRemoved { reason, .. }) = allio.get(id, Recency::Any) {
+        Some(reason)
+      } else {
+        None
+      }
+    };
+
+    desktop.remove_element(group);
+    let removed = next_event(
+      &mut rx,
+      |e| matches!(e, Event::ElementRemoved { element_id, .. } if *element_id == button_id),
+    );
+    assert_eq!(
+      serde_json::to_value(&removed).unwrap()["data"],
+      serde_json::json!({ "element_id": button_id, "reason": "cascade" })
+    );
+    assert_eq!(reason(group_id), Some(RemovalReason::Destroyed));
+    assert_eq!(reason(button_id), Some(RemovalReason::Cascade));
+    assert_eq!(reason(notes_root), None);
+
+    // Destroyed notifications arrive first; the poll then attributes them to the window.
+    desktop.remove_window(draft.id);
+    next_event(
+      &mut rx,
+      |e| matches!(e, Event::WindowRemoved { window_id } if *window_id == draft.id),
+    );
+    assert_eq!(reason(draft_root), Some(RemovalReason::WindowClosed));
+
+    desktop.quit_app(200);
+    next_event(
+      &mut rx,
+      |e| matches!(e, Event::WindowRemoved { window_id } if *window_id == mail.id),
+    );
+    assert_eq!(reason(mail_root), Some(RemovalReason::ProcessExited));
+    assert_eq!(reason(list_id), Some(RemovalReason::ProcessExited));
+    assert_eq!(allio.tombstone(list_id).unwrap().window_id, mail.id);
+
+    let bogus = ElementId::new(list_id.epoch(), u32::MAX);
+    assert!(matches!(
+      allio.get(bogus, Recency::Any),
+      Err(AllioError::ElementNotFound(id)) if id == bogus
+    ));
+    assert!(allio.tombstone(bogus).is_none());
+  }
+
+  #[test]
+  fn ids_from_another_session_are_rejected() {
+    let desktop = MockDesktop::acquire();
+    let window = desktop.open_window("Notes", "Untitled");
+
+    let earlier = desktop.start_allio().unwrap();
+    let stale = earlier.window_root(window.id).unwrap().unwrap().id;
+    assert_eq!(stale.epoch(), earlier.session());
+    drop(earlier);
+
+    let allio = desktop.start_allio().unwrap();
+    let root = allio.window_root(window.id).unwrap().unwrap().id;
+    assert_ne!(allio.session(), stale.epoch());
+    assert_eq!(root.epoch(), allio.session());
+    assert!(matches!(
+      allio.get(stale, Recency::Any),
+      Err(AllioError::ForeignElementId(id)) if id == stale
+    ));
+    assert!(matches!(
+      allio.children(stale, Recency::Current),
+      Err(AllioError::ForeignElementId(_))
+    ));
+    assert_eq!(allio.snapshot().session, allio.session());
+  }
+
+  #[test]
+  fn writes_reach_the_desktop() {
+    let desktop = MockDesktop::acquire();
+    let window = desktop.open_window("Notes", "Untitled");
+    let field = desktop
+      .add_element(window.node, MockElement::new(Role::TextField))
+      .unwrap();
+    let checkbox = desktop
+      .add_element(
+        window.node,
+        MockElement::new(Role::Checkbox)
+          .value(Value::Boolean(false))
+          .actions(&[Action::Press]),
+      )
+      .unwrap();
+
+    let allio = desktop.start_allio().unwrap();
+    let root = allio.window_root(window.id).unwrap().unwrap();
+    let children = allio.children(root.id, Recency::Current).unwrap();
+
+    allio
+      .set_value(children[0].id, &Value::String("typed".into()))
+      .unwrap();
+    assert_eq!(
+      desktop.element(field).unwrap().value,
+      Some(Value::String("typed".into()))
+    );
+
+    allio.perform_action(children[1].id, Action::Press).unwrap();
+    assert_eq!(
+      desktop.element(checkbox).unwrap().value,
+      Some(Value::Boolean(true))
+    );
+    assert_eq!(desktop.performed_actions(), vec![(checkbox, Action::Press)]);
+    assert!(allio.perform_action(children[0].id, Action::Press).is_err());
+  }
+
+  #[test]
+  fn element_at_hit_tests_deepest_element() {
+    let desktop = MockDesktop::acquire();
+    let window = desktop.open_window("Notes", "Untitled");
+    let group = desktop
+      .add_element(window.node, MockElement::new(Role::Group).bounds(MockDesktop::WINDOW_BOUNDS))
+      .unwrap();
+    desktop.add_element(
+      group,
+      MockElement::new(Role::Button)
+        .label("Inner")
+        .bounds(rect(10.0, 10.0, 50.0, 20.0)),
+    );
+
+    let allio = desktop.start_allio().unwrap();
+    let hit = allio.element_at(20.0, 15.0).unwrap().unwrap();
+    assert_eq!(hit.label.as_deref(), Some("Inner"));
+  }
+
+  /// Capture writer that can be read back while the capture still owns it.
+  #[derive(Clone, Default)]
+  struct Shared(Arc<parking_lot::Mutex<Vec<u8>>>);
+
+  impl std::io::Write for Shared {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+      self.0.lock().extend_from_slice(buf);
+      Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+      Ok(())
+    }
+  }
+
+  #[test]
+  fn captures_replay_without_the_desktop() {
+    let desktop = MockDesktop::acquire();
+    let window = desktop.open_window("Notes", "Untitled");
+    desktop.add_element(window.node, MockElement::new(Role::Button).label("Save"));
+    let field = desktop
+      .add_element(window.node, MockElement::new(Role::TextField))
+      .unwrap();
+
+    let out = Shared::default();
+    crate::capture::start(out.clone());
+    let allio = Allio::new().unwrap();
+    let mut rx = allio.subscribe();
+    desktop.wait_for_window(&allio, window.id);
+    let root = allio.window_root(window.id).unwrap().unwrap();
+    let field_id = allio.children(root.id, Recency::Current).unwrap()[1].id;
+    allio.watch(field_id).unwrap();
+    desktop.set_value(field, Value::String("hello".into()));
+    next_event(
+      &mut rx,
+      |e| matches!(e, Event::ElementChanged { element_id, .. } if *element_id == field_id),
+    );
+    crate::capture::stop().unwrap();
+    drop(allio);
+    drop(desktop);
+
+    // A fresh, empty desktop: everything below comes from the capture.
+    let desktop = MockDesktop::acquire();
+    let capture = Capture::read(out.0.lock().as_slice()).unwrap();
+    assert!(!capture.is_empty());
+    desktop.replay(capture);
+
+    let allio = Allio::new().unwrap();
+    let mut rx = allio.subscribe();
+    desktop.wait_for_window(&allio, window.id);
+    assert_eq!(allio.window(window.id).unwrap().title, "Untitled");
+    let root = allio.window_root(window.id).unwrap().unwrap();
+    let children = allio.children(root.id, Recency::Current).unwrap();
+    assert_eq!(children.len(), 2);
+    assert_eq!(children[0].label.as_deref(), Some("Save"));
+    assert_eq!(children[1].role, Role::TextField);
+    allio.watch(children[1].id).unwrap();
+
+    desktop.finish_replay();
+    let Event::ElementChanged { changes, .. } = next_event(
+      &mut rx,
+      |e| matches!(e, Event::ElementChanged { element_id, .. } if *element_id == children[1].id),
+    ) else {
+      unreachable!()
+    };
+    assert_eq!(
+      changes,
+      vec![ElementChange::Value {
+        old: None,
+        new: Some(Value::String("hello".into())),
+      }]
+    );
+  }
+
+  #[test]
+  fn captures_skip_callbacks_for_watches_made_before_them() {
+    let desktop = MockDesktop::acquire();
+    let window = desktop.open_window("Notes", "Untitled");
+    let field = desktop
+      .add_element(window.node, MockElement::new(Role::TextField))
+      .unwrap();
+    let allio = Allio::new().unwrap();
+    let mut rx = allio.subscribe();
+    desktop.wait_for_window(&allio, window.id);
+    let root = allio.window_root(window.id).unwrap().unwrap();
+    let field_id = allio.children(root.id, Recency::Current).unwrap()[0].id;
+    allio.watch(field_id).unwrap();
+
+    let out = Shared::default();
+    crate::capture::start(out.clone());
+    desktop.set_value(field, Value::String("hello".into()));
+    next_event(
+      &mut rx,
+      |e| matches!(e, Event::ElementChanged { element_id, .. } if *element_id == field_id),
+    );
+    crate::capture::stop().unwrap();
+
+    // The watch's handle was never recorded, so its callback can't be replayed.
+    let capture = Capture::read(out.0.lock().as_slice()).unwrap();
+    assert!(!capture
+      .entries
+      .iter()
+      .any(|entry| matches!(entry, Entry::Callback { .. })));
+  }
+
+  #[test]
+  fn registry_stays_consistent_under_faults() {
+    let desktop = MockDesktop::acquire();
+    let window = desktop.open_window("Notes", "Untitled");
+    let groups: Vec<_> = (0..4)
+      .filter_map(|g| {
+        let group = desktop.add_element(
+          window.node,
+          MockElement::new(Role::Group).label(format!("group {g}")),
+        )?;
+        for b in 0..4 {
+          desktop.add_element(
+            group,
+            MockElement::new(Role::TextField).label(format!("field {g}.{b}")),
+          );
+        }
+        Some(group)
+      })
+      .collect();
+
+    let injected = crate::faults::inject(Faults {
+      seed: 42,
+      latency: Duration::from_micros(200),
+      error_rate: 0.05,
+      stale_rate: 0.02,
+      spurious_destroyed_rate: 0.2,
+      reorder_rate: 0.3,
+      drop_rate: 0.2,
+    });
+    let allio = Allio::new().unwrap();
+    let root = eventually(|| allio.window_root(window.id).ok().flatten());
+    let config = ObserveConfig {
+      depth: None,
+      wait_between: Some(Duration::from_millis(5)),
+    };
+    let _observation = allio.observe(root.id, config).unwrap();
+
+    for round in 0..30 {
+      let group = groups[round % groups.len()];
+      match round % 3 {
+        0 => {
+          desktop.add_element(group, MockElement::new(Role::TextField));
+        }
+        1 => {
+          if let Some(&child) = desktop.children(group).first() {
+            desktop.remove_element(child);
+          }
+        }
+        _ => {
+          for child in desktop.children(group) {
+            desktop.set_value(child, Value::String(format!("round {round}")));
+          }
+        }
+      }
+      // Watch what is cached, so callbacks (and their faults) flow.
+      for element in allio.snapshot().elements {
+        drop(allio.watch(element.id));
+      }
+      std::thread::sleep(Duration::from_millis(10));
+      allio.read(Registry::check_consistency).unwrap();
+    }
+
+    desktop.remove_window(window.id);
+    drop(injected);
+    eventually(|| allio.window(window.id).is_none().then_some(()));
+    // Sweeps of the observed root must not bring the window's elements back.
+    std::thread::sleep(Duration::from_millis(50));
+    allio.read(Registry::check_consistency).unwrap();
+    assert!(allio
+      .snapshot()
+      .elements
+      .iter()
+      .all(|e| e.window_id != window.id));
+  }
+
+  #[test]
+  fn window_closed_mid_fetch_caches_nothing() {
+    let desktop = MockDesktop::acquire();
+    let window = desktop.open_window("Notes", "Untitled");
+    for i in 0..40 {
+      desktop.add_element(
+        window.node,
+        MockElement::new(Role::Button).label(format!("button {i}")),
+      );
+    }
+    let allio = Allio::new().unwrap();
+    let root = eventually(|| allio.window_root(window.id).ok().flatten());
+
+    // Every call takes up to 5ms, so fetching the children outlives the window.
+    let injected = crate::faults::inject(Faults {
+      latency: Duration::from_millis(5),
+      ..Faults::default()
+    });
+    std::thread::scope(|scope| {
+      let fetch = scope.spawn(|| allio.children(root.id, Recency::Current));
+      std::thread::sleep(Duration::from_millis(20));
+      desktop.remove_window(window.id);
+      eventually(|| allio.window(window.id).is_none().then_some(()));
+      drop(fetch.join().unwrap());
+    });
+    drop(injected);
+
+    allio.read(Registry::check_consistency).unwrap();
+    assert!(allio
+      .snapshot()
+      .elements
+      .iter()
+      .all(|e| e.window_id != window.id));
+  }
+
+  #[test]
+  fn injected_errors_fail_writes_and_empty_reads_until_cleared() {
+    let desktop = MockDesktop::acquire();
+    let window = desktop.open_window("Notes", "Untitled");
+    let field = desktop
+      .add_element(window.node, MockElement::new(Role::TextField))
+      .unwrap();
+    let allio = Allio::new().unwrap();
+    let root = eventually(|| allio.window_root(window.id).ok().flatten());
+    let field_id = allio.children(root.id, Recency::Current).unwrap()[0].id;
+
+    let injected = crate::faults::inject(Faults {
+      error_rate: 1.0,
+      ..Faults::default()
+    });
+    let value = Value::String("hello".into());
+    assert!(matches!(
+      allio.set_value(field_id, &value),
+      Err(AllioError::SetValueFailed { .. })
+    ));
+    assert_eq!(desktop.element(field).unwrap().value, None);
+    assert!(allio.children(root.id, Recency::Current).unwrap().is_empty());
+
+    drop(injected);
+    allio.set_value(field_id, &value).unwrap();
+    assert_eq!(desktop.element(field).unwrap().value, Some(value));
+    assert_eq!(allio.children(root.id, Recency::Current).unwrap().len(), 1);
+  }
+
+  #[test]
+  fn dropped_callbacks_never_reach_events() {
+    let desktop = MockDesktop::acquire();
+    let window = desktop.open_window("Notes", "Untitled");
+    let field = desktop
+      .add_element(window.node, MockElement::new(Role::TextField))
+      .unwrap();
+    let allio = Allio::new().unwrap();
+    let mut rx = allio.subscribe();
+    let root = eventually(|| allio.window_root(window.id).ok().flatten());
+    let field_id = allio.children(root.id, Recency::Current).unwrap()[0].id;
+    allio.watch(field_id).unwrap();
+
+    let injected = crate::faults::inject(Faults {
+      drop_rate: 1.0,
+      ..Faults::default()
+    });
+    desktop.set_value(field, Value::String("lost".into()));
+    std::thread::sleep(Duration::from_millis(50));
+    while let Ok(stamped) = rx.try_recv() {
+      assert!(
+        !matches!(stamped.event, Event::ElementChanged { element_id, .. } if element_id == field_id),
+        "dropped callback was delivered"
+      );
+    }
+
+    drop(injected);
+    desktop.set_value(field, Value::String("seen".into()));
+    next_event(
+      &mut rx,
+      |e| matches!(e, Event::ElementChanged { element_id, .. } if *element_id == field_id),
+    );
+  }
+
+  #[test]
+  fn reordered_children_keep_their_ids() {
+    let desktop = MockDesktop::acquire();
+    let window = desktop.open_window("Notes", "Untitled");
+    for i in 0..8 {
+      desktop.add_element(
+        window.node,
+        MockElement::new(Role::Button).label(format!("button {i}")),
+      );
+    }
+    let allio = Allio::new().unwrap();
+    let root = eventually(|| allio.window_root(window.id).ok().flatten());
+    let ids = |allio: &Allio| {
+      let mut ids: Vec<_> = allio
+        .children(root.id, Recency::Current)
+        .unwrap()
+        .into_iter()
+        .map(|c| (c.label, c.id))
+        .collect();
+      ids.sort_unstable_by(|a, b| a.0.cmp(&b.0));
+      ids
+    };
+    let before = ids(&allio);
+
+    let injected = crate::faults::inject(Faults {
+      seed: 3,
+      reorder_rate: 1.0,
+      ..Faults::default()
+    });
+    for _ in 0..5 {
+      assert_eq!(ids(&allio), before);
+      allio.read(Registry::check_consistency).unwrap();
+    }
+    drop(injected);
+  }
+}
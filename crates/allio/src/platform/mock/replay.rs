@@ -0,0 +1,146 @@
+/*!
+Replay of platform captures (see `platform/capture.rs`).
+
+While a capture is loaded, the mock platform answers calls from it instead of
+from the desktop. A call is matched to the recordings of the same method with
+the same arguments, in recorded order; once those run out, the last one repeats
+(polling keeps asking). Calls that were never recorded fall back to the desktop.
+
+Each recorded callback is delivered as soon as every call recorded before it
+has been replayed, so callbacks land at the same point relative to the calls
+as they did when captured.
+*/
+
+use std::collections::{HashMap, VecDeque};
+
+use crate::a11y::Action;
+use crate::platform::capture::{Callback, Capture, Entry};
+use crate::types::WindowId;
+
+/// A call and its arguments, with handles as capture IDs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) enum Key {
+  HasPermissions,
+  FetchWindows,
+  FetchScreenSize,
+  FetchMousePosition,
+  FetchWindowHandle(WindowId),
+  CreateObserver(u32),
+  AppElement(u32),
+  IsProcessRunning(u32),
+  FetchChildren(u64),
+  FetchParent(u64),
+  FetchAttributes(u64),
+  /// Coordinates as bits, so they hash.
+  FetchElementAtPosition(u64, u64, u64),
+  Window(u64),
+  SetValue(u64),
+  PerformAction(u64, Action),
+  SubscribeApp(u32),
+  CreateWatch(u64),
+}
+
+impl Key {
+  const fn of(entry: &Entry) -> Option<Self> {
+    Some(match entry {
+      Entry::HasPermissions { .. } => Self::HasPermissions,
+      Entry::FetchWindows { .. } => Self::FetchWindows,
+      Entry::FetchScreenSize { .. } => Self::FetchScreenSize,
+      Entry::FetchMousePosition { .. } => Self::FetchMousePosition,
+      Entry::FetchWindowHandle { window_id, .. } => Self::FetchWindowHandle(*window_id),
+      Entry::CreateObserver { pid, .. } => Self::CreateObserver(*pid),
+      Entry::AppElement { pid, .. } => Self::AppElement(*pid),
+      Entry::IsProcessRunning { pid, .. } => Self::IsProcessRunning(*pid),
+      Entry::FetchChildren { handle, .. } => Self::FetchChildren(*handle),
+      Entry::FetchParent { handle, .. } => Self::FetchParent(*handle),
+      Entry::FetchAttributes { handle, .. } => Self::FetchAttributes(*handle),
+      Entry::FetchElementAtPosition { handle, x, y, .. } => {
+        Self::FetchElementAtPosition(*handle, x.to_bits(), y.to_bits())
+      }
+      Entry::Window { handle, .. } => Self::Window(*handle),
+      Entry::SetValue { handle, .. } => Self::SetValue(*handle),
+      Entry::PerformAction { handle, action, .. } => Self::PerformAction(*handle, *action),
+      Entry::SubscribeApp { pid, .. } => Self::SubscribeApp(*pid),
+      Entry::CreateWatch { handle, .. } => Self::CreateWatch(*handle),
+      Entry::Callback { .. } => return None,
+    })
+  }
+}
+
+/// A loaded capture and how far it has been replayed.
+pub(crate) struct Replay {
+  entries: Vec<Entry>,
+  /// Unreplayed recordings of each call, in order.
+  pending: HashMap<Key, VecDeque<usize>>,
+  /// Most recently replayed recording of each call.
+  last: HashMap<Key, usize>,
+  replayed: Vec<bool>,
+  /// Everything before this entry has been replayed.
+  cursor: usize,
+}
+
+impl Replay {
+  pub(crate) fn new(capture: Capture) -> Self {
+    let mut pending: HashMap<Key, VecDeque<usize>> = HashMap::new();
+    for (index, entry) in capture.entries.iter().enumerate() {
+      if let Some(key) = Key::of(entry) {
+        pending.entry(key).or_default().push_back(index);
+      }
+    }
+    Self {
+      replayed: vec![false; capture.entries.len()],
+      entries: capture.entries,
+      pending,
+      last: HashMap::new(),
+      cursor: 0,
+    }
+  }
+
+  /// The next recording of a call, or its last one once they run out.
+  pub(crate) fn answer(&mut self, key: Key) -> Option<&Entry> {
+    let index = match self.pending.get_mut(&key).and_then(VecDeque::pop_front) {
+      Some(index) => {
+        if let Some(replayed) = self.replayed.get_mut(index) {
+          *replayed = true;
+        }
+        self.last.insert(key, index);
+        index
+      }
+      None => *self.last.get(&key)?,
+    };
+    self.entries.get(index)
+  }
+
+  /// Callbacks whose preceding calls have all been replayed, in recorded order.
+  pub(crate) fn due_callbacks(&mut self) -> Vec<Callback> {
+    let mut due = Vec::new();
+    while let Some(entry) = self.entries.get(self.cursor) {
+      if let Entry::Callback { callback } = entry {
+        due.push(callback.clone());
+      } else if !self.replayed.get(self.cursor).copied().unwrap_or(true) {
+        break;
+      }
+      self.cursor += 1;
+    }
+    due
+  }
+
+  /// Every callback not yet delivered, in recorded order, regardless of calls.
+  pub(crate) fn remaining_callbacks(&mut self) -> Vec<Callback> {
+    let remaining = self
+      .entries
+      .get(self.cursor..)
+      .unwrap_or_default()
+      .iter()
+      .filter_map(|entry| {
+        if let Entry::Callback { callback } = entry {
+          Some(callback.clone())
+        } else {
+          None
+        }
+      })
+      .collect();
+    self.cursor = self.entries.len();
+    remaining
+  }
+}
@@ -13,6 +13,10 @@ Core code should only import from this module - never from platform-specific sub
 - `Handle`/`Observer` type aliases: opaque handles for core code
 
 Core code uses `CurrentPlatform::method()` for platform operations.
+`CurrentPlatform` is the OS backend wrapped twice: `faults::Faulty` injects
+faults while configured (with the `faults` feature), and `capture::Captured`
+above it records calls while a capture is running (with the `capture`
+feature). Both are otherwise transparent.
 All platform-specific details (`CFType`, `AXUIElement`, etc.) stay hidden.
 
 # Adding a New Platform
@@ -20,8 +24,17 @@ All platform-specific details (`CFType`, `AXUIElement`, etc.) stay hidden.
 1. Create `platform/newos/mod.rs`
 2. Implement `Platform`, `PlatformHandle`, `PlatformObserver` traits
 3. Add conditional compilation in this file
+
+# Mock Platform
+
+The `mock` feature replaces the OS backend with an in-memory desktop that tests
+can script (see `platform/mock/`).
 */
 
+#[cfg(feature = "capture")]
+pub(crate) mod capture;
+#[cfg(feature = "faults")]
+pub(crate) mod faults;
 mod traits;
 
 pub(crate) use traits::{
@@ -31,23 +44,57 @@ pub(crate) use traits::{
 
 // === Platform Implementations ===
 
-#[cfg(target_os = "macos")]
+#[cfg(all(target_os = "macos", not(feature = "mock")))]
 pub(crate) mod macos;
 
-#[cfg(target_os = "windows")]
-compile_error!("Windows support is not yet implemented");
-
+// Always compiled on Linux so the backend is checked (and its tests run) with `mock` on.
 #[cfg(target_os = "linux")]
-compile_error!("Linux support is not yet implemented");
+#[cfg_attr(feature = "mock", allow(dead_code))]
+pub(crate) mod linux;
+
+#[cfg(feature = "mock")]
+pub(crate) mod mock;
+
+#[cfg(all(target_os = "windows", not(feature = "mock")))]
+compile_error!("Windows support is not yet implemented");
 
-#[cfg(not(any(target_os = "macos", target_os = "windows", target_os = "linux")))]
-compile_error!("Unsupported platform - Allio only supports macOS currently");
+#[cfg(not(any(
+  feature = "mock",
+  target_os = "macos",
+  target_os = "windows",
+  target_os = "linux"
+)))]
+compile_error!("Unsupported platform - Allio supports macOS and Linux");
 
 // === Type Aliases for Current Platform ===
 
-/// The platform implementation for the current OS.
-#[cfg(target_os = "macos")]
-pub(crate) type CurrentPlatform = macos::MacOS;
+/// The backend for the current OS.
+#[cfg(all(target_os = "macos", not(feature = "mock")))]
+pub(crate) type Backend = macos::MacOS;
+
+/// The backend for the current OS.
+#[cfg(all(target_os = "linux", not(feature = "mock")))]
+pub(crate) type Backend = linux::Linux;
+
+/// The in-memory backend (tests).
+#[cfg(feature = "mock")]
+pub(crate) type Backend = mock::Mock;
+
+/// The backend with fault injection layered on.
+#[cfg(feature = "faults")]
+pub(crate) type FaultyBackend = faults::Faulty;
+
+/// The backend as is: fault injection is compiled out.
+#[cfg(not(feature = "faults"))]
+pub(crate) type FaultyBackend = Backend;
+
+/// The platform core code calls: the backend, with faults and captures layered on.
+#[cfg(feature = "capture")]
+pub(crate) type CurrentPlatform = capture::Captured;
+
+/// The platform core code calls: captures are compiled out.
+#[cfg(not(feature = "capture"))]
+pub(crate) type CurrentPlatform = FaultyBackend;
 
 /// Opaque handle to a UI element.
 /// Core code can hold and clone this, but cannot inspect its contents.
@@ -8,6 +8,7 @@ Core code only uses these traits - never platform-specific types directly.
 
 #![allow(unsafe_code)]
 
+use serde::{Deserialize, Serialize};
 use std::hash::Hash;
 use std::sync::Arc;
 
@@ -41,7 +42,7 @@ pub(crate) enum ElementEvent<H> {
 }
 
 /// Attributes fetched from a platform element.
-#[derive(Debug, Default, Clone)]
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
 pub(crate) struct ElementAttributes {
   /// Semantic role (mapped from platform-specific role).
   pub role: crate::a11y::Role,
@@ -62,11 +63,18 @@ pub(crate) struct ElementAttributes {
   pub row_count: Option<usize>,
   pub column_count: Option<usize>,
   pub actions: Vec<crate::a11y::Action>,
-  /// Platform accessibility identifier (AXIdentifier on macOS).
+  /// Platform accessibility identifier (`AXIdentifier` on macOS).
   /// May provide stable identity across element moves if the app sets it.
   pub identifier: Option<String>,
 }
 
+impl ElementAttributes {
+  /// Whether these came from a destroyed element, which reports no role.
+  pub(crate) fn is_dead(&self) -> bool {
+    self.role == crate::a11y::Role::Unknown && self.platform_role.is_empty()
+  }
+}
+
 /// Callbacks from platform to core when OS events fire.
 pub(crate) trait EventHandler: Send + Sync + 'static {
   /// The handle type for this platform.
@@ -112,6 +120,9 @@ pub(crate) trait Platform {
 
   /// Get the root application element for a process.
   fn app_element(pid: u32) -> Self::Handle;
+
+  /// Check whether a process is still running.
+  fn is_process_running(pid: u32) -> bool;
 }
 
 /// Per-element operations. Clone is cheap (reference-counted).
@@ -164,8 +175,12 @@ pub(crate) trait PlatformObserver: Send + Sync {
 
 /// Handle to app-level notification subscriptions. Cleans up on drop.
 pub(crate) struct AppNotificationHandle {
-  #[cfg(target_os = "macos")]
+  #[cfg(all(target_os = "macos", not(feature = "mock")))]
   pub(crate) _inner: super::macos::AppNotificationHandleInner,
+  #[cfg(all(target_os = "linux", not(feature = "mock")))]
+  pub(crate) _inner: super::linux::AppNotificationHandleInner,
+  #[cfg(feature = "mock")]
+  pub(crate) _inner: super::mock::AppNotificationHandleInner,
 }
 
 unsafe impl Send for AppNotificationHandle {}
@@ -173,22 +188,22 @@ unsafe impl Sync for AppNotificationHandle {}
 
 /// Handle to notification subscriptions for an element. Unsubscribes on drop.
 pub(crate) struct WatchHandle {
-  #[cfg(target_os = "macos")]
+  #[cfg(all(target_os = "macos", not(feature = "mock")))]
   pub(crate) inner: super::macos::WatchHandleInner,
+  #[cfg(all(target_os = "linux", not(feature = "mock")))]
+  pub(crate) inner: super::linux::WatchHandleInner,
+  #[cfg(feature = "mock")]
+  pub(crate) inner: super::mock::WatchHandleInner,
 }
 
 impl WatchHandle {
   /// Add notifications to the watch set.
   pub(crate) fn add(&mut self, notifs: &[Notification]) -> usize {
-    #[cfg(target_os = "macos")]
-    {
-      self.inner.add(notifs)
-    }
+    self.inner.add(notifs)
   }
 
   /// Remove notifications from the watch set.
   pub(crate) fn remove(&mut self, notifs: &[Notification]) {
-    #[cfg(target_os = "macos")]
     self.inner.remove(notifs);
   }
 }
@@ -198,13 +213,16 @@ unsafe impl Sync for WatchHandle {}
 
 /// Handle to a display link (vsync callback). Stops on drop.
 pub(crate) struct DisplayLinkHandle {
-  #[cfg(target_os = "macos")]
+  #[cfg(all(target_os = "macos", not(feature = "mock")))]
   pub(crate) inner: super::macos::MacOSDisplayLinkHandle,
+  #[cfg(all(target_os = "linux", not(feature = "mock")))]
+  pub(crate) inner: super::linux::DisplayLinkHandleInner,
+  #[cfg(feature = "mock")]
+  pub(crate) inner: super::mock::DisplayLinkHandleInner,
 }
 
 impl DisplayLinkHandle {
   pub(crate) fn stop(&self) {
-    #[cfg(target_os = "macos")]
     self.inner.stop();
   }
 }
@@ -20,7 +20,6 @@ enum PollingImpl {
     stop_signal: Arc<AtomicBool>,
     thread: Option<JoinHandle<()>>,
   },
-  #[cfg(target_os = "macos")]
   DisplayLink(DisplayLinkHandle),
 }
 
@@ -41,7 +40,6 @@ impl PollingHandle {
       PollingImpl::Thread { stop_signal, .. } => {
         stop_signal.store(true, Ordering::SeqCst);
       }
-      #[cfg(target_os = "macos")]
       PollingImpl::DisplayLink(handle) => {
         handle.stop();
       }
@@ -158,7 +156,6 @@ impl Default for PollingConfig {
 }
 
 pub(crate) fn start_polling(allio: Allio, config: PollingConfig) -> PollingHandle {
-  #[cfg(target_os = "macos")]
   if config.use_display_link {
     if let Some(handle) = try_start_display_synced_polling(allio.clone(), config) {
       return handle;
@@ -179,10 +176,9 @@ fn start_thread_polling(allio: Allio, config: PollingConfig) -> PollingHandle {
 
       poll_iteration(&allio, &config);
 
-      let elapsed = loop_start.elapsed();
       let target = Duration::from_millis(config.interval_ms);
-      if elapsed < target {
-        thread::sleep(target - elapsed);
+      if let Some(remaining) = target.checked_sub(loop_start.elapsed()) {
+        thread::sleep(remaining);
       }
     }
   });
@@ -195,7 +191,6 @@ fn start_thread_polling(allio: Allio, config: PollingConfig) -> PollingHandle {
   }
 }
 
-#[cfg(target_os = "macos")]
 fn try_start_display_synced_polling(allio: Allio, config: PollingConfig) -> Option<PollingHandle> {
   let handle = CurrentPlatform::start_display_link(move || {
     poll_iteration(&allio, &config);
@@ -0,0 +1,346 @@
+/*!
+Multi-element selections.
+
+A [`Selection`] is an ordered set of element IDs with bulk operations that take
+the registry lock once per batch instead of once per element.
+
+```ignore
+let checkboxes = allio.select(|e| e.role == Role::Checkbox);
+let todo = Selection::from_elements(&allio.query(list_id, "listitem > checkbox", Recency::Any)?);
+
+// Press every checkbox in the list that is not already checked
+let unchecked = allio.select(|e| e.value == Some(Value::Boolean(false)));
+for result in todo.intersect(&unchecked).perform(&allio, Action::Press) {
+    result?;
+}
+```
+*/
+
+use std::collections::HashSet;
+
+use crate::a11y::{Action, Value};
+use crate::core::{Allio, Registry};
+use crate::types::{AllioError, AllioResult, Element, ElementId, Recency};
+
+/// Ordered set of element IDs. Duplicates are dropped, keeping the first occurrence.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Selection {
+  ids: Vec<ElementId>,
+}
+
+impl Selection {
+  /// Selection of the given IDs, in order.
+  pub fn new(ids: impl IntoIterator<Item = ElementId>) -> Self {
+    ids.into_iter().collect()
+  }
+
+  /// Selection of the given elements, in order.
+  pub fn from_elements(elements: &[Element]) -> Self {
+    elements.iter().map(|e| e.id).collect()
+  }
+
+  /// Selected IDs, in order.
+  pub fn ids(&self) -> &[ElementId] {
+    &self.ids
+  }
+
+  /// Iterate over selected IDs, in order.
+  pub fn iter(&self) -> std::slice::Iter<'_, ElementId> {
+    self.ids.iter()
+  }
+
+  /// Number of selected elements.
+  pub const fn len(&self) -> usize {
+    self.ids.len()
+  }
+
+  /// Whether nothing is selected.
+  pub const fn is_empty(&self) -> bool {
+    self.ids.is_empty()
+  }
+
+  /// Whether `id` is selected.
+  pub fn contains(&self, id: ElementId) -> bool {
+    self.ids.contains(&id)
+  }
+
+  /// Elements in either selection: `self` first, then new elements of `other`.
+  #[must_use]
+  pub fn union(&self, other: &Self) -> Self {
+    self.ids.iter().chain(&other.ids).copied().collect()
+  }
+
+  /// Elements in both selections, in `self` order.
+  #[must_use]
+  pub fn intersect(&self, other: &Self) -> Self {
+    let keep: HashSet<ElementId> = other.ids.iter().copied().collect();
+    self.filter(|id| keep.contains(&id))
+  }
+
+  /// Elements of `self` not in `other`, in `self` order.
+  #[must_use]
+  pub fn difference(&self, other: &Self) -> Self {
+    let drop: HashSet<ElementId> = other.ids.iter().copied().collect();
+    self.filter(|id| !drop.contains(&id))
+  }
+
+  fn filter(&self, keep: impl Fn(ElementId) -> bool) -> Self {
+    Self {
+      ids: self.ids.iter().copied().filter(|id| keep(*id)).collect(),
+    }
+  }
+
+  /// Get every selected element with one recency. Results are in selection order.
+  pub fn get(&self, allio: &Allio, recency: Recency) -> Vec<AllioResult<Element>> {
+    allio.get_each(&self.ids, recency)
+  }
+
+  /// Perform `action` on every selected element. Results are in selection order.
+  pub fn perform(&self, allio: &Allio, action: Action) -> Vec<AllioResult<()>> {
+    allio.perform_actions(&self.ids, action)
+  }
+
+  /// Set one value per selected element, in order.
+  ///
+  /// Fails without writing anything if `values` doesn't have one value per element.
+  pub fn set_values(&self, allio: &Allio, values: &[Value]) -> AllioResult<Vec<AllioResult<()>>> {
+    if values.len() != self.ids.len() {
+      return Err(AllioError::SetValueFailed {
+        reason: format!(
+          "{} values for a selection of {} elements",
+          values.len(),
+          self.ids.len()
+        ),
+      });
+    }
+    Ok(allio.set_values(&self.ids, values))
+  }
+}
+
+impl FromIterator<ElementId> for Selection {
+  fn from_iter<I: IntoIterator<Item = ElementId>>(iter: I) -> Self {
+    let mut seen = HashSet::new();
+    Self {
+      ids: iter.into_iter().filter(|id| seen.insert(*id)).collect(),
+    }
+  }
+}
+
+impl IntoIterator for Selection {
+  type Item = ElementId;
+  type IntoIter = std::vec::IntoIter<ElementId>;
+
+  fn into_iter(self) -> Self::IntoIter {
+    self.ids.into_iter()
+  }
+}
+
+impl<'a> IntoIterator for &'a Selection {
+  type Item = &'a ElementId;
+  type IntoIter = std::slice::Iter<'a, ElementId>;
+
+  fn into_iter(self) -> Self::IntoIter {
+    self.iter()
+  }
+}
+
+impl Allio {
+  /// Select cached elements matching `predicate`, under one registry lock.
+  ///
+  /// Results are in document order: windows front to back, each element before its
+  /// children. Only cached elements are considered; use [`Allio::query`] to fetch a
+  /// subtree first.
+  pub fn select(&self, predicate: impl Fn(&Element) -> bool) -> Selection {
+    self.read(|r| {
+      let ids = document_order(r)
+        .into_iter()
+        .filter_map(|id| crate::core::build_element(r, id))
+        .filter(|e| predicate(e))
+        .map(|e| e.id)
+        .collect();
+      Selection { ids }
+    })
+  }
+}
+
+/// Every cached element, each before its children. Trees are ordered by their
+/// window's z-order (front first); elements of unknown windows come last.
+fn document_order(registry: &Registry) -> Vec<ElementId> {
+  let z_index = |window_id| {
+    registry
+      .z_order()
+      .iter()
+      .position(|w| *w == window_id)
+      .unwrap_or(usize::MAX)
+  };
+  let mut roots: Vec<(usize, u32, ElementId)> = registry
+    .elements()
+    .filter(|(id, _)| registry.tree_parent(*id).is_none())
+    .map(|(id, elem)| (z_index(elem.window_id), id.counter(), id))
+    .collect();
+  roots.sort_unstable_by_key(|(z, counter, _)| (*z, *counter));
+
+  let mut order = Vec::new();
+  let mut stack: Vec<ElementId> = roots.into_iter().rev().map(|(_, _, id)| id).collect();
+  while let Some(id) = stack.pop() {
+    order.push(id);
+    stack.extend(registry.tree_children(id).iter().rev());
+  }
+  order
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn ids(selection: &Selection) -> Vec<u64> {
+    selection.ids().iter().map(|id| id.0).collect()
+  }
+
+  fn selection(ids: &[u64]) -> Selection {
+    ids.iter().map(|id| ElementId(*id)).collect()
+  }
+
+  #[test]
+  fn deduplicates_keeping_first_occurrence() {
+    let s = selection(&[3, 1, 3, 2, 1]);
+    assert_eq!(ids(&s), [3, 1, 2]);
+    assert_eq!(s.len(), 3);
+    assert!(s.contains(ElementId(2)));
+    assert!(!s.contains(ElementId(4)));
+    assert!(Selection::default().is_empty());
+  }
+
+  #[test]
+  fn set_operations_preserve_order() {
+    let a = selection(&[1, 2, 3, 4]);
+    let b = selection(&[5, 3, 1]);
+    assert_eq!(ids(&a.union(&b)), [1, 2, 3, 4, 5]);
+    assert_eq!(ids(&a.intersect(&b)), [1, 3]);
+    assert_eq!(ids(&a.difference(&b)), [2, 4]);
+    assert_eq!(ids(&b.difference(&a)), [5]);
+    assert!(a.intersect(&Selection::default()).is_empty());
+  }
+
+  #[cfg(feature = "mock")]
+  mod bulk {
+    use super::super::*;
+    use crate::a11y::Role;
+    use crate::mock::{MockDesktop, MockElement, MockNodeId};
+
+    /// A window with three checkboxes (the middle one checked) and a text field.
+    fn setup() -> (MockDesktop, Allio, Vec<MockNodeId>, Vec<Element>) {
+      let desktop = MockDesktop::acquire();
+      let window = desktop.open_window("Todos", "Todos");
+      let mut nodes = Vec::new();
+      for checked in [false, true, false] {
+        let node = desktop
+          .add_element(
+            window.node,
+            MockElement::new(Role::Checkbox)
+              .value(Value::Boolean(checked))
+              .actions(&[Action::Press]),
+          )
+          .unwrap();
+        nodes.push(node);
+      }
+      nodes.push(
+        desktop
+          .add_element(window.node, MockElement::new(Role::TextField))
+          .unwrap(),
+      );
+
+      let allio = desktop.start_allio().unwrap();
+      let root = allio.window_root(window.id).unwrap().unwrap();
+      let children = allio.children(root.id, Recency::Current).unwrap();
+      (desktop, allio, nodes, children)
+    }
+
+    #[test]
+    fn selects_by_predicate_and_performs_on_all() {
+      let (desktop, allio, nodes, children) = setup();
+      let checkboxes = allio.select(|e| e.role == Role::Checkbox);
+      let unchecked = allio.select(|e| e.value == Some(Value::Boolean(false)));
+      assert_eq!(
+        checkboxes.ids(),
+        [children[0].id, children[1].id, children[2].id]
+      );
+
+      let targets = checkboxes.intersect(&unchecked);
+      assert_eq!(targets.ids(), [children[0].id, children[2].id]);
+      let results = targets.perform(&allio, Action::Press);
+      assert!(results.iter().all(Result::is_ok));
+      for node in &nodes[..3] {
+        assert_eq!(
+          desktop.element(*node).unwrap().value,
+          Some(Value::Boolean(true))
+        );
+      }
+
+      let fresh = checkboxes.get(&allio, Recency::Current);
+      assert!(fresh
+        .iter()
+        .all(|e| e.as_ref().unwrap().value == Some(Value::Boolean(true))));
+    }
+
+    #[test]
+    fn selects_in_document_order() {
+      let desktop = MockDesktop::acquire();
+      let window = desktop.open_window("Todos", "Todos");
+      desktop.add_element(
+        window.node,
+        MockElement::new(Role::Checkbox).label("second"),
+      );
+      let allio = desktop.start_allio().unwrap();
+      let root = allio.window_root(window.id).unwrap().unwrap();
+      allio.children(root.id, Recency::Current).unwrap();
+
+      // Discovered later, but first among its siblings.
+      desktop.insert_element(
+        window.node,
+        0,
+        MockElement::new(Role::Checkbox).label("first"),
+      );
+      let children = allio.children(root.id, Recency::Current).unwrap();
+      assert!(children[0].id.counter() > children[1].id.counter());
+
+      let checkboxes = allio.select(|e| e.role == Role::Checkbox);
+      assert_eq!(checkboxes.ids(), [children[0].id, children[1].id]);
+    }
+
+    #[test]
+    fn reports_per_element_failures() {
+      let (desktop, allio, nodes, children) = setup();
+      let field = children[3].id;
+      let missing = ElementId::new(field.epoch(), 999_999);
+      let selection = Selection::new([children[0].id, field, missing]);
+
+      let results = selection.perform(&allio, Action::Press);
+      assert!(results[0].is_ok());
+      assert!(results[1].is_err());
+      assert!(matches!(results[2], Err(AllioError::ElementNotFound(id)) if id == missing));
+
+      let results = selection
+        .set_values(
+          &allio,
+          &[
+            Value::String("wrong type".into()),
+            Value::String("typed".into()),
+            Value::Boolean(true),
+          ],
+        )
+        .unwrap();
+      assert!(matches!(results[0], Err(AllioError::TypeMismatch { .. })));
+      assert!(results[1].is_ok());
+      assert!(results[2].is_err());
+      assert_eq!(
+        desktop.element(nodes[3]).unwrap().value,
+        Some(Value::String("typed".into()))
+      );
+
+      assert!(selection.set_values(&allio, &[]).is_err());
+      let gotten = selection.get(&allio, Recency::Any);
+      assert!(gotten[0].is_ok() && gotten[1].is_ok() && gotten[2].is_err());
+    }
+  }
+}
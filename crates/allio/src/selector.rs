@@ -0,0 +1,1007 @@
+/*!
+CSS-like selectors over the element tree.
+
+Selectors are evaluated against the registry by [`Allio::query`](crate::Allio::query).
+
+## Syntax
+
+```text
+button                        role (case-insensitive, serialized role names)
+*                             any element
+[label]                       attribute is present
+[label="Save"]                exact match (also ^= prefix, $= suffix, *= contains, ~= word)
+[label^="todo" i]             case-insensitive match
+:focused :disabled :enabled   states (also :selected :expanded :collapsed :checked)
+:first-child :last-child      position among siblings
+:nth-child(2n+1)              also :nth-child(odd), :nth-child(even), :nth-child(3)
+list > listitem               child combinator
+window textfield              descendant combinator
+button, link                  selector list
+```
+
+Attributes: `label`, `description`, `placeholder`, `url`, `value`, `identifier`,
+`role`, `platform_role`. Values are compared as strings (`value` uses
+[`Value::into_string`](crate::a11y::Value::into_string)).
+
+The query root itself is a candidate, and combinators never look above it.
+Position pseudo-classes only match elements whose parent has all its children
+loaded; a fetching query loads the root's siblings for them.
+*/
+
+use serde::de::IntoDeserializer;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+
+use crate::a11y::{Role, Value};
+use crate::core::registry::CachedElement;
+use crate::core::Registry;
+use crate::types::{AllioError, AllioResult, ElementId};
+
+/// A parsed selector list, e.g. `list > listitem[label^="Todo"]:first-child, button`.
+///
+/// Parse with [`Selector::parse`] or [`str::parse`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Selector {
+  source: String,
+  alternatives: Vec<Complex>,
+}
+
+/// Compound selectors joined by combinators. `steps[0]` is the leftmost.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Complex {
+  steps: Vec<Step>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Step {
+  /// How this step relates to the previous one (None for the first step).
+  combinator: Option<Combinator>,
+  compound: Compound,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Combinator {
+  Child,
+  Descendant,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct Compound {
+  /// None for `*` or when only attributes/pseudo-classes are given.
+  role: Option<Role>,
+  attributes: Vec<AttributeSelector>,
+  pseudos: Vec<Pseudo>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct AttributeSelector {
+  attribute: Attribute,
+  /// None for a presence test (`[label]`).
+  test: Option<(Operator, String)>,
+  case_insensitive: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Attribute {
+  Label,
+  Description,
+  Placeholder,
+  Url,
+  Value,
+  Identifier,
+  Role,
+  PlatformRole,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Operator {
+  Equals,
+  Prefix,
+  Suffix,
+  Contains,
+  Word,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Pseudo {
+  Focused,
+  Disabled,
+  Enabled,
+  Selected,
+  Expanded,
+  Collapsed,
+  Checked,
+  FirstChild,
+  LastChild,
+  /// `an+b`, 1-based.
+  NthChild {
+    a: i64,
+    b: i64,
+  },
+}
+
+impl Selector {
+  /// Parse a selector list.
+  pub fn parse(source: &str) -> AllioResult<Self> {
+    let mut parser = Parser {
+      source,
+      chars: source.chars().collect(),
+      pos: 0,
+    };
+    let alternatives = parser.parse_list()?;
+    Ok(Self {
+      source: source.trim().to_owned(),
+      alternatives,
+    })
+  }
+
+  /// Does the element `id` match, with combinators confined to the subtree of `scope`?
+  fn matches(&self, registry: &Registry, scope: ElementId, id: ElementId, memo: &mut Memo) -> bool {
+    self
+      .alternatives
+      .iter()
+      .enumerate()
+      .any(|(alternative, complex)| complex.matches(registry, scope, id, (alternative, memo)))
+  }
+
+  /// Does any alternative test an element's position among its siblings?
+  pub(crate) fn uses_position(&self) -> bool {
+    self.alternatives.iter().any(|complex| {
+      complex.steps.iter().any(|step| {
+        step.compound.pseudos.iter().any(|pseudo| {
+          matches!(
+            pseudo,
+            Pseudo::FirstChild | Pseudo::LastChild | Pseudo::NthChild { .. }
+          )
+        })
+      })
+    })
+  }
+
+  /// Matching elements in the cached subtree of `scope` (inclusive), in document order.
+  pub(crate) fn select(&self, registry: &Registry, scope: ElementId) -> Vec<ElementId> {
+    let mut matched = Vec::new();
+    let mut memo = Memo::new();
+    let mut stack = vec![scope];
+    while let Some(id) = stack.pop() {
+      if self.matches(registry, scope, id, &mut memo) {
+        matched.push(id);
+      }
+      stack.extend(registry.tree_children(id).iter().rev());
+    }
+    matched
+  }
+}
+
+impl FromStr for Selector {
+  type Err = AllioError;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    Self::parse(s)
+  }
+}
+
+impl fmt::Display for Selector {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.write_str(&self.source)
+  }
+}
+
+// ============================================================================
+// Matching
+// ============================================================================
+
+/// Step results by (alternative, step, element), shared while selecting so a
+/// descendant combinator tries each ancestor once per step instead of once per
+/// path to it.
+type Memo = HashMap<(usize, usize, ElementId), bool>;
+
+impl Complex {
+  fn matches(
+    &self,
+    registry: &Registry,
+    scope: ElementId,
+    id: ElementId,
+    memo: (usize, &mut Memo),
+  ) -> bool {
+    self
+      .steps
+      .len()
+      .checked_sub(1)
+      .is_some_and(|last| self.matches_step(registry, scope, last, id, memo))
+  }
+
+  /// Match step `index` against `id`, then the steps to its left against its ancestors.
+  fn matches_step(
+    &self,
+    registry: &Registry,
+    scope: ElementId,
+    index: usize,
+    id: ElementId,
+    (alternative, memo): (usize, &mut Memo),
+  ) -> bool {
+    let key = (alternative, index, id);
+    if let Some(&matched) = memo.get(&key) {
+      return matched;
+    }
+    let matched = self.match_step(registry, scope, index, id, (alternative, &mut *memo));
+    memo.insert(key, matched);
+    matched
+  }
+
+  fn match_step(
+    &self,
+    registry: &Registry,
+    scope: ElementId,
+    index: usize,
+    id: ElementId,
+    (alternative, memo): (usize, &mut Memo),
+  ) -> bool {
+    let Some(step) = self.steps.get(index) else {
+      return false;
+    };
+    if !registry
+      .element(id)
+      .is_some_and(|elem| step.compound.matches(registry, id, elem))
+    {
+      return false;
+    }
+    let Some(combinator) = step.combinator else {
+      return true;
+    };
+    let previous = index - 1;
+    let mut ancestor = parent_within(registry, scope, id);
+    match combinator {
+      Combinator::Child => ancestor.is_some_and(|parent| {
+        self.matches_step(registry, scope, previous, parent, (alternative, memo))
+      }),
+      Combinator::Descendant => {
+        while let Some(candidate) = ancestor {
+          if self.matches_step(
+            registry,
+            scope,
+            previous,
+            candidate,
+            (alternative, &mut *memo),
+          ) {
+            return true;
+          }
+          ancestor = parent_within(registry, scope, candidate);
+        }
+        false
+      }
+    }
+  }
+}
+
+/// Parent of `id`, unless `id` is the scope root.
+fn parent_within(registry: &Registry, scope: ElementId, id: ElementId) -> Option<ElementId> {
+  if id == scope {
+    None
+  } else {
+    registry.tree_parent(id)
+  }
+}
+
+impl Compound {
+  fn matches(&self, registry: &Registry, id: ElementId, elem: &CachedElement) -> bool {
+    self.role.is_none_or(|role| elem.role == role)
+      && self.attributes.iter().all(|attr| attr.matches(elem))
+      && self
+        .pseudos
+        .iter()
+        .all(|pseudo| pseudo.matches(registry, id, elem))
+  }
+}
+
+impl AttributeSelector {
+  fn matches(&self, elem: &CachedElement) -> bool {
+    let Some(actual) = self.attribute.read(elem) else {
+      return false;
+    };
+    let Some((operator, expected)) = &self.test else {
+      return true;
+    };
+    let (actual, expected) = if self.case_insensitive {
+      (actual.to_lowercase(), expected.to_lowercase())
+    } else {
+      (actual, expected.clone())
+    };
+    match operator {
+      Operator::Equals => actual == expected,
+      Operator::Prefix => !expected.is_empty() && actual.starts_with(&expected),
+      Operator::Suffix => !expected.is_empty() && actual.ends_with(&expected),
+      Operator::Contains => !expected.is_empty() && actual.contains(&expected),
+      Operator::Word => actual.split_whitespace().any(|word| word == expected),
+    }
+  }
+}
+
+impl Attribute {
+  fn from_name(name: &str) -> Option<Self> {
+    Some(match name {
+      "label" => Self::Label,
+      "description" => Self::Description,
+      "placeholder" => Self::Placeholder,
+      "url" => Self::Url,
+      "value" => Self::Value,
+      "identifier" => Self::Identifier,
+      "role" => Self::Role,
+      "platform_role" => Self::PlatformRole,
+      _ => return None,
+    })
+  }
+
+  fn read(self, elem: &CachedElement) -> Option<String> {
+    match self {
+      Self::Label => elem.label.clone(),
+      Self::Description => elem.description.clone(),
+      Self::Placeholder => elem.placeholder.clone(),
+      Self::Url => elem.url.clone(),
+      Self::Value => elem.value.clone().map(Value::into_string),
+      Self::Identifier => elem.identifier.clone(),
+      // Debug names lowercase to the serialized role names.
+      Self::Role => Some(format!("{:?}", elem.role).to_lowercase()),
+      Self::PlatformRole => Some(elem.platform_role.clone()),
+    }
+  }
+}
+
+impl Pseudo {
+  fn matches(self, registry: &Registry, id: ElementId, elem: &CachedElement) -> bool {
+    match self {
+      Self::Focused => elem.focused == Some(true),
+      Self::Disabled => elem.disabled,
+      Self::Enabled => !elem.disabled,
+      Self::Selected => elem.selected == Some(true),
+      Self::Expanded => elem.expanded == Some(true),
+      Self::Collapsed => elem.expanded == Some(false),
+      Self::Checked => elem.value == Some(Value::Boolean(true)),
+      Self::FirstChild => sibling_position(registry, id).is_some_and(|(index, _)| index == 1),
+      Self::LastChild => {
+        sibling_position(registry, id).is_some_and(|(index, count)| index == count)
+      }
+      Self::NthChild { a, b } => {
+        sibling_position(registry, id).is_some_and(|(index, _)| nth_matches(a, b, index))
+      }
+    }
+  }
+}
+
+/// 1-based index among siblings, and sibling count. None unless all of the
+/// parent's children are loaded: a page of them would give the wrong position.
+fn sibling_position(registry: &Registry, id: ElementId) -> Option<(i64, i64)> {
+  let parent = registry.tree_parent(id)?;
+  if !registry.tree_children_loaded(parent) {
+    return None;
+  }
+  let siblings = registry.tree_children(parent);
+  let index = siblings.iter().position(|sibling| *sibling == id)?;
+  Some((
+    i64::try_from(index).ok()? + 1,
+    i64::try_from(siblings.len()).ok()?,
+  ))
+}
+
+/// Is `index = a*n + b` for some n >= 0? Arithmetic overflow means no match.
+const fn nth_matches(a: i64, b: i64, index: i64) -> bool {
+  let Some(offset) = index.checked_sub(b) else {
+    return false;
+  };
+  if a == 0 {
+    return offset == 0;
+  }
+  matches!(
+    (offset.checked_rem(a), offset.checked_div(a)),
+    (Some(0), Some(n)) if n >= 0
+  )
+}
+
+// ============================================================================
+// Parsing
+// ============================================================================
+
+struct Parser<'a> {
+  source: &'a str,
+  chars: Vec<char>,
+  pos: usize,
+}
+
+impl Parser<'_> {
+  fn error(&self, reason: impl Into<String>) -> AllioError {
+    AllioError::InvalidSelector {
+      selector: self.source.to_owned(),
+      reason: reason.into(),
+    }
+  }
+
+  fn unexpected(&self) -> AllioError {
+    match self.peek() {
+      Some(c) => self.error(format!("unexpected '{c}' at position {}", self.pos)),
+      None => self.error("unexpected end of selector"),
+    }
+  }
+
+  fn peek(&self) -> Option<char> {
+    self.chars.get(self.pos).copied()
+  }
+
+  fn bump(&mut self) -> Option<char> {
+    let c = self.peek()?;
+    self.pos += 1;
+    Some(c)
+  }
+
+  fn eat(&mut self, expected: char) -> bool {
+    if self.peek() == Some(expected) {
+      self.pos += 1;
+      true
+    } else {
+      false
+    }
+  }
+
+  fn expect(&mut self, expected: char) -> AllioResult<()> {
+    if self.eat(expected) {
+      Ok(())
+    } else {
+      Err(self.unexpected())
+    }
+  }
+
+  /// Skip whitespace. Returns whether any was skipped.
+  fn skip_whitespace(&mut self) -> bool {
+    let start = self.pos;
+    while self.peek().is_some_and(char::is_whitespace) {
+      self.pos += 1;
+    }
+    self.pos > start
+  }
+
+  fn ident(&mut self) -> String {
+    let mut ident = String::new();
+    while let Some(c) = self
+      .peek()
+      .filter(|c| c.is_alphanumeric() || *c == '-' || *c == '_')
+    {
+      ident.push(c);
+      self.pos += 1;
+    }
+    ident
+  }
+
+  fn parse_list(&mut self) -> AllioResult<Vec<Complex>> {
+    let mut alternatives = Vec::new();
+    loop {
+      self.skip_whitespace();
+      alternatives.push(self.parse_complex()?);
+      if self.peek().is_none() {
+        return Ok(alternatives);
+      }
+      self.expect(',')?;
+    }
+  }
+
+  fn parse_complex(&mut self) -> AllioResult<Complex> {
+    let mut steps = vec![Step {
+      combinator: None,
+      compound: self.parse_compound()?,
+    }];
+    loop {
+      let had_whitespace = self.skip_whitespace();
+      let combinator = match self.peek() {
+        None | Some(',') => return Ok(Complex { steps }),
+        Some('>') => {
+          self.pos += 1;
+          self.skip_whitespace();
+          Combinator::Child
+        }
+        Some(_) if had_whitespace => Combinator::Descendant,
+        Some(_) => return Err(self.unexpected()),
+      };
+      steps.push(Step {
+        combinator: Some(combinator),
+        compound: self.parse_compound()?,
+      });
+    }
+  }
+
+  fn parse_compound(&mut self) -> AllioResult<Compound> {
+    let start = self.pos;
+    let mut compound = Compound::default();
+
+    if !self.eat('*') {
+      let name = self.ident();
+      if !name.is_empty() {
+        compound.role = Some(self.parse_role(&name)?);
+      }
+    }
+    loop {
+      match self.peek() {
+        Some('[') => compound.attributes.push(self.parse_attribute()?),
+        Some(':') => compound.pseudos.push(self.parse_pseudo()?),
+        _ => break,
+      }
+    }
+
+    if self.pos == start {
+      return Err(match self.peek() {
+        Some(_) => self.unexpected(),
+        None => self.error("expected a selector"),
+      });
+    }
+    Ok(compound)
+  }
+
+  fn parse_role(&self, name: &str) -> AllioResult<Role> {
+    Role::deserialize(name.to_lowercase().into_deserializer())
+      .map_err(|_: serde::de::value::Error| self.error(format!("unknown role '{name}'")))
+  }
+
+  fn parse_attribute(&mut self) -> AllioResult<AttributeSelector> {
+    self.expect('[')?;
+    self.skip_whitespace();
+    let name = self.ident();
+    let attribute = Attribute::from_name(&name)
+      .ok_or_else(|| self.error(format!("unknown attribute '{name}'")))?;
+    self.skip_whitespace();
+
+    if self.eat(']') {
+      return Ok(AttributeSelector {
+        attribute,
+        test: None,
+        case_insensitive: false,
+      });
+    }
+
+    let operator = match self.peek() {
+      Some('=') => Operator::Equals,
+      Some('^') => Operator::Prefix,
+      Some('$') => Operator::Suffix,
+      Some('*') => Operator::Contains,
+      Some('~') => Operator::Word,
+      _ => return Err(self.unexpected()),
+    };
+    self.pos += 1;
+    if operator != Operator::Equals {
+      self.expect('=')?;
+    }
+    self.skip_whitespace();
+    let value = self.parse_value()?;
+    self.skip_whitespace();
+
+    let case_insensitive = match self.peek() {
+      Some('i' | 'I') => true,
+      Some('s' | 'S') => false,
+      _ => {
+        self.expect(']')?;
+        return Ok(AttributeSelector {
+          attribute,
+          test: Some((operator, value)),
+          case_insensitive: false,
+        });
+      }
+    };
+    self.pos += 1;
+    self.skip_whitespace();
+    self.expect(']')?;
+    Ok(AttributeSelector {
+      attribute,
+      test: Some((operator, value)),
+      case_insensitive,
+    })
+  }
+
+  /// A quoted string (with `\` escapes) or a bare word.
+  fn parse_value(&mut self) -> AllioResult<String> {
+    let Some(quote @ ('"' | '\'')) = self.peek() else {
+      let value = self.ident();
+      return if value.is_empty() {
+        Err(self.unexpected())
+      } else {
+        Ok(value)
+      };
+    };
+    self.pos += 1;
+    let mut value = String::new();
+    loop {
+      match self.bump() {
+        None => return Err(self.error("unterminated string")),
+        Some('\\') => value.push(
+          self
+            .bump()
+            .ok_or_else(|| self.error("unterminated string"))?,
+        ),
+        Some(c) if c == quote => return Ok(value),
+        Some(c) => value.push(c),
+      }
+    }
+  }
+
+  fn parse_pseudo(&mut self) -> AllioResult<Pseudo> {
+    self.expect(':')?;
+    let name = self.ident().to_lowercase();
+    Ok(match name.as_str() {
+      "focused" | "focus" => Pseudo::Focused,
+      "disabled" => Pseudo::Disabled,
+      "enabled" => Pseudo::Enabled,
+      "selected" => Pseudo::Selected,
+      "expanded" => Pseudo::Expanded,
+      "collapsed" => Pseudo::Collapsed,
+      "checked" => Pseudo::Checked,
+      "first-child" => Pseudo::FirstChild,
+      "last-child" => Pseudo::LastChild,
+      "nth-child" => {
+        self.expect('(')?;
+        let start = self.pos;
+        while self.peek().is_some_and(|c| c != ')') {
+          self.pos += 1;
+        }
+        let argument: String = self
+          .chars
+          .get(start..self.pos)
+          .unwrap_or_default()
+          .iter()
+          .collect();
+        self.expect(')')?;
+        let (a, b) = parse_nth(&argument).ok_or_else(|| {
+          self.error(format!("invalid :nth-child argument '{}'", argument.trim()))
+        })?;
+        Pseudo::NthChild { a, b }
+      }
+      "" => return Err(self.unexpected()),
+      _ => return Err(self.error(format!("unknown pseudo-class ':{name}'"))),
+    })
+  }
+}
+
+/// Parse an `:nth-child` argument: `odd`, `even`, `b`, `an`, `an+b`, `-n+b`.
+fn parse_nth(argument: &str) -> Option<(i64, i64)> {
+  let argument: String = argument
+    .chars()
+    .filter(|c| !c.is_whitespace())
+    .collect::<String>()
+    .to_lowercase();
+  match argument.as_str() {
+    "odd" => return Some((2, 1)),
+    "even" => return Some((2, 0)),
+    _ => {}
+  }
+  let Some((a, b)) = argument.split_once('n') else {
+    return argument.parse().ok().map(|b| (0, b));
+  };
+  let a = match a {
+    "" | "+" => 1,
+    "-" => -1,
+    _ => a.parse().ok()?,
+  };
+  let b = match b {
+    "" => 0,
+    _ if b.starts_with(['+', '-']) => b.parse().ok()?,
+    _ => return None,
+  };
+  Some((a, b))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn compound(selector: &str) -> Compound {
+    let parsed = Selector::parse(selector).unwrap();
+    assert_eq!(parsed.alternatives.len(), 1);
+    assert_eq!(parsed.alternatives[0].steps.len(), 1);
+    parsed.alternatives[0].steps[0].compound.clone()
+  }
+
+  fn reason(selector: &str) -> String {
+    match Selector::parse(selector) {
+      Err(AllioError::InvalidSelector { reason, .. }) => reason,
+      other => panic!("expected InvalidSelector for {selector:?}, got {other:?}"),
+    }
+  }
+
+  #[test]
+  fn parses_roles_case_insensitively() {
+    assert_eq!(compound("button").role, Some(Role::Button));
+    assert_eq!(compound("TextField").role, Some(Role::TextField));
+    assert_eq!(compound("*").role, None);
+    assert_eq!(reason("widget"), "unknown role 'widget'");
+  }
+
+  #[test]
+  fn parses_attributes() {
+    let attrs =
+      compound(r#"listitem[label^="Todo" i][value="a \"b\""][identifier=save][url]"#).attributes;
+    assert_eq!(
+      attrs,
+      vec![
+        AttributeSelector {
+          attribute: Attribute::Label,
+          test: Some((Operator::Prefix, "Todo".into())),
+          case_insensitive: true,
+        },
+        AttributeSelector {
+          attribute: Attribute::Value,
+          test: Some((Operator::Equals, "a \"b\"".into())),
+          case_insensitive: false,
+        },
+        AttributeSelector {
+          attribute: Attribute::Identifier,
+          test: Some((Operator::Equals, "save".into())),
+          case_insensitive: false,
+        },
+        AttributeSelector {
+          attribute: Attribute::Url,
+          test: None,
+          case_insensitive: false,
+        },
+      ]
+    );
+    assert_eq!(reason("[size=1]"), "unknown attribute 'size'");
+    assert_eq!(reason("[label=\"open"), "unterminated string");
+  }
+
+  #[test]
+  fn parses_pseudo_classes() {
+    assert_eq!(
+      compound(":focused:disabled:first-child:nth-child(odd):nth-child(-n + 3)").pseudos,
+      vec![
+        Pseudo::Focused,
+        Pseudo::Disabled,
+        Pseudo::FirstChild,
+        Pseudo::NthChild { a: 2, b: 1 },
+        Pseudo::NthChild { a: -1, b: 3 },
+      ]
+    );
+    assert_eq!(reason(":hover"), "unknown pseudo-class ':hover'");
+    assert_eq!(reason(":nth-child(x)"), "invalid :nth-child argument 'x'");
+  }
+
+  #[test]
+  fn parses_combinators_and_lists() {
+    let parsed = Selector::parse(" tree > listitem  textfield , button ").unwrap();
+    assert_eq!(parsed.to_string(), "tree > listitem  textfield , button");
+    assert_eq!(parsed.alternatives.len(), 2);
+    let combinators: Vec<_> = parsed.alternatives[0]
+      .steps
+      .iter()
+      .map(|step| step.combinator)
+      .collect();
+    assert_eq!(
+      combinators,
+      vec![None, Some(Combinator::Child), Some(Combinator::Descendant)]
+    );
+  }
+
+  #[test]
+  fn rejects_malformed_selectors() {
+    assert_eq!(reason(""), "expected a selector");
+    assert_eq!(reason("button >"), "expected a selector");
+    assert_eq!(reason("button,"), "expected a selector");
+    assert_eq!(reason("> button"), "unexpected '>' at position 0");
+    assert_eq!(reason("button!"), "unexpected '!' at position 6");
+    assert_eq!(reason("[label"), "unexpected end of selector");
+    assert_eq!(reason("[label!x]"), "unexpected '!' at position 6");
+    assert!(matches!(
+      "list >> item".parse::<Selector>(),
+      Err(AllioError::InvalidSelector { .. })
+    ));
+  }
+
+  #[test]
+  fn nth_child_formula() {
+    assert_eq!(parse_nth("3"), Some((0, 3)));
+    assert_eq!(parse_nth("2n"), Some((2, 0)));
+    assert_eq!(parse_nth("n"), Some((1, 0)));
+    assert_eq!(parse_nth("2n-1"), Some((2, -1)));
+    assert_eq!(parse_nth("n3"), None);
+    assert!(nth_matches(0, 3, 3));
+    assert!(!nth_matches(0, 3, 4));
+    assert!(nth_matches(2, 1, 1) && nth_matches(2, 1, 5) && !nth_matches(2, 1, 4));
+    assert!(nth_matches(-1, 3, 3) && nth_matches(-1, 3, 1) && !nth_matches(-1, 3, 4));
+    // Overflowing formulas match nothing instead of panicking.
+    assert_eq!(parse_nth("n-9223372036854775808"), Some((1, i64::MIN)));
+    assert!(!nth_matches(1, i64::MIN, 1));
+    assert!(!nth_matches(-1, 0, i64::MIN));
+    assert_eq!(
+      compound(":nth-child(n-9223372036854775808)").pseudos.len(),
+      1
+    );
+  }
+
+  #[cfg(feature = "mock")]
+  mod query {
+    use crate::a11y::{Role, Value};
+    use crate::mock::{MockDesktop, MockElement, MockWindow};
+    use crate::types::{AllioError, Element};
+    use crate::{Allio, Recency};
+
+    /// A todo window: toolbar with a disabled button, and a list of three items.
+    fn setup() -> (MockDesktop, Allio, MockWindow) {
+      let desktop = MockDesktop::acquire();
+      let window = desktop.open_window("Todos", "Todos");
+      let toolbar = desktop
+        .add_element(window.node, MockElement::new(Role::Toolbar))
+        .unwrap();
+      let mut clear = MockElement::new(Role::Button).label("Clear");
+      clear.disabled = true;
+      desktop.add_element(toolbar, clear);
+      let list = desktop
+        .add_element(window.node, MockElement::new(Role::List))
+        .unwrap();
+      for (label, done) in [("Todo: milk", true), ("Todo: eggs", false), ("Note", false)] {
+        let item = desktop
+          .add_element(list, MockElement::new(Role::ListItem).label(label))
+          .unwrap();
+        desktop.add_element(
+          item,
+          MockElement::new(Role::Checkbox).value(Value::Boolean(done)),
+        );
+      }
+
+      let allio = desktop.start_allio().unwrap();
+      (desktop, allio, window)
+    }
+
+    fn labels(elements: &[Element]) -> Vec<&str> {
+      elements
+        .iter()
+        .map(|e| e.label.as_deref().unwrap_or_default())
+        .collect()
+    }
+
+    #[test]
+    fn fetches_subtree_on_demand() {
+      let (_desktop, allio, window) = setup();
+      let root = allio.window_root(window.id).unwrap().unwrap();
+
+      assert!(allio
+        .query(root.id, "listitem", Recency::Any)
+        .unwrap()
+        .is_empty());
+      let items = allio.query(root.id, "listitem", Recency::Current).unwrap();
+      assert_eq!(labels(&items), ["Todo: milk", "Todo: eggs", "Note"]);
+      let cached = allio.query(root.id, "listitem", Recency::Any).unwrap();
+      assert_eq!(cached, items);
+    }
+
+    #[test]
+    fn matches_attributes_states_and_positions() {
+      let (_desktop, allio, window) = setup();
+      let root = allio.window_root(window.id).unwrap().unwrap().id;
+      let query = |selector: &str| {
+        allio
+          .query(root, selector, Recency::max_age_ms(60_000))
+          .unwrap()
+      };
+
+      assert_eq!(
+        labels(&query(r#"list > listitem[label^="todo" i]"#)),
+        ["Todo: milk", "Todo: eggs"]
+      );
+      assert_eq!(labels(&query("button:disabled")), ["Clear"]);
+      assert!(query("button:enabled").is_empty());
+      assert_eq!(
+        labels(&query("listitem:nth-child(2n+1)")),
+        ["Todo: milk", "Note"]
+      );
+      assert_eq!(
+        labels(&query("listitem:last-child, toolbar > *")),
+        ["Clear", "Note"]
+      );
+
+      let checked = query("listitem > checkbox:checked");
+      assert_eq!(checked.len(), 1);
+      assert_eq!(checked[0].value, Some(Value::Boolean(true)));
+      assert_eq!(query(r#"checkbox[value="false"]"#).len(), 2);
+    }
+
+    #[test]
+    fn positions_need_every_sibling() {
+      let (_desktop, allio, window) = setup();
+      let root = allio.window_root(window.id).unwrap().unwrap().id;
+      let list = allio.children(root, Recency::Current).unwrap()[1].id;
+      let first = allio
+        .children_page(list, 0, 1, Recency::Current)
+        .unwrap()
+        .children[0]
+        .id;
+
+      // One of three items is loaded: it is first, but not known to be last.
+      assert!(allio
+        .query(first, "listitem:last-child", Recency::Any)
+        .unwrap()
+        .is_empty());
+      assert!(allio
+        .query(list, "listitem:first-child", Recency::Any)
+        .unwrap()
+        .is_empty());
+
+      // A fetching query loads the root's siblings.
+      assert!(allio
+        .query(first, "listitem:last-child", Recency::Current)
+        .unwrap()
+        .is_empty());
+      assert_eq!(
+        labels(
+          &allio
+            .query(first, "listitem:first-child", Recency::Current)
+            .unwrap()
+        ),
+        ["Todo: milk"]
+      );
+    }
+
+    #[test]
+    fn descendant_combinators_try_each_ancestor_once() {
+      let desktop = MockDesktop::acquire();
+      let window = desktop.open_window("Deep", "Deep");
+      let mut parent = window.node;
+      for _ in 0..200 {
+        parent = desktop
+          .add_element(parent, MockElement::new(Role::Group))
+          .unwrap();
+      }
+      desktop.add_element(parent, MockElement::new(Role::StaticText));
+      let allio = desktop.start_allio().unwrap();
+      let root = allio.window_root(window.id).unwrap().unwrap().id;
+
+      // Without memoising, each of the 200 ancestors is retried along every
+      // path of ten groups above it.
+      let selector = "list group group group group group group group group group group statictext";
+      assert!(allio
+        .query(root, selector, Recency::Current)
+        .unwrap()
+        .is_empty());
+      assert_eq!(
+        allio
+          .query(root, "group > statictext", Recency::Any)
+          .unwrap()
+          .len(),
+        1
+      );
+    }
+
+    #[test]
+    fn scope_root_is_a_candidate_but_bounds_combinators() {
+      let (_desktop, allio, window) = setup();
+      let root = allio.window_root(window.id).unwrap().unwrap().id;
+      let list = allio.query(root, "list", Recency::Current).unwrap()[0].id;
+
+      assert_eq!(allio.query(list, "list", Recency::Any).unwrap().len(), 1);
+      assert!(allio
+        .query(list, "window listitem", Recency::Any)
+        .unwrap()
+        .is_empty());
+      assert_eq!(
+        allio
+          .query(list, "list listitem", Recency::Any)
+          .unwrap()
+          .len(),
+        3
+      );
+    }
+
+    #[test]
+    fn reports_invalid_selectors_and_missing_roots() {
+      let (_desktop, allio, window) = setup();
+      let root = allio.window_root(window.id).unwrap().unwrap().id;
+
+      assert!(matches!(
+        allio.query(root, "list >", Recency::Any),
+        Err(AllioError::InvalidSelector { .. })
+      ));
+      assert!(matches!(
+        allio.query(
+          crate::ElementId::new(root.epoch(), 999_999),
+          "list",
+          Recency::Current
+        ),
+        Err(AllioError::ElementNotFound(_))
+      ));
+    }
+  }
+}
@@ -63,7 +63,7 @@ pub struct Element {
   pub actions: Vec<crate::a11y::Action>,
 
   // === Identity ===
-  /// Platform accessibility identifier (AXIdentifier on macOS).
+  /// Platform accessibility identifier (`AXIdentifier` on macOS).
   /// May provide stable identity across element moves if the app sets it.
   pub identifier: Option<String>,
 
@@ -74,3 +74,160 @@ pub struct Element {
   #[serde(default)]
   pub is_fallback: bool,
 }
+
+/// A page of an element's children, from `Allio::children_page`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct ChildrenPage {
+  /// Children in the page, in order.
+  pub children: Vec<Element>,
+  /// How many children the element has in all. None if unknown: the page came
+  /// from the cache, which doesn't hold all of them.
+  pub total: Option<usize>,
+  /// Offset of the next page. None if this is the last one.
+  pub next_offset: Option<usize>,
+}
+
+/// An element with its loaded descendants nested, from `Allio::fetch_subtree`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct ElementNode {
+  pub element: Element,
+  /// Children in tree order. Empty at the depth limit, even if `element.children` isn't.
+  pub children: Vec<ElementNode>,
+}
+
+impl ElementNode {
+  /// Every element in the tree, each before its children (document order).
+  pub fn flatten(self) -> Vec<Element> {
+    let mut elements = Vec::new();
+    let mut stack = vec![self];
+    while let Some(node) = stack.pop() {
+      elements.push(node.element);
+      stack.extend(node.children.into_iter().rev());
+    }
+    elements
+  }
+}
+
+/// A changed element field, with its old and new value.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, TS)]
+#[serde(tag = "field", rename_all = "snake_case")]
+#[ts(export)]
+pub enum ElementChange {
+  ParentId {
+    old: Option<ElementId>,
+    new: Option<ElementId>,
+  },
+  Children {
+    old: Option<Vec<ElementId>>,
+    new: Option<Vec<ElementId>>,
+  },
+  Role {
+    old: crate::a11y::Role,
+    new: crate::a11y::Role,
+  },
+  PlatformRole {
+    old: String,
+    new: String,
+  },
+  Label {
+    old: Option<String>,
+    new: Option<String>,
+  },
+  Description {
+    old: Option<String>,
+    new: Option<String>,
+  },
+  Placeholder {
+    old: Option<String>,
+    new: Option<String>,
+  },
+  Url {
+    old: Option<String>,
+    new: Option<String>,
+  },
+  Value {
+    old: Option<crate::a11y::Value>,
+    new: Option<crate::a11y::Value>,
+  },
+  Bounds {
+    old: Option<Bounds>,
+    new: Option<Bounds>,
+  },
+  Focused {
+    old: Option<bool>,
+    new: Option<bool>,
+  },
+  Disabled {
+    old: bool,
+    new: bool,
+  },
+  Selected {
+    old: Option<bool>,
+    new: Option<bool>,
+  },
+  Expanded {
+    old: Option<bool>,
+    new: Option<bool>,
+  },
+  RowIndex {
+    old: Option<usize>,
+    new: Option<usize>,
+  },
+  ColumnIndex {
+    old: Option<usize>,
+    new: Option<usize>,
+  },
+  RowCount {
+    old: Option<usize>,
+    new: Option<usize>,
+  },
+  ColumnCount {
+    old: Option<usize>,
+    new: Option<usize>,
+  },
+  Actions {
+    old: Vec<crate::a11y::Action>,
+    new: Vec<crate::a11y::Action>,
+  },
+  Identifier {
+    old: Option<String>,
+    new: Option<String>,
+  },
+  IsFallback {
+    old: bool,
+    new: bool,
+  },
+}
+
+impl Element {
+  /// Apply changes from an `ElementChanged` event, bringing a copy up to date.
+  pub fn apply(&mut self, changes: &[ElementChange]) {
+    for change in changes.iter().cloned() {
+      match change {
+        ElementChange::ParentId { new, .. } => self.parent_id = new,
+        ElementChange::Children { new, .. } => self.children = new,
+        ElementChange::Role { new, .. } => self.role = new,
+        ElementChange::PlatformRole { new, .. } => self.platform_role = new,
+        ElementChange::Label { new, .. } => self.label = new,
+        ElementChange::Description { new, .. } => self.description = new,
+        ElementChange::Placeholder { new, .. } => self.placeholder = new,
+        ElementChange::Url { new, .. } => self.url = new,
+        ElementChange::Value { new, .. } => self.value = new,
+        ElementChange::Bounds { new, .. } => self.bounds = new,
+        ElementChange::Focused { new, .. } => self.focused = new,
+        ElementChange::Disabled { new, .. } => self.disabled = new,
+        ElementChange::Selected { new, .. } => self.selected = new,
+        ElementChange::Expanded { new, .. } => self.expanded = new,
+        ElementChange::RowIndex { new, .. } => self.row_index = new,
+        ElementChange::ColumnIndex { new, .. } => self.column_index = new,
+        ElementChange::RowCount { new, .. } => self.row_count = new,
+        ElementChange::ColumnCount { new, .. } => self.column_count = new,
+        ElementChange::Actions { new, .. } => self.actions = new,
+        ElementChange::Identifier { new, .. } => self.identifier = new,
+        ElementChange::IsFallback { new, .. } => self.is_fallback = new,
+      }
+    }
+  }
+}
@@ -1,10 +1,17 @@
 /*! Error types for Allio operations. */
 
-use super::{ElementId, ProcessId, WindowId};
+use super::{ElementId, ProcessId, RemovalReason, WindowId};
 use crate::a11y::{Action, ValueType};
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
 
 /// Errors that can occur during Allio operations.
-#[derive(Debug, thiserror::Error)]
+///
+/// Serialized as `{ "kind": "ElementRemoved", "details": { ... } }`, so RPC
+/// clients can tell errors apart without parsing messages.
+#[derive(Debug, Clone, thiserror::Error, Serialize, Deserialize, TS)]
+#[serde(tag = "kind", content = "details")]
+#[ts(export)]
 pub enum AllioError {
   #[error("Accessibility permissions not granted")]
   PermissionDenied,
@@ -12,6 +19,17 @@ pub enum AllioError {
   #[error("Element not found: {0}")]
   ElementNotFound(ElementId),
 
+  /// The element existed but has been removed. See [`crate::Allio::tombstone`].
+  #[error("Element removed: {id} ({reason})")]
+  ElementRemoved {
+    id: ElementId,
+    reason: RemovalReason,
+  },
+
+  /// The ID was assigned by another `Allio` instance, such as an earlier server run.
+  #[error("Element ID {0} is from another session")]
+  ForeignElementId(ElementId),
+
   #[error("Window not found: {0}")]
   WindowNotFound(WindowId),
 
@@ -33,6 +51,29 @@ pub enum AllioError {
   #[error("Observer error: {0}")]
   ObserverError(String),
 
+  #[error("Invalid selector '{selector}': {reason}")]
+  InvalidSelector { selector: String, reason: String },
+
+  /// A [`crate::Allio::wait_for`] condition didn't hold in time.
+  #[error("Timed out after {timeout_ms}ms waiting for condition")]
+  Timeout {
+    #[ts(type = "number")]
+    timeout_ms: u64,
+  },
+
+  /// A verified write was accepted, but reading the element back never showed
+  /// its outcome. See [`crate::Allio::set_value_verified`].
+  #[error("Write to element {element_id} not applied after {attempts} attempts: {reason}")]
+  WriteNotApplied {
+    element_id: ElementId,
+    attempts: u32,
+    reason: String,
+  },
+
+  /// An RPC request that couldn't be parsed.
+  #[error("Invalid request: {0}")]
+  InvalidRequest(String),
+
   #[error("Operation not supported: {0}")]
   NotSupported(String),
 
@@ -62,6 +103,21 @@ mod tests {
       assert_eq!(err.to_string(), "Element not found: 42");
     }
 
+    #[test]
+    fn element_removed() {
+      let err = AllioError::ElementRemoved {
+        id: ElementId(42),
+        reason: RemovalReason::WindowClosed,
+      };
+      assert_eq!(err.to_string(), "Element removed: 42 (window closed)");
+    }
+
+    #[test]
+    fn foreign_element_id() {
+      let err = AllioError::ForeignElementId(ElementId(42));
+      assert_eq!(err.to_string(), "Element ID 42 is from another session");
+    }
+
     #[test]
     fn window_not_found() {
       let err = AllioError::WindowNotFound(WindowId(123));
@@ -115,6 +171,40 @@ mod tests {
       assert_eq!(err.to_string(), "Observer error: failed to create observer");
     }
 
+    #[test]
+    fn invalid_selector() {
+      let err = AllioError::InvalidSelector {
+        selector: "list >".into(),
+        reason: "expected a selector".into(),
+      };
+      assert_eq!(
+        err.to_string(),
+        "Invalid selector 'list >': expected a selector"
+      );
+    }
+
+    #[test]
+    fn timeout() {
+      let err = AllioError::Timeout { timeout_ms: 500 };
+      assert_eq!(
+        err.to_string(),
+        "Timed out after 500ms waiting for condition"
+      );
+    }
+
+    #[test]
+    fn write_not_applied() {
+      let err = AllioError::WriteNotApplied {
+        element_id: ElementId(42),
+        attempts: 3,
+        reason: "value stayed None".into(),
+      };
+      assert_eq!(
+        err.to_string(),
+        "Write to element 42 not applied after 3 attempts: value stayed None"
+      );
+    }
+
     #[test]
     fn not_supported() {
       let err = AllioError::NotSupported("action not available on this element".into());
@@ -124,6 +214,15 @@ mod tests {
       );
     }
 
+    #[test]
+    fn invalid_request() {
+      let err = AllioError::InvalidRequest("missing field `element_id`".into());
+      assert_eq!(
+        err.to_string(),
+        "Invalid request: missing field `element_id`"
+      );
+    }
+
     #[test]
     fn internal_error() {
       let err = AllioError::Internal("unexpected state".into());
@@ -131,6 +230,31 @@ mod tests {
     }
   }
 
+  mod serialization {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn tagged_by_kind() {
+      let err = AllioError::ElementRemoved {
+        id: ElementId(42),
+        reason: RemovalReason::WindowClosed,
+      };
+      assert_eq!(
+        serde_json::to_value(&err).unwrap(),
+        json!({ "kind": "ElementRemoved", "details": { "id": 42, "reason": "window_closed" } })
+      );
+      assert_eq!(
+        serde_json::to_value(AllioError::ForeignElementId(ElementId(7))).unwrap(),
+        json!({ "kind": "ForeignElementId", "details": 7 })
+      );
+      assert_eq!(
+        serde_json::to_value(AllioError::PermissionDenied).unwrap(),
+        json!({ "kind": "PermissionDenied" })
+      );
+    }
+  }
+
   mod error_properties {
     use super::*;
 
@@ -153,6 +277,11 @@ mod tests {
       let errors: Vec<AllioError> = vec![
         AllioError::PermissionDenied,
         AllioError::ElementNotFound(ElementId(0)),
+        AllioError::ElementRemoved {
+          id: ElementId(0),
+          reason: RemovalReason::Destroyed,
+        },
+        AllioError::ForeignElementId(ElementId(0)),
         AllioError::WindowNotFound(WindowId(0)),
         AllioError::ProcessNotFound(ProcessId(0)),
         AllioError::ActionFailed {
@@ -168,10 +297,21 @@ mod tests {
         },
         AllioError::NoElementAtPosition { x: 0.0, y: 0.0 },
         AllioError::ObserverError(String::new()),
+        AllioError::InvalidSelector {
+          selector: String::new(),
+          reason: String::new(),
+        },
+        AllioError::Timeout { timeout_ms: 0 },
+        AllioError::WriteNotApplied {
+          element_id: ElementId(0),
+          attempts: 0,
+          reason: String::new(),
+        },
+        AllioError::InvalidRequest(String::new()),
         AllioError::NotSupported(String::new()),
         AllioError::Internal(String::new()),
       ];
-      assert_eq!(errors.len(), 11, "all error variants should be covered");
+      assert_eq!(errors.len(), 17, "all error variants should be covered");
     }
   }
 
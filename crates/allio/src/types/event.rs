@@ -1,11 +1,12 @@
 /*! Event types for state changes and synchronization. */
 
-use super::{Element, ElementId, Point, Window, WindowId};
-use serde::Serialize;
+use super::{Element, ElementChange, ElementId, Point, RemovalReason, Window, WindowId};
+use serde::{Deserialize, Serialize};
+use std::time::{SystemTime, UNIX_EPOCH};
 use ts_rs::TS;
 
 /// Character range within text. End is exclusive, matching Rust's `Range` semantics.
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, TS)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, TS)]
 #[ts(export)]
 pub struct TextRange {
   /// Start position (inclusive).
@@ -43,7 +44,7 @@ impl From<(u32, u32)> for TextRange {
 }
 
 /// Text selection within an element.
-#[derive(Debug, Clone, PartialEq, Serialize, TS)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, TS)]
 #[ts(export)]
 pub struct TextSelection {
   pub element_id: ElementId,
@@ -76,7 +77,7 @@ impl TextSelection {
 }
 
 /// Initial state sent on connection.
-#[derive(Debug, Clone, Serialize, TS)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, TS)]
 #[ts(export)]
 pub struct Snapshot {
   pub windows: Vec<Window>,
@@ -88,10 +89,17 @@ pub struct Snapshot {
   pub z_order: Vec<WindowId>,
   /// Current mouse position
   pub mouse_position: Option<Point>,
+  /// Sequence number of the last event reflected in this snapshot
+  #[ts(type = "number")]
+  pub seq: u64,
+  /// Session epoch tagging the element IDs (see [`ElementId::epoch`]).
+  /// Sequence numbers are only comparable within one session.
+  #[serde(default)]
+  pub session: u32,
 }
 
 /// Events emitted when state changes.
-#[derive(Debug, Clone, Serialize, TS)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, TS)]
 #[serde(tag = "event", content = "data")]
 #[ts(export)]
 pub enum Event {
@@ -111,9 +119,21 @@ pub enum Event {
   #[serde(rename = "element:added")]
   ElementAdded { element: Element },
   #[serde(rename = "element:changed")]
-  ElementChanged { element: Element },
+  ElementChanged {
+    element_id: ElementId,
+    changes: Vec<ElementChange>,
+  },
+  /// `reason` is as known at removal: a `destroyed` element whose window then
+  /// closes is reattributed in its tombstone, not re-sent.
   #[serde(rename = "element:removed")]
-  ElementRemoved { element_id: ElementId },
+  ElementRemoved {
+    element_id: ElementId,
+    reason: RemovalReason,
+  },
+  /// A removed element was recreated (e.g. by a view rebuild) as `new`. Both
+  /// have had their own `element:removed` and `element:added`.
+  #[serde(rename = "element:replaced")]
+  ElementReplaced { old: ElementId, new: ElementId },
 
   // Window focus (from polling)
   #[serde(rename = "focus:window")]
@@ -149,3 +169,38 @@ pub enum Event {
     modified: Vec<ElementId>,
   },
 }
+
+/// An event stamped at emission. Serializes as the event with `seq` and `timestamp_ms` alongside.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct SequencedEvent {
+  /// Position in the event stream: increases by one per emitted event, starting at 1.
+  /// A `SyncInit` carries the sequence number of its snapshot instead.
+  #[ts(type = "number")]
+  pub seq: u64,
+  /// Emission time, in milliseconds since the Unix epoch.
+  #[ts(type = "number")]
+  pub timestamp_ms: u64,
+  #[serde(flatten)]
+  #[ts(flatten)]
+  pub event: Event,
+}
+
+impl SequencedEvent {
+  /// Stamp `event` with `seq` and the current time.
+  pub fn new(seq: u64, event: Event) -> Self {
+    let timestamp_ms = SystemTime::now()
+      .duration_since(UNIX_EPOCH)
+      .map_or(0, |d| u64::try_from(d.as_millis()).unwrap_or(u64::MAX));
+    Self {
+      seq,
+      timestamp_ms,
+      event,
+    }
+  }
+
+  /// `SyncInit` for `snapshot`, stamped with the snapshot's sequence number.
+  pub fn sync_init(snapshot: Snapshot) -> Self {
+    Self::new(snapshot.seq, Event::SyncInit(snapshot))
+  }
+}
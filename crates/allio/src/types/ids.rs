@@ -2,7 +2,10 @@
 
 use derive_more::{Display, From, Into};
 use serde::{Deserialize, Serialize};
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hash, Hasher};
 use std::sync::atomic::{AtomicU32, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
 use ts_rs::TS;
 
 /// Window identifier.
@@ -13,22 +16,109 @@ use ts_rs::TS;
 pub struct WindowId(pub u32);
 
 /// Element identifier.
+///
+/// Tagged with the session epoch of the `Allio` instance that assigned it, so
+/// IDs held over from another session (a restarted server, say) are rejected
+/// rather than mistaken for live elements. The epoch takes the 21 bits above a
+/// 32-bit counter, keeping IDs within JavaScript's safe integer range.
 #[derive(
   Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, TS, Display, From, Into,
 )]
-#[ts(export)]
-pub struct ElementId(pub u32);
+#[ts(export, type = "number")]
+pub struct ElementId(pub u64);
 
-/// Global counter for `ElementId` generation. Starts at 1 (0 could be confused with "null").
-static ELEMENT_COUNTER: AtomicU32 = AtomicU32::new(1);
+/// Bits of an `ElementId` holding the session epoch.
+const EPOCH_BITS: u32 = 21;
 
 impl ElementId {
-  /// Generate a new unique `ElementId`.
+  /// Compose an ID from a session epoch and counter.
   ///
   /// This is `pub(crate)` to prevent external code from creating arbitrary IDs
   /// that don't correspond to real elements.
+  pub(crate) const fn new(epoch: u32, counter: u32) -> Self {
+    Self((((epoch & ElementIds::EPOCH_MASK) as u64) << 32) | counter as u64)
+  }
+
+  /// Session epoch of the `Allio` instance that assigned this ID.
+  #[must_use]
+  pub const fn epoch(self) -> u32 {
+    // Masked to EPOCH_BITS, so the truncation is exact.
+    #[allow(clippy::cast_possible_truncation)]
+    let epoch = (self.0 >> 32) as u32;
+    epoch & ElementIds::EPOCH_MASK
+  }
+
+  /// Position of this ID in its session's sequence.
+  #[must_use]
+  pub const fn counter(self) -> u32 {
+    #[allow(clippy::cast_possible_truncation)]
+    let counter = self.0 as u32;
+    counter
+  }
+}
+
+/// `ElementId` allocator for one `Allio` instance.
+///
+/// Once the counter is exhausted, allocation moves to a further epoch rather
+/// than wrapping, so no ID is ever handed out twice.
+pub(crate) struct ElementIds {
+  /// Epochs allocated under, the session's first.
+  epochs: Vec<u32>,
+  next: u32,
+}
+
+impl ElementIds {
+  const EPOCH_MASK: u32 = (1 << EPOCH_BITS) - 1;
+
+  /// Start a session with a fresh epoch.
   pub(crate) fn new() -> Self {
-    Self(ELEMENT_COUNTER.fetch_add(1, Ordering::Relaxed))
+    Self {
+      epochs: vec![Self::draw_epoch()],
+      next: 1,
+    }
+  }
+
+  /// A random epoch, never 0.
+  fn draw_epoch() -> u32 {
+    static INSTANCES: AtomicU32 = AtomicU32::new(0);
+    let mut hasher = RandomState::new().build_hasher();
+    SystemTime::now()
+      .duration_since(UNIX_EPOCH)
+      .unwrap_or_default()
+      .hash(&mut hasher);
+    std::process::id().hash(&mut hasher);
+    INSTANCES.fetch_add(1, Ordering::Relaxed).hash(&mut hasher);
+    // Truncation intended: only the low EPOCH_BITS are kept.
+    #[allow(clippy::cast_possible_truncation)]
+    let epoch = hasher.finish() as u32 & Self::EPOCH_MASK;
+    // 0 is left out so that no ID is ever 0 (which could be confused with "null").
+    epoch.max(1)
+  }
+
+  /// This session's epoch.
+  pub(crate) fn epoch(&self) -> u32 {
+    self.epochs.first().copied().unwrap_or_default()
+  }
+
+  /// Whether this allocator assigned `id`'s epoch.
+  pub(crate) fn owns(&self, id: ElementId) -> bool {
+    self.epochs.contains(&id.epoch())
+  }
+
+  /// Allocate the next ID. After 2^32 - 1 IDs in an epoch, moves to a new one.
+  pub(crate) fn next(&mut self) -> ElementId {
+    let epoch = self.epochs.last().copied().unwrap_or_default();
+    let id = ElementId::new(epoch, self.next);
+    if let Some(next) = self.next.checked_add(1) {
+      self.next = next;
+    } else {
+      let fresh = std::iter::repeat_with(Self::draw_epoch)
+        .find(|e| !self.epochs.contains(e))
+        .unwrap_or_default();
+      self.epochs.push(fresh);
+      self.next = 1;
+    }
+    id
   }
 }
 
@@ -39,3 +129,42 @@ impl ElementId {
 #[ts(export)]
 pub struct ProcessId(pub u32);
 
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn element_ids_pack_epoch_and_counter() {
+    let id = ElementId::new(0x1F_FFFF, u32::MAX);
+    assert_eq!(id.epoch(), 0x1F_FFFF);
+    assert_eq!(id.counter(), u32::MAX);
+    // Stays exactly representable as a JavaScript number.
+    assert!(id.0 < 1 << 53);
+    assert_eq!(
+      serde_json::to_string(&ElementId::new(1, 2)).unwrap(),
+      "4294967298"
+    );
+  }
+
+  #[test]
+  fn allocator_moves_to_a_new_epoch_instead_of_wrapping() {
+    let mut ids = ElementIds::new();
+    assert_ne!(ids.epoch(), 0);
+    let first = ids.next();
+    assert_eq!(first.counter(), 1);
+    ids.next = u32::MAX;
+    let last = ids.next();
+    assert_eq!(last.counter(), u32::MAX);
+    assert_eq!(last.epoch(), ids.epoch());
+
+    let wrapped = ids.next();
+    assert_eq!(wrapped.counter(), 1);
+    assert_ne!(wrapped.epoch(), 0);
+    assert_ne!(wrapped, first);
+    // The session keeps its epoch and still owns IDs from both.
+    assert_eq!(first.epoch(), ids.epoch());
+    assert!(ids.owns(first) && ids.owns(last) && ids.owns(wrapped));
+    let foreign = (1..).find(|&e| e != first.epoch() && e != wrapped.epoch());
+    assert!(!ids.owns(ElementId::new(foreign.unwrap(), 1)));
+  }
+}
@@ -11,12 +11,17 @@ mod event;
 mod geometry;
 mod ids;
 mod recency;
+mod tombstone;
+mod verification;
 mod window;
 
-pub use element::Element;
+pub use element::{ChildrenPage, Element, ElementChange, ElementNode};
 pub use error::{AllioError, AllioResult};
-pub use event::{Event, Snapshot, TextRange, TextSelection};
+pub use event::{Event, SequencedEvent, Snapshot, TextRange, TextSelection};
 pub use geometry::{Bounds, Point};
+pub(crate) use ids::ElementIds;
 pub use ids::{ElementId, ProcessId, WindowId};
 pub use recency::Recency;
+pub use tombstone::{RemovalReason, Tombstone};
+pub use verification::{Expect, Verification};
 pub use window::Window;
@@ -0,0 +1,45 @@
+/*! Tombstones: what is remembered about removed elements. */
+
+use super::WindowId;
+use derive_more::Display;
+use serde::{Deserialize, Serialize};
+use std::time::Instant;
+use ts_rs::TS;
+
+/// Why an element was removed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, Display, TS)]
+#[serde(rename_all = "snake_case")]
+#[ts(export)]
+pub enum RemovalReason {
+  /// The platform destroyed it, or it went missing from its parent.
+  #[display("destroyed")]
+  Destroyed,
+  /// Its window closed.
+  #[display("window closed")]
+  WindowClosed,
+  /// Its process exited.
+  #[display("process exited")]
+  ProcessExited,
+  /// An ancestor was destroyed.
+  #[display("ancestor removed")]
+  Cascade,
+}
+
+impl RemovalReason {
+  /// Reason for the descendants of an element removed for `self`.
+  pub(crate) const fn cascaded(self) -> Self {
+    match self {
+      Self::Destroyed | Self::Cascade => Self::Cascade,
+      Self::WindowClosed | Self::ProcessExited => self,
+    }
+  }
+}
+
+/// When and why an element was removed. See [`crate::Allio::tombstone`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Tombstone {
+  pub reason: RemovalReason,
+  /// Window the element belonged to.
+  pub window_id: WindowId,
+  pub removed_at: Instant,
+}
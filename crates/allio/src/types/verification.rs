@@ -0,0 +1,64 @@
+/*! Postconditions and reports for verified writes. */
+
+use super::Element;
+use crate::a11y::Value;
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+
+/// What a verified write should leave behind, checked by reading the element back.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, TS)]
+#[serde(tag = "expect", rename_all = "snake_case")]
+#[ts(export)]
+pub enum Expect {
+  /// The value equals `value`.
+  Value { value: Value },
+  /// The value differs from before the write, e.g. after toggling a checkbox.
+  ValueChanged,
+  /// The element is expanded (or collapsed, if false).
+  Expanded { expanded: bool },
+  /// The element is selected (or deselected, if false).
+  Selected { selected: bool },
+  /// The element has keyboard focus.
+  Focused,
+}
+
+impl Expect {
+  /// Whether `now` shows the outcome, given the element as it was `before` the write.
+  pub fn holds(&self, before: &Element, now: &Element) -> bool {
+    match self {
+      Self::Value { value } => now.value.as_ref() == Some(value),
+      Self::ValueChanged => now.value != before.value,
+      Self::Expanded { expanded } => now.expanded == Some(*expanded),
+      Self::Selected { selected } => now.selected == Some(*selected),
+      Self::Focused => now.focused == Some(true),
+    }
+  }
+
+  /// How `now` falls short, for `AllioError::WriteNotApplied`.
+  pub(crate) fn mismatch(&self, now: &Element) -> String {
+    match self {
+      Self::Value { value } => format!("expected value {value:?}, found {:?}", now.value),
+      Self::ValueChanged => format!("value stayed {:?}", now.value),
+      Self::Expanded { expanded } => {
+        format!("expected expanded {expanded}, found {:?}", now.expanded)
+      }
+      Self::Selected { selected } => {
+        format!("expected selected {selected}, found {:?}", now.selected)
+      }
+      Self::Focused => format!("expected focus, found focused {:?}", now.focused),
+    }
+  }
+}
+
+/// A verified write that took, from `Allio::set_value_verified` or `Allio::perform_verified`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct Verification {
+  /// The element as read back once the outcome held.
+  pub element: Element,
+  /// Writes made, counting the one that took.
+  pub attempts: u32,
+  /// Time from the first write until the outcome was seen.
+  #[ts(type = "number")]
+  pub elapsed_ms: u64,
+}
@@ -0,0 +1,370 @@
+/*!
+Simplified tree projections ("views").
+
+A view projects an element subtree into a tree that is closer to what a user
+perceives, dropping the layout noise that platform trees are full of:
+
+- Unlabeled `GenericGroup`/`GenericElement` nodes with a single child collapse
+  into that child
+- Unlabeled `Separator`/`Image` leaves are dropped as decorative
+- `StaticText` leaves are merged into their parent's label
+
+Nodes keep the IDs of the elements folded into them in `merged`, so events and
+actions can be mapped back to the real tree.
+
+## Liveness
+
+[`View`] projects the registry cache and re-projects when `ElementAdded`,
+`ElementChanged` or `ElementRemoved` events touch its subtree. Combine it with
+[`Allio::observe`] to keep the underlying subtree fresh.
+
+```ignore
+let mut view = allio.view(root_id, Recency::Current)?;
+let handle = allio.observe(root_id, ObserveConfig::default())?;
+
+if view.update() {
+    render(view.tree());
+}
+```
+*/
+
+use async_broadcast::{Receiver, TryRecvError};
+use serde::Serialize;
+use std::collections::HashSet;
+use ts_rs::TS;
+
+use crate::a11y::{Role, Value};
+use crate::core::registry::CachedElement;
+use crate::core::{Allio, Registry};
+use crate::types::{AllioResult, Bounds, ElementId, Event, Recency, SequencedEvent};
+
+/// A node in a simplified tree projection.
+#[derive(Debug, Clone, PartialEq, Serialize, TS)]
+#[ts(export)]
+pub struct ViewNode {
+  /// Element this node represents (the innermost element of a collapsed chain).
+  pub id: ElementId,
+  /// Role of the represented element.
+  pub role: Role,
+  /// Element label, followed by the text of merged `StaticText` children.
+  pub label: Option<String>,
+  /// Value of the represented element.
+  pub value: Option<Value>,
+  /// Screen bounds of the represented element.
+  pub bounds: Option<Bounds>,
+  /// Elements folded into this node: collapsed generic ancestors, merged text.
+  pub merged: Vec<ElementId>,
+  /// Projected children, in tree order.
+  pub children: Vec<ViewNode>,
+}
+
+/// A live projection of an element subtree. Updated by [`View::update`].
+pub struct View {
+  allio: Allio,
+  root: ElementId,
+  events: Receiver<SequencedEvent>,
+  tree: Option<ViewNode>,
+  /// Every element visited by the last projection, including folded ones.
+  members: HashSet<ElementId>,
+}
+
+impl std::fmt::Debug for View {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.debug_struct("View")
+      .field("root", &self.root)
+      .field("tree", &self.tree)
+      .finish_non_exhaustive()
+  }
+}
+
+impl View {
+  /// Root element of the projected subtree.
+  pub const fn root_id(&self) -> ElementId {
+    self.root
+  }
+
+  /// Current projection. None if the root was removed or projects to nothing.
+  pub const fn tree(&self) -> Option<&ViewNode> {
+    self.tree.as_ref()
+  }
+
+  /// Apply pending events. Returns true if the projection changed.
+  pub fn update(&mut self) -> bool {
+    let mut dirty = false;
+    let mut added = Vec::new();
+    loop {
+      match self.events.try_recv().map(|stamped| stamped.event) {
+        Ok(Event::ElementAdded { element }) => added.push(element.id),
+        Ok(Event::ElementChanged { element_id, .. } | Event::ElementRemoved { element_id, .. }) => {
+          dirty |= self.members.contains(&element_id);
+        }
+        Ok(_) => {}
+        // Missed events: assume the worst.
+        Err(TryRecvError::Overflowed(_)) => dirty = true,
+        Err(TryRecvError::Empty | TryRecvError::Closed) => break,
+      }
+    }
+    if !dirty && !added.is_empty() {
+      let root = self.root;
+      dirty = self
+        .allio
+        .read(|r| added.iter().any(|id| r.tree_contains(root, *id)));
+    }
+    if !dirty {
+      return false;
+    }
+
+    let (tree, members) = self.allio.read(|r| project_root(r, self.root));
+    self.members = members;
+    if tree == self.tree {
+      return false;
+    }
+    self.tree = tree;
+    true
+  }
+}
+
+impl Allio {
+  /// Create a live simplified view of the subtree at `root`.
+  ///
+  /// `recency` controls how much of the subtree is fetched first, as in [`Allio::query`].
+  #[must_use = "this returns a Result that may contain a view"]
+  pub fn view(&self, root: ElementId, recency: Recency) -> AllioResult<View> {
+    // Subscribe first so no change between loading and projecting is missed.
+    let events = self.subscribe();
+    self.get(root, recency)?;
+    self.load_subtree(root, recency)?;
+    let (tree, members) = self.read(|r| project_root(r, root));
+    if members.is_empty() {
+      return Err(self.read(|r| r.missing(root)));
+    }
+    Ok(View {
+      allio: self.clone(),
+      root,
+      events,
+      tree,
+      members,
+    })
+  }
+}
+
+fn project_root(registry: &Registry, root: ElementId) -> (Option<ViewNode>, HashSet<ElementId>) {
+  let mut members = HashSet::new();
+  let tree = project(registry, root, &mut members);
+  (tree, members)
+}
+
+fn project(
+  registry: &Registry,
+  id: ElementId,
+  members: &mut HashSet<ElementId>,
+) -> Option<ViewNode> {
+  let elem = registry.element(id)?;
+  members.insert(id);
+  let mut children: Vec<ViewNode> = registry
+    .tree_children(id)
+    .iter()
+    .filter_map(|child| project(registry, *child, members))
+    .collect();
+
+  let labeled = elem.label.as_deref().is_some_and(|l| !l.is_empty());
+  let generic = matches!(elem.role, Role::GenericGroup | Role::GenericElement);
+  if generic && !labeled && children.len() == 1 {
+    let mut child = children.pop()?;
+    child.merged.insert(0, id);
+    return Some(child);
+  }
+  let decorative = matches!(elem.role, Role::Separator | Role::Image);
+  if decorative && !labeled && children.is_empty() {
+    return None;
+  }
+
+  let mut node = ViewNode {
+    id,
+    role: elem.role,
+    label: text_of(elem),
+    value: elem.value.clone(),
+    bounds: elem.bounds,
+    merged: Vec::new(),
+    children: Vec::with_capacity(children.len()),
+  };
+  for child in children {
+    if child.role == Role::StaticText && child.children.is_empty() {
+      merge_text(&mut node, child);
+    } else {
+      node.children.push(child);
+    }
+  }
+  Some(node)
+}
+
+/// Label, or the value for static text (which carries its text there on macOS).
+fn text_of(elem: &CachedElement) -> Option<String> {
+  let label = elem.label.clone().filter(|l| !l.is_empty());
+  if label.is_some() || elem.role != Role::StaticText {
+    return label;
+  }
+  elem
+    .value
+    .clone()
+    .map(Value::into_string)
+    .filter(|v| !v.is_empty())
+}
+
+fn merge_text(parent: &mut ViewNode, text: ViewNode) {
+  parent.merged.push(text.id);
+  parent.merged.extend(text.merged);
+  let Some(text) = text.label else {
+    return;
+  };
+  parent.label = match parent.label.take() {
+    None => Some(text),
+    Some(label) if label == text => Some(label),
+    Some(label) => Some(format!("{label} {text}")),
+  };
+}
+
+#[cfg(test)]
+#[cfg(feature = "mock")]
+mod tests {
+  use super::*;
+  use crate::mock::{eventually, MockDesktop, MockElement, MockNodeId, MockWindow};
+
+  struct Fixture {
+    desktop: MockDesktop,
+    allio: Allio,
+    window: MockWindow,
+    toolbar: MockNodeId,
+    save_text: MockNodeId,
+  }
+
+  /// window > generic > generic > toolbar > [button > statictext "Save", separator, image, image "Logo"]
+  fn setup() -> Fixture {
+    let desktop = MockDesktop::acquire();
+    let window = desktop.open_window("Editor", "Untitled");
+    let outer = desktop
+      .add_element(window.node, MockElement::new(Role::GenericGroup))
+      .unwrap();
+    let inner = desktop
+      .add_element(outer, MockElement::new(Role::GenericElement))
+      .unwrap();
+    let toolbar = desktop
+      .add_element(inner, MockElement::new(Role::Toolbar))
+      .unwrap();
+    let button = desktop
+      .add_element(toolbar, MockElement::new(Role::Button))
+      .unwrap();
+    let save_text = desktop
+      .add_element(
+        button,
+        MockElement::new(Role::StaticText).value(Value::String("Save".into())),
+      )
+      .unwrap();
+    desktop.add_element(toolbar, MockElement::new(Role::Separator));
+    desktop.add_element(toolbar, MockElement::new(Role::Image));
+    desktop.add_element(toolbar, MockElement::new(Role::Image).label("Logo"));
+
+    let allio = desktop.start_allio().unwrap();
+    Fixture {
+      desktop,
+      allio,
+      window,
+      toolbar,
+      save_text,
+    }
+  }
+
+  fn shape(node: &ViewNode) -> String {
+    let label = node.label.as_deref().unwrap_or_default();
+    let children: Vec<String> = node.children.iter().map(shape).collect();
+    if children.is_empty() {
+      format!("{:?}({label})", node.role)
+    } else {
+      format!("{:?}({label})[{}]", node.role, children.join(", "))
+    }
+  }
+
+  fn view_of(f: &Fixture) -> View {
+    let root = f.allio.window_root(f.window.id).unwrap().unwrap();
+    f.allio.view(root.id, Recency::Current).unwrap()
+  }
+
+  #[test]
+  fn projects_simplified_tree() {
+    let f = setup();
+    let view = view_of(&f);
+    let tree = view.tree().unwrap();
+
+    assert_eq!(
+      shape(tree),
+      "Window(Untitled)[Toolbar()[Button(Save), Image(Logo)]]"
+    );
+    // The toolbar absorbed the generic chain, the button its text.
+    assert_eq!(tree.children[0].merged.len(), 2);
+    assert_eq!(tree.children[0].children[0].merged.len(), 1);
+  }
+
+  #[test]
+  fn follows_changes_additions_and_removals() {
+    let f = setup();
+    let mut view = view_of(&f);
+    assert!(!view.update());
+
+    // Changed: the merged text is refreshed.
+    f.desktop
+      .set_value(f.save_text, Value::String("Save As".into()));
+    let button = view.tree().unwrap().children[0].children[0].clone();
+    f.allio.get(button.merged[0], Recency::Current).unwrap();
+    assert!(view.update());
+    assert_eq!(
+      shape(view.tree().unwrap()),
+      "Window(Untitled)[Toolbar()[Button(Save As), Image(Logo)]]"
+    );
+
+    // Added: a new child is fetched into the toolbar.
+    let toolbar_id = view.tree().unwrap().children[0].id;
+    let link = f
+      .desktop
+      .add_element(f.toolbar, MockElement::new(Role::Link).label("Help"))
+      .unwrap();
+    f.allio.children(toolbar_id, Recency::Current).unwrap();
+    assert!(view.update());
+    assert_eq!(
+      shape(view.tree().unwrap()),
+      "Window(Untitled)[Toolbar()[Button(Save As), Image(Logo), Link(Help)]]"
+    );
+
+    // Removed: the element disappears from the projection.
+    f.desktop.remove_element(link);
+    f.allio.children(toolbar_id, Recency::Current).unwrap();
+    eventually(|| view.update().then_some(()));
+    assert_eq!(
+      shape(view.tree().unwrap()),
+      "Window(Untitled)[Toolbar()[Button(Save As), Image(Logo)]]"
+    );
+  }
+
+  #[test]
+  fn ignores_events_outside_the_subtree() {
+    let f = setup();
+    let toolbar = f
+      .allio
+      .query(
+        f.allio.window_root(f.window.id).unwrap().unwrap().id,
+        "toolbar",
+        Recency::Current,
+      )
+      .unwrap()[0]
+      .id;
+    let mut view = f.allio.view(toolbar, Recency::Any).unwrap();
+    assert_eq!(
+      shape(view.tree().unwrap()),
+      "Toolbar()[Button(Save), Image(Logo)]"
+    );
+
+    f.desktop.set_window_title(f.window.id, "Renamed");
+    let root = f.allio.window_root(f.window.id).unwrap().unwrap().id;
+    f.allio.get(root, Recency::Current).unwrap();
+    assert!(!view.update());
+  }
+}
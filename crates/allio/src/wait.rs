@@ -0,0 +1,481 @@
+/*!
+Waiting for conditions.
+
+[`Allio::wait_for`] blocks until a [`Target`] satisfies a predicate, so scripts
+don't have to sleep between an action and the next read:
+
+```ignore
+use allio::wait;
+
+allio.perform_action(save_id, Action::Press)?;
+
+// A window opens...
+let timeout = Duration::from_secs(5);
+allio.wait_for(&wait::windows(), |ws| ws.iter().any(|w| w.title == "Saved"), timeout)?;
+// ...an element appears under a root...
+let ok = allio.wait_for(&wait::query(root_id, "button[label=\"OK\"]")?, |m| !m.is_empty(), timeout)?;
+// ...a value settles...
+let done = Some(Value::String("Done".into()));
+allio.wait_for(&wait::element(status_id), |e| e.as_ref().is_some_and(|e| e.value == done), timeout)?;
+// ...or an element goes away.
+allio.wait_for(&wait::element(spinner_id), Option::is_none, timeout)?;
+```
+
+The predicate is checked against the cache, again whenever an event touches
+the target, so most waits end as soon as the change is reported. Apps don't
+report everything, so the target is also re-read from the OS: right away, then
+after 100ms, 250ms and 500ms, then every second (like Playwright's polling).
+Slow re-reads, such as a query over a big subtree, are spaced further apart,
+so they take at most a tenth of the wait.
+*/
+
+use async_broadcast::{Receiver, RecvError};
+use std::future::Future;
+use std::pin::pin;
+use std::sync::Arc;
+use std::task::{Context, Poll, Wake, Waker};
+use std::thread::Thread;
+use std::time::{Duration, Instant};
+
+use crate::core::{build_element, Allio};
+use crate::platform::PlatformHandle;
+use crate::selector::Selector;
+use crate::types::{
+  AllioError, AllioResult, Element, ElementId, Event, Recency, SequencedEvent, Window,
+};
+
+/// Pauses between the first OS re-reads.
+const POLL_BACKOFF: [Duration; 3] = [
+  Duration::from_millis(100),
+  Duration::from_millis(250),
+  Duration::from_millis(500),
+];
+
+/// Pause between OS re-reads after the backoff.
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Minimum pause after a re-read, as a multiple of the time it took.
+const POLL_COST_FACTOR: u32 = 10;
+
+/// Something [`Allio::wait_for`] can wait on.
+pub trait Target {
+  /// What the predicate is checked against.
+  type Output;
+
+  /// Current state, from the cache.
+  fn read(&self, allio: &Allio) -> Self::Output;
+
+  /// Re-read the target from the OS into the cache, for changes no event
+  /// reported. Errors are ignored: `read` shows whatever is left.
+  fn refresh(&self, allio: &Allio);
+
+  /// Whether `event` may change what `read` returns.
+  fn affected_by(&self, allio: &Allio, event: &Event) -> bool;
+}
+
+/// An element, or `None` once it's removed. See [`element`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ElementTarget {
+  id: ElementId,
+}
+
+/// Wait on an element's state, or its removal.
+pub const fn element(id: ElementId) -> ElementTarget {
+  ElementTarget { id }
+}
+
+impl Target for ElementTarget {
+  type Output = Option<Element>;
+
+  fn read(&self, allio: &Allio) -> Option<Element> {
+    allio.get(self.id, Recency::Any).ok()
+  }
+
+  fn refresh(&self, allio: &Allio) {
+    let Some(handle) = allio.read(|r| r.element(self.id).map(|e| e.handle.clone())) else {
+      return;
+    };
+    let attrs = handle.fetch_attributes();
+    allio.write(|r| {
+      if attrs.is_dead() {
+        r.remove_element(self.id);
+      } else {
+        r.refresh_element(self.id, attrs);
+      }
+    });
+  }
+
+  fn affected_by(&self, _allio: &Allio, event: &Event) -> bool {
+    match event {
+      Event::ElementAdded { element } => element.id == self.id,
+      Event::ElementChanged { element_id, .. } | Event::ElementRemoved { element_id, .. } => {
+        *element_id == self.id
+      }
+      Event::ElementReplaced { old, .. } => *old == self.id,
+      Event::SyncInit(_)
+      | Event::WindowAdded { .. }
+      | Event::WindowChanged { .. }
+      | Event::WindowRemoved { .. }
+      | Event::FocusWindow { .. }
+      | Event::FocusElement { .. }
+      | Event::SelectionChanged { .. }
+      | Event::MousePosition(_)
+      | Event::SubtreeChanged { .. } => false,
+    }
+  }
+}
+
+/// Elements under a root matching a selector, in tree order. See [`query`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QueryTarget {
+  root: ElementId,
+  selector: Selector,
+}
+
+/// Wait on the elements under `root` that match `selector`, as in [`Allio::query`].
+pub fn query(root: ElementId, selector: &str) -> AllioResult<QueryTarget> {
+  Ok(QueryTarget {
+    root,
+    selector: Selector::parse(selector)?,
+  })
+}
+
+impl Target for QueryTarget {
+  type Output = Vec<Element>;
+
+  fn read(&self, allio: &Allio) -> Vec<Element> {
+    allio.read(|r| {
+      self
+        .selector
+        .select(r, self.root)
+        .into_iter()
+        .filter_map(|id| build_element(r, id))
+        .collect()
+    })
+  }
+
+  fn refresh(&self, allio: &Allio) {
+    if allio.get(self.root, Recency::Current).is_ok() {
+      drop(allio.load_subtree(self.root, Recency::Current));
+    }
+  }
+
+  fn affected_by(&self, allio: &Allio, event: &Event) -> bool {
+    match event {
+      Event::ElementAdded { element } => allio.in_subtree(self.root, element.id),
+      Event::ElementChanged { element_id, .. } => allio.in_subtree(self.root, *element_id),
+      // Removed elements have already left the tree, so can't be placed.
+      Event::ElementRemoved { .. } | Event::ElementReplaced { .. } => true,
+      Event::SyncInit(_)
+      | Event::WindowAdded { .. }
+      | Event::WindowChanged { .. }
+      | Event::WindowRemoved { .. }
+      | Event::FocusWindow { .. }
+      | Event::FocusElement { .. }
+      | Event::SelectionChanged { .. }
+      | Event::MousePosition(_)
+      | Event::SubtreeChanged { .. } => false,
+    }
+  }
+}
+
+/// All windows. See [`windows`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WindowsTarget;
+
+/// Wait on the set of windows, e.g. for one with a given title.
+pub const fn windows() -> WindowsTarget {
+  WindowsTarget
+}
+
+impl Target for WindowsTarget {
+  type Output = Vec<Window>;
+
+  fn read(&self, allio: &Allio) -> Vec<Window> {
+    allio.all_windows()
+  }
+
+  /// Nothing to do: the polling loop re-reads windows on its own interval.
+  fn refresh(&self, _allio: &Allio) {}
+
+  fn affected_by(&self, _allio: &Allio, event: &Event) -> bool {
+    matches!(
+      event,
+      Event::WindowAdded { .. } | Event::WindowChanged { .. } | Event::WindowRemoved { .. }
+    )
+  }
+}
+
+impl Allio {
+  /// Block until `predicate` holds for `target`, and return the output it held for.
+  ///
+  /// Fails with `AllioError::Timeout` if it still doesn't after `timeout`.
+  /// A `timeout` too long to represent (e.g. `Duration::MAX`) never expires.
+  /// See the [`wait`](crate::wait) module for targets.
+  pub fn wait_for<T: Target>(
+    &self,
+    target: &T,
+    mut predicate: impl FnMut(&T::Output) -> bool,
+    timeout: Duration,
+  ) -> AllioResult<T::Output> {
+    // Subscribe first so no change between reading and waiting is missed.
+    let mut events = self.subscribe();
+    let deadline = Instant::now().checked_add(timeout);
+    let mut pauses = POLL_BACKOFF.into_iter();
+    let mut next_poll = Instant::now();
+    loop {
+      let output = target.read(self);
+      if predicate(&output) {
+        return Ok(output);
+      }
+      loop {
+        let now = Instant::now();
+        if deadline.is_some_and(|deadline| now >= deadline) {
+          return Err(AllioError::Timeout {
+            timeout_ms: u64::try_from(timeout.as_millis()).unwrap_or(u64::MAX),
+          });
+        }
+        if now >= next_poll {
+          target.refresh(self);
+          let cost = now.elapsed().saturating_mul(POLL_COST_FACTOR);
+          let pause = pauses.next().unwrap_or(POLL_INTERVAL).max(cost);
+          next_poll = Instant::now() + pause;
+          break;
+        }
+        let relevant = |event: &Event| target.affected_by(self, event);
+        let until = deadline.map_or(next_poll, |deadline| deadline.min(next_poll));
+        if next_event(&mut events, until, relevant) {
+          break;
+        }
+      }
+    }
+  }
+}
+
+/// Unparks a thread when woken.
+struct Unpark(Thread);
+
+impl Wake for Unpark {
+  fn wake(self: Arc<Self>) {
+    self.0.unpark();
+  }
+}
+
+/// A waker for futures polled on this thread, which parks between polls.
+pub(crate) fn park_waker() -> Waker {
+  Waker::from(Arc::new(Unpark(std::thread::current())))
+}
+
+/// Block until the next event, or until `until`. Returns true if the event
+/// is `relevant`, or if events were missed and any of them might have been.
+fn next_event(
+  events: &mut Receiver<SequencedEvent>,
+  until: Instant,
+  relevant: impl Fn(&Event) -> bool,
+) -> bool {
+  let waker = park_waker();
+  let mut cx = Context::from_waker(&waker);
+  let mut recv = pin!(events.recv());
+  loop {
+    match recv.as_mut().poll(&mut cx) {
+      Poll::Ready(Ok(stamped)) => return relevant(&stamped.event),
+      Poll::Ready(Err(RecvError::Overflowed(_))) => return true,
+      Poll::Ready(Err(RecvError::Closed)) => {
+        std::thread::sleep(until.saturating_duration_since(Instant::now()));
+        return false;
+      }
+      Poll::Pending => {}
+    }
+    let left = until.saturating_duration_since(Instant::now());
+    if left.is_zero() {
+      return false;
+    }
+    std::thread::park_timeout(left);
+  }
+}
+
+#[cfg(test)]
+#[cfg(feature = "mock")]
+mod tests {
+  use super::*;
+  use crate::a11y::{Role, Value};
+  use crate::faults::Faults;
+  use crate::mock::{MockDesktop, MockElement, MockNodeId};
+  use crate::types::WindowId;
+  use std::sync::atomic::{AtomicUsize, Ordering};
+
+  const TIMEOUT: Duration = Duration::from_secs(2);
+
+  struct Setup {
+    desktop: MockDesktop,
+    allio: Allio,
+    window: WindowId,
+    window_node: MockNodeId,
+    root: ElementId,
+    field_node: MockNodeId,
+    field: ElementId,
+  }
+
+  /// A window holding a text field, with the field loaded into the cache.
+  fn setup() -> Setup {
+    let desktop = MockDesktop::acquire();
+    let window = desktop.open_window("Notes", "Untitled");
+    let field_node = desktop
+      .add_element(window.node, MockElement::new(Role::TextField))
+      .unwrap();
+
+    let allio = desktop.start_allio().unwrap();
+    let root = allio.window_root(window.id).unwrap().unwrap().id;
+    let field = allio.children(root, Recency::Current).unwrap()[0].id;
+    Setup {
+      desktop,
+      allio,
+      window: window.id,
+      window_node: window.node,
+      root,
+      field_node,
+      field,
+    }
+  }
+
+  /// Run `change` on another thread after a short delay, while `wait` blocks.
+  fn after_delay<R>(change: impl FnOnce() + Send, wait: impl FnOnce() -> R) -> R {
+    std::thread::scope(|s| {
+      s.spawn(|| {
+        std::thread::sleep(Duration::from_millis(20));
+        change();
+      });
+      wait()
+    })
+  }
+
+  fn has_value(text: &str) -> impl Fn(&Option<Element>) -> bool + '_ {
+    move |e| {
+      e.as_ref()
+        .is_some_and(|e| e.value == Some(Value::String(text.into())))
+    }
+  }
+
+  #[test]
+  fn waits_end_when_the_condition_holds() {
+    let s = setup();
+    let (desktop, allio) = (&s.desktop, &s.allio);
+
+    let field = after_delay(
+      || {
+        desktop.set_value(s.field_node, Value::String("done".into()));
+      },
+      || allio.wait_for(&element(s.field), has_value("done"), TIMEOUT),
+    )
+    .unwrap();
+    assert_eq!(field.unwrap().id, s.field);
+
+    let selector = "button[label=\"OK\"]";
+    let matches = after_delay(
+      || {
+        desktop.add_element(s.window_node, MockElement::new(Role::Button).label("OK"));
+      },
+      || {
+        allio.wait_for(
+          &query(s.root, selector).unwrap(),
+          |m| !m.is_empty(),
+          TIMEOUT,
+        )
+      },
+    )
+    .unwrap();
+    assert_eq!(matches.len(), 1);
+
+    let titled = |ws: &Vec<Window>| ws.iter().any(|w| w.title == "Saved");
+    after_delay(
+      || desktop.set_window_title(s.window, "Saved"),
+      || allio.wait_for(&windows(), titled, TIMEOUT),
+    )
+    .unwrap();
+
+    after_delay(
+      || desktop.remove_element(s.field_node),
+      || allio.wait_for(&element(s.field), Option::is_none, TIMEOUT),
+    )
+    .unwrap();
+    assert!(allio.get(s.field, Recency::Any).is_err());
+  }
+
+  #[test]
+  fn polling_catches_unreported_changes() {
+    let s = setup();
+    let (desktop, allio) = (&s.desktop, &s.allio);
+    let _injected = crate::faults::inject(Faults {
+      drop_rate: 1.0,
+      ..Faults::default()
+    });
+
+    desktop.set_value(s.field_node, Value::String("done".into()));
+    allio
+      .wait_for(&element(s.field), has_value("done"), TIMEOUT)
+      .unwrap();
+    desktop.remove_element(s.field_node);
+    allio
+      .wait_for(&element(s.field), Option::is_none, TIMEOUT)
+      .unwrap();
+  }
+
+  #[test]
+  fn times_out() {
+    let s = setup();
+    let start = Instant::now();
+    let result = s.allio.wait_for(
+      &element(s.field),
+      has_value("never"),
+      Duration::from_millis(150),
+    );
+    assert!(matches!(
+      result,
+      Err(AllioError::Timeout { timeout_ms: 150 })
+    ));
+    assert!(start.elapsed() >= Duration::from_millis(150));
+
+    assert!(matches!(
+      query(s.root, "button >"),
+      Err(AllioError::InvalidSelector { .. })
+    ));
+  }
+
+  #[test]
+  fn unrepresentable_timeouts_never_expire() {
+    let s = setup();
+    let field = s
+      .allio
+      .wait_for(&element(s.field), Option::is_some, Duration::MAX)
+      .unwrap();
+    assert_eq!(field.unwrap().id, s.field);
+  }
+
+  /// A target whose re-reads take 100ms.
+  struct Slow(AtomicUsize);
+
+  impl Target for Slow {
+    type Output = ();
+
+    fn read(&self, _allio: &Allio) {}
+
+    fn refresh(&self, _allio: &Allio) {
+      self.0.fetch_add(1, Ordering::SeqCst);
+      std::thread::sleep(Duration::from_millis(100));
+    }
+
+    fn affected_by(&self, _allio: &Allio, _event: &Event) -> bool {
+      false
+    }
+  }
+
+  #[test]
+  fn slow_refreshes_are_spaced_out() {
+    let s = setup();
+    let slow = Slow(AtomicUsize::new(0));
+    let result = s.allio.wait_for(&slow, |()| false, Duration::from_secs(1));
+    assert!(matches!(result, Err(AllioError::Timeout { .. })));
+    // Without spacing, refreshes would start at 0, 200ms and 550ms.
+    assert!(slow.0.load(Ordering::SeqCst) < 3);
+  }
+}
@@ -22,6 +22,21 @@ import type {
 } from "./types";
 import { ROLE_VALUES } from "./types";
 
+/**
+ * A failed RPC call. `error` is the server's structured error (`null` for
+ * custom handlers that only send a message): match on `error.kind`, e.g.
+ * `ElementRemoved` (with `details.reason`) to drop a stale reference.
+ */
+export class AllioRpcError extends Error {
+  constructor(
+    message: string,
+    readonly error: AX.AllioError | null
+  ) {
+    super(message);
+    this.name = "AllioRpcError";
+  }
+}
+
 export class Allio extends EventEmitter<AllioEvents> {
   private ws: WebSocket | null = null;
   private requestId = 0;
@@ -42,6 +57,11 @@ export class Allio extends EventEmitter<AllioEvents> {
   selection: AX.TextSelection | null = null;
   passthrough = false;
 
+  /** Sequence number of the last event applied. Reconnects resume from here. */
+  lastSeq: number | null = null;
+  /** Server session of the last sync:init. Element IDs from other sessions are rejected. */
+  session: number | null = null;
+
   // === Options ===
   debug: boolean;
 
@@ -64,9 +84,15 @@ export class Allio extends EventEmitter<AllioEvents> {
 
   // === Connection ===
   connect(): Promise<void> {
-    this.log("connecting to", this.url);
+    // Resume after the last seen event: the server replays what was missed,
+    // or sends a fresh sync:init if it no longer can (or has restarted).
+    const url =
+      this.lastSeq === null || this.session === null
+        ? this.url
+        : `${this.url}${this.url.includes("?") ? "&" : "?"}resume=${this.lastSeq}&session=${this.session}`;
+    this.log("connecting to", url);
     return new Promise((resolve, reject) => {
-      this.ws = new WebSocket(this.url);
+      this.ws = new WebSocket(url);
       this.ws.onopen = () => {
         this.log("connected ✓");
         resolve();
@@ -161,18 +187,122 @@ export class Allio extends EventEmitter<AllioEvents> {
   getElement = (element_id: AX.ElementId, recency: Recency | null = null) =>
     this.call("get", { element_id, recency });
 
+  /**
+   * Get many elements in one call, in order. Rejects if any of them can't be read.
+   * @param recency - "any" (cache), "current" (fetch from OS, in parallel), or { max_age_ms: number }
+   */
+  getMany = (element_ids: AX.ElementId[], recency: Recency | null = null) =>
+    this.call("get_many", { element_ids, recency });
+
+  /**
+   * Load the subtree under `element_id` (inclusive) in one call, a level at a time
+   * with each level fetched in parallel. Use instead of repeated `children` calls.
+   * @param depth - levels below `element_id` to load (null: all)
+   * @param recency - defaults to "current"
+   */
+  subtree = (
+    element_id: AX.ElementId,
+    depth: number | null = null,
+    recency: Recency | null = null
+  ) =>
+    this.call("subtree", {
+      element_id,
+      depth,
+      recency,
+      flat: false,
+    }) as Promise<AX.ElementNode>;
+
+  /** Like `subtree`, but listed in document order (each element before its children). */
+  subtreeElements = (
+    element_id: AX.ElementId,
+    depth: number | null = null,
+    recency: Recency | null = null
+  ) =>
+    this.call("subtree", {
+      element_id,
+      depth,
+      recency,
+      flat: true,
+    }) as Promise<TypedElement[]>;
+
+  /**
+   * Find elements under `element_id` (inclusive) matching a CSS-like selector.
+   * @param recency - "any" (cache only), "current" (fetch whole subtree), or { max_age_ms: number }
+   * @example
+   * await allio.query(root.id, 'list > listitem[label^="Todo"]', "current");
+   */
+  query = (
+    element_id: AX.ElementId,
+    selector: string,
+    recency: Recency | null = null
+  ) => this.call("query", { element_id, selector, recency });
+
+  /**
+   * Simplified projection of the subtree under `element_id`: generic wrapper chains
+   * collapsed, decorative images/separators dropped, static text merged into labels.
+   * Re-request on `subtree:changed` to follow an observed subtree.
+   */
+  view = (element_id: AX.ElementId, recency: Recency | null = null) =>
+    this.call("view", { element_id, recency });
+
+  /**
+   * Receive only matching events on this connection. Until the first subscription
+   * every event is sent; after it, only events matched by some subscription are,
+   * so the local element/window cache follows only what is subscribed to.
+   * Returns a subscription ID for `unsubscribe`.
+   * @example
+   * await allio.subscribe({ events: ["element:changed"], window_ids: [win.id] });
+   */
+  subscribe = (filter: AX.EventFilter = {}) =>
+    this.call("subscribe", { filter });
+
+  /** Remove a subscription made with `subscribe`. */
+  unsubscribe = (subscription_id: number) =>
+    this.call("unsubscribe", { subscription_id });
+
   /** Get root element for a window (fetches from OS if not cached) */
   windowRoot = (window_id: AX.WindowId) =>
     this.call("window_root", { window_id });
 
-  /** Get children of element (fetches from OS) */
-  children = (element_id: AX.ElementId, max_children = 1000) =>
+  /**
+   * Get children of element (fetches from OS), only the first `max_children`
+   * if given. Use `childrenPage` for the total or the rest of a long list.
+   */
+  children = (element_id: AX.ElementId, max_children: number | null = null) =>
     this.call("children", { element_id, max_children });
 
+  /**
+   * Get a page of element's children, with the total count and the offset of
+   * the next page (null after the last). Only the page's children are fetched
+   * in full, so huge lists and outlines can be scrolled through page by page.
+   * @param recency - defaults to "current"
+   */
+  childrenPage = (
+    element_id: AX.ElementId,
+    offset = 0,
+    limit = 1000,
+    recency: Recency | null = null
+  ) => this.call("children_page", { element_id, offset, limit, recency });
+
   /** Get parent of element (fetches from OS, null if element is root) */
   parent = (element_id: AX.ElementId): Promise<TypedElement | null> =>
     this.call("parent", { element_id });
 
+  /**
+   * Portable path to an element (app, window title, role/identifier/index steps).
+   * Unlike element IDs, paths stay valid across restarts: store them, then `resolve`.
+   */
+  pathOf = (element_id: AX.ElementId) => this.call("path_of", { element_id });
+
+  /**
+   * Find the element at a path from `pathOf` (null if it can't be found).
+   * @param recency - defaults to "current", walking the live tree
+   */
+  resolve = (
+    path: AX.ElementPath,
+    recency: Recency | null = null
+  ): Promise<TypedElement | null> => this.call("resolve", { path, recency });
+
   /**
    * Set element value with type-safe primitive.
    * TypeScript enforces correct value type based on element's role.
@@ -209,6 +339,42 @@ export class Allio extends EventEmitter<AllioEvents> {
   perform = (element_id: AX.ElementId, action: AX.Action) =>
     this.call("perform", { element_id, action });
 
+  /**
+   * Set element value, then read it back until it shows, setting it again if
+   * the app ignored the write. Rejects if it never shows.
+   *
+   * @example
+   * const { attempts, element } = await allio.setVerified(textfield, "hi");
+   */
+  setVerified<R extends WritableRole>(
+    element: ElementOfRole<R>,
+    value: PrimitiveForRole<R>
+  ): Promise<AX.Verification & { element: TypedElement }> {
+    const valueType = ROLE_VALUES[element.role];
+    if (!valueType) {
+      throw new Error(`Role ${element.role} does not accept values`);
+    }
+    return this.call("set_verified", {
+      element_id: element.id,
+      value: value as AX.Value,
+    });
+  }
+
+  /**
+   * Perform an action, then read the element back until `expect` holds,
+   * performing it again if the app ignored it and the action is safe to
+   * repeat (not `press`, `increment`...). Rejects if it never holds.
+   *
+   * @example
+   * await allio.performVerified(nodeId, "expand", { expect: "expanded", expanded: true });
+   * await allio.performVerified(checkboxId, "press", { expect: "value_changed" });
+   */
+  performVerified = (
+    element_id: AX.ElementId,
+    action: AX.Action,
+    expect: AX.Expect
+  ) => this.call("perform_verified", { element_id, action, expect });
+
   /**
    * Watch an element for changes.
    * Returns a cleanup function.
@@ -318,7 +484,9 @@ export class Allio extends EventEmitter<AllioEvents> {
       const { resolve, reject, timer } = this.pending.get(msg.id)!;
       this.pending.delete(msg.id);
       clearTimeout(timer);
-      msg.error ? reject(new Error(msg.error)) : resolve(msg.result);
+      if (!msg.error) resolve(msg.result);
+      else if (typeof msg.error === "string") reject(new AllioRpcError(msg.error, null));
+      else reject(new AllioRpcError(msg.message ?? msg.error.kind, msg.error));
       return;
     }
 
@@ -329,7 +497,8 @@ export class Allio extends EventEmitter<AllioEvents> {
       this.log(msg.event, msg.data);
     }
 
-    const event = msg as AX.Event;
+    const event = msg as AX.SequencedEvent;
+    this.lastSeq = event.seq;
 
     switch (event.event) {
       case "sync:init": {
@@ -340,7 +509,9 @@ export class Allio extends EventEmitter<AllioEvents> {
           focused_element,
           selection,
           z_order,
+          session,
         } = event.data;
+        this.session = session;
         this.windows.clear();
         this.elements.clear();
         windows.forEach((w) => this.windows.set(w.id, w));
@@ -387,11 +558,14 @@ export class Allio extends EventEmitter<AllioEvents> {
       }
 
       case "element:changed": {
-        const { element } = event.data;
-        this.elements.set(element.id, element as TypedElement);
-        this.watchCallbacks
-          .get(element.id)
-          ?.forEach((cb) => cb(element as TypedElement));
+        // Only the changed fields are sent; patch the cached copy.
+        const { element_id, changes } = event.data;
+        const element = this.elements.get(element_id);
+        if (!element) break;
+        for (const change of changes) {
+          Object.assign(element, { [change.field]: change.new });
+        }
+        this.watchCallbacks.get(element_id)?.forEach((cb) => cb(element));
         break;
       }
 
@@ -402,6 +576,11 @@ export class Allio extends EventEmitter<AllioEvents> {
         break;
       }
 
+      case "element:replaced":
+        // No state update needed - the old element was removed and the new one
+        // added by their own events; listeners move anchors from old to new.
+        break;
+
       case "focus:window": {
         this.focusedWindow = event.data.window_id;
         break;
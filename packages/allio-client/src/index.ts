@@ -2,7 +2,7 @@
 // Types are auto-generated from Rust via ts-rs
 
 export * from "./types";
-export { Allio } from "./allio";
+export { Allio, AllioRpcError } from "./allio";
 export { AllioOcclusion } from "./occlusion";
 export { AllioPassthrough, type PassthroughMode } from "./passthrough";
 export {
@@ -1,10 +1,22 @@
 // AX namespace - groups all Allio types to avoid collisions with browser types
 // Usage: import { AX } from 'allio'; then AX.Element, AX.Window, etc.
 
+export type { AllioError } from "./generated/AllioError";
 export type { Element } from "./generated/Element";
+export type { ElementChange } from "./generated/ElementChange";
 export type { Window } from "./generated/Window";
 export type { Event } from "./generated/Event";
+export type { SequencedEvent } from "./generated/SequencedEvent";
+export type { EventFilter } from "./generated/EventFilter";
+export type { EventKind } from "./generated/EventKind";
 export type { Snapshot } from "./generated/Snapshot";
+export type { ViewNode } from "./generated/ViewNode";
+export type { ElementNode } from "./generated/ElementNode";
+export type { ChildrenPage } from "./generated/ChildrenPage";
+export type { Expect } from "./generated/Expect";
+export type { Verification } from "./generated/Verification";
+export type { ElementPath } from "./generated/ElementPath";
+export type { PathStep } from "./generated/PathStep";
 export type { TextSelection } from "./generated/TextSelection";
 export type { TextRange } from "./generated/TextRange";
 export type { Bounds } from "./generated/Bounds";
@@ -0,0 +1,15 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { Action } from "./Action";
+import type { ElementId } from "./ElementId";
+import type { ProcessId } from "./ProcessId";
+import type { RemovalReason } from "./RemovalReason";
+import type { ValueType } from "./ValueType";
+import type { WindowId } from "./WindowId";
+
+/**
+ * Errors that can occur during Allio operations.
+ *
+ * Serialized as `{ "kind": "ElementRemoved", "details": { ... } }`, so RPC
+ * clients can tell errors apart without parsing messages.
+ */
+export type AllioError = { "kind": "PermissionDenied" } | { "kind": "ElementNotFound", "details": ElementId } | { "kind": "ElementRemoved", "details": { id: ElementId, reason: RemovalReason, } } | { "kind": "ForeignElementId", "details": ElementId } | { "kind": "WindowNotFound", "details": WindowId } | { "kind": "ProcessNotFound", "details": ProcessId } | { "kind": "ActionFailed", "details": { action: Action, reason: string, } } | { "kind": "SetValueFailed", "details": { reason: string, } } | { "kind": "TypeMismatch", "details": { expected: ValueType, got: ValueType, } } | { "kind": "NoElementAtPosition", "details": { x: number, y: number, } } | { "kind": "ObserverError", "details": string } | { "kind": "InvalidSelector", "details": { selector: string, reason: string, } } | { "kind": "Timeout", "details": { timeout_ms: number, } } | { "kind": "WriteNotApplied", "details": { element_id: ElementId, attempts: number, reason: string, } } | { "kind": "InvalidRequest", "details": string } | { "kind": "NotSupported", "details": string } | { "kind": "Internal", "details": string };
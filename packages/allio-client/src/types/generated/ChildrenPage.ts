@@ -0,0 +1,20 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { Element } from "./Element";
+
+/**
+ * A page of an element's children, from `Allio::children_page`.
+ */
+export type ChildrenPage = { 
+/**
+ * Children in the page, in order.
+ */
+children: Array<Element>, 
+/**
+ * How many children the element has in all. None if unknown: the page came
+ * from the cache, which doesn't hold all of them.
+ */
+total: number | null, 
+/**
+ * Offset of the next page. None if this is the last one.
+ */
+next_offset: number | null, };
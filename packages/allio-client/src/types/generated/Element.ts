@@ -63,7 +63,7 @@ row_count: number | null,
  */
 column_count: number | null, actions: Array<Action>, 
 /**
- * Platform accessibility identifier (AXIdentifier on macOS).
+ * Platform accessibility identifier (`AXIdentifier` on macOS).
  * May provide stable identity across element moves if the app sets it.
  */
 identifier: string | null, 
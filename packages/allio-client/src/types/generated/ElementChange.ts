@@ -0,0 +1,11 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { Action } from "./Action";
+import type { Bounds } from "./Bounds";
+import type { ElementId } from "./ElementId";
+import type { Role } from "./Role";
+import type { Value } from "./Value";
+
+/**
+ * A changed element field, with its old and new value.
+ */
+export type ElementChange = { "field": "parent_id", old: ElementId | null, new: ElementId | null, } | { "field": "children", old: Array<ElementId> | null, new: Array<ElementId> | null, } | { "field": "role", old: Role, new: Role, } | { "field": "platform_role", old: string, new: string, } | { "field": "label", old: string | null, new: string | null, } | { "field": "description", old: string | null, new: string | null, } | { "field": "placeholder", old: string | null, new: string | null, } | { "field": "url", old: string | null, new: string | null, } | { "field": "value", old: Value | null, new: Value | null, } | { "field": "bounds", old: Bounds | null, new: Bounds | null, } | { "field": "focused", old: boolean | null, new: boolean | null, } | { "field": "disabled", old: boolean, new: boolean, } | { "field": "selected", old: boolean | null, new: boolean | null, } | { "field": "expanded", old: boolean | null, new: boolean | null, } | { "field": "row_index", old: number | null, new: number | null, } | { "field": "column_index", old: number | null, new: number | null, } | { "field": "row_count", old: number | null, new: number | null, } | { "field": "column_count", old: number | null, new: number | null, } | { "field": "actions", old: Array<Action>, new: Array<Action>, } | { "field": "identifier", old: string | null, new: string | null, } | { "field": "is_fallback", old: boolean, new: boolean, };
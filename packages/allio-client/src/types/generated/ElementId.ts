@@ -2,5 +2,10 @@
 
 /**
  * Element identifier.
+ *
+ * Tagged with the session epoch of the `Allio` instance that assigned it, so
+ * IDs held over from another session (a restarted server, say) are rejected
+ * rather than mistaken for live elements. The epoch takes the 21 bits above a
+ * 32-bit counter, keeping IDs within JavaScript's safe integer range.
  */
 export type ElementId = number;
@@ -0,0 +1,11 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { Element } from "./Element";
+
+/**
+ * An element with its loaded descendants nested, from `Allio::fetch_subtree`.
+ */
+export type ElementNode = { element: Element, 
+/**
+ * Children in tree order. Empty at the depth limit, even if `element.children` isn't.
+ */
+children: Array<ElementNode>, };
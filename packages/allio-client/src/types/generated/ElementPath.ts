@@ -0,0 +1,19 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { PathStep } from "./PathStep";
+
+/**
+ * Where an element sits, in terms that survive restarts.
+ */
+export type ElementPath = { 
+/**
+ * Name of the app owning the window.
+ */
+app: string, 
+/**
+ * Window title.
+ */
+window: string, 
+/**
+ * Steps from the window root down to the element. Empty for the root itself.
+ */
+steps: Array<PathStep>, };
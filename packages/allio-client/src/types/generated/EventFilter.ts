@@ -0,0 +1,30 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { ElementId } from "./ElementId";
+import type { EventKind } from "./EventKind";
+import type { ProcessId } from "./ProcessId";
+import type { WindowId } from "./WindowId";
+
+/**
+ * Which events a subscription receives.
+ *
+ * Every given field must match; omitted fields match everything. Window,
+ * process and subtree filters only match events about windows and elements
+ * (so never `mouse:position`).
+ */
+export type EventFilter = { 
+/**
+ * Event kinds to receive.
+ */
+events?: Array<EventKind>, 
+/**
+ * Only events about these windows (or their elements).
+ */
+window_ids?: Array<WindowId>, 
+/**
+ * Only events about windows or elements of these processes.
+ */
+process_ids?: Array<ProcessId>, 
+/**
+ * Only events about elements in this element's subtree (inclusive).
+ */
+subtree?: ElementId, };
@@ -0,0 +1,6 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Event kinds, named as in the serialized `Event` tag.
+ */
+export type EventKind = "sync:init" | "window:added" | "window:changed" | "window:removed" | "element:added" | "element:changed" | "element:removed" | "element:replaced" | "focus:window" | "focus:element" | "selection:changed" | "mouse:position" | "subtree:changed";
@@ -0,0 +1,7 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { Value } from "./Value";
+
+/**
+ * What a verified write should leave behind, checked by reading the element back.
+ */
+export type Expect = { "expect": "value", value: Value, } | { "expect": "value_changed" } | { "expect": "expanded", expanded: boolean, } | { "expect": "selected", selected: boolean, } | { "expect": "focused" };
@@ -0,0 +1,19 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { Role } from "./Role";
+
+/**
+ * One level of an [`ElementPath`].
+ */
+export type PathStep = { 
+/**
+ * Role of the element at this level.
+ */
+role: Role, 
+/**
+ * Platform accessibility identifier, when the app sets one.
+ */
+identifier: string | null, 
+/**
+ * Index among siblings with the same role and identifier.
+ */
+index: number, };
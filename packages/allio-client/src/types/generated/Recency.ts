@@ -1,6 +1,6 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
 
 /**
- * Recency for RPC requests (serializable subset of allio::Recency).
+ * Recency for RPC requests (serializable subset of `allio::Recency`).
  */
 export type Recency = "any" | "current" | { "max_age_ms": number };
@@ -0,0 +1,6 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Why an element was removed.
+ */
+export type RemovalReason = "destroyed" | "window_closed" | "process_exited" | "cascade";
@@ -1,6 +1,9 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
 import type { Action } from "./Action";
 import type { ElementId } from "./ElementId";
+import type { ElementPath } from "./ElementPath";
+import type { EventFilter } from "./EventFilter";
+import type { Expect } from "./Expect";
 import type { Recency } from "./Recency";
 import type { Value } from "./Value";
 import type { WindowId } from "./WindowId";
@@ -8,8 +11,8 @@ import type { WindowId } from "./WindowId";
 /**
  * RPC request.
  */
-export type RpcRequest = { "method": "snapshot" } | { "method": "element_at", "args": { x: number, y: number, } } | { "method": "get", "args": { element_id: ElementId, recency: Recency | null, } } | { "method": "window_root", "args": { window_id: WindowId, } } | { "method": "children", "args": { element_id: ElementId, max_children: number, } } | { "method": "parent", "args": { element_id: ElementId, } } | { "method": "set", "args": { element_id: ElementId, value: Value, } } | { "method": "perform", "args": { element_id: ElementId, action: Action, } } | { "method": "watch", "args": { element_id: ElementId, } } | { "method": "unwatch", "args": { element_id: ElementId, } } | { "method": "observe", "args": { element_id: ElementId, depth: number | null, 
+export type RpcRequest = { "method": "snapshot" } | { "method": "element_at", "args": { x: number, y: number, } } | { "method": "get", "args": { element_id: ElementId, recency: Recency | null, } } | { "method": "get_many", "args": { element_ids: Array<ElementId>, recency: Recency | null, } } | { "method": "subtree", "args": { element_id: ElementId, depth: number | null, recency: Recency | null, flat: boolean, } } | { "method": "query", "args": { element_id: ElementId, selector: string, recency: Recency | null, } } | { "method": "view", "args": { element_id: ElementId, recency: Recency | null, } } | { "method": "window_root", "args": { window_id: WindowId, } } | { "method": "children", "args": { element_id: ElementId, max_children: number | null, } } | { "method": "children_page", "args": { element_id: ElementId, offset: number, limit: number, recency: Recency | null, } } | { "method": "parent", "args": { element_id: ElementId, } } | { "method": "path_of", "args": { element_id: ElementId, } } | { "method": "resolve", "args": { path: ElementPath, recency: Recency | null, } } | { "method": "set", "args": { element_id: ElementId, value: Value, } } | { "method": "perform", "args": { element_id: ElementId, action: Action, } } | { "method": "set_verified", "args": { element_id: ElementId, value: Value, } } | { "method": "perform_verified", "args": { element_id: ElementId, action: Action, expect: Expect, } } | { "method": "watch", "args": { element_id: ElementId, } } | { "method": "unwatch", "args": { element_id: ElementId, } } | { "method": "observe", "args": { element_id: ElementId, depth: number | null, 
 /**
  * Wait time between sweeps in milliseconds.
  */
-wait_between_ms: bigint | null, } } | { "method": "unobserve", "args": { element_id: ElementId, } };
+wait_between_ms: bigint | null, } } | { "method": "unobserve", "args": { element_id: ElementId, } } | { "method": "subscribe", "args": { filter: EventFilter, } } | { "method": "unsubscribe", "args": { subscription_id: number, } };
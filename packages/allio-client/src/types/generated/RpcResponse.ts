@@ -1,8 +1,13 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { ChildrenPage } from "./ChildrenPage";
 import type { Element } from "./Element";
+import type { ElementNode } from "./ElementNode";
+import type { ElementPath } from "./ElementPath";
 import type { Snapshot } from "./Snapshot";
+import type { Verification } from "./Verification";
+import type { ViewNode } from "./ViewNode";
 
 /**
  * RPC response.
  */
-export type RpcResponse = Snapshot | Element | Element | null | Array<Element> | null;
+export type RpcResponse = Snapshot | Element | Element | null | Array<Element> | ChildrenPage | ElementNode | ViewNode | null | ElementPath | Verification | number | null;
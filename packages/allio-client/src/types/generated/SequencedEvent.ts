@@ -0,0 +1,28 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { Element } from "./Element";
+import type { ElementChange } from "./ElementChange";
+import type { ElementId } from "./ElementId";
+import type { Point } from "./Point";
+import type { RemovalReason } from "./RemovalReason";
+import type { Snapshot } from "./Snapshot";
+import type { TextRange } from "./TextRange";
+import type { Window } from "./Window";
+import type { WindowId } from "./WindowId";
+
+/**
+ * An event stamped at emission. Serializes as the event with `seq` and `timestamp_ms` alongside.
+ */
+export type SequencedEvent = { 
+/**
+ * Position in the event stream: increases by one per emitted event, starting at 1.
+ * A `SyncInit` carries the sequence number of its snapshot instead.
+ */
+seq: number, 
+/**
+ * Emission time, in milliseconds since the Unix epoch.
+ */
+timestamp_ms: number, } & ({ "event": "sync:init", "data": Snapshot } | { "event": "window:added", "data": { window: Window, } } | { "event": "window:changed", "data": { window: Window, } } | { "event": "window:removed", "data": { window_id: WindowId, } } | { "event": "element:added", "data": { element: Element, } } | { "event": "element:changed", "data": { element_id: ElementId, changes: Array<ElementChange>, } } | { "event": "element:removed", "data": { element_id: ElementId, reason: RemovalReason, } } | { "event": "element:replaced", "data": { old: ElementId, new: ElementId, } } | { "event": "focus:window", "data": { window_id: WindowId | null, } } | { "event": "focus:element", "data": { element: Element, previous_element_id: ElementId | null, } } | { "event": "selection:changed", "data": { window_id: WindowId, element_id: ElementId, text: string, 
+/**
+ * Character range. None if range is unknown.
+ */
+range: TextRange | null, } } | { "event": "mouse:position", "data": Point } | { "event": "subtree:changed", "data": { root_id: ElementId, added: Array<ElementId>, removed: Array<ElementId>, modified: Array<ElementId>, } });
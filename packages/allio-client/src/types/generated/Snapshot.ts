@@ -16,4 +16,13 @@ z_order: Array<WindowId>,
 /**
  * Current mouse position
  */
-mouse_position: Point | null, };
+mouse_position: Point | null, 
+/**
+ * Sequence number of the last event reflected in this snapshot
+ */
+seq: number, 
+/**
+ * Session epoch tagging the element IDs (see [`ElementId::epoch`]).
+ * Sequence numbers are only comparable within one session.
+ */
+session: number, };
@@ -0,0 +1,19 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { Element } from "./Element";
+
+/**
+ * A verified write that took, from `Allio::set_value_verified` or `Allio::perform_verified`.
+ */
+export type Verification = { 
+/**
+ * The element as read back once the outcome held.
+ */
+element: Element, 
+/**
+ * Writes made, counting the one that took.
+ */
+attempts: number, 
+/**
+ * Time from the first write until the outcome was seen.
+ */
+elapsed_ms: number, };
@@ -0,0 +1,38 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { Bounds } from "./Bounds";
+import type { ElementId } from "./ElementId";
+import type { Role } from "./Role";
+import type { Value } from "./Value";
+
+/**
+ * A node in a simplified tree projection.
+ */
+export type ViewNode = { 
+/**
+ * Element this node represents (the innermost element of a collapsed chain).
+ */
+id: ElementId, 
+/**
+ * Role of the represented element.
+ */
+role: Role, 
+/**
+ * Element label, followed by the text of merged `StaticText` children.
+ */
+label: string | null, 
+/**
+ * Value of the represented element.
+ */
+value: Value | null, 
+/**
+ * Screen bounds of the represented element.
+ */
+bounds: Bounds | null, 
+/**
+ * Elements folded into this node: collapsed generic ancestors, merged text.
+ */
+merged: Array<ElementId>, 
+/**
+ * Projected children, in tree order.
+ */
+children: Array<ViewNode>, };
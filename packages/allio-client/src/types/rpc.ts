@@ -20,15 +20,28 @@ export type RpcReturns = {
   snapshot: AX.Snapshot;
   element_at: TypedElement;
   get: TypedElement;
+  get_many: TypedElement[];
+  subtree: AX.ElementNode | TypedElement[];
+  query: TypedElement[];
+  view: AX.ViewNode | null;
   window_root: TypedElement;
   children: TypedElement[];
+  children_page: Omit<AX.ChildrenPage, "children"> & {
+    children: TypedElement[];
+  };
   parent: TypedElement | null;
+  path_of: AX.ElementPath;
+  resolve: TypedElement | null;
   set: boolean;
   perform: boolean;
+  set_verified: AX.Verification & { element: TypedElement };
+  perform_verified: AX.Verification & { element: TypedElement };
   watch: void;
   unwatch: void;
   observe: void;
   unobserve: void;
+  subscribe: number;
+  unsubscribe: void;
 };
 
 // Event types derived from ServerEvent
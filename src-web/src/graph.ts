@@ -143,7 +143,8 @@ class AXGraph {
     });
 
     this.allio.on("element:changed", (data) => {
-      this.onElementChanged(data.element as AX.TypedElement);
+      const element = this.allio.get(data.element_id);
+      if (element) this.onElementChanged(element);
     });
 
     this.allio.on("element:removed", (data) => {
@@ -122,9 +122,10 @@ function setupEventListeners() {
   allio.on("window:changed", render);
 
   // Element value changes trigger propagation
-  allio.on("element:changed", ({ element }) =>
-    handleElementUpdate(element as AX.TypedElement)
-  );
+  allio.on("element:changed", ({ element_id }) => {
+    const element = allio.get(element_id);
+    if (element) handleElementUpdate(element);
+  });
 
   // Clean up ports when elements are removed
   allio.on("element:removed", ({ element_id }) => {
@@ -223,8 +223,9 @@ function setupEventListeners() {
   });
 
   // Element value changes trigger propagation
-  allio.on("element:changed", ({ element }) => {
-    handleElementUpdate(element as AX.TypedElement);
+  allio.on("element:changed", ({ element_id }) => {
+    const element = allio.get(element_id);
+    if (element) handleElementUpdate(element);
   });
 
   // Clean up ports when elements are removed